@@ -0,0 +1,60 @@
+//! Upload load generation against a local endpoint
+//!
+//! CAKE shapes egress, so the load is TCP uploads to any host on the LAN that
+//! accepts and discards a byte stream (e.g. `nc -lk 9000 > /dev/null`).
+
+use anyhow::{Context, Result};
+use log::debug;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::time::{self, Instant};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Saturate the uplink to `endpoint` with `streams` TCP connections for `duration`
+/// Returns the achieved throughput in Mbit/s.
+pub async fn saturate(endpoint: &str, streams: usize, duration: Duration) -> Result<f64> {
+    // Connect every stream up front so an unreachable endpoint fails the bench early
+    let mut connections = Vec::with_capacity(streams);
+    for _ in 0..streams.max(1) {
+        let stream = time::timeout(CONNECT_TIMEOUT, TcpStream::connect(endpoint))
+            .await
+            .with_context(|| format!("Timed out connecting to load endpoint {}", endpoint))?
+            .with_context(|| format!("Failed to connect to load endpoint {}", endpoint))?;
+        connections.push(stream);
+    }
+
+    let started = Instant::now();
+    let deadline = started + duration;
+    let tasks: Vec<_> = connections.into_iter()
+        .map(|stream| tokio::spawn(upload_until(stream, deadline)))
+        .collect();
+
+    let mut total_bytes = 0;
+    for task in tasks {
+        total_bytes += task.await.unwrap_or(0);
+    }
+
+    let secs = started.elapsed().as_secs_f64().max(0.001);
+    Ok(total_bytes as f64 * 8.0 / secs / 1_000_000.0)
+}
+
+/// Write to `stream` until `deadline`; returns bytes sent
+async fn upload_until(mut stream: TcpStream, deadline: Instant) -> u64 {
+    let chunk = vec![0u8; CHUNK_BYTES];
+    let mut sent = 0;
+
+    while Instant::now() < deadline {
+        match time::timeout_at(deadline, stream.write_all(&chunk)).await {
+            Ok(Ok(())) => sent += CHUNK_BYTES as u64,
+            Ok(Err(e)) => {
+                debug!("Load stream closed: {}", e);
+                break;
+            }
+            Err(_) => break,
+        }
+    }
+    sent
+}
@@ -0,0 +1,214 @@
+//! Bufferbloat benchmark (`hifi-wifi bench`)
+//!
+//! Measures latency to the gateway (or a `hifi-wifi peer` reflector) while idle
+//! and while the uplink is saturated against a local endpoint, then grades the
+//! increase.
+//! With `--compare` the service is switched off and on around two runs to
+//! show what the optimizations change.
+
+pub mod load;
+pub mod peer;
+pub mod probe;
+
+use anyhow::{bail, Result};
+use log::info;
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+
+use crate::bench::probe::LatencyStats;
+
+/// Time given to the load to fill the queues before sampling
+const RAMP_UP: Duration = Duration::from_secs(1);
+
+/// How latency is measured
+#[derive(Debug, Clone)]
+pub enum LatencyTarget {
+    /// ICMP echo to a host
+    Ping(String),
+    /// UDP echo to a `hifi-wifi peer` (`host:port`)
+    Peer(String),
+}
+
+impl LatencyTarget {
+    async fn sample(&self, duration: Duration) -> Result<LatencyStats> {
+        match self {
+            LatencyTarget::Ping(host) => probe::sample(host, duration).await,
+            LatencyTarget::Peer(endpoint) => probe::sample_udp(endpoint, duration).await,
+        }
+    }
+}
+
+impl fmt::Display for LatencyTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LatencyTarget::Ping(host) => write!(f, "{} (ping)", host),
+            LatencyTarget::Peer(endpoint) => write!(f, "{} (peer echo)", endpoint),
+        }
+    }
+}
+
+/// What to measure against
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub target: LatencyTarget,
+    /// `host:port` accepting the upload load
+    pub load_endpoint: String,
+    pub streams: usize,
+    /// Length of each phase (idle and loaded)
+    pub duration: Duration,
+}
+
+/// Bufferbloat grade, from latency increase under load
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+pub enum Grade {
+    A,
+    B,
+    C,
+    D,
+    F,
+}
+
+impl Grade {
+    /// Thresholds follow the common web bufferbloat tests
+    pub fn from_bloat(bloat_ms: f64, loaded_loss_pct: f64) -> Self {
+        if loaded_loss_pct >= 10.0 {
+            return Grade::F;
+        }
+        match bloat_ms {
+            b if b < 5.0 => Grade::A,
+            b if b < 30.0 => Grade::B,
+            b if b < 60.0 => Grade::C,
+            b if b < 200.0 => Grade::D,
+            _ => Grade::F,
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Grade::A => "Excellent",
+            Grade::B => "Good",
+            Grade::C => "Fair",
+            Grade::D => "Poor",
+            Grade::F => "Bad",
+        }
+    }
+}
+
+impl fmt::Display for Grade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} ({})", self, self.describe())
+    }
+}
+
+/// One idle + loaded run
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchResult {
+    pub target: String,
+    pub idle: LatencyStats,
+    pub loaded: LatencyStats,
+    pub upload_mbit: f64,
+    pub bloat_ms: f64,
+    pub grade: Grade,
+}
+
+impl BenchResult {
+    pub fn new(target: &str, idle: LatencyStats, loaded: LatencyStats, upload_mbit: f64) -> Self {
+        let bloat_ms = if loaded.samples > 0 {
+            (loaded.avg_ms - idle.avg_ms).max(0.0)
+        } else {
+            f64::INFINITY
+        };
+        let grade = Grade::from_bloat(bloat_ms, loaded.loss_pct);
+        Self { target: target.to_string(), idle, loaded, upload_mbit, bloat_ms, grade }
+    }
+}
+
+/// Measure idle latency, then latency while saturating the uplink
+pub async fn run(options: &BenchOptions) -> Result<BenchResult> {
+    info!("Measuring idle latency to {} ({}s)...", options.target, options.duration.as_secs());
+    let idle = options.target.sample(options.duration).await?;
+    if idle.samples == 0 {
+        bail!("No replies from {} - pass a reachable host with --target", options.target);
+    }
+
+    info!("Measuring loaded latency ({} streams to {})...", options.streams, options.load_endpoint);
+    let load = load::saturate(&options.load_endpoint, options.streams, RAMP_UP + options.duration);
+    let loaded = async {
+        tokio::time::sleep(RAMP_UP).await;
+        options.target.sample(options.duration).await
+    };
+    let (upload_mbit, loaded) = tokio::try_join!(load, loaded)?;
+
+    Ok(BenchResult::new(&options.target.to_string(), idle, loaded, upload_mbit))
+}
+
+/// Print a single run
+pub fn print_report(result: &BenchResult) {
+    println!();
+    println!("Target:          {}", result.target);
+    print_phase("Idle latency:", &result.idle);
+    print_phase("Loaded latency:", &result.loaded);
+    println!("Upload:          {:.1} Mbit/s", result.upload_mbit);
+    println!("Bufferbloat:     +{:.1} ms", result.bloat_ms);
+    println!("Grade:           {}", result.grade);
+}
+
+fn print_phase(label: &str, stats: &LatencyStats) {
+    println!("{:<16} {:.1} ms avg, {:.1} ms p95, {:.1} ms jitter, {:.0}% loss",
+             label, stats.avg_ms, stats.p95_ms, stats.jitter_ms, stats.loss_pct);
+}
+
+/// Print a before/after table for `--compare`
+pub fn print_comparison(before: &BenchResult, after: &BenchResult) {
+    println!();
+    println!("Target: {}", before.target);
+    println!();
+    println!("{:<18} {:>12} {:>12}", "", "hifi-wifi off", "hifi-wifi on");
+    let row = |label: &str, b: f64, a: f64, unit: &str| {
+        println!("{:<18} {:>9.1} {:<2} {:>9.1} {:<2}", label, b, unit, a, unit);
+    };
+    row("Idle latency", before.idle.avg_ms, after.idle.avg_ms, "ms");
+    row("Loaded latency", before.loaded.avg_ms, after.loaded.avg_ms, "ms");
+    row("Loaded p95", before.loaded.p95_ms, after.loaded.p95_ms, "ms");
+    row("Loaded jitter", before.loaded.jitter_ms, after.loaded.jitter_ms, "ms");
+    row("Loaded loss", before.loaded.loss_pct, after.loaded.loss_pct, "%");
+    row("Upload", before.upload_mbit, after.upload_mbit, "Mb");
+    row("Bufferbloat", before.bloat_ms, after.bloat_ms, "ms");
+    println!("{:<18} {:>12} {:>12}", "Grade", format!("{:?}", before.grade), format!("{:?}", after.grade));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(avg_ms: f64, loss_pct: f64) -> LatencyStats {
+        LatencyStats { samples: 50, avg_ms, p95_ms: avg_ms, jitter_ms: 1.0, loss_pct }
+    }
+
+    #[test]
+    fn test_grades() {
+        assert_eq!(Grade::from_bloat(2.0, 0.0), Grade::A);
+        assert_eq!(Grade::from_bloat(29.9, 0.0), Grade::B);
+        assert_eq!(Grade::from_bloat(45.0, 0.0), Grade::C);
+        assert_eq!(Grade::from_bloat(150.0, 0.0), Grade::D);
+        assert_eq!(Grade::from_bloat(400.0, 0.0), Grade::F);
+        // Heavy loss under load fails regardless of latency
+        assert_eq!(Grade::from_bloat(2.0, 15.0), Grade::F);
+    }
+
+    #[test]
+    fn test_bloat_from_phases() {
+        let result = BenchResult::new("192.168.1.1", stats(3.0, 0.0), stats(48.0, 0.0), 80.0);
+        assert_eq!(result.bloat_ms, 45.0);
+        assert_eq!(result.grade, Grade::C);
+
+        // Faster under load than idle (power save woke up) is not negative bloat
+        let result = BenchResult::new("192.168.1.1", stats(6.0, 0.0), stats(4.0, 0.0), 80.0);
+        assert_eq!(result.bloat_ms, 0.0);
+
+        let lost = LatencyStats::from_replies(&[]);
+        let result = BenchResult::new("192.168.1.1", stats(3.0, 0.0), lost, 80.0);
+        assert_eq!(result.grade, Grade::F);
+    }
+}
@@ -0,0 +1,310 @@
+//! LAN measurement peer (`hifi-wifi peer`)
+//!
+//! Runs on another host on the network (e.g. the streaming PC) so benchmarks
+//! need no internet speed-test servers:
+//!
+//! | Port       | TCP                          | UDP                                  |
+//! |------------|------------------------------|--------------------------------------|
+//! | `port`     | sink (reads and discards)    | echo reflector (latency probes)      |
+//! | `port + 1` | source (writes until closed) | sink, or source on a `SOURCE_MAGIC` request |
+//!
+//! A UDP source only starts for an address with an open TCP connection to
+//! either port: a TCP handshake can't come from a spoofed address, so the
+//! peer can't be used to flood a third host. By default it listens on the
+//! LAN address only.
+
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::time::{self, Instant};
+
+pub const DEFAULT_PORT: u16 = 9000;
+
+/// Prefix of a UDP request asking the peer to send traffic back
+/// Followed by the rate in kbit/s and the duration in ms (both u32, big endian).
+pub const SOURCE_MAGIC: &[u8] = b"HWSRC";
+
+const CHUNK_BYTES: usize = 64 * 1024;
+const DATAGRAM_BYTES: usize = 1200;
+/// Longest UDP source burst one request can ask for
+const MAX_SOURCE_DURATION: Duration = Duration::from_secs(60);
+/// Fastest UDP source one request can ask for (kbit/s)
+const MAX_SOURCE_RATE_KBIT: u32 = 1_000_000;
+
+/// Address to listen on by default: the one that reaches the default gateway,
+/// else loopback
+pub fn default_bind() -> IpAddr {
+    crate::network::tc::default_gateway()
+        .and_then(|gateway| {
+            let socket = std::net::UdpSocket::bind("0.0.0.0:0").ok()?;
+            // Connecting a UDP socket only picks the route; nothing is sent
+            socket.connect((gateway.as_str(), 9)).ok()?;
+            Some(socket.local_addr().ok()?.ip())
+        })
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
+
+/// Normalize `host` or `host:port` to `host:port`, filling in the default port
+pub fn endpoint(addr: &str) -> String {
+    if addr.parse::<SocketAddr>().is_ok() {
+        return addr.to_string();
+    }
+    match addr.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') && port.parse::<u16>().is_ok() => addr.to_string(),
+        // Bare IPv6 address
+        Some(_) => format!("[{}]:{}", addr.trim_matches(['[', ']']), DEFAULT_PORT),
+        None => format!("{}:{}", addr, DEFAULT_PORT),
+    }
+}
+
+/// Decode a UDP source request into (rate_kbit, duration)
+fn parse_source_request(datagram: &[u8]) -> Option<(u32, Duration)> {
+    let body = datagram.strip_prefix(SOURCE_MAGIC)?;
+    let rate_kbit = u32::from_be_bytes(body.get(0..4)?.try_into().ok()?);
+    let duration_ms = u32::from_be_bytes(body.get(4..8)?.try_into().ok()?);
+    Some((rate_kbit.min(MAX_SOURCE_RATE_KBIT), Duration::from_millis(duration_ms as u64).min(MAX_SOURCE_DURATION)))
+}
+
+/// Open TCP connections per client address
+#[derive(Clone, Default)]
+struct Sessions(Arc<Mutex<HashMap<IpAddr, usize>>>);
+
+impl Sessions {
+    fn open(&self, ip: IpAddr) -> Session {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()).entry(ip).or_default() += 1;
+        Session { sessions: self.clone(), ip }
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).contains_key(&ip)
+    }
+}
+
+/// One open TCP connection; forgotten when dropped
+struct Session {
+    sessions: Sessions,
+    ip: IpAddr,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let mut open = self.sessions.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = open.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                open.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/// Bound sockets of a running peer
+pub struct Peer {
+    tcp_sink: TcpListener,
+    tcp_source: TcpListener,
+    udp_echo: UdpSocket,
+    udp_data: Arc<UdpSocket>,
+}
+
+impl Peer {
+    /// Bind all listeners on `bind:port` and `bind:port+1`
+    pub async fn bind(bind: &str, port: u16) -> Result<Self> {
+        let data_port = port.checked_add(1).context("Port must be below 65535")?;
+        let at = |port: u16| format!("{}:{}", bind, port);
+
+        Ok(Self {
+            tcp_sink: TcpListener::bind(at(port)).await
+                .with_context(|| format!("Failed to listen on TCP {}", at(port)))?,
+            tcp_source: TcpListener::bind(at(data_port)).await
+                .with_context(|| format!("Failed to listen on TCP {}", at(data_port)))?,
+            udp_echo: UdpSocket::bind(at(port)).await
+                .with_context(|| format!("Failed to bind UDP {}", at(port)))?,
+            udp_data: Arc::new(UdpSocket::bind(at(data_port)).await
+                .with_context(|| format!("Failed to bind UDP {}", at(data_port)))?),
+        })
+    }
+
+    /// Serve until the task is dropped
+    pub async fn run(self) -> Result<()> {
+        info!("Peer listening: TCP sink + UDP echo on {}, TCP source + UDP sink/source on {}",
+              self.tcp_sink.local_addr()?, self.tcp_source.local_addr()?);
+
+        let sessions = Sessions::default();
+        tokio::try_join!(
+            accept_loop(self.tcp_sink, sessions.clone(), sink),
+            accept_loop(self.tcp_source, sessions.clone(), source),
+            echo_loop(self.udp_echo),
+            udp_data_loop(self.udp_data, sessions.clone()),
+        )?;
+        Ok(())
+    }
+}
+
+async fn accept_loop<F, Fut>(listener: TcpListener, sessions: Sessions, handler: F) -> Result<()>
+where
+    F: Fn(TcpStream) -> Fut,
+    Fut: std::future::Future<Output = std::io::Result<u64>> + Send + 'static,
+{
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        debug!("Peer: connection from {}", addr);
+        let session = sessions.open(addr.ip());
+        let transfer = handler(stream);
+        tokio::spawn(async move {
+            let _session = session;
+            match transfer.await {
+                Ok(bytes) => debug!("Peer: {} done after {} bytes", addr, bytes),
+                Err(e) => debug!("Peer: {} closed: {}", addr, e),
+            }
+        });
+    }
+}
+
+/// Read and discard until the client closes
+async fn sink(mut stream: TcpStream) -> std::io::Result<u64> {
+    let mut buf = vec![0u8; CHUNK_BYTES];
+    let mut received = 0;
+    loop {
+        match stream.read(&mut buf).await? {
+            0 => return Ok(received),
+            n => received += n as u64,
+        }
+    }
+}
+
+/// Write until the client closes
+async fn source(mut stream: TcpStream) -> std::io::Result<u64> {
+    let chunk = vec![0u8; CHUNK_BYTES];
+    let mut sent = 0;
+    // The client closing the connection is the normal way a download ends
+    while stream.write_all(&chunk).await.is_ok() {
+        sent += CHUNK_BYTES as u64;
+    }
+    Ok(sent)
+}
+
+/// Send every datagram straight back to its sender
+async fn echo_loop(socket: UdpSocket) -> Result<()> {
+    let mut buf = vec![0u8; 65536];
+    loop {
+        let (n, from) = socket.recv_from(&mut buf).await?;
+        if let Err(e) = socket.send_to(&buf[..n], from).await {
+            debug!("Peer: echo to {} failed: {}", from, e);
+        }
+    }
+}
+
+/// Discard datagrams, except source requests from a client with an open TCP
+/// connection, which start a paced burst back
+async fn udp_data_loop(socket: Arc<UdpSocket>, sessions: Sessions) -> Result<()> {
+    let mut buf = vec![0u8; 65536];
+    loop {
+        let (n, from) = socket.recv_from(&mut buf).await?;
+        if let Some((rate_kbit, duration)) = parse_source_request(&buf[..n]) {
+            if !sessions.contains(from.ip()) {
+                debug!("Peer: ignoring UDP source request from {} without a TCP connection", from);
+                continue;
+            }
+            info!("Peer: UDP source to {} at {} kbit/s for {:?}", from, rate_kbit, duration);
+            tokio::spawn(udp_source(socket.clone(), from, rate_kbit, duration));
+        }
+    }
+}
+
+async fn udp_source(socket: Arc<UdpSocket>, to: SocketAddr, rate_kbit: u32, duration: Duration) {
+    if rate_kbit == 0 {
+        return;
+    }
+    let datagram = vec![0u8; DATAGRAM_BYTES];
+    let gap = Duration::from_secs_f64((DATAGRAM_BYTES * 8) as f64 / (rate_kbit as f64 * 1000.0));
+    let deadline = Instant::now() + duration;
+
+    let mut interval = time::interval(gap);
+    interval.set_missed_tick_behavior(time::MissedTickBehavior::Burst);
+    while Instant::now() < deadline {
+        interval.tick().await;
+        if let Err(e) = socket.send_to(&datagram, to).await {
+            warn!("Peer: UDP source to {} stopped: {}", to, e);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::{load, probe};
+
+    fn source_request(rate_kbit: u32, duration: Duration) -> Vec<u8> {
+        let mut request = SOURCE_MAGIC.to_vec();
+        request.extend_from_slice(&rate_kbit.to_be_bytes());
+        request.extend_from_slice(&(duration.as_millis() as u32).to_be_bytes());
+        request
+    }
+
+    /// Bind a peer on loopback at the first free pair of ports
+    async fn loopback_peer() -> u16 {
+        for port in (40000..41000).step_by(2) {
+            if let Ok(peer) = Peer::bind("127.0.0.1", port).await {
+                tokio::spawn(peer.run());
+                return port;
+            }
+        }
+        panic!("No free loopback ports");
+    }
+
+    #[test]
+    fn test_endpoint() {
+        assert_eq!(endpoint("192.168.1.50"), "192.168.1.50:9000");
+        assert_eq!(endpoint("desktop.lan:7000"), "desktop.lan:7000");
+        assert_eq!(endpoint("fe80::1"), "[fe80::1]:9000");
+        assert_eq!(endpoint("[fe80::1]:7000"), "[fe80::1]:7000");
+    }
+
+    #[test]
+    fn test_source_request_round_trip() {
+        let request = source_request(5000, Duration::from_secs(2));
+        assert_eq!(parse_source_request(&request), Some((5000, Duration::from_secs(2))));
+        let greedy = source_request(u32::MAX, Duration::from_secs(3600));
+        assert_eq!(parse_source_request(&greedy), Some((MAX_SOURCE_RATE_KBIT, MAX_SOURCE_DURATION)));
+        assert_eq!(parse_source_request(b"HWSRC"), None);
+        assert_eq!(parse_source_request(&[0u8; 13]), None);
+    }
+
+    #[tokio::test]
+    async fn test_loopback_echo_and_load() {
+        let port = loopback_peer().await;
+        let addr = format!("127.0.0.1:{}", port);
+
+        let stats = probe::sample_udp(&addr, Duration::from_millis(700)).await.unwrap();
+        assert!(stats.samples >= 3, "{:?}", stats);
+        assert_eq!(stats.loss_pct, 0.0);
+
+        let mbit = load::saturate(&addr, 2, Duration::from_millis(200)).await.unwrap();
+        assert!(mbit > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_loopback_sources() {
+        let port = loopback_peer().await;
+        let mut buf = vec![0u8; CHUNK_BYTES];
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.connect(("127.0.0.1", port + 1)).await.unwrap();
+
+        // No TCP connection from this address yet: the request is ignored
+        socket.send(&source_request(10_000, Duration::from_millis(100))).await.unwrap();
+        assert!(time::timeout(Duration::from_millis(300), socket.recv(&mut buf)).await.is_err());
+
+        let mut stream = TcpStream::connect(("127.0.0.1", port + 1)).await.unwrap();
+        stream.read_exact(&mut buf).await.unwrap();
+
+        socket.send(&source_request(10_000, Duration::from_millis(100))).await.unwrap();
+        let n = time::timeout(Duration::from_secs(1), socket.recv(&mut buf)).await.unwrap().unwrap();
+        assert_eq!(n, DATAGRAM_BYTES);
+    }
+}
@@ -0,0 +1,187 @@
+//! Latency sampling with ping or a peer's UDP echo
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader, Lines};
+use tokio::net::UdpSocket;
+use tokio::process::{Child, ChildStdout, Command};
+use tokio::time::{self, Instant};
+
+/// Interval between echo requests (fastest ping allows without root)
+const PING_INTERVAL: &str = "0.2";
+const PROBE_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Latency over one measurement phase
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LatencyStats {
+    pub samples: usize,
+    pub avg_ms: f64,
+    pub p95_ms: f64,
+    /// Mean difference between consecutive replies
+    pub jitter_ms: f64,
+    pub loss_pct: f64,
+}
+
+impl LatencyStats {
+    /// Summarize `(icmp_seq, rtt_ms)` replies
+    pub fn from_replies(replies: &[(u32, f64)]) -> Self {
+        if replies.is_empty() {
+            return Self { loss_pct: 100.0, ..Default::default() };
+        }
+
+        let rtts: Vec<f64> = replies.iter().map(|(_, rtt)| *rtt).collect();
+        let avg_ms = rtts.iter().sum::<f64>() / rtts.len() as f64;
+
+        let jitter_ms = if rtts.len() > 1 {
+            rtts.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f64>() / (rtts.len() - 1) as f64
+        } else {
+            0.0
+        };
+
+        let mut sorted = rtts.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let p95_ms = sorted[((sorted.len() - 1) as f64 * 0.95).round() as usize];
+
+        // Sequence numbers start at 1, so the highest one seen is how many were sent
+        let sent = replies.iter().map(|(seq, _)| *seq).max().unwrap_or(0).max(rtts.len() as u32);
+        let loss_pct = 100.0 * (sent as usize - rtts.len()) as f64 / sent as f64;
+
+        Self { samples: rtts.len(), avg_ms, p95_ms, jitter_ms, loss_pct }
+    }
+}
+
+/// A running `ping` process
+pub struct Pinger {
+    // Killed when dropped
+    _child: Child,
+    lines: Lines<BufReader<ChildStdout>>,
+}
+
+impl Pinger {
+    pub fn spawn(target: &str) -> Result<Self> {
+        let mut child = Command::new("ping")
+            .args(["-n", "-i", PING_INTERVAL, target])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .context("ping command not found - please install iputils")?;
+
+        let Some(stdout) = child.stdout.take() else {
+            bail!("Failed to read ping output");
+        };
+        Ok(Self { _child: child, lines: BufReader::new(stdout).lines() })
+    }
+
+    /// Next `(icmp_seq, rtt_ms)` reply; None once ping exits (unknown host, no route)
+    pub async fn next_reply(&mut self) -> Result<Option<(u32, f64)>> {
+        while let Some(line) = self.lines.next_line().await? {
+            if let Some(reply) = parse_reply(&line) {
+                return Ok(Some(reply));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Ping `target` for `duration` and summarize the replies
+pub async fn sample(target: &str, duration: Duration) -> Result<LatencyStats> {
+    let mut pinger = Pinger::spawn(target)?;
+    let mut replies = Vec::new();
+
+    let deadline = time::sleep(duration);
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            _ = &mut deadline => break,
+            reply = pinger.next_reply() => match reply? {
+                Some(reply) => replies.push(reply),
+                None => break,
+            }
+        }
+    }
+
+    Ok(LatencyStats::from_replies(&replies))
+}
+
+/// Probe a peer's UDP echo reflector for `duration` and summarize the replies
+pub async fn sample_udp(endpoint: &str, duration: Duration) -> Result<LatencyStats> {
+    let Some(peer) = tokio::net::lookup_host(endpoint).await
+        .with_context(|| format!("Failed to resolve peer {}", endpoint))?
+        .next() else {
+        bail!("Failed to resolve peer {}", endpoint);
+    };
+    let socket = UdpSocket::bind(if peer.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }).await?;
+    socket.connect(peer).await?;
+
+    let mut sent_at: Vec<Instant> = Vec::new();
+    let mut replies = Vec::new();
+    let mut buf = [0u8; 64];
+    let mut interval = time::interval(PROBE_INTERVAL);
+
+    let deadline = time::sleep(duration);
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            _ = &mut deadline => break,
+            _ = interval.tick() => {
+                sent_at.push(Instant::now());
+                // Sequence numbers start at 1, matching ping
+                let seq = sent_at.len() as u32;
+                if let Err(e) = socket.send(&seq.to_be_bytes()).await {
+                    bail!("Failed to send probe to {}: {}", endpoint, e);
+                }
+            }
+            received = socket.recv(&mut buf) => {
+                // ICMP port unreachable surfaces here as an error; count it as loss
+                let Ok(n) = received else { continue };
+                let Some(seq) = buf[..n].try_into().ok().map(u32::from_be_bytes) else { continue };
+                if let Some(at) = seq.checked_sub(1).and_then(|i| sent_at.get(i as usize)) {
+                    replies.push((seq, at.elapsed().as_secs_f64() * 1000.0));
+                }
+            }
+        }
+    }
+
+    Ok(LatencyStats::from_replies(&replies))
+}
+
+/// Parse "64 bytes from 192.168.1.1: icmp_seq=3 ttl=64 time=1.93 ms"
+fn parse_reply(line: &str) -> Option<(u32, f64)> {
+    let field = |name: &str| {
+        line.split_whitespace()
+            .find_map(|w| w.strip_prefix(name))
+    };
+    let seq = field("icmp_seq=")?.parse().ok()?;
+    let rtt = field("time=")?.parse().ok()?;
+    Some((seq, rtt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reply() {
+        assert_eq!(parse_reply("64 bytes from 192.168.1.1: icmp_seq=3 ttl=64 time=1.93 ms"), Some((3, 1.93)));
+        assert_eq!(parse_reply("64 bytes from 10.0.0.1: icmp_seq=12 ttl=63 time=104 ms"), Some((12, 104.0)));
+        assert_eq!(parse_reply("PING 192.168.1.1 (192.168.1.1) 56(84) bytes of data."), None);
+        assert_eq!(parse_reply("From 192.168.1.5 icmp_seq=4 Destination Host Unreachable"), None);
+    }
+
+    #[test]
+    fn test_stats_from_replies() {
+        let replies = [(1, 2.0), (2, 4.0), (4, 2.0), (5, 4.0)];
+        let stats = LatencyStats::from_replies(&replies);
+
+        assert_eq!(stats.samples, 4);
+        assert_eq!(stats.avg_ms, 3.0);
+        assert_eq!(stats.jitter_ms, 2.0);
+        assert_eq!(stats.p95_ms, 4.0);
+        assert_eq!(stats.loss_pct, 20.0);
+
+        assert_eq!(LatencyStats::from_replies(&[]).loss_pct, 100.0);
+    }
+}
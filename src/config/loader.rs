@@ -1,17 +1,23 @@
 use super::structs::Config;
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
 use std::fs;
 use std::path::Path;
-use log::{info, warn};
+use log::{debug, info, warn};
+
+use crate::system::devices;
 
 const CONFIG_PATH: &str = "/etc/hifi-wifi/config.toml";
 
 pub fn load_config() -> Config {
+    let mut table = toml::Table::new();
     if Path::new(CONFIG_PATH).exists() {
         match fs::read_to_string(CONFIG_PATH) {
             Ok(content) => match toml::from_str(&content) {
-                Ok(config) => {
+                Ok(parsed) => {
                     info!("Loaded configuration from {}", CONFIG_PATH);
-                    return config;
+                    table = parsed;
                 }
                 Err(e) => {
                     warn!("Failed to parse config file: {}. Using defaults.", e);
@@ -24,6 +30,111 @@ pub fn load_config() -> Config {
     } else {
         info!("No config file found at {}. Using defaults.", CONFIG_PATH);
     }
-    
-    Config::default()
+
+    if let Some(device) = devices::current() {
+        apply_power_defaults(&mut table, device.power_defaults());
+    }
+
+    match Config::deserialize(table) {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("Invalid configuration: {}. Using defaults.", e);
+            Config::default()
+        }
+    }
+}
+
+/// Fill in [power] keys the config file doesn't set (device profile defaults)
+fn apply_power_defaults(table: &mut toml::Table, defaults: toml::Table) {
+    if defaults.is_empty() {
+        return;
+    }
+    let power = table.entry("power").or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    if let toml::Value::Table(power) = power {
+        for (key, value) in defaults {
+            power.entry(key).or_insert(value);
+        }
+    }
+}
+
+/// Load a specific config file, failing loudly (used by `replay --config`)
+pub fn load_config_from(path: &Path) -> Result<Config> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    toml::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Parse every `*.toml` in `dir`, in name order
+///
+/// This is how the embedded databases (quirks, devices, firmware targets) pick
+/// up their drop-in directories under /etc/hifi-wifi. A file that can't be
+/// read or parsed is skipped with a warning, leaving the built-in entries and
+/// the other files in effect.
+pub fn load_toml_dir<T: DeserializeOwned>(dir: &Path) -> Vec<T> {
+    let mut paths: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "toml"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+
+    let mut files = Vec::new();
+    for path in paths {
+        let parsed = fs::read_to_string(&path)
+            .context("read failed")
+            .and_then(|text| toml::from_str(&text).context("parse failed"));
+        match parsed {
+            Ok(file) => {
+                debug!("Loaded {}", path.display());
+                files.push(file);
+            }
+            Err(e) => warn!("Ignoring {}: {:#}", path.display(), e),
+        }
+    }
+    files
+}
+
+/// Put drop-in entries ahead of the built-in ones so they match first; an
+/// override with the same name as a built-in entry replaces it
+pub fn merge_by_name<T>(overrides: Vec<T>, builtin: Vec<T>, name: impl Fn(&T) -> &str) -> Vec<T> {
+    let mut merged = overrides;
+    for entry in builtin {
+        if !merged.iter().any(|e| name(e) == name(&entry)) {
+            merged.push(entry);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Entry {
+        name: String,
+        value: u32,
+    }
+
+    #[test]
+    fn test_load_toml_dir_skips_bad_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("b.toml"), "name = \"b\"\nvalue = 2\n").unwrap();
+        fs::write(dir.path().join("a.toml"), "name = \"a\"\nvalue = 1\n").unwrap();
+        fs::write(dir.path().join("broken.toml"), "name = \"c\"\n").unwrap();
+        fs::write(dir.path().join("notes.txt"), "name = \"d\"\nvalue = 4\n").unwrap();
+
+        let entries: Vec<Entry> = load_toml_dir(dir.path());
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+        assert!(load_toml_dir::<Entry>(&dir.path().join("missing")).is_empty());
+
+        let builtin = vec![Entry { name: "a".into(), value: 10 }, Entry { name: "z".into(), value: 26 }];
+        let merged = merge_by_name(entries, builtin, |e| &e.name);
+        let merged: Vec<_> = merged.iter().map(|e| (e.name.as_str(), e.value)).collect();
+        assert_eq!(merged, [("a", 1), ("b", 2), ("z", 26)]);
+    }
 }
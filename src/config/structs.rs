@@ -1,4 +1,7 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
@@ -14,6 +17,8 @@ pub struct Config {
     pub backend: BackendConfig,
     #[serde(default)]
     pub governor: GovernorConfig,
+    #[serde(default)]
+    pub firmware: FirmwareConfig,
 }
 
 impl Default for Config {
@@ -25,6 +30,7 @@ impl Default for Config {
             system: SystemConfig::default(),
             backend: BackendConfig::default(),
             governor: GovernorConfig::default(),
+            firmware: FirmwareConfig::default(),
         }
     }
 }
@@ -74,12 +80,34 @@ impl Default for WifiConfig {
 }
 
 #[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
 pub struct PowerConfig {
     #[allow(dead_code)]
     #[serde(default = "default_true")]
     pub enabled: bool,
     #[serde(default = "default_adaptive")]
     pub wlan_power_save: String, // "on", "off", "adaptive"
+    /// Pin the power tier ("performance", "balanced", "saver"); unset picks it
+    /// from power source, battery and temperature
+    pub tier: Option<PowerTier>,
+    /// Switch to saver below this battery percentage
+    pub saver_below_battery_pct: u32,
+    /// Switch to saver when the battery would last less than this (minutes)
+    pub saver_below_minutes_left: u32,
+    /// Leave performance when the hottest thermal zone reaches this (°C)
+    pub balanced_above_temp_c: f64,
+    /// Switch to saver on battery when the hottest thermal zone reaches this (°C)
+    pub saver_above_temp_c: f64,
+}
+
+/// How much latency to trade for battery life, least to most
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerTier {
+    #[default]
+    Performance,
+    Balanced,
+    Saver,
 }
 
 fn default_true() -> bool { true }
@@ -90,15 +118,52 @@ impl Default for PowerConfig {
         Self {
             enabled: true,
             wlan_power_save: "adaptive".to_string(),
+            tier: None,
+            saver_below_battery_pct: 15,
+            saver_below_minutes_left: 20,
+            balanced_above_temp_c: 85.0,
+            saver_above_temp_c: 95.0,
         }
     }
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(default)]
 pub struct SystemConfig {
     pub sysctl_enabled: bool,
     pub irq_affinity_enabled: bool,
     pub driver_tweaks_enabled: bool,
+    /// latency (default), throughput, battery or minimal
+    pub sysctl_profile: SysctlProfile,
+    /// Extra sysctls, overriding the profile's value for the same key
+    pub sysctl_extra: BTreeMap<String, String>,
+}
+
+/// A named set of sysctls
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SysctlProfile {
+    /// Low queueing delay for gaming and streaming (default)
+    #[default]
+    Latency,
+    /// Large buffers for bulk transfers and fast links
+    Throughput,
+    /// Fewer wakeups on handhelds and laptops
+    Battery,
+    /// Congestion control and fast open only
+    Minimal,
+}
+
+impl fmt::Display for SysctlProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SysctlProfile::Latency => "latency",
+            SysctlProfile::Throughput => "throughput",
+            SysctlProfile::Battery => "battery",
+            SysctlProfile::Minimal => "minimal",
+        };
+        f.write_str(name)
+    }
 }
 
 impl Default for SystemConfig {
@@ -107,19 +172,39 @@ impl Default for SystemConfig {
             sysctl_enabled: true,
             irq_affinity_enabled: true,
             driver_tweaks_enabled: true,
+            sysctl_profile: SysctlProfile::default(),
+            sysctl_extra: BTreeMap::new(),
         }
     }
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(default)]
 pub struct BackendConfig {
+    /// Disable iwd's periodic background scans while connected (DisablePeriodicScan)
     pub iwd_periodic_scan_disable: bool,
+    /// Remove wpa_supplicant's bgscan while connected; this also stops it roaming
+    pub wpa_bgscan_disable: bool,
+    /// wpa_supplicant bgscan interval below the roam threshold (seconds)
+    pub bgscan_short_interval_secs: u32,
+    /// wpa_supplicant bgscan interval above the roam threshold (seconds)
+    pub bgscan_long_interval_secs: u32,
+    /// Signal (dBm) below which wpa_supplicant scans more often to roam,
+    /// and iwd's background scans are no longer suppressed
+    pub roam_threshold_dbm: i32,
+    /// wpa_supplicant autoscan while disconnected ("" leaves it alone)
+    pub wpa_autoscan: String,
 }
 
 impl Default for BackendConfig {
     fn default() -> Self {
         Self {
             iwd_periodic_scan_disable: true,
+            wpa_bgscan_disable: false,
+            bgscan_short_interval_secs: 120,  // NM uses 30
+            bgscan_long_interval_secs: 86400,
+            roam_threshold_dbm: -75,           // Same as iwd RoamThreshold
+            wpa_autoscan: "exponential:3:300".to_string(),
         }
     }
 }
@@ -142,6 +227,8 @@ pub struct GovernorConfig {
     pub cake_hysteresis_up: u32,
     /// Hysteresis ticks for bandwidth DECREASES (fast, prevents bufferbloat)
     pub cake_hysteresis_down: u32,
+    /// WAN uplink capacity per network (CAKE = min(WAN, link))
+    pub wan: WanConfig,
     
     /// Enable game mode detection via PPS
     pub game_mode_enabled: bool,
@@ -161,6 +248,11 @@ pub struct GovernorConfig {
     pub cpu_coalescing_enabled: bool,
     /// CPU load threshold for coalescing (0.0-1.0)
     pub cpu_coalescing_threshold: f64,
+
+    /// Move IRQs and RPS/XPS steering off CPU cores the game saturates
+    pub irq_steering_enabled: bool,
+    /// Per-core load (0.0-1.0) above which network work is moved away
+    pub irq_steering_threshold: f64,
     
     /// Rolling average window size for CPU monitoring
     pub cpu_avg_window_size: usize,
@@ -171,6 +263,47 @@ pub struct GovernorConfig {
     /// Disables roaming and band steering while active (scans resume if disconnected).
     #[serde(default = "default_true")]
     pub scan_suppress: bool,
+
+    /// Also react to /run/hifi-wifi/connection-changed (touched by the NM
+    /// dispatcher script). Always on when NM/iwd signals are unavailable.
+    #[serde(default)]
+    pub connection_event_file: bool,
+}
+
+/// WAN-aware CAKE sizing
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WanConfig {
+    /// Cap CAKE at the WAN uplink when one is known
+    pub enabled: bool,
+    /// Uplink for networks without a profile or measurement (Mbit)
+    pub upload_mbit: Option<u32>,
+    /// Fraction of the WAN uplink CAKE shapes to (must sit below the ISP's queue)
+    pub shaping_factor: f64,
+    /// Per-network capacities
+    pub profiles: Vec<WanProfile>,
+}
+
+impl Default for WanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            upload_mbit: None,
+            shaping_factor: 0.95,
+            profiles: Vec::new(),
+        }
+    }
+}
+
+/// WAN capacity of one network
+#[derive(Debug, Clone, Deserialize)]
+pub struct WanProfile {
+    /// SSID for WiFi, interface name for Ethernet
+    pub network: String,
+    pub upload_mbit: Option<u32>,
+    /// Informational: CAKE shapes egress only
+    #[allow(dead_code)]
+    pub download_mbit: Option<u32>,
 }
 
 impl Default for GovernorConfig {
@@ -183,6 +316,7 @@ impl Default for GovernorConfig {
             cake_overhead_factor: 0.85,        // 85% of link bandwidth
             cake_hysteresis_up: 3,             // 3 ticks (6 sec) for increases
             cake_hysteresis_down: 1,           // 1 tick (2 sec) for decreases - FAST
+            wan: WanConfig::default(),
             
             game_mode_enabled: true,
             game_mode_pps_threshold: 200,
@@ -194,11 +328,39 @@ impl Default for GovernorConfig {
             
             cpu_coalescing_enabled: true,
             cpu_coalescing_threshold: 0.90,
+
+            irq_steering_enabled: true,
+            irq_steering_threshold: 0.85,
             
             cpu_avg_window_size: 3,
 
             scan_suppress: true,
+            connection_event_file: false,
         }
     }
 }
 
+/// Where `hifi-wifi firmware` gets linux-firmware from
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct FirmwareConfig {
+    /// gitlab (default), http (a mirror), dir or git (a local clone)
+    pub source: SourceKind,
+    /// Base URL of the mirror, laid out like linux-firmware.git
+    pub url: Option<String>,
+    /// Local directory or linux-firmware.git clone
+    pub path: Option<PathBuf>,
+    /// Tag, branch or commit to install from (gitlab and git; default: latest)
+    pub rev: Option<String>,
+}
+
+/// Kind of firmware source
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    #[default]
+    Gitlab,
+    Http,
+    Dir,
+    Git,
+}
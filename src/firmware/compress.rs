@@ -0,0 +1,97 @@
+//! Firmware file compression
+//!
+//! Distros ship /lib/firmware plain, zstd-compressed (SteamOS, Arch) or
+//! xz-compressed (Fedora). Updated files are written the way the installed
+//! ones are, using the system `zstd` and `xz` commands.
+
+use anyhow::{Result, Context, bail};
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Zstd compression level (match SteamOS default)
+const ZSTD_COMPRESSION_LEVEL: i32 = 19;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Zstd,
+    Xz,
+    None,
+}
+
+impl Compression {
+    /// Suffix added to the firmware file name
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Compression::Zstd => ".zst",
+            Compression::Xz => ".xz",
+            Compression::None => "",
+        }
+    }
+
+    /// How `name` is stored in `dir`, if it is there at all
+    pub fn detect(dir: &Path, name: &str) -> Option<Self> {
+        [Compression::Zstd, Compression::Xz, Compression::None]
+            .into_iter()
+            .find(|c| dir.join(format!("{}{}", name, c.suffix())).is_file())
+    }
+
+    /// Read and decompress a firmware file
+    pub fn read(self, path: &Path) -> Result<Vec<u8>> {
+        let tool = match self {
+            Compression::Zstd => "zstd",
+            Compression::Xz => "xz",
+            Compression::None => {
+                return fs::read(path).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+
+        let output = Command::new(tool)
+            .args(["-d", "-c"])
+            .arg(path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .with_context(|| format!("Failed to run {} to decompress {}", tool, path.display()))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            bail!("{} decompression failed for {}: {}", tool, path.display(), stderr);
+        }
+
+        Ok(output.stdout)
+    }
+
+    /// Compress `src` into `dst`
+    pub fn write(self, src: &Path, dst: &Path) -> Result<()> {
+        let output = match self {
+            Compression::Zstd => Command::new("zstd")
+                .arg(format!("-{}", ZSTD_COMPRESSION_LEVEL))
+                .arg("-f")  // force overwrite
+                .arg("-o")
+                .arg(dst)
+                .arg(src)
+                .output()
+                .with_context(|| format!("Failed to run zstd to compress {}", src.display()))?,
+            // The kernel only accepts xz firmware with CRC32 checks
+            Compression::Xz => Command::new("xz")
+                .args(["-C", "crc32", "-c"])
+                .arg(src)
+                .stdout(File::create(dst).with_context(|| format!("Failed to create {}", dst.display()))?)
+                .stderr(Stdio::piped())
+                .output()
+                .with_context(|| format!("Failed to run xz to compress {}", src.display()))?,
+            Compression::None => {
+                fs::copy(src, dst).with_context(|| format!("Failed to copy {}", src.display()))?;
+                return Ok(());
+            }
+        };
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            bail!("Compression failed for {}: {}", src.display(), stderr);
+        }
+
+        Ok(())
+    }
+}
@@ -15,10 +15,9 @@ use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
-use crate::firmware::version::FirmwareVersion;
-
-/// Files we manage (NOT Data.msc - that's Valve-specific)
-const MANAGED_FILES: &[&str] = &["amss.bin.zst", "m3.bin.zst", "board-2.bin.zst"];
+use crate::firmware::compress::Compression;
+use crate::firmware::targets::Installed;
+use crate::firmware::version::{extract_version, FirmwareVersion};
 
 /// Backup file suffix
 const BACKUP_SUFFIX: &str = ".hifi-backup";
@@ -26,15 +25,12 @@ const BACKUP_SUFFIX: &str = ".hifi-backup";
 /// Backup metadata filename
 const BACKUP_METADATA_FILE: &str = ".hifi-backup.json";
 
-/// Zstd compression level (match SteamOS default)
-const ZSTD_COMPRESSION_LEVEL: i32 = 19;
-
 /// Backup metadata stored alongside backup files
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BackupInfo {
     /// Date backup was created
     pub backup_date: DateTime<Utc>,
-    /// Whether backup is Valve stock firmware
+    /// Whether backup is the device vendor's stock firmware (Valve on the Steam Deck)
     pub is_valve_stock: bool,
     /// Firmware version string
     pub version: String,
@@ -51,20 +47,22 @@ pub struct FileHash {
 
 /// Backup manager
 pub struct BackupManager {
+    installed: Installed,
     firmware_path: PathBuf,
 }
 
 impl BackupManager {
-    /// Create a new backup manager
-    pub fn new(firmware_path: &Path) -> Self {
+    /// Create a backup manager for a target's installed files
+    pub fn new(installed: &Installed) -> Self {
         Self {
-            firmware_path: firmware_path.to_path_buf(),
+            installed: installed.clone(),
+            firmware_path: installed.path.clone(),
         }
     }
 
     /// Check if backup files exist
     pub fn backup_files_exist(&self) -> bool {
-        MANAGED_FILES.iter().all(|f| {
+        self.installed.files().iter().all(|f| {
             self.firmware_path.join(format!("{}{}", f, BACKUP_SUFFIX)).exists()
         })
     }
@@ -85,7 +83,7 @@ impl BackupManager {
         let mut files = std::collections::HashMap::new();
 
         // Copy each managed file to backup
-        for filename in MANAGED_FILES {
+        for filename in &self.installed.files() {
             let src = self.firmware_path.join(filename);
             let dst = self.firmware_path.join(format!("{}{}", filename, BACKUP_SUFFIX));
 
@@ -109,7 +107,7 @@ impl BackupManager {
         // Write metadata
         let info = BackupInfo {
             backup_date: Utc::now(),
-            is_valve_stock: current_version.is_valve_stock(),
+            is_valve_stock: self.installed.target.is_stock(current_version),
             version: current_version.version_string.clone(),
             files,
         };
@@ -153,57 +151,38 @@ impl BackupManager {
 
     /// Extract version from backup (when metadata is missing)
     pub fn extract_backup_version(&self) -> Result<String> {
-        let backup_amss = self.firmware_path.join(format!("amss.bin.zst{}", BACKUP_SUFFIX));
-
-        if !backup_amss.exists() {
-            bail!("Backup amss.bin.zst not found");
-        }
-
-        // Decompress using system zstd command
-        let output = Command::new("zstd")
-            .args(["-d", "-c"])
-            .arg(&backup_amss)
-            .output()
-            .context("Failed to run zstd to decompress backup")?;
+        let version_path = self.installed.version_path();
+        let backup = PathBuf::from(format!("{}{}", version_path.display(), BACKUP_SUFFIX));
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            bail!("zstd decompression failed: {}", stderr);
+        if !backup.exists() {
+            bail!("Backup {} not found", backup.display());
         }
 
-        let data = &output.stdout;
-        let pattern = b"QC_IMAGE_VERSION_STRING=";
-        if let Some(pos) = data.windows(pattern.len()).position(|w| w == pattern) {
-            let start = pos + pattern.len();
-            let mut end = start;
-            while end < data.len() && data[end] >= 0x20 && data[end] < 0x7F {
-                end += 1;
-            }
-            if end > start {
-                return Ok(String::from_utf8_lossy(&data[start..end]).to_string());
-            }
-        }
-
-        bail!("Could not extract version from backup")
+        let data = self.installed.compression.read(&backup)
+            .context("Failed to decompress backup")?;
+        extract_version(&data, self.installed.target.version)
+            .context("Could not extract version from backup")
     }
 }
 
 /// Firmware deployer
 pub struct FirmwareDeployer {
+    installed: Installed,
     firmware_path: PathBuf,
 }
 
 impl FirmwareDeployer {
-    /// Create a new deployer
-    pub fn new(firmware_path: &Path) -> Self {
+    /// Create a deployer for a target's installed files
+    pub fn new(installed: &Installed) -> Self {
         Self {
-            firmware_path: firmware_path.to_path_buf(),
+            installed: installed.clone(),
+            firmware_path: installed.path.clone(),
         }
     }
 
     /// Deploy firmware from staging directory
     ///
-    /// Compresses files and copies them atomically
+    /// Compresses files the way the installed ones are and copies them atomically
     pub fn deploy(&self, staging_dir: &Path) -> Result<()> {
         // Handle SteamOS readonly filesystem
         let is_steamos = is_steamos();
@@ -225,24 +204,23 @@ impl FirmwareDeployer {
 
     /// Inner deploy logic (separated for readonly handling)
     fn deploy_inner(&self, staging_dir: &Path) -> Result<()> {
-        // Map of source filename (without .zst) to compressed destination
-        let files = [
-            ("amss.bin", "amss.bin.zst"),
-            ("m3.bin", "m3.bin.zst"),
-            ("board-2.bin", "board-2.bin.zst"),
-        ];
-
-        // Phase 1: Compress all files to staging with .zst extension
-        for (src_name, _dst_name) in &files {
-            let src = staging_dir.join(src_name);
-            let compressed = staging_dir.join(format!("{}.zst", src_name));
-
-            compress_file(&src, &compressed)?;
+        // Map of source filename (e.g. amss.bin) to installed destination (amss.bin.zst)
+        let compression = self.installed.compression;
+        let files: Vec<(String, String)> = self.installed.target.files.iter()
+            .map(|f| f.name.clone())
+            .zip(self.installed.files())
+            .collect();
+
+        // Phase 1: Compress all files to staging with the installed suffix
+        if compression != Compression::None {
+            for (src_name, dst_name) in &files {
+                compression.write(&staging_dir.join(src_name), &staging_dir.join(dst_name))?;
+            }
         }
 
         // Phase 2: Copy to firmware directory with .new suffix
         for (_src_name, dst_name) in &files {
-            let src = staging_dir.join(format!("{}", dst_name));
+            let src = staging_dir.join(dst_name);
             let dst_new = self.firmware_path.join(format!("{}.new", dst_name));
 
             fs::copy(&src, &dst_new)
@@ -282,8 +260,10 @@ impl FirmwareDeployer {
 
     /// Inner restore logic
     fn restore_inner(&self) -> Result<()> {
+        let files = self.installed.files();
+
         // Phase 1: Copy backups to .new
-        for filename in MANAGED_FILES {
+        for filename in &files {
             let backup = self.firmware_path.join(format!("{}{}", filename, BACKUP_SUFFIX));
             let dst_new = self.firmware_path.join(format!("{}.new", filename));
 
@@ -296,7 +276,7 @@ impl FirmwareDeployer {
         }
 
         // Phase 2: Atomic rename
-        for filename in MANAGED_FILES {
+        for filename in &files {
             let dst_new = self.firmware_path.join(format!("{}.new", filename));
             let dst_final = self.firmware_path.join(filename);
 
@@ -325,25 +305,6 @@ fn calculate_file_hash(path: &Path) -> Result<String> {
     Ok(format!("{:x}", hasher.finalize()))
 }
 
-/// Compress a file with zstd using system command
-fn compress_file(src: &Path, dst: &Path) -> Result<()> {
-    let output = Command::new("zstd")
-        .arg(format!("-{}", ZSTD_COMPRESSION_LEVEL))
-        .arg("-f")  // force overwrite
-        .arg("-o")
-        .arg(dst)
-        .arg(src)
-        .output()
-        .with_context(|| format!("Failed to run zstd to compress {}", src.display()))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("zstd compression failed for {}: {}", src.display(), stderr);
-    }
-
-    Ok(())
-}
-
 /// Check if running on SteamOS
 pub fn is_steamos() -> bool {
     if let Ok(content) = fs::read_to_string("/etc/os-release") {
@@ -1,61 +1,27 @@
 //! Firmware download from linux-firmware.git
 //!
-//! Downloads firmware files from GitLab and validates them before deployment.
+//! Fetches firmware files from the configured source (GitLab by default) and
+//! validates them before deployment.
 
 use anyhow::{Result, Context, bail};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
+use crate::firmware::source::Source;
+use crate::firmware::targets::{FirmwareFile, Target, VersionMethod};
 use crate::firmware::version::FirmwareVersion;
 
-/// Base URL for linux-firmware.git raw files
-const FIRMWARE_BASE_URL: &str = "https://gitlab.com/kernel-firmware/linux-firmware/-/raw/main/ath11k/QCA2066/hw2.1";
-
-/// Firmware files to download
-const FIRMWARE_FILES: &[FirmwareFile] = &[
-    FirmwareFile {
-        name: "amss.bin",
-        min_size: 5_000_000,  // ~5.3MB actual
-        description: "Main WiFi firmware",
-    },
-    FirmwareFile {
-        name: "m3.bin",
-        min_size: 200_000,    // ~260KB actual
-        description: "M3 microcontroller firmware",
-    },
-    FirmwareFile {
-        name: "board-2.bin",
-        min_size: 500_000,    // ~745KB actual
-        description: "Board configuration data",
-    },
-];
-
-/// Firmware file metadata
-struct FirmwareFile {
-    name: &'static str,
-    min_size: u64,
-    #[allow(dead_code)]
-    description: &'static str,
+/// Firmware downloader
+pub struct FirmwareDownloader {
+    target: Target,
+    source: Source,
 }
 
-/// Firmware downloader using system curl
-pub struct FirmwareDownloader;
-
 impl FirmwareDownloader {
-    /// Create a new downloader
-    pub fn new() -> Result<Self> {
-        // Verify curl is available
-        let output = Command::new("curl")
-            .arg("--version")
-            .output()
-            .context("curl command not found - please install curl")?;
-
-        if !output.status.success() {
-            bail!("curl command failed");
-        }
-
-        Ok(Self)
+    /// Create a downloader for a target's files
+    pub fn new(target: &Target, source: &Source) -> Result<Self> {
+        source.check()?;
+        Ok(Self { target: target.clone(), source: source.clone() })
     }
 
     /// Download all firmware files to a staging directory
@@ -69,37 +35,29 @@ impl FirmwareDownloader {
             .context("Failed to create staging directory")?
             .keep();
 
-        for file in FIRMWARE_FILES {
+        for file in &self.target.files {
             self.download_file(file, &staging_dir)?;
         }
 
         Ok(staging_dir)
     }
 
-    /// Download a single firmware file using curl
+    /// Fetch a single firmware file from the source
     fn download_file(&self, file: &FirmwareFile, staging_dir: &Path) -> Result<()> {
-        let url = format!("{}/{}", FIRMWARE_BASE_URL, file.name);
-        let dest_path = staging_dir.join(file.name);
+        let dest_path = staging_dir.join(&file.name);
 
         print!("  Downloading {}... ", file.name);
         std::io::Write::flush(&mut std::io::stdout()).ok();
 
-        let output = Command::new("curl")
-            .args([
-                "-sfL",                              // silent, fail on error, follow redirects
-                "--max-time", "120",                 // 2 min timeout for large files
-                "-o",
-            ])
-            .arg(&dest_path)
-            .arg(&url)
-            .output()
-            .with_context(|| format!("Failed to run curl to download {}", file.name))?;
-
-        if !output.status.success() {
-            println!("FAILED");
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            bail!("Failed to download {}: {}", file.name, stderr);
-        }
+        let data = match self.source.read(&self.target.upstream_path(&file.name), None) {
+            Ok(data) => data,
+            Err(e) => {
+                println!("FAILED");
+                return Err(e);
+            }
+        };
+        fs::write(&dest_path, data)
+            .with_context(|| format!("Failed to write {} to staging", file.name))?;
 
         // Validate size
         let metadata = fs::metadata(&dest_path)
@@ -122,11 +80,11 @@ impl FirmwareDownloader {
 
     /// Validate downloaded firmware files
     ///
-    /// Checks file sizes and verifies we can extract version from amss.bin
+    /// Checks file sizes and verifies we can extract the version (e.g. from amss.bin)
     pub fn validate(&self, staging_dir: &Path) -> Result<()> {
         // Verify all files exist and have reasonable sizes
-        for file in FIRMWARE_FILES {
-            let path = staging_dir.join(file.name);
+        for file in &self.target.files {
+            let path = staging_dir.join(&file.name);
             let metadata = fs::metadata(&path)
                 .with_context(|| format!("Missing file: {}", file.name))?;
 
@@ -141,13 +99,13 @@ impl FirmwareDownloader {
             println!("OK ({} bytes)", metadata.len());
         }
 
-        // Verify we can extract version from amss.bin (proves it's valid firmware)
+        // Verify we can extract the version (proves it's valid firmware)
         print!("  Extracting version... ");
-        let amss_path = staging_dir.join("amss.bin");
-        let version = FirmwareVersion::from_raw(&amss_path)
+        let version_path = staging_dir.join(self.target.version_file());
+        let version = FirmwareVersion::from_raw(&version_path, self.target.version)
             .context("Failed to extract version from downloaded firmware")?;
 
-        if !version.version_string.contains("WLAN") {
+        if self.target.version == VersionMethod::QcImage && !version.version_string.contains("WLAN") {
             bail!("Downloaded firmware has unexpected version format: {}", version.version_string);
         }
 
@@ -164,7 +122,10 @@ mod tests {
     #[test]
     #[ignore]  // Requires network access
     fn test_download() {
-        let downloader = FirmwareDownloader::new().unwrap();
+        let device = crate::firmware::device::DeviceInfo::detect();
+        let installed = crate::firmware::targets::detect(&device).unwrap();
+        let source = crate::firmware::source::Source::from_config(&Default::default(), None).unwrap();
+        let downloader = FirmwareDownloader::new(&installed.target, &source).unwrap();
         let staging = downloader.download_all().unwrap();
         downloader.validate(&staging).unwrap();
 
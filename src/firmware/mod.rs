@@ -1,21 +1,33 @@
-//! Firmware update module for Steam Deck OLED WiFi (QCA2066/ath11k)
+//! Firmware update module for WiFi cards (ath11k/ath12k, iwlwifi, rtw89, mt7921)
 //!
 //! This module provides firmware management capabilities:
 //! - `status`: Show current vs latest available firmware version
 //! - `update`: Download and install latest upstream firmware from linux-firmware.git
 //! - `revert`: Restore original stock firmware from backup
+//! - `versions`: List firmware versions across linux-firmware.git commits
 //!
-//! **Hardware gate**: Only runs on Steam Deck OLED (Galileo) with QCA2066 WiFi card.
+//! Firmware comes from GitLab unless `[firmware]` in config.toml names a
+//! mirror, local directory or local clone (see `source`).
+//!
+//! **Hardware gate**: Only runs on WiFi cards with a firmware target (see
+//! `targets.toml`), and on the boards a target is limited to (the Steam Deck
+//! OLED's QCA2066 only on Galileo).
 
 pub mod device;
 pub mod version;
 pub mod download;
 pub mod deploy;
+pub mod compress;
+pub mod targets;
+pub mod source;
 
 use anyhow::{Result, bail, Context};
 use clap::Subcommand;
 
+use crate::config::structs::FirmwareConfig;
 use crate::firmware::device::DeviceInfo;
+use crate::firmware::source::Source;
+use crate::firmware::targets::Installed;
 use crate::firmware::version::{FirmwareVersion, get_upstream_version};
 use crate::firmware::deploy::{BackupManager, FirmwareDeployer, is_steamos, disable_readonly, enable_readonly};
 use crate::firmware::download::FirmwareDownloader;
@@ -31,12 +43,18 @@ pub enum FirmwareAction {
         /// Skip fetching upstream version (offline mode)
         #[arg(long)]
         offline: bool,
+        /// linux-firmware tag, branch or commit to compare against
+        #[arg(long)]
+        rev: Option<String>,
     },
     /// Download and install latest upstream firmware
     Update {
         /// Skip confirmation prompts
         #[arg(long, short = 'y')]
         force: bool,
+        /// linux-firmware tag, branch or commit to install (overrides [firmware] rev)
+        #[arg(long)]
+        rev: Option<String>,
     },
     /// Revert to original stock firmware from backup
     Revert {
@@ -44,14 +62,25 @@ pub enum FirmwareAction {
         #[arg(long, short = 'y')]
         force: bool,
     },
+    /// List firmware versions across the commits of a local linux-firmware.git clone
+    Versions {
+        /// Number of commits to show
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
 }
 
 /// Main entry point for firmware commands
-pub fn run_firmware(action: FirmwareAction, dry_run: bool) -> Result<()> {
+pub fn run_firmware(action: FirmwareAction, dry_run: bool, config: &FirmwareConfig) -> Result<()> {
     match action {
-        FirmwareAction::Status { json, offline } => run_status(json, offline),
-        FirmwareAction::Update { force } => run_update(force, dry_run),
+        FirmwareAction::Status { json, offline, rev } => {
+            run_status(json, offline, &Source::from_config(config, rev.as_deref())?)
+        }
+        FirmwareAction::Update { force, rev } => {
+            run_update(force, dry_run, &Source::from_config(config, rev.as_deref())?)
+        }
         FirmwareAction::Revert { force } => run_revert(force, dry_run),
+        FirmwareAction::Versions { limit } => run_versions(limit, &Source::from_config(config, None)?),
     }
 }
 
@@ -67,24 +96,27 @@ mod colors {
 }
 
 /// Run firmware status check
-fn run_status(json: bool, offline: bool) -> Result<()> {
+fn run_status(json: bool, offline: bool, source: &Source) -> Result<()> {
     use colors::*;
 
-    // Detect device (informational only for status - don't gate)
+    // Detect device (informational only for status - don't gate on the board)
     let device = DeviceInfo::detect();
 
-    // Get firmware path and current version
-    let firmware_path = version::detect_firmware_path()?;
-    let current = FirmwareVersion::from_installed(&firmware_path)?;
+    // Get target, firmware path and current version
+    let installed = targets::detect(&device)?;
+    let target = &installed.target;
+    let supported = target.supports_board(&device);
+    let firmware_path = installed.path.clone();
+    let current = FirmwareVersion::from_installed(&installed)?;
 
     // Check for interrupted update
-    let health_warnings = check_health(&firmware_path);
+    let health_warnings = check_health(&installed);
 
     // Get upstream version (unless offline mode)
     let upstream = if offline {
         None
     } else {
-        match get_upstream_version() {
+        match get_upstream_version(target, source) {
             Ok(v) => Some(v),
             Err(e) => {
                 if !json {
@@ -96,25 +128,27 @@ fn run_status(json: bool, offline: bool) -> Result<()> {
     };
 
     // Check backup status
-    let backup_mgr = BackupManager::new(&firmware_path);
+    let backup_mgr = BackupManager::new(&installed);
     let backup_info = backup_mgr.get_backup_info();
 
     if json {
         // JSON output for scripting
         let output = serde_json::json!({
             "device": {
-                "supported": device.is_supported(),
+                "supported": supported,
+                "target": target.name,
                 "board_vendor": device.board_vendor,
                 "board_name": device.board_name,
-                "wifi_vendor": device.wifi_vendor,
-                "wifi_device": device.wifi_device,
+                "wifi_vendor": installed.card.vendor,
+                "wifi_device": installed.card.device,
             },
             "firmware": {
                 "path": firmware_path.to_string_lossy(),
                 "current_version": current.version_string,
-                "is_valve_stock": current.is_valve_stock(),
+                "is_valve_stock": target.is_stock(&current),
             },
             "upstream": upstream.as_ref().map(|u| &u.version_string),
+            "source": source.to_string(),
             "backup": backup_info.as_ref().map(|b| serde_json::json!({
                 "version": b.version,
                 "date": b.backup_date.to_rfc3339(),
@@ -134,8 +168,11 @@ fn run_status(json: bool, offline: bool) -> Result<()> {
     println!();
 
     // Device info
-    if device.is_supported() {
-        println!("{}Device:{}\t\tSteam Deck OLED (Galileo) {}✓{}", BOLD, NC, GREEN, NC);
+    if supported {
+        println!("{}Device:{}\t\t{} {} {}✓{}", BOLD, NC,
+                 device.board_vendor.as_deref().unwrap_or("Unknown"),
+                 device.board_name.as_deref().unwrap_or("Unknown"),
+                 GREEN, NC);
     } else {
         println!("{}Device:{}\t\t{} {} {}[Not Supported]{}", BOLD, NC,
                  device.board_vendor.as_deref().unwrap_or("Unknown"),
@@ -144,14 +181,7 @@ fn run_status(json: bool, offline: bool) -> Result<()> {
     }
 
     // WiFi card info
-    if device.is_wifi_supported() {
-        println!("{}WiFi Card:{}\t\tQCA2066 (17cb:1103) {}✓{}", BOLD, NC, GREEN, NC);
-    } else {
-        println!("{}WiFi Card:{}\t\t{}:{} {}[Not Supported]{}", BOLD, NC,
-                 device.wifi_vendor.as_deref().unwrap_or("????"),
-                 device.wifi_device.as_deref().unwrap_or("????"),
-                 RED, NC);
-    }
+    println!("{}WiFi Card:{}\t\t{} ({}) {}✓{}", BOLD, NC, target.name, installed.card.id().unwrap_or_default(), GREEN, NC);
 
     println!("{}Firmware Path:{}\t{}", BOLD, NC, firmware_path.display());
     println!();
@@ -165,20 +195,22 @@ fn run_status(json: bool, offline: bool) -> Result<()> {
     }
 
     // Version info
-    let stock_indicator = if current.is_valve_stock() {
-        format!(" {}(Valve stock){}", DIM, NC)
+    let label = target.version_label(&current);
+    let stock_indicator = if label.is_empty() {
+        String::new()
     } else {
-        format!(" {}(upstream){}", DIM, NC)
+        format!(" {}{}{}", DIM, label, NC)
     };
     println!("{}Current:{}\t\t{}{}", BOLD, NC, current.version_string, stock_indicator);
 
     if let Some(ref upstream_ver) = upstream {
         println!("{}Latest upstream:{}\t{}", BOLD, NC, upstream_ver.version_string);
+        println!("{}Source:{}\t\t{}", BOLD, NC, source);
 
         // Status
         if upstream_ver.version_string == current.version_string {
             println!("{}Status:{}\t\t{}Up to date{}", BOLD, NC, GREEN, NC);
-        } else if current.is_valve_stock() {
+        } else if target.is_stock(&current) {
             println!("{}Status:{}\t\t{}Update available{}", BOLD, NC, YELLOW, NC);
         } else {
             // Already on upstream but different version
@@ -188,7 +220,11 @@ fn run_status(json: bool, offline: bool) -> Result<()> {
 
     // Backup info
     if let Some(ref backup) = backup_info {
-        let stock_str = if backup.is_valve_stock { " (Valve stock)" } else { "" };
+        let stock_str = if backup.is_valve_stock {
+            format!(" ({} stock)", target.stock_vendor.as_deref().unwrap_or("vendor"))
+        } else {
+            String::new()
+        };
         println!("{}Backup:{}\t\t{}{} ({})", BOLD, NC, backup.version, stock_str,
                  backup.backup_date.format("%Y-%m-%d"));
     } else {
@@ -198,8 +234,9 @@ fn run_status(json: bool, offline: bool) -> Result<()> {
     println!();
 
     // Helpful hints
-    if !device.is_supported() {
-        println!("{}Note:{} Firmware updates are only available on Steam Deck OLED.", DIM, NC);
+    if !supported {
+        println!("{}Note:{} Firmware updates for the {} are only available on {}.", DIM, NC,
+                 target.name, target.boards.join(", "));
     } else if upstream.as_ref().map(|u| u.version_string != current.version_string).unwrap_or(false) {
         println!("{}Tip:{} Run 'sudo hifi-wifi firmware update' to install the latest firmware.", DIM, NC);
     }
@@ -208,19 +245,19 @@ fn run_status(json: bool, offline: bool) -> Result<()> {
 }
 
 /// Check for health issues (interrupted updates, missing files, etc.)
-fn check_health(firmware_path: &std::path::Path) -> Vec<String> {
+fn check_health(installed: &Installed) -> Vec<String> {
     let mut warnings = Vec::new();
 
     // Check for .new files (interrupted update)
-    for file in &["amss.bin.zst.new", "m3.bin.zst.new", "board-2.bin.zst.new"] {
-        if firmware_path.join(file).exists() {
+    for file in installed.files() {
+        if installed.path.join(format!("{}.new", file)).exists() {
             warnings.push("Interrupted update detected. Run 'hifi-wifi firmware update' to complete.".to_string());
             break;
         }
     }
 
     // Check for backup without metadata
-    let backup_mgr = BackupManager::new(firmware_path);
+    let backup_mgr = BackupManager::new(installed);
     if backup_mgr.backup_files_exist() && backup_mgr.get_backup_info().is_none() {
         warnings.push("Backup metadata missing. Integrity cannot be verified.".to_string());
     }
@@ -229,7 +266,7 @@ fn check_health(firmware_path: &std::path::Path) -> Vec<String> {
 }
 
 /// Run firmware update
-fn run_update(force: bool, dry_run: bool) -> Result<()> {
+fn run_update(force: bool, dry_run: bool, source: &Source) -> Result<()> {
     use colors::*;
 
     println!();
@@ -242,32 +279,25 @@ fn run_update(force: bool, dry_run: bool) -> Result<()> {
 
     // Hardware gate
     let device = DeviceInfo::detect();
-    if !device.is_supported() {
-        bail!(
-            "Firmware updates are only supported on Steam Deck OLED (Galileo) with QCA2066 WiFi.\n\
-             Detected device: {} {}\n\
-             Detected WiFi:   {}:{} (subsystem {}:{})",
-            device.board_vendor.as_deref().unwrap_or("Unknown"),
-            device.board_name.as_deref().unwrap_or("Unknown"),
-            device.wifi_vendor.as_deref().unwrap_or("????"),
-            device.wifi_device.as_deref().unwrap_or("????"),
-            device.wifi_subsys_vendor.as_deref().unwrap_or("????"),
-            device.wifi_subsys_device.as_deref().unwrap_or("????"),
-        );
-    }
-    println!("  Device: Steam Deck OLED {}✓{}", GREEN, NC);
-    println!("  WiFi:   QCA2066 (17cb:1103) {}✓{}", GREEN, NC);
+    let installed = targets::detect(&device)?;
+    let target = &installed.target;
+    check_board(&installed, &device)?;
+    println!("  Device: {} {} {}✓{}",
+             device.board_vendor.as_deref().unwrap_or("Unknown"),
+             device.board_name.as_deref().unwrap_or("Unknown"),
+             GREEN, NC);
+    println!("  WiFi:   {} ({}) {}✓{}", target.name, installed.card.id().unwrap_or_default(), GREEN, NC);
 
     // Firmware path
-    let firmware_path = version::detect_firmware_path()?;
+    let firmware_path = installed.path.clone();
     println!("  Path:   {} {}✓{}", firmware_path.display(), GREEN, NC);
 
     // Current version
-    let current = FirmwareVersion::from_installed(&firmware_path)?;
+    let current = FirmwareVersion::from_installed(&installed)?;
     println!("  Current: {}", current.version_string);
 
     // Upstream version
-    let upstream = get_upstream_version()?;
+    let upstream = get_upstream_version(target, source)?;
     println!("  Latest:  {}", upstream.version_string);
 
     // Check if update needed
@@ -284,8 +314,9 @@ fn run_update(force: bool, dry_run: bool) -> Result<()> {
     if dry_run {
         println!();
         println!("{}[DRY-RUN]{} Would download and install firmware.", YELLOW, NC);
-        println!("  Files: amss.bin, m3.bin, board-2.bin");
-        println!("  From:  linux-firmware.git (GitLab)");
+        let files: Vec<&str> = target.files.iter().map(|f| f.name.as_str()).collect();
+        println!("  Files: {}", files.join(", "));
+        println!("  From:  {}", source);
         return Ok(());
     }
 
@@ -293,7 +324,7 @@ fn run_update(force: bool, dry_run: bool) -> Result<()> {
     println!();
     println!("{}[2/5]{} Downloading firmware...", DIM, NC);
 
-    let downloader = FirmwareDownloader::new()?;
+    let downloader = FirmwareDownloader::new(target, source)?;
     let staging_dir = downloader.download_all()?;
     println!("  Downloaded to staging {}✓{}", GREEN, NC);
 
@@ -315,17 +346,18 @@ fn run_update(force: bool, dry_run: bool) -> Result<()> {
 
     // Use a closure to ensure we re-enable readonly even on error
     let result = (|| -> Result<()> {
-        let backup_mgr = BackupManager::new(&firmware_path);
+        let backup_mgr = BackupManager::new(&installed);
         if !backup_mgr.backup_files_exist() {
             // First update - create backup
-            if !current.is_valve_stock() && !force {
+            if let (Some(prefix), false) = (&target.stock_prefix, target.is_stock(&current) || force) {
+                let vendor = target.stock_vendor.as_deref().unwrap_or("vendor");
                 println!();
-                println!("{}Warning:{} Current firmware is not Valve stock.", YELLOW, NC);
+                println!("{}Warning:{} Current firmware is not {} stock.", YELLOW, NC, vendor);
                 println!("  Current: {}", current.version_string);
-                println!("  Expected: CI_WLAN.HSP.1.1-... (Valve prefix)");
+                println!("  Expected: {}... ({} prefix)", prefix, vendor);
                 println!();
-                println!("Creating backup of current (modified) state. To restore true Valve");
-                println!("stock firmware, use SteamOS recovery or reinstall.");
+                println!("Creating backup of current (modified) state. To restore true {}", vendor);
+                println!("stock firmware, use the vendor's recovery image or reinstall.");
                 println!();
 
                 if !confirm("Continue with backup and update?")? {
@@ -343,7 +375,7 @@ fn run_update(force: bool, dry_run: bool) -> Result<()> {
         println!();
         println!("{}[5/5]{} Deploying firmware...", DIM, NC);
 
-        let deployer = FirmwareDeployer::new(&firmware_path);
+        let deployer = FirmwareDeployer::new(&installed);
         deployer.deploy(&staging_dir)?;
         println!("  Firmware deployed {}✓{}", GREEN, NC);
 
@@ -361,7 +393,7 @@ fn run_update(force: bool, dry_run: bool) -> Result<()> {
     result?;
 
     // Verify
-    let new_version = FirmwareVersion::from_installed(&firmware_path)?;
+    let new_version = FirmwareVersion::from_installed(&installed)?;
 
     // Cleanup staging
     let _ = std::fs::remove_dir_all(&staging_dir);
@@ -396,21 +428,14 @@ fn run_revert(force: bool, dry_run: bool) -> Result<()> {
 
     // Hardware gate
     let device = DeviceInfo::detect();
-    if !device.is_supported() {
-        bail!(
-            "Firmware management is only supported on Steam Deck OLED (Galileo) with QCA2066 WiFi.\n\
-             Detected device: {} {}",
-            device.board_vendor.as_deref().unwrap_or("Unknown"),
-            device.board_name.as_deref().unwrap_or("Unknown"),
-        );
-    }
+    let installed = targets::detect(&device)?;
+    check_board(&installed, &device)?;
 
-    // Get paths and versions
-    let firmware_path = version::detect_firmware_path()?;
-    let current = FirmwareVersion::from_installed(&firmware_path)?;
+    // Get versions
+    let current = FirmwareVersion::from_installed(&installed)?;
 
     // Check backup exists
-    let backup_mgr = BackupManager::new(&firmware_path);
+    let backup_mgr = BackupManager::new(&installed);
     let backup_info = backup_mgr.get_backup_info();
 
     if !backup_mgr.backup_files_exist() {
@@ -419,7 +444,7 @@ fn run_revert(force: bool, dry_run: bool) -> Result<()> {
              This can happen if:\n\
                - You haven't run 'hifi-wifi firmware update' yet\n\
                - The backup files were deleted\n\n\
-             To restore Valve stock firmware, use SteamOS recovery or reinstall."
+             To restore stock firmware, use the vendor's recovery image or reinstall."
         );
     }
 
@@ -450,8 +475,9 @@ fn run_revert(force: bool, dry_run: bool) -> Result<()> {
     if !force && !dry_run {
         println!();
         let stock_str = backup_info.as_ref()
-            .map(|i| if i.is_valve_stock { " (Valve stock)" } else { "" })
-            .unwrap_or("");
+            .filter(|i| i.is_valve_stock)
+            .map(|_| format!(" ({} stock)", installed.target.stock_vendor.as_deref().unwrap_or("vendor")))
+            .unwrap_or_default();
         let date_str = backup_info.as_ref()
             .map(|i| format!(" from {}", i.backup_date.format("%Y-%m-%d")))
             .unwrap_or_default();
@@ -475,14 +501,14 @@ fn run_revert(force: bool, dry_run: bool) -> Result<()> {
     println!();
     println!("{}[2/3]{} Restoring firmware...", DIM, NC);
 
-    let deployer = FirmwareDeployer::new(&firmware_path);
+    let deployer = FirmwareDeployer::new(&installed);
     deployer.restore_backup()?;
     println!("  Firmware restored {}✓{}", GREEN, NC);
 
     // Verify
     println!();
     println!("{}[3/3]{} Verifying restoration...", DIM, NC);
-    let new_version = FirmwareVersion::from_installed(&firmware_path)?;
+    let new_version = FirmwareVersion::from_installed(&installed)?;
     println!("  Version: {} {}✓{}", new_version.version_string, GREEN, NC);
 
     println!();
@@ -504,6 +530,73 @@ fn run_revert(force: bool, dry_run: bool) -> Result<()> {
     Ok(())
 }
 
+/// List the versions of this card's firmware across linux-firmware.git commits
+fn run_versions(limit: usize, source: &Source) -> Result<()> {
+    use colors::*;
+
+    let device = DeviceInfo::detect();
+    let installed = targets::detect(&device)?;
+    let target = &installed.target;
+    let current = FirmwareVersion::from_installed(&installed).ok();
+
+    source.check()?;
+    let path = target.upstream_path(target.version_file());
+    let revisions = source.history(&path, limit, |data| version::extract_version(data, target.version))?;
+
+    println!();
+    println!("{}{}WiFi Firmware Versions{}", BOLD, CYAN, NC);
+    println!("{}═══════════════════════════════════════{}", CYAN, NC);
+    println!();
+    println!("{}WiFi Card:{}\t{} ({})", BOLD, NC, target.name, installed.card.id().unwrap_or_default());
+    println!("{}Source:{}\t\t{} ({})", BOLD, NC, source, path);
+    println!();
+
+    if revisions.is_empty() {
+        println!("{}No commits touch {}.{}", DIM, path, NC);
+        return Ok(());
+    }
+
+    for revision in &revisions {
+        let version = revision.version.as_deref().unwrap_or("?");
+        let installed_mark = if current.as_ref().is_some_and(|c| c.version_string == version) {
+            format!(" {}← installed{}", GREEN, NC)
+        } else {
+            String::new()
+        };
+        println!("  {} {}  {:<10} {}{}",
+                 &revision.commit[..12.min(revision.commit.len())],
+                 revision.date,
+                 revision.release.as_deref().unwrap_or("unreleased"),
+                 version,
+                 installed_mark);
+    }
+
+    println!();
+    println!("{}Tip:{} Run 'sudo hifi-wifi firmware update --rev <tag or commit>' to install one.", DIM, NC);
+
+    Ok(())
+}
+
+/// Refuse to touch firmware of a target limited to other boards
+fn check_board(installed: &Installed, device: &DeviceInfo) -> Result<()> {
+    let target = &installed.target;
+    if !target.supports_board(device) {
+        bail!(
+            "Firmware updates for the {} are only supported on {} {}.\n\
+             Detected device: {} {}\n\
+             Detected WiFi:   {} (subsystem {})",
+            target.name,
+            target.board_vendors.join("/"),
+            target.boards.join("/"),
+            device.board_vendor.as_deref().unwrap_or("Unknown"),
+            device.board_name.as_deref().unwrap_or("Unknown"),
+            installed.card.id().unwrap_or_default(),
+            installed.card.subsystem_id().unwrap_or_default(),
+        );
+    }
+    Ok(())
+}
+
 /// Check if there's enough disk space
 fn check_disk_space(path: &std::path::Path, required_bytes: u64) -> Result<()> {
     use std::process::Command;
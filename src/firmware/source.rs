@@ -0,0 +1,304 @@
+//! Where firmware updates come from
+//!
+//! `[firmware] source` in config.toml picks one:
+//! - `gitlab`: linux-firmware.git on GitLab (default), at `rev` if set
+//! - `http`: a mirror serving files laid out like linux-firmware.git
+//! - `dir`: a local directory laid out like linux-firmware.git (an extracted
+//!   release, a network share or another machine's /lib/firmware)
+//! - `git`: a local clone of linux-firmware.git, read at `rev` without
+//!   touching the work tree; `hifi-wifi firmware versions` lists its history
+//!
+//! Nothing is fetched into a local clone: offline machines update it themselves.
+
+use anyhow::{Result, Context, bail};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::structs::{FirmwareConfig, SourceKind};
+use crate::firmware::compress::Compression;
+
+/// Raw file URL of linux-firmware.git on GitLab, followed by the revision
+const GITLAB_RAW_URL: &str = "https://gitlab.com/kernel-firmware/linux-firmware/-/raw";
+
+/// Branch used when no revision is pinned
+const GITLAB_DEFAULT_REV: &str = "main";
+
+/// A configured firmware source
+#[derive(Debug, Clone)]
+pub enum Source {
+    /// Files under a base URL (GitLab at a revision, or a mirror)
+    Http { base: String },
+    Dir { path: PathBuf },
+    Git { repo: PathBuf, rev: String },
+}
+
+/// One commit that changed a file, from `Source::history`
+#[derive(Debug, Clone)]
+pub struct Revision {
+    pub commit: String,
+    /// Commit date (YYYY-MM-DD)
+    pub date: String,
+    /// First release tag containing the commit
+    pub release: Option<String>,
+    /// Version of the file at that commit, if it could be read
+    pub version: Option<String>,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Http { base } => write!(f, "{}", base),
+            Source::Dir { path } => write!(f, "{}", path.display()),
+            Source::Git { repo, rev } => write!(f, "{} at {}", repo.display(), rev),
+        }
+    }
+}
+
+impl Source {
+    /// Build the source from `[firmware]`, with `rev` overriding the configured revision
+    pub fn from_config(config: &FirmwareConfig, rev: Option<&str>) -> Result<Self> {
+        let rev = rev.or(config.rev.as_deref());
+        if let Some(rev) = rev.filter(|r| r.starts_with('-')) {
+            bail!("Invalid firmware revision {:?}", rev);
+        }
+        let path = |kind: &str| {
+            config.path.clone()
+                .with_context(|| format!("[firmware] source = \"{}\" needs a path", kind))
+        };
+
+        Ok(match config.source {
+            SourceKind::Gitlab => Source::Http {
+                base: format!("{}/{}", GITLAB_RAW_URL, rev.unwrap_or(GITLAB_DEFAULT_REV)),
+            },
+            SourceKind::Http => {
+                let url = config.url.as_deref().context("[firmware] source = \"http\" needs a url")?;
+                if rev.is_some() {
+                    bail!("[firmware] source = \"http\" serves one version; rev only applies to gitlab and git");
+                }
+                Source::Http { base: url.trim_end_matches('/').to_string() }
+            }
+            SourceKind::Dir => {
+                if rev.is_some() {
+                    bail!("[firmware] source = \"dir\" holds one version; rev only applies to gitlab and git");
+                }
+                Source::Dir { path: path("dir")? }
+            }
+            SourceKind::Git => Source::Git {
+                repo: path("git")?,
+                rev: rev.unwrap_or("HEAD").to_string(),
+            },
+        })
+    }
+
+    /// Check the tools and paths the source needs
+    pub fn check(&self) -> Result<()> {
+        match self {
+            Source::Http { .. } => {
+                // Verify curl is available
+                let output = Command::new("curl")
+                    .arg("--version")
+                    .output()
+                    .context("curl command not found - please install curl")?;
+                if !output.status.success() {
+                    bail!("curl command failed");
+                }
+            }
+            Source::Dir { path } => {
+                if !path.is_dir() {
+                    bail!("Firmware directory {} not found", path.display());
+                }
+            }
+            Source::Git { repo, rev } => {
+                resolve(repo, rev)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a file, given by its path in linux-firmware.git
+    ///
+    /// `range` ("0-4194303") limits HTTP downloads to the part that's needed
+    pub fn read(&self, path: &str, range: Option<&str>) -> Result<Vec<u8>> {
+        match self {
+            Source::Http { base } => {
+                let url = format!("{}/{}", base, path);
+                let mut cmd = Command::new("curl");
+                cmd.args([
+                    "-sfL",                     // silent, fail on error, follow redirects
+                    "--max-time", "120",        // 2 min timeout for large files
+                ]);
+                if let Some(range) = range {
+                    cmd.args(["--range", range]);
+                }
+                let output = cmd
+                    .arg(&url)
+                    .output()
+                    .with_context(|| format!("Failed to run curl to download {}", path))?;
+
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    bail!("Failed to download {}: {}", url, stderr);
+                }
+                Ok(output.stdout)
+            }
+            Source::Dir { path: root } => {
+                // Plain files, or compressed ones when pointed at a /lib/firmware
+                let file = root.join(path);
+                let dir = file.parent().unwrap_or(root);
+                let name = file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                let compression = Compression::detect(dir, &name)
+                    .with_context(|| format!("{} not found", file.display()))?;
+                compression.read(&dir.join(format!("{}{}", name, compression.suffix())))
+            }
+            Source::Git { repo, rev } => {
+                let commit = resolve(repo, rev)?;
+                git(repo, &["show", &format!("{}:{}", commit, path)])
+            }
+        }
+    }
+
+    /// Commits that changed `path`, newest first, with the file's version at each
+    pub fn history(
+        &self,
+        path: &str,
+        limit: usize,
+        version: impl Fn(&[u8]) -> Result<String>,
+    ) -> Result<Vec<Revision>> {
+        let Source::Git { repo, rev } = self else {
+            bail!("Version history needs a linux-firmware.git clone ([firmware] source = \"git\")");
+        };
+
+        let commit = resolve(repo, rev)?;
+        let log = git(repo, &["log", "--format=%H %as", "-n", &limit.to_string(), &commit, "--", path])?;
+        let mut revisions = Vec::new();
+        for line in String::from_utf8_lossy(&log).lines() {
+            let Some((commit, date)) = line.split_once(' ') else {
+                continue;
+            };
+            // "20240909~3" -> "20240909"; no tag yet for commits after the last release
+            let release = git(repo, &["describe", "--tags", "--contains", commit]).ok()
+                .map(|out| String::from_utf8_lossy(&out).trim().to_string())
+                .and_then(|tag| tag.split(['~', '^']).next().map(str::to_string))
+                .filter(|tag| !tag.is_empty());
+            let version = git(repo, &["show", &format!("{}:{}", commit, path)])
+                .and_then(|data| version(&data))
+                .ok();
+            revisions.push(Revision {
+                commit: commit.to_string(),
+                date: date.to_string(),
+                release,
+                version,
+            });
+        }
+        Ok(revisions)
+    }
+}
+
+/// The commit `rev` names in `repo`
+///
+/// Revisions come from the config and the command line, and git runs as root:
+/// only the resolved hash is passed on, so a revision can never be taken as an option.
+fn resolve(repo: &Path, rev: &str) -> Result<String> {
+    let out = git(repo, &["rev-parse", "--verify", "--quiet", "--end-of-options", &format!("{}^{{commit}}", rev)])
+        .with_context(|| format!("{} is not a linux-firmware.git clone with revision {}", repo.display(), rev))?;
+    Ok(String::from_utf8_lossy(&out).trim().to_string())
+}
+
+/// Run git in `repo`, returning stdout
+fn git(repo: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .context("git command not found - please install git")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("git {} failed: {}", args.join(" "), stderr.trim());
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn config(text: &str) -> FirmwareConfig {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_source_from_config() {
+        let gitlab = Source::from_config(&config(""), None).unwrap();
+        assert_eq!(gitlab.to_string(), "https://gitlab.com/kernel-firmware/linux-firmware/-/raw/main");
+        let pinned = Source::from_config(&config("rev = \"20240909\""), None).unwrap();
+        assert!(pinned.to_string().ends_with("/raw/20240909"));
+        let cli = Source::from_config(&config("rev = \"20240909\""), Some("20241017")).unwrap();
+        assert!(cli.to_string().ends_with("/raw/20241017"));
+
+        let mirror = Source::from_config(&config("source = \"http\"\nurl = \"http://mirror.lab/linux-firmware/\""), None).unwrap();
+        assert_eq!(mirror.to_string(), "http://mirror.lab/linux-firmware");
+        assert!(Source::from_config(&config("source = \"http\""), None).is_err());
+        assert!(Source::from_config(&config("source = \"dir\""), None).is_err());
+        assert!(Source::from_config(&config("source = \"dir\"\npath = \"/srv/fw\"\nrev = \"x\""), None).is_err());
+
+        let git = Source::from_config(&config("source = \"git\"\npath = \"/srv/linux-firmware\""), None).unwrap();
+        assert_eq!(git.to_string(), "/srv/linux-firmware at HEAD");
+        assert!(Source::from_config(&config("source = \"git\"\npath = \"/srv/fw\""), Some("--output=/etc/passwd")).is_err());
+        assert!(Source::from_config(&config("rev = \"-x\""), None).is_err());
+    }
+
+    #[test]
+    fn test_dir_source_reads_plain_files() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("ath11k/QCA2066/hw2.1")).unwrap();
+        fs::write(root.path().join("ath11k/QCA2066/hw2.1/m3.bin"), b"m3").unwrap();
+
+        let source = Source::Dir { path: root.path().to_path_buf() };
+        source.check().unwrap();
+        assert_eq!(source.read("ath11k/QCA2066/hw2.1/m3.bin", None).unwrap(), b"m3");
+        assert!(source.read("ath11k/QCA2066/hw2.1/amss.bin", None).is_err());
+        assert!(source.history("ath11k/QCA2066/hw2.1/m3.bin", 5, |_| Ok(String::new())).is_err());
+    }
+
+    #[test]
+    fn test_git_source_lists_versions() {
+        let repo = tempfile::tempdir().unwrap();
+        let run = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C").arg(repo.path())
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+                .expect("git is installed")
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        let commit = |version: &str, message: &str| {
+            fs::write(repo.path().join("rtw89/fw.bin"), version).unwrap();
+            run(&["add", "-A"]);
+            run(&["commit", "-q", "-m", message]);
+        };
+        run(&["init", "-q"]);
+        fs::create_dir_all(repo.path().join("rtw89")).unwrap();
+        commit("v1", "first");
+        run(&["tag", "20240909"]);
+        commit("v2", "second");
+
+        let pinned = Source::Git { repo: repo.path().to_path_buf(), rev: "20240909".to_string() };
+        pinned.check().unwrap();
+        assert_eq!(pinned.read("rtw89/fw.bin", None).unwrap(), b"v1");
+        assert!(Source::Git { repo: repo.path().to_path_buf(), rev: "nope".to_string() }.check().is_err());
+        let option = Source::Git { repo: repo.path().to_path_buf(), rev: "--all".to_string() };
+        assert!(option.check().is_err());
+        assert!(option.history("rtw89/fw.bin", 10, |_| Ok(String::new())).is_err());
+
+        let head = Source::Git { repo: repo.path().to_path_buf(), rev: "HEAD".to_string() };
+        let history = head.history("rtw89/fw.bin", 10, |data| Ok(String::from_utf8_lossy(data).to_string())).unwrap();
+        let summary: Vec<_> = history.iter().map(|r| (r.version.as_deref(), r.release.as_deref())).collect();
+        assert_eq!(summary, [(Some("v2"), None), (Some("v1"), Some("20240909"))]);
+    }
+}
@@ -0,0 +1,324 @@
+//! Firmware target registry
+//!
+//! Which WiFi chips `hifi-wifi firmware` can manage: the PCI IDs that identify
+//! the chip, where its firmware lives under /lib/firmware, the files to update,
+//! how to read their version and where they are in linux-firmware.git. Like the
+//! quirk and device databases it is an embedded TOML file, extended or
+//! overridden by files in `/etc/hifi-wifi/firmware.d`.
+
+use anyhow::{Result, bail};
+use log::warn;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::config::loader::{load_toml_dir, merge_by_name};
+use crate::firmware::compress::Compression;
+use crate::firmware::device::DeviceInfo;
+use crate::firmware::version::FirmwareVersion;
+use crate::network::device::DeviceIdentity;
+use crate::system::quirks::glob_match;
+
+const BUILTIN: &str = include_str!("targets.toml");
+const TARGETS_DIR: &str = "/etc/hifi-wifi/firmware.d";
+
+/// Where the kernel loads firmware from
+pub const FIRMWARE_ROOT: &str = "/lib/firmware";
+
+/// How the version is read out of a firmware file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VersionMethod {
+    /// QC_IMAGE_VERSION_STRING (ath11k, ath12k)
+    QcImage,
+    /// Human readable string in the ucode header
+    Iwlwifi,
+    /// Version and build date in the RAM code trailer (mt7921, mt7922)
+    Mediatek,
+    /// Version numbers in the firmware header
+    Rtw89,
+    /// Hash of the file, when the format has no usable version
+    #[default]
+    Sha256,
+}
+
+/// One file of a target's firmware
+#[derive(Debug, Clone, Deserialize)]
+pub struct FirmwareFile {
+    pub name: String,
+    /// Smaller downloads are rejected as truncated
+    #[serde(default)]
+    pub min_size: u64,
+}
+
+/// A WiFi chip whose firmware can be updated
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Target {
+    pub name: String,
+    /// PCI "vendor:device" IDs
+    pub ids: Vec<String>,
+    /// PCI subsystem IDs; empty matches any
+    pub subsystems: Vec<String>,
+    /// DMI board_vendor patterns; empty matches any
+    pub board_vendors: Vec<String>,
+    /// DMI board_name patterns; empty matches any
+    pub boards: Vec<String>,
+    /// Candidate directories under /lib/firmware, in order
+    pub dirs: Vec<String>,
+    /// Directory in linux-firmware.git
+    pub upstream: String,
+    pub files: Vec<FirmwareFile>,
+    pub version: VersionMethod,
+    /// File the version is read from; the first file if unset
+    pub version_file: Option<String>,
+    /// Version prefix of the device vendor's own firmware
+    pub stock_prefix: Option<String>,
+    pub stock_vendor: Option<String>,
+}
+
+impl Target {
+    /// Reject entries that couldn't update anything
+    fn check(&self) -> Result<()> {
+        if self.files.is_empty() {
+            bail!("target {:?} has no files", self.name);
+        }
+        Ok(())
+    }
+
+    fn matches_card(&self, card: &DeviceIdentity) -> bool {
+        let Some(id) = card.id() else {
+            return false;
+        };
+        let subsystem = card.subsystem_id().unwrap_or_default();
+        self.ids.iter().any(|known| known.eq_ignore_ascii_case(&id))
+            && (self.subsystems.is_empty() || self.subsystems.iter().any(|s| s.eq_ignore_ascii_case(&subsystem)))
+    }
+
+    /// Whether updates are allowed on this board (the DMI gate)
+    pub fn supports_board(&self, device: &DeviceInfo) -> bool {
+        let any = |patterns: &[String], value: &Option<String>| {
+            patterns.is_empty() || patterns.iter().any(|p| glob_match(p, value.as_deref().unwrap_or("")))
+        };
+        any(&self.board_vendors, &device.board_vendor) && any(&self.boards, &device.board_name)
+    }
+
+    /// Name of the file the version is read from
+    pub fn version_file(&self) -> &str {
+        self.version_file.as_deref()
+            .or_else(|| self.files.first().map(|f| f.name.as_str()))
+            .unwrap_or("")
+    }
+
+    /// Path of `file` in linux-firmware.git
+    pub fn upstream_path(&self, file: &str) -> String {
+        if self.upstream.is_empty() {
+            file.to_string()
+        } else {
+            format!("{}/{}", self.upstream.trim_end_matches('/'), file)
+        }
+    }
+
+    /// Whether `version` is the device vendor's own firmware
+    pub fn is_stock(&self, version: &FirmwareVersion) -> bool {
+        version.is_stock(self.stock_prefix.as_deref())
+    }
+
+    /// "(Valve stock)" or "(upstream)"; empty for targets without stock firmware
+    pub fn version_label(&self, version: &FirmwareVersion) -> String {
+        if self.stock_prefix.is_none() {
+            String::new()
+        } else if self.is_stock(version) {
+            format!("({} stock)", self.stock_vendor.as_deref().unwrap_or("vendor"))
+        } else {
+            "(upstream)".to_string()
+        }
+    }
+}
+
+/// A target's firmware as installed on this machine
+#[derive(Debug, Clone)]
+pub struct Installed {
+    pub target: Target,
+    pub card: DeviceIdentity,
+    /// Directory holding the files
+    pub path: PathBuf,
+    pub compression: Compression,
+}
+
+impl Installed {
+    /// Find the target's firmware directory under `root`
+    pub fn locate(target: &Target, card: &DeviceIdentity, root: &Path) -> Result<Self> {
+        for dir in &target.dirs {
+            let path = root.join(dir);
+            if let Some(compression) = Compression::detect(&path, target.version_file()) {
+                return Ok(Self {
+                    target: target.clone(),
+                    card: card.clone(),
+                    path,
+                    compression,
+                });
+            }
+        }
+
+        let checked: Vec<String> = target.dirs.iter()
+            .map(|dir| format!("  - {}", root.join(dir).join(target.version_file()).display()))
+            .collect();
+        bail!("Firmware for {} not found. Checked:\n{}", target.name, checked.join("\n"));
+    }
+
+    /// Installed file names (e.g. "amss.bin.zst")
+    pub fn files(&self) -> Vec<String> {
+        self.target.files.iter()
+            .map(|f| format!("{}{}", f.name, self.compression.suffix()))
+            .collect()
+    }
+
+    /// Installed path of the version file
+    pub fn version_path(&self) -> PathBuf {
+        self.path.join(format!("{}{}", self.target.version_file(), self.compression.suffix()))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TargetFile {
+    #[serde(default)]
+    target: Vec<Target>,
+}
+
+/// Built-in targets plus the ones from firmware.d, in match order
+#[derive(Debug, Default)]
+pub struct Registry {
+    targets: Vec<Target>,
+}
+
+impl Registry {
+    /// Parse one target file
+    pub fn parse(text: &str) -> Result<Vec<Target>> {
+        let file: TargetFile = toml::from_str(text)?;
+        for target in &file.target {
+            target.check()?;
+        }
+        Ok(file.target)
+    }
+
+    /// The embedded registry merged with `overrides` (see `merge_by_name`)
+    fn with_overrides(overrides: Vec<Target>) -> Self {
+        let builtin = Self::parse(BUILTIN).expect("embedded targets.toml is valid");
+        Self { targets: merge_by_name(overrides, builtin, |t| &t.name) }
+    }
+
+    /// Load the embedded registry and the files in `dir`
+    pub fn load(dir: &Path) -> Self {
+        let files: Vec<TargetFile> = load_toml_dir(dir);
+        let overrides = files.into_iter()
+            .flat_map(|f| f.target)
+            .filter(|target| match target.check() {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Ignoring firmware target: {}", e);
+                    false
+                }
+            })
+            .collect();
+        Self::with_overrides(overrides)
+    }
+
+    /// The first target matching one of the device's WiFi cards
+    pub fn lookup<'d>(&self, device: &'d DeviceInfo) -> Option<(&Target, &'d DeviceIdentity)> {
+        self.targets.iter().find_map(|target| {
+            device.cards.iter().find(|card| target.matches_card(card)).map(|card| (target, card))
+        })
+    }
+}
+
+/// Find the target for this machine's WiFi card and its installed firmware
+pub fn detect(device: &DeviceInfo) -> Result<Installed> {
+    let registry = Registry::load(Path::new(TARGETS_DIR));
+    let Some((target, card)) = registry.lookup(device) else {
+        let ids: Vec<String> = device.cards.iter().filter_map(|c| c.id()).collect();
+        bail!(
+            "No supported WiFi card found (detected: {}).\n\
+             Add a target in {} to manage its firmware.",
+            if ids.is_empty() { "none".to_string() } else { ids.join(", ") },
+            TARGETS_DIR
+        );
+    };
+    Installed::locate(target, card, Path::new(FIRMWARE_ROOT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn device(board: &str, id: &str, subsystem: &str) -> DeviceInfo {
+        let split = |s: &str| {
+            let (v, d) = s.split_once(':').unwrap();
+            (Some(v.to_string()), Some(d.to_string()))
+        };
+        let (vendor, device) = split(id);
+        let (subsystem_vendor, subsystem_device) = split(subsystem);
+        DeviceInfo {
+            board_vendor: Some("Valve".to_string()),
+            board_name: Some(board.to_string()),
+            cards: vec![DeviceIdentity { vendor, device, subsystem_vendor, subsystem_device, ..Default::default() }],
+        }
+    }
+
+    #[test]
+    fn test_builtin_registry_matches_chips() {
+        let registry = Registry::with_overrides(Vec::new());
+        let name = |device: &DeviceInfo| registry.lookup(device).map(|(t, _)| t.name.clone());
+
+        let oled = device("Galileo", "17cb:1103", "17cb:0108");
+        let (target, _) = registry.lookup(&oled).unwrap();
+        assert_eq!(target.name, "Qualcomm QCA2066 (Steam Deck OLED)");
+        assert!(target.supports_board(&oled));
+        assert_eq!(target.version_file(), "amss.bin");
+        assert_eq!(target.upstream_path("m3.bin"), "ath11k/QCA2066/hw2.1/m3.bin");
+
+        // The OLED's card in another machine is still gated on the board
+        let swapped = device("Jupiter", "17cb:1103", "17cb:0108");
+        assert!(!registry.lookup(&swapped).unwrap().0.supports_board(&swapped));
+
+        assert_eq!(name(&device("LNVNB161216", "17cb:1103", "17aa:9309")).as_deref(), Some("Qualcomm WCN6855 (ath11k)"));
+        assert_eq!(name(&device("RC71L", "14c3:0616", "1a3b:5300")).as_deref(), Some("MediaTek MT7922 (mt7921e)"));
+        assert_eq!(name(&device("X", "8086:2725", "8086:0024")).as_deref(), Some("Intel Wi-Fi 6E AX210 (iwlwifi)"));
+        assert_eq!(name(&device("X", "10ec:c822", "10ec:c822")), None);
+
+        let intel = registry.lookup(&device("X", "8086:2725", "8086:0024")).unwrap().0;
+        assert_eq!(intel.upstream_path("iwlwifi-ty-a0-gf-a0.pnvm"), "iwlwifi-ty-a0-gf-a0.pnvm");
+    }
+
+    #[test]
+    fn test_override_and_locate() {
+        let overrides = Registry::parse(r#"
+            [[target]]
+            name = "MediaTek MT7922 (mt7921e)"
+            ids = ["14c3:0616"]
+            dirs = ["missing", "mediatek"]
+            upstream = "mediatek"
+            files = [
+                { name = "WIFI_RAM_CODE_MT7922_1.bin", min_size = 1 },
+                { name = "WIFI_MT7922_patch_mcu_1_1_hdr.bin" },
+            ]
+            version = "mediatek"
+        "#).unwrap();
+        assert!(Registry::parse("[[target]]\nname = \"empty\"\n").is_err());
+
+        let registry = Registry::with_overrides(overrides);
+        let ally = device("RC71L", "14c3:0616", "1a3b:5300");
+        let (target, card) = registry.lookup(&ally).unwrap();
+        assert_eq!(target.dirs, ["missing", "mediatek"]);
+
+        let root = tempfile::tempdir().unwrap();
+        assert!(Installed::locate(target, card, root.path()).is_err());
+
+        fs::create_dir_all(root.path().join("mediatek")).unwrap();
+        fs::write(root.path().join("mediatek/WIFI_RAM_CODE_MT7922_1.bin.xz"), b"").unwrap();
+        let installed = Installed::locate(target, card, root.path()).unwrap();
+        assert_eq!(installed.path, root.path().join("mediatek"));
+        assert_eq!(installed.compression, Compression::Xz);
+        assert_eq!(installed.files(), ["WIFI_RAM_CODE_MT7922_1.bin.xz", "WIFI_MT7922_patch_mcu_1_1_hdr.bin.xz"]);
+    }
+}
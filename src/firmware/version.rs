@@ -4,9 +4,20 @@
 //! fetching the latest upstream version from linux-firmware.git
 
 use anyhow::{Result, Context, bail};
-use std::io::Read;
-use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use sha2::{Sha256, Digest};
+use std::path::Path;
+
+use crate::firmware::source::Source;
+use crate::firmware::targets::{Installed, Target, VersionMethod};
+
+/// iwlwifi TLV ucode magic ("IWL\n")
+const IWL_TLV_UCODE_MAGIC: u32 = 0x0a4c_5749;
+
+/// Size of the MediaTek connac2 RAM code trailer
+const MTK_TRAILER_LEN: usize = 36;
+
+/// Signature of an rtw89 multi-firmware container
+const RTW89_MFW_SIG: u8 = 0xff;
 
 /// Firmware version information
 #[derive(Debug, Clone)]
@@ -16,120 +27,62 @@ pub struct FirmwareVersion {
 }
 
 impl FirmwareVersion {
-    /// Extract version from an installed firmware file
+    /// Extract version from a target's installed firmware
     ///
-    /// Decompresses amss.bin.zst and searches for the QC_IMAGE_VERSION_STRING
-    pub fn from_installed(firmware_path: &Path) -> Result<Self> {
-        let amss_path = firmware_path.join("amss.bin.zst");
+    /// Decompresses the version file (e.g. amss.bin.zst) and reads the
+    /// version the way the target says
+    pub fn from_installed(installed: &Installed) -> Result<Self> {
+        let path = installed.version_path();
 
-        if !amss_path.exists() {
-            bail!("Firmware file not found: {}", amss_path.display());
+        if !path.exists() {
+            bail!("Firmware file not found: {}", path.display());
         }
 
-        // Decompress and extract version
-        let version_string = extract_version_from_zst(&amss_path)?;
+        let data = installed.compression.read(&path)?;
+        let version_string = extract_version(&data, installed.target.version)?;
 
         Ok(Self { version_string })
     }
 
     /// Extract version from an uncompressed firmware file
-    pub fn from_raw(amss_path: &Path) -> Result<Self> {
-        let version_string = extract_version_from_raw(amss_path)?;
+    pub fn from_raw(path: &Path, method: VersionMethod) -> Result<Self> {
+        let data = std::fs::read(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let version_string = extract_version(&data, method)?;
         Ok(Self { version_string })
     }
 
-    /// Check if this is Valve stock firmware (has CI_WLAN prefix)
-    pub fn is_valve_stock(&self) -> bool {
-        self.version_string.starts_with("CI_WLAN")
-    }
-}
-
-/// Detect the firmware path for the QCA2066/ath11k device
-///
-/// Checks multiple possible paths (SteamOS uses QCA206X, upstream uses QCA2066)
-pub fn detect_firmware_path() -> Result<PathBuf> {
-    // Valve/SteamOS path (most likely on Steam Deck)
-    let valve_path = Path::new("/lib/firmware/ath11k/QCA206X/hw2.1");
-    if valve_path.exists() && valve_path.join("amss.bin.zst").exists() {
-        return Ok(valve_path.to_path_buf());
-    }
-
-    // Upstream linux-firmware path
-    let upstream_path = Path::new("/lib/firmware/ath11k/QCA2066/hw2.1");
-    if upstream_path.exists() && upstream_path.join("amss.bin.zst").exists() {
-        return Ok(upstream_path.to_path_buf());
-    }
-
-    // Generic WCN6855 path (same chip, different name)
-    let generic_path = Path::new("/lib/firmware/ath11k/WCN6855/hw2.1");
-    if generic_path.exists() && generic_path.join("amss.bin.zst").exists() {
-        return Ok(generic_path.to_path_buf());
-    }
-
-    // Also check hw2.0 variants
-    let generic_path_20 = Path::new("/lib/firmware/ath11k/WCN6855/hw2.0");
-    if generic_path_20.exists() && generic_path_20.join("amss.bin.zst").exists() {
-        return Ok(generic_path_20.to_path_buf());
+    /// Check if this is the device vendor's stock firmware (e.g. Valve's CI_WLAN prefix)
+    pub fn is_stock(&self, stock_prefix: Option<&str>) -> bool {
+        stock_prefix.is_some_and(|prefix| self.version_string.starts_with(prefix))
     }
-
-    bail!(
-        "Firmware directory not found. Checked:\n\
-         - /lib/firmware/ath11k/QCA206X/hw2.1/ (SteamOS)\n\
-         - /lib/firmware/ath11k/QCA2066/hw2.1/ (upstream)\n\
-         - /lib/firmware/ath11k/WCN6855/hw2.1/ (generic)"
-    );
 }
 
-/// Extract version string from a zstd-compressed firmware file
-fn extract_version_from_zst(zst_path: &Path) -> Result<String> {
-    // Use system zstd command to decompress (avoids C compilation issues)
-    let output = Command::new("zstd")
-        .args(["-d", "-c"])
-        .arg(zst_path)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .with_context(|| format!("Failed to run zstd to decompress {}", zst_path.display()))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("zstd decompression failed for {}: {}", zst_path.display(), stderr);
+/// Extract the version string from firmware bytes
+pub fn extract_version(data: &[u8], method: VersionMethod) -> Result<String> {
+    match method {
+        VersionMethod::QcImage => extract_qc_image_version(data),
+        VersionMethod::Iwlwifi => extract_iwlwifi_version(data),
+        VersionMethod::Mediatek => extract_mediatek_version(data),
+        VersionMethod::Rtw89 => extract_rtw89_version(data),
+        VersionMethod::Sha256 => {
+            let hash = format!("{:x}", Sha256::digest(data));
+            Ok(format!("sha256:{}", &hash[..16]))
+        }
     }
-
-    extract_version_from_bytes(&output.stdout)
-}
-
-/// Extract version string from an uncompressed firmware file
-fn extract_version_from_raw(path: &Path) -> Result<String> {
-    let mut file = std::fs::File::open(path)
-        .with_context(|| format!("Failed to open {}", path.display()))?;
-
-    let mut data = Vec::new();
-    file.read_to_end(&mut data)
-        .with_context(|| format!("Failed to read {}", path.display()))?;
-
-    extract_version_from_bytes(&data)
 }
 
 /// Extract QC_IMAGE_VERSION_STRING from bytes
 ///
 /// Searches through the binary for the version string pattern
-fn extract_version_from_bytes(data: &[u8]) -> Result<String> {
+fn extract_qc_image_version(data: &[u8]) -> Result<String> {
     // The version string is embedded in the binary as: QC_IMAGE_VERSION_STRING=<version>
     let pattern = b"QC_IMAGE_VERSION_STRING=";
 
     // Search for the pattern
     if let Some(pos) = find_subsequence(data, pattern) {
-        let start = pos + pattern.len();
-
-        // Find the end of the version string (null terminator or non-printable)
-        let mut end = start;
-        while end < data.len() && data[end] >= 0x20 && data[end] < 0x7F {
-            end += 1;
-        }
-
-        if end > start {
-            let version = String::from_utf8_lossy(&data[start..end]).to_string();
+        let version = printable_prefix(&data[pos + pattern.len()..]);
+        if !version.is_empty() {
             return Ok(version);
         }
     }
@@ -137,6 +90,69 @@ fn extract_version_from_bytes(data: &[u8]) -> Result<String> {
     bail!("Could not find QC_IMAGE_VERSION_STRING in firmware binary")
 }
 
+/// Extract the human readable version from an iwlwifi TLV ucode header
+///
+/// Layout: zero (u32), magic (u32), human_readable[64], ...
+fn extract_iwlwifi_version(data: &[u8]) -> Result<String> {
+    if data.len() < 72 || le32(data, 4) != IWL_TLV_UCODE_MAGIC {
+        bail!("Not an iwlwifi TLV ucode file");
+    }
+    let version = printable_prefix(&data[8..72]);
+    if version.is_empty() {
+        bail!("iwlwifi ucode has no version string");
+    }
+    Ok(version)
+}
+
+/// Extract version and build date from a MediaTek RAM code trailer
+///
+/// The last 36 bytes: chip_id, eco_code, n_region, format_ver, format_flag,
+/// rsv[2], fw_ver[10], build_date[15], crc (u32)
+fn extract_mediatek_version(data: &[u8]) -> Result<String> {
+    if data.len() < MTK_TRAILER_LEN {
+        bail!("MediaTek firmware too small for a trailer");
+    }
+    let trailer = &data[data.len() - MTK_TRAILER_LEN..];
+    let fw_ver = printable_prefix(&trailer[7..17]);
+    let build_date = printable_prefix(&trailer[17..32]);
+    if build_date.is_empty() {
+        bail!("Could not find build date in MediaTek firmware trailer");
+    }
+    Ok(format!("{} {}", fw_ver, build_date).trim().to_string())
+}
+
+/// Extract major.minor.sub.index from an rtw89 firmware header
+///
+/// Multi-firmware containers start with 0xff and a table of 16-byte entries
+/// (cv, type, mp, rsvd, shift, size, rsv); the first entry's header is used
+fn extract_rtw89_version(data: &[u8]) -> Result<String> {
+    let header = if data.first() == Some(&RTW89_MFW_SIG) {
+        if data.len() < 32 {
+            bail!("rtw89 firmware container too small");
+        }
+        le32(data, 20) as usize
+    } else {
+        0
+    };
+    if data.len() < header + 8 {
+        bail!("rtw89 firmware header out of range");
+    }
+    let v = &data[header + 4..header + 8];
+    Ok(format!("{}.{}.{}.{}", v[0], v[1], v[2], v[3]))
+}
+
+/// Leading printable ASCII of `data`, up to a null or control byte
+fn printable_prefix(data: &[u8]) -> String {
+    let end = data.iter()
+        .position(|&b| !(0x20..0x7F).contains(&b))
+        .unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).trim().to_string()
+}
+
+fn le32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
 /// Find a subsequence in a byte slice
 fn find_subsequence(haystack: &[u8], needle: &[u8]) -> Option<usize> {
     haystack.windows(needle.len())
@@ -145,50 +161,19 @@ fn find_subsequence(haystack: &[u8], needle: &[u8]) -> Option<usize> {
 
 /// Fetch the latest upstream version from linux-firmware.git
 ///
-/// Downloads the amss.bin file header to extract the version string
-pub fn get_upstream_version() -> Result<FirmwareVersion> {
+/// Reads the target's version file from the source (over HTTP only the
+/// start of amss.bin for ath11k/ath12k) to extract the version string
+pub fn get_upstream_version(target: &Target, source: &Source) -> Result<FirmwareVersion> {
     // We could parse the WHENCE file, but it doesn't contain version strings.
-    // Instead, we'll fetch enough of amss.bin to extract the version.
-    // The version string is located around 2-3MB into the file.
-
-    let url = "https://gitlab.com/kernel-firmware/linux-firmware/-/raw/main/ath11k/QCA2066/hw2.1/amss.bin";
-
-    // Use system curl to fetch partial file (first 4MB to ensure we get version string)
-    let output = Command::new("curl")
-        .args([
-            "-sfL",                         // silent, fail on error, follow redirects
-            "--range", "0-4194303",         // First 4MB
-            "--max-time", "60",             // 60 second timeout for larger download
-            url,
-        ])
-        .output()
-        .context("Failed to run curl to fetch upstream firmware")?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("Failed to fetch upstream firmware: {}", stderr);
-    }
-
-    let data = &output.stdout;
-
-    // Search for version string
-    let pattern = b"QC_IMAGE_VERSION_STRING=";
-    if let Some(pos) = find_subsequence(data, pattern) {
-        let start = pos + pattern.len();
-        let mut end = start;
-        while end < data.len() && data[end] >= 0x20 && data[end] < 0x7F {
-            end += 1;
-        }
-
-        if end > start {
-            let version = String::from_utf8_lossy(&data[start..end]).to_string();
-            return Ok(FirmwareVersion {
-                version_string: version,
-            });
-        }
-    }
-
-    bail!("Could not extract version from upstream firmware")
+    // The QC version string is located around 2-3MB into amss.bin (first 4MB
+    // to ensure we get it), the other formats need the whole (small) file.
+    let range = (target.version == VersionMethod::QcImage).then_some("0-4194303");
+    let data = source.read(&target.upstream_path(target.version_file()), range)
+        .context("Failed to fetch upstream firmware")?;
+
+    let version_string = extract_version(&data, target.version)
+        .context("Could not extract version from upstream firmware")?;
+    Ok(FirmwareVersion { version_string })
 }
 
 #[cfg(test)]
@@ -200,11 +185,43 @@ mod tests {
         let valve = FirmwareVersion {
             version_string: "CI_WLAN.HSP.1.1-03926.9.1-QCAHSPSWPL_V2_SILICONZ_CE-15".to_string(),
         };
-        assert!(valve.is_valve_stock());
+        assert!(valve.is_stock(Some("CI_WLAN")));
 
         let upstream = FirmwareVersion {
             version_string: "WLAN.HSP.1.1-03926.13-QCAHSPSWPL_V2_SILICONZ_CE-2.52297.9".to_string(),
         };
-        assert!(!upstream.is_valve_stock());
+        assert!(!upstream.is_stock(Some("CI_WLAN")));
+        assert!(!valve.is_stock(None));
+    }
+
+    #[test]
+    fn test_extract_versions() {
+        let mut qc = vec![0u8; 64];
+        qc.extend_from_slice(b"QC_IMAGE_VERSION_STRING=WLAN.HSP.1.1-03926.13\0junk");
+        assert_eq!(extract_version(&qc, VersionMethod::QcImage).unwrap(), "WLAN.HSP.1.1-03926.13");
+        assert!(extract_version(&[0u8; 64], VersionMethod::QcImage).is_err());
+
+        let mut iwl = vec![0u8; 128];
+        iwl[4..8].copy_from_slice(&IWL_TLV_UCODE_MAGIC.to_le_bytes());
+        iwl[8..8 + 22].copy_from_slice(b"release/core89::6f9f21");
+        assert_eq!(extract_version(&iwl, VersionMethod::Iwlwifi).unwrap(), "release/core89::6f9f21");
+        assert!(extract_version(&qc, VersionMethod::Iwlwifi).is_err());
+
+        let mut mtk = vec![0u8; 100];
+        let trailer = mtk.len() - MTK_TRAILER_LEN;
+        mtk[trailer + 7..trailer + 17].copy_from_slice(b"____000000");
+        mtk[trailer + 17..trailer + 31].copy_from_slice(b"20240716163327");
+        assert_eq!(extract_version(&mtk, VersionMethod::Mediatek).unwrap(), "____000000 20240716163327");
+
+        let mut rtw = vec![0u8; 64];
+        rtw[4..8].copy_from_slice(&[0, 29, 29, 7]);
+        assert_eq!(extract_version(&rtw, VersionMethod::Rtw89).unwrap(), "0.29.29.7");
+        let mut mfw = vec![0u8; 96];
+        mfw[0] = RTW89_MFW_SIG;
+        mfw[20..24].copy_from_slice(&32u32.to_le_bytes());
+        mfw[36..40].copy_from_slice(&[0, 29, 128, 0]);
+        assert_eq!(extract_version(&mfw, VersionMethod::Rtw89).unwrap(), "0.29.128.0");
+
+        assert!(extract_version(b"abc", VersionMethod::Sha256).unwrap().starts_with("sha256:ba7816bf"));
     }
 }
@@ -3,17 +3,26 @@ mod system;
 mod config;
 mod utils;
 mod firmware;
+mod bench;
+mod watch;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use log::{info, error, warn};
+use std::path::{Path, PathBuf};
+use tokio::signal::unix::{signal, SignalKind};
 
-use crate::config::loader::load_config;
+use crate::config::loader::{load_config, load_config_from};
 use crate::network::wifi::{WifiManager, WifiInterface};
 use crate::network::backend_tuner::BackendTuner;
 use crate::network::governor::Governor;
+use crate::network::wan;
+use crate::network::wpa;
+use crate::network::governor::core::{Action, GovernorCore};
+use crate::network::governor::trace::{self, TraceRecorder};
 use crate::system::power::PowerManager;
 use crate::system::optimizer::SystemOptimizer;
+use crate::system::rollback::{self, Change, Journal};
 
 #[derive(Parser)]
 #[command(name = "hifi-wifi")]
@@ -33,7 +42,19 @@ enum Commands {
     /// Apply Wi-Fi optimizations once (default)
     Apply,
     /// Run as daemon with continuous monitoring
-    Monitor,
+    Monitor {
+        /// Record every governor tick's inputs to this file (for `replay`)
+        #[arg(long)]
+        record: Option<PathBuf>,
+    },
+    /// Replay a recorded trace offline and show what the governor would do
+    Replay {
+        /// Trace file written by `monitor --record`
+        file: PathBuf,
+        /// Config file to replay with (default: /etc/hifi-wifi/config.toml)
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
     /// Revert all optimizations to defaults
     Revert,
     /// Show current Wi-Fi status and detected hardware
@@ -60,11 +81,73 @@ enum Commands {
         /// Mode: on (suppress scans for lowest latency), off (allow scans for roaming), status (show current)
         mode: String,
     },
-    /// Manage WiFi firmware updates (Steam Deck OLED only)
+    /// Manage WiFi firmware updates (ath11k/ath12k, iwlwifi, rtw89 and mt7921 cards)
     Firmware {
         #[command(subcommand)]
         action: firmware::FirmwareAction,
     },
+    /// Measure latency under load and grade bufferbloat (A-F)
+    Bench {
+        /// Host to measure latency to (default: gateway)
+        #[arg(long)]
+        target: Option<String>,
+        /// LAN host running `hifi-wifi peer` (host or host:port); used for load and latency
+        #[arg(long)]
+        peer: Option<String>,
+        /// host:port on the LAN that accepts upload traffic (default: the peer)
+        #[arg(long)]
+        load: Option<String>,
+        /// Parallel upload streams
+        #[arg(long, default_value_t = 4)]
+        streams: usize,
+        /// Seconds per phase (idle and loaded)
+        #[arg(long, default_value_t = 10)]
+        duration: u64,
+        /// Run with hifi-wifi off, then on, and compare
+        #[arg(long)]
+        compare: bool,
+        /// Output as JSON for scripting
+        #[arg(long)]
+        json: bool,
+    },
+    /// Live view of latency, throughput and Governor state (e.g. during a stream)
+    Watch {
+        /// Interface to watch (default: first connected)
+        #[arg(long)]
+        interface: Option<String>,
+        /// Host to ping (default: gateway)
+        #[arg(long)]
+        target: Option<String>,
+        /// RTT above baseline that counts as a spike (ms)
+        #[arg(long, default_value_t = 30.0)]
+        spike_ms: f64,
+    },
+    /// Run a benchmark reflector and load sink/source for other hosts to target
+    Peer {
+        /// Address to listen on (default: this host's LAN address)
+        #[arg(long)]
+        bind: Option<String>,
+        /// UDP echo and TCP sink port (source uses port + 1)
+        #[arg(long, default_value_t = bench::peer::DEFAULT_PORT)]
+        port: u16,
+    },
+    /// Measure or show WAN uplink capacity used to size CAKE
+    Wan {
+        #[command(subcommand)]
+        action: WanAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum WanAction {
+    /// Run a speed test and remember the result for the current network
+    Measure {
+        /// Network to store the result under (default: current SSID or wired interface)
+        #[arg(long)]
+        network: Option<String>,
+    },
+    /// Show configured and measured WAN capacities
+    Status,
 }
 
 #[tokio::main]
@@ -75,15 +158,23 @@ async fn main() -> Result<()> {
 
     // Suppress INFO logs for status-like commands (clean output)
     let is_status_cmd = matches!(cli.command, Some(Commands::Status))
+        || matches!(cli.command, Some(Commands::Replay { .. }))
+        || matches!(cli.command, Some(Commands::Wan { action: WanAction::Status }))
+        || matches!(cli.command, Some(Commands::Watch { .. }))
         || matches!(cli.command, Some(Commands::PowerSave { ref mode }) if mode == "status")
         || matches!(cli.command, Some(Commands::ScanSuppress { ref mode }) if mode == "status")
-        || matches!(cli.command, Some(Commands::Firmware { action: firmware::FirmwareAction::Status { .. } }));
+        || matches!(cli.command, Some(Commands::Firmware { action: firmware::FirmwareAction::Status { .. } }))
+        || matches!(cli.command, Some(Commands::Firmware { action: firmware::FirmwareAction::Versions { .. } }));
     if is_status_cmd {
         log::set_max_level(log::LevelFilter::Warn);
     }
 
+    // Measurement commands that change nothing on this host
+    let is_unprivileged_cmd = matches!(cli.command, Some(Commands::Bench { compare: false, .. }))
+        || matches!(cli.command, Some(Commands::Peer { .. }));
+
     // Root check (except for status commands)
-    if !is_status_cmd && !utils::privilege::is_root() {
+    if !is_status_cmd && !is_unprivileged_cmd && !utils::privilege::is_root() {
         error!("This application must be run as root.");
         error!("Try: sudo hifi-wifi");
         std::process::exit(1);
@@ -100,8 +191,15 @@ async fn main() -> Result<()> {
                 run_apply(&config)?;
             }
         }
-        Commands::Monitor => {
-            run_monitor(&config).await?;
+        Commands::Monitor { record } => {
+            run_monitor(&config, record.as_deref()).await?;
+        }
+        Commands::Replay { file, config: config_path } => {
+            let replay_config = match config_path {
+                Some(path) => load_config_from(&path)?,
+                None => config,
+            };
+            run_replay(&file, &replay_config)?;
         }
         Commands::Revert => {
             run_revert()?;
@@ -110,7 +208,7 @@ async fn main() -> Result<()> {
             run_status_async().await?;
         }
         Commands::Install => {
-            run_install()?;
+            run_install(&config)?;
         }
         Commands::Uninstall => {
             run_uninstall()?;
@@ -131,7 +229,40 @@ async fn main() -> Result<()> {
             run_scan_suppress(&mode, &config)?;
         }
         Commands::Firmware { action } => {
-            firmware::run_firmware(action, cli.dry_run)?;
+            firmware::run_firmware(action, cli.dry_run, &config.firmware)?;
+        }
+        Commands::Wan { action } => {
+            run_wan(action, &config).await?;
+        }
+        Commands::Bench { target, peer, load, streams, duration, compare, json } => {
+            use bench::LatencyTarget;
+
+            let peer = peer.as_deref().map(bench::peer::endpoint);
+            let target = match (target, &peer) {
+                (Some(host), _) => LatencyTarget::Ping(host),
+                (None, Some(peer)) => LatencyTarget::Peer(peer.clone()),
+                (None, None) => match crate::network::tc::default_gateway() {
+                    Some(gateway) => LatencyTarget::Ping(gateway),
+                    None => anyhow::bail!("No default gateway found - pass --target or --peer"),
+                },
+            };
+            let Some(load_endpoint) = load.or(peer) else {
+                anyhow::bail!("Nothing to load - pass --peer (a host running `hifi-wifi peer`) or --load");
+            };
+            let options = bench::BenchOptions {
+                target,
+                load_endpoint,
+                streams,
+                duration: std::time::Duration::from_secs(duration),
+            };
+            run_bench(&options, compare, json).await?;
+        }
+        Commands::Watch { interface, target, spike_ms } => {
+            watch::run(watch::WatchOptions { interface, target, spike_ms }).await?;
+        }
+        Commands::Peer { bind, port } => {
+            let bind = bind.unwrap_or_else(|| bench::peer::default_bind().to_string());
+            bench::peer::Peer::bind(&bind, port).await?.run().await?;
         }
     }
 
@@ -156,6 +287,9 @@ fn run_apply(config: &config::structs::Config) -> Result<()> {
               ifc.name, ifc.driver, ifc.category);
     }
 
+    // Everything below records its prior state here first, for `revert`
+    let mut journal = Journal::open(Path::new(rollback::JOURNAL_PATH));
+
     // 2. Detect power state
     let power_mgr = PowerManager::new();
     info!("Device type: {:?}", power_mgr.device_type());
@@ -167,7 +301,8 @@ fn run_apply(config: &config::structs::Config) -> Result<()> {
             config.system.sysctl_enabled,
             config.system.irq_affinity_enabled,
             config.system.driver_tweaks_enabled,
-        );
+        )
+        .with_sysctl(config.system.sysctl_profile, config.system.sysctl_extra.clone());
         
         // Only optimize connected/active interfaces
         let active_interfaces: Vec<WifiInterface> = interfaces
@@ -180,11 +315,12 @@ fn run_apply(config: &config::structs::Config) -> Result<()> {
             warn!("No active network connections - skipping IRQ optimizations");
         } else {
             info!("Optimizing {} active interface(s)", active_interfaces.len());
-            sys_opt.apply(&active_interfaces)?;
+            sys_opt.apply(&active_interfaces, &mut journal)?;
         }
     }
 
     // 3b. Write persistent NetworkManager power save config (survives sleep/wake)
+    journal.record("power", Change::file(Path::new(NM_POWERSAVE_CONF)));
     if let Err(e) = write_nm_powersave_config(&config.power.wlan_power_save) {
         warn!("Failed to write NM powersave config: {}", e);
     }
@@ -218,6 +354,11 @@ fn run_apply(config: &config::structs::Config) -> Result<()> {
             }
         };
 
+        journal.record("power", Change::power_save(&ifc.name));
+        // The Governor retunes these later; keep what the system had
+        journal.record("governor", Change::coalescing(&ifc.name));
+        journal.record("governor", Change::eee(&ifc.name));
+
         if should_save {
             wifi_mgr.enable_power_save(ifc)?;
         } else {
@@ -226,11 +367,12 @@ fn run_apply(config: &config::structs::Config) -> Result<()> {
 
         // 5. Get link stats and apply CAKE
         // Always apply CAKE, even if we can't get link stats
-        let bandwidth = match wifi_mgr.get_link_stats(ifc) {
+        let stats = wifi_mgr.get_link_stats(ifc);
+        let link_mbit = match &stats {
             Ok(stats) if stats.tx_bitrate_mbps > 0.0 => {
                 info!("Link: {}Mbps TX, {}dBm signal", stats.tx_bitrate_mbps, stats.signal_dbm);
-                // Use 60% of link rate for realistic Wi-Fi throughput
-                (stats.tx_bitrate_mbps * 0.60) as u32
+                ((stats.tx_bitrate_mbps * config.governor.cake_overhead_factor) as u32)
+                    .max(crate::network::tc::MIN_LINK_MBIT)
             }
             Ok(stats) => {
                 warn!("Link stats returned 0 bitrate (signal: {}dBm), using 200Mbit default", stats.signal_dbm);
@@ -241,17 +383,24 @@ fn run_apply(config: &config::structs::Config) -> Result<()> {
                 200
             }
         };
-        
+
+        // Same WAN-aware sizing as the Governor, so CAKE doesn't jump when it starts
+        let ssid = stats.as_ref().ok().and_then(|s| s.ssid.as_deref());
+        let network = wan::network_key(&ifc.name, ssid);
+        let uplink = wan::resolve_uplink(&config.governor.wan, &network, wan::measured_uplink(&network));
+        let (bandwidth, constraint) = wan::combine(&config.governor.wan, link_mbit, uplink);
+        info!("CAKE on {} ({}): sized by {:?} at {}Mbit", ifc.name, network, constraint, bandwidth);
+
+        journal.record("cake", Change::qdisc(&ifc.name));
         if let Err(e) = wifi_mgr.apply_cake(ifc, bandwidth.max(1)) {
             error!("Failed to apply CAKE on {}: {}", ifc.name, e);
         }
     }
 
     // 6. Apply backend tuning
-    if config.backend.iwd_periodic_scan_disable {
-        let backend_tuner = BackendTuner::new(true);
-        backend_tuner.apply()?;
-    }
+    let backend_tuner = BackendTuner::new(config.backend.iwd_periodic_scan_disable)
+        .with_wpa_tuning(wpa::Tuning::from_config(&config.backend));
+    backend_tuner.apply(wifi_mgr.interfaces(), &mut journal)?;
 
     info!("\n=== Optimization Complete ===");
     Ok(())
@@ -286,6 +435,47 @@ fn run_dry_run() -> Result<()> {
 fn run_revert() -> Result<()> {
     info!("=== Reverting hifi-wifi Optimizations ===\n");
 
+    let journal = Journal::open(Path::new(rollback::JOURNAL_PATH));
+    if journal.is_empty() {
+        info!("No rollback journal found - removing known hifi-wifi settings");
+        run_legacy_revert()?;
+        info!("\n=== Revert Complete ===");
+        return Ok(());
+    }
+
+    let reload_nm = journal.touches_file(Path::new(NM_POWERSAVE_CONF));
+    let restart_iwd = journal.touches_file(Path::new("/etc/iwd/main.conf"));
+    let restart_irqbalance = crate::system::irq::IRQBALANCE_CONFIGS.iter()
+        .any(|conf| journal.touches_file(Path::new(conf)));
+
+    // Restore exactly what was there before `apply`, newest change first
+    info!("Restoring {} recorded change(s)", journal.entries().len());
+    if let Err(e) = journal.revert() {
+        warn!("Revert incomplete: {} (run `hifi-wifi revert` again to retry)", e);
+    }
+
+    if reload_nm {
+        let _ = std::process::Command::new("nmcli")
+            .args(["general", "reload"])
+            .output();
+    }
+    if restart_iwd {
+        let _ = std::process::Command::new("systemctl")
+            .args(["restart", "iwd.service"])
+            .output();
+    }
+    if restart_irqbalance {
+        let _ = std::process::Command::new("systemctl")
+            .args(["try-restart", "irqbalance.service"])
+            .output();
+    }
+
+    info!("\n=== Revert Complete ===");
+    Ok(())
+}
+
+/// Revert to assumed defaults, for optimizations applied before the rollback journal existed
+fn run_legacy_revert() -> Result<()> {
     let wifi_mgr = WifiManager::new()?;
     
     // Remove CAKE qdiscs and restore defaults
@@ -318,13 +508,12 @@ fn run_revert() -> Result<()> {
 
     // Revert system optimizations
     let sys_opt = SystemOptimizer::default();
-    sys_opt.revert()?;
+    sys_opt.revert(wifi_mgr.interfaces())?;
 
     // Revert backend tuning
     let backend_tuner = BackendTuner::default();
     backend_tuner.revert()?;
 
-    info!("\n=== Revert Complete ===");
     Ok(())
 }
 
@@ -338,7 +527,7 @@ fn is_steamos() -> bool {
 }
 
 /// Run the Governor in monitor mode (daemon)
-async fn run_monitor(config: &config::structs::Config) -> Result<()> {
+async fn run_monitor(config: &config::structs::Config, record: Option<&Path>) -> Result<()> {
     info!("=== hifi-wifi v3.0 Monitor Mode ===");
 
     info!("Starting continuous optimization daemon...\n");
@@ -347,13 +536,19 @@ async fn run_monitor(config: &config::structs::Config) -> Result<()> {
     run_apply(config)?;
 
     // Start the Governor
-    let mut governor = Governor::new(config.governor.clone(), config.wifi.clone(), config.power.clone()).await?;
+    let mut governor = Governor::new(config.governor.clone(), config.wifi.clone(), config.power.clone(), &config.backend).await?;
+
+    if let Some(path) = record {
+        governor.record_to(TraceRecorder::create(path)?)?;
+        info!("Recording governor inputs to {}", path.display());
+    }
     
     info!("Governor initialized, entering main loop (tick: {}s)", 
           config.global.tick_rate_secs);
     
-    // Handle graceful shutdown
+    // Handle graceful shutdown: Ctrl+C, or SIGTERM from `systemctl stop`
     let ctrl_c = tokio::signal::ctrl_c();
+    let mut terminate = signal(SignalKind::terminate())?;
     
     tokio::select! {
         result = governor.run(config.global.tick_rate_secs) => {
@@ -363,7 +558,11 @@ async fn run_monitor(config: &config::structs::Config) -> Result<()> {
         }
         _ = ctrl_c => {
             info!("\nReceived shutdown signal");
-            governor.stop();
+            governor.stop().await;
+        }
+        _ = terminate.recv() => {
+            info!("Received SIGTERM");
+            governor.stop().await;
         }
     }
 
@@ -371,6 +570,155 @@ async fn run_monitor(config: &config::structs::Config) -> Result<()> {
     Ok(())
 }
 
+/// Replay a recorded trace through the governor logic and print its decisions
+fn run_replay(file: &Path, config: &config::structs::Config) -> Result<()> {
+    let trace::Trace { topology, ticks } = trace::read_trace(file)?;
+    let core = GovernorCore::new(config.governor.clone(), config.wifi.clone(), config.power.clone())
+        .with_topology(topology);
+    let decisions = trace::replay(&core, &ticks);
+
+    let duration = ticks.last().map(|t| t.at.as_secs_f64()).unwrap_or(0.0);
+    println!("Replayed {} ticks ({:.0}s) from {}", ticks.len(), duration, file.display());
+    println!();
+
+    if decisions.is_empty() {
+        println!("No actions.");
+        return Ok(());
+    }
+
+    let mut cake_changes = 0;
+    for (at, action) in &decisions {
+        if matches!(action, Action::ApplyCake { .. }) {
+            cake_changes += 1;
+        }
+        println!("  {:>8.1}s  {}", at.as_secs_f64(), action);
+    }
+
+    println!();
+    println!("{} actions, {} CAKE changes", decisions.len(), cake_changes);
+    Ok(())
+}
+
+/// Run the bufferbloat benchmark, optionally before/after toggling hifi-wifi
+async fn run_bench(options: &bench::BenchOptions, compare: bool, json: bool) -> Result<()> {
+    use std::process::Command;
+
+    if !compare {
+        let result = bench::run(options).await?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        } else {
+            bench::print_report(&result);
+        }
+        return Ok(());
+    }
+
+    let was_active = Command::new("systemctl")
+        .args(["is-active", "--quiet", "hifi-wifi"])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+
+    run_off()?;
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    let before = bench::run(options).await;
+
+    run_on()?;
+    // Let the governor apply CAKE and settle its bandwidth estimate
+    info!("Waiting 15s for the governor to settle...");
+    tokio::time::sleep(std::time::Duration::from_secs(15)).await;
+    let after = bench::run(options).await;
+
+    if !was_active {
+        info!("Restoring previous state (hifi-wifi off)");
+        run_off()?;
+    }
+
+    let (before, after) = (before?, after?);
+    if json {
+        let report = serde_json::json!({ "off": before, "on": after });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        bench::print_comparison(&before, &after);
+    }
+    Ok(())
+}
+
+/// Measure or show WAN uplink capacity
+async fn run_wan(action: WanAction, config: &config::structs::Config) -> Result<()> {
+    let path = Path::new(wan::MEASUREMENTS_PATH);
+
+    match action {
+        WanAction::Measure { network } => {
+            use crate::network::governor::io::{LinkSource, SystemLinks};
+
+            let links = SystemLinks::connect().await?.active_links().await?;
+            let network = match network {
+                Some(n) => n,
+                None => current_network(&links)?,
+            };
+            info!("Measuring WAN capacity for network '{}'...", network);
+
+            let interfaces: Vec<String> = links.iter().map(|l| l.interface.clone()).collect();
+            let measurement = tokio::task::spawn_blocking(move || wan::measure_unshaped(&interfaces)).await??;
+            println!("{}: {} Mbit up / {} Mbit down",
+                     network, measurement.upload_mbit, measurement.download_mbit);
+
+            wan::save_measurement(path, &network, measurement)?;
+            info!("Saved to {} (used by the governor on its next tick)", path.display());
+        }
+        WanAction::Status => {
+            let wan_config = &config.governor.wan;
+            println!("WAN-aware CAKE: {}", if wan_config.enabled { "enabled" } else { "disabled" });
+            match wan_config.upload_mbit {
+                Some(mbit) => println!("Default uplink: {} Mbit", mbit),
+                None => println!("Default uplink: none"),
+            }
+
+            println!();
+            println!("Configured:");
+            if wan_config.profiles.is_empty() {
+                println!("  (none)");
+            }
+            for profile in &wan_config.profiles {
+                println!("  {:<24} up {:>5} Mbit   down {:>5} Mbit", profile.network,
+                         profile.upload_mbit.map(|m| m.to_string()).unwrap_or_else(|| "-".into()),
+                         profile.download_mbit.map(|m| m.to_string()).unwrap_or_else(|| "-".into()));
+            }
+
+            println!();
+            println!("Measured:");
+            let measurements = wan::load_measurements(path);
+            if measurements.is_empty() {
+                println!("  (none - run: sudo hifi-wifi wan measure)");
+            }
+            let mut networks: Vec<_> = measurements.iter().collect();
+            networks.sort_by_key(|(network, _)| network.as_str());
+            for (network, m) in networks {
+                println!("  {:<24} up {:>5} Mbit   down {:>5} Mbit   ({})", network,
+                         m.upload_mbit, m.download_mbit, m.measured_at.format("%Y-%m-%d %H:%M"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Network the default link is on: SSID of the active WiFi, else the wired interface
+fn current_network(links: &[crate::network::governor::io::ActiveLink]) -> Result<String> {
+    use crate::network::wifi::InterfaceType;
+
+    // Prefer WiFi, then a wired link
+    if let Some(link) = links.iter().find(|l| l.interface_type == InterfaceType::Wifi) {
+        let ssid = link.active_ap.as_ref().map(|ap| ap.ssid.as_str());
+        return Ok(wan::network_key(&link.interface, ssid));
+    }
+    if let Some(link) = links.first() {
+        return Ok(link.interface.clone());
+    }
+    anyhow::bail!("No active connection found (use --network to name one)")
+}
+
 /// Convert WiFi frequency (MHz) to channel number
 fn freq_to_channel(freq: u32) -> u32 {
     match freq {
@@ -401,6 +749,8 @@ fn freq_to_channel(freq: u32) -> u32 {
 
 /// Run status with async NetworkManager info
 async fn run_status_async() -> Result<()> {
+    use crate::network::governor::io::{KernelLinks, LinkSource};
+    use crate::network::iwd::IwdClient;
     use crate::network::nm::NmClient;
     use std::process::Command;
 
@@ -437,18 +787,18 @@ async fn run_status_async() -> Result<()> {
     // 2. System and Power
     let power_mgr = PowerManager::new();
     println!("{}{}{}┌─ System Info{}", BOLD, BLUE, NC, NC);
-    println!("{}│{}  Device: {:?}", BLUE, NC, power_mgr.device_type());
+    match crate::system::devices::current() {
+        Some(device) => println!("{}│{}  Device: {} ({:?})", BLUE, NC, device.name, power_mgr.device_type()),
+        None => println!("{}│{}  Device: {:?}", BLUE, NC, power_mgr.device_type()),
+    }
     let bat_pct = power_mgr.battery_percentage().map(|p| format!("{}%", p)).unwrap_or("N/A".to_string());
     println!("{}│{}  Power:  {:?} (Battery: {})", BLUE, NC, power_mgr.power_source(), bat_pct);
 
     // Firmware info
-    if let Ok(fw_path) = crate::firmware::version::detect_firmware_path() {
-        if let Ok(fw_ver) = crate::firmware::version::FirmwareVersion::from_installed(&fw_path) {
-            let fw_type = if fw_ver.is_valve_stock() {
-                format!("{}(Valve stock){}", DIM, NC)
-            } else {
-                format!("{}(upstream){}", DIM, NC)
-            };
+    let fw_device = crate::firmware::device::DeviceInfo::detect();
+    if let Ok(installed) = crate::firmware::targets::detect(&fw_device) {
+        if let Ok(fw_ver) = crate::firmware::version::FirmwareVersion::from_installed(&installed) {
+            let fw_type = format!("{}{}{}", DIM, installed.target.version_label(&fw_ver), NC);
             // Truncate version string for display
             let ver_short = if fw_ver.version_string.len() > 40 {
                 format!("{}...", &fw_ver.version_string[..37])
@@ -463,6 +813,7 @@ async fn run_status_async() -> Result<()> {
     println!();
 
     // 3. Interfaces & Tweaks (CAKE, Power Save)
+    let config = load_config();
     let wifi_mgr = WifiManager::new_quiet()?;
     println!("{}{}{}┌─ Interfaces & Tweaks{}", BOLD, BLUE, NC, NC);
     
@@ -476,6 +827,7 @@ async fn run_status_async() -> Result<()> {
             crate::network::wifi::InterfaceType::Ethernet => "Ethernet",
         };
         println!("{}│{}  {}{}{} (Type: {}, Driver: {}, {:?})", BLUE, NC, BOLD, ifc.name, NC, ifc_type, ifc.driver, ifc.category);
+        println!("{}│{}    ├─ Device:     {}", BLUE, NC, ifc.device.summary());
 
         // CAKE Status (tc)
         let qdisc_out = Command::new("tc")
@@ -494,6 +846,23 @@ async fn run_status_async() -> Result<()> {
                 .and_then(|s| s.split_whitespace().next())
                 .unwrap_or("default");
              println!("{}│{}    ├─ CAKE:       {}[ACTIVE]{} Bandwidth: {} RTT: {}", BLUE, NC, GREEN, NC, bw, rtt);
+
+             // Which limit the governor sizes CAKE from
+             if let Ok(stats) = wifi_mgr.get_link_stats(ifc) {
+                 let network = wan::network_key(&ifc.name, stats.ssid.as_deref());
+                 let link_mbit = ((stats.tx_bitrate_mbps * config.governor.cake_overhead_factor) as u32)
+                     .max(crate::network::tc::MIN_LINK_MBIT);
+                 let uplink = wan::resolve_uplink(&config.governor.wan, &network, wan::measured_uplink(&network));
+                 let (_, constraint) = wan::combine(&config.governor.wan, link_mbit, uplink);
+                 let limit = match (constraint, uplink) {
+                     (wan::Constraint::Wan(source), Some((wan_mbit, _))) => format!(
+                         "{}WAN uplink{} {} Mbit ({:?}) < link {} Mbit", YELLOW, NC, wan_mbit, source, link_mbit),
+                     (_, Some((wan_mbit, _))) => format!(
+                         "{}Link rate{} {} Mbit < WAN uplink {} Mbit", GREEN, NC, link_mbit, wan_mbit),
+                     _ => format!("{}Link rate{} {} Mbit {}(WAN unknown){}", GREEN, NC, link_mbit, DIM, NC),
+                 };
+                 println!("{}│{}    ├─ Limit:      {}", BLUE, NC, limit);
+             }
         } else {
              println!("{}│{}    ├─ CAKE:       {}[INACTIVE]{}", BLUE, NC, RED, NC);
         }
@@ -613,7 +982,6 @@ async fn run_status_async() -> Result<()> {
     println!("{}{}{}┌─ Network Governor & Backend{}", BOLD, BLUE, NC, NC);
     println!("{}│{}  Backend: {:?}", BLUE, NC, backend.backend());
     
-    let config = load_config();
     let gov_status = if service_active { "Running" } else { "Stopped" };
     println!("{}│{}  Governor: {}", BLUE, NC, gov_status);
     println!("{}│{}    ├─ QoS Mode:   {}", BLUE, NC, if config.governor.breathing_cake_enabled { "Breathing CAKE (Dynamic)" } else { "Static CAKE" });
@@ -706,14 +1074,58 @@ async fn run_status_async() -> Result<()> {
             Err(_) => println!("{}│{}  Error querying NetworkManager", BLUE, NC),
         }
         println!("{}└{}", BLUE, NC);
+    } else if let Ok(iwd) = IwdClient::new().await {
+        println!("{}{}{}┌─ Active Connection (iwd){}", BOLD, BLUE, NC, NC);
+        let mut found_conn = false;
+        for station in iwd.stations().await.unwrap_or_default() {
+            let (Some(ssid), Ok(diag)) = (&station.ssid, iwd.diagnostics(&station.path).await) else {
+                continue;
+            };
+            found_conn = true;
+            let ap = diag.access_point(&station.path, ssid);
+            println!("{}│{}  {}{}{}: {}", BLUE, NC, BOLD, station.interface, NC, ap.ssid);
+            println!("{}│{}    ├─ BSSID:    {}", BLUE, NC, ap.bssid);
+            println!("{}│{}    ├─ Band:     {:?} (Ch {} @ {} MHz)", BLUE, NC, ap.band, freq_to_channel(ap.frequency), ap.frequency);
+            println!("{}│{}    ├─ Signal:   {} dBm", BLUE, NC, ap.signal_strength);
+            println!("{}│{}    └─ Link:     {} Mbit/s", BLUE, NC, diag.tx_bitrate_kbit / 1000);
+        }
+        if !found_conn {
+            println!("{}│{}  No active connection found", BLUE, NC);
+        }
+        if let Ok(known) = iwd.known_networks().await {
+            let names: Vec<String> = known.iter()
+                .map(|n| if n.auto_connect { n.name.clone() } else { format!("{} (manual)", n.name) })
+                .collect();
+            println!("{}│{}  Known networks: {}", BLUE, NC, if names.is_empty() { "none".to_string() } else { names.join(", ") });
+        }
+        println!("{}└{}", BLUE, NC);
+    } else {
+        println!("{}{}{}┌─ Active Connection (kernel){}", BOLD, BLUE, NC, NC);
+        let links = KernelLinks.active_links().await.unwrap_or_default();
+        for link in &links {
+            match &link.active_ap {
+                Some(ap) => {
+                    println!("{}│{}  {}{}{}: {}", BLUE, NC, BOLD, link.interface, NC, ap.ssid);
+                    println!("{}│{}    ├─ BSSID:    {}", BLUE, NC, ap.bssid);
+                    println!("{}│{}    ├─ Band:     {:?} (Ch {} @ {} MHz)", BLUE, NC, ap.band, freq_to_channel(ap.frequency), ap.frequency);
+                    println!("{}│{}    ├─ Signal:   {} dBm", BLUE, NC, ap.signal_strength);
+                }
+                None => println!("{}│{}  {}{}{}: Wired Ethernet", BLUE, NC, BOLD, link.interface, NC),
+            }
+            println!("{}│{}    └─ Link:     {} Mbit/s", BLUE, NC, link.bitrate_kbit / 1000);
+        }
+        if links.is_empty() {
+            println!("{}│{}  No active connection found", BLUE, NC);
+        }
+        println!("{}└{}", BLUE, NC);
     }
-    
+
     Ok(())
 }
 
 /// Install the systemd service
 /// Per rewrite.md: Binary in /var/lib/hifi-wifi (survives SteamOS updates)
-fn run_install() -> Result<()> {
+fn run_install(config: &config::structs::Config) -> Result<()> {
     use std::fs::{self, File};
     use std::io::Write;
     use std::os::unix::fs::PermissionsExt;
@@ -814,8 +1226,13 @@ WantedBy=multi-user.target
     Command::new("systemctl").args(["enable", "hifi-wifi.service"]).output()?;
     Command::new("systemctl").args(["start", "hifi-wifi.service"]).output()?;
 
-    // Install NetworkManager dispatcher for connection events (per roadmap-beta2.md)
-    install_nm_dispatcher()?;
+    // The Governor follows NM's D-Bus signals; the dispatcher (per roadmap-beta2.md)
+    // is only installed for setups that opt into the event file
+    if config.governor.connection_event_file {
+        install_nm_dispatcher()?;
+    } else if fs::remove_file(NM_DISPATCHER_PATH).is_ok() {
+        info!("Removed NetworkManager dispatcher (connection events now come over D-Bus)");
+    }
 
     info!("\n=== Installation Complete ===");
     info!("Service installed and started.");
@@ -833,6 +1250,8 @@ WantedBy=multi-user.target
     Ok(())
 }
 
+const NM_DISPATCHER_PATH: &str = "/etc/NetworkManager/dispatcher.d/99-hifi-wifi-connect";
+
 /// Install NetworkManager dispatcher for connection events
 /// Per roadmap-beta2.md: This signals the daemon when WiFi reconnects
 fn install_nm_dispatcher() -> Result<()> {
@@ -840,8 +1259,8 @@ fn install_nm_dispatcher() -> Result<()> {
     use std::io::Write;
     use std::os::unix::fs::PermissionsExt;
     
-    let dispatcher_dir = std::path::Path::new("/etc/NetworkManager/dispatcher.d");
-    let dispatcher_path = dispatcher_dir.join("99-hifi-wifi-connect");
+    let dispatcher_path = std::path::Path::new(NM_DISPATCHER_PATH);
+    let dispatcher_dir = dispatcher_path.parent().unwrap_or(std::path::Path::new("/"));
     
     info!("Installing NetworkManager dispatcher: {}", dispatcher_path.display());
     
@@ -887,13 +1306,13 @@ touch /run/hifi-wifi/connection-changed
 logger -t hifi-wifi "Connection event: $INTERFACE $ACTION - signaled daemon"
 "#;
 
-    let mut file = File::create(&dispatcher_path)?;
+    let mut file = File::create(dispatcher_path)?;
     file.write_all(dispatcher_content.as_bytes())?;
     
     // Must be executable
-    let mut perms = fs::metadata(&dispatcher_path)?.permissions();
+    let mut perms = fs::metadata(dispatcher_path)?.permissions();
     perms.set_mode(0o755);
-    fs::set_permissions(&dispatcher_path, perms)?;
+    fs::set_permissions(dispatcher_path, perms)?;
     
     // Create the run directory and event file
     let run_dir = std::path::Path::new("/run/hifi-wifi");
@@ -1127,7 +1546,7 @@ fn run_uninstall() -> Result<()> {
         "/etc/systemd/system/hifi-wifi-bootstrap.timer",
         "/var/lib/hifi-wifi/hifi-wifi-bootstrap.service",
         "/var/lib/hifi-wifi/hifi-wifi-bootstrap.timer",
-        "/etc/NetworkManager/dispatcher.d/99-hifi-wifi-connect",
+        NM_DISPATCHER_PATH,
         "/etc/NetworkManager/conf.d/99-hifi-wifi-powersave.conf",
     ];
     
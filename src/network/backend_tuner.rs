@@ -9,6 +9,10 @@ use std::io::Write;
 use std::path::Path;
 use std::process::Command;
 
+use crate::network::wifi::{InterfaceType, WifiInterface};
+use crate::network::wpa::{self, Tuning};
+use crate::system::rollback::{Change, Journal};
+
 /// Detected Wi-Fi backend
 #[derive(Debug, Clone, PartialEq)]
 pub enum WifiBackend {
@@ -21,6 +25,8 @@ pub enum WifiBackend {
 pub struct BackendTuner {
     backend: WifiBackend,
     disable_periodic_scan: bool,
+    /// Per-connection wpa_supplicant settings
+    wpa_tuning: Option<Tuning>,
 }
 
 impl BackendTuner {
@@ -31,9 +37,16 @@ impl BackendTuner {
         Self {
             backend,
             disable_periodic_scan,
+            wpa_tuning: None,
         }
     }
 
+    /// Tune wpa_supplicant connections with `tuning`
+    pub fn with_wpa_tuning(mut self, tuning: Tuning) -> Self {
+        self.wpa_tuning = Some(tuning);
+        self
+    }
+
     /// Detect the active Wi-Fi backend
     fn detect_backend() -> WifiBackend {
         // Check if iwd is running
@@ -82,11 +95,12 @@ impl BackendTuner {
         &self.backend
     }
 
-    /// Apply backend-specific optimizations
-    pub fn apply(&self) -> Result<()> {
+    /// Apply backend-specific optimizations, recording prior state in `journal`
+    pub fn apply(&self, interfaces: &[WifiInterface], journal: &mut Journal) -> Result<()> {
         match self.backend {
-            WifiBackend::Iwd => self.tune_iwd(),
-            WifiBackend::WpaSupplicant => self.tune_wpa_supplicant(),
+            WifiBackend::Iwd if self.disable_periodic_scan => self.tune_iwd(journal),
+            WifiBackend::Iwd => Ok(()),
+            WifiBackend::WpaSupplicant => self.tune_wpa_supplicant(interfaces, journal),
             WifiBackend::Unknown => {
                 debug!("Unknown backend, skipping tuning");
                 Ok(())
@@ -95,7 +109,7 @@ impl BackendTuner {
     }
 
     /// Apply iwd-specific optimizations
-    fn tune_iwd(&self) -> Result<()> {
+    fn tune_iwd(&self, journal: &mut Journal) -> Result<()> {
         info!("Applying iwd optimizations...");
 
         let iwd_conf_dir = Path::new("/etc/iwd");
@@ -104,7 +118,7 @@ impl BackendTuner {
         // Don't overwrite existing config
         if iwd_conf_path.exists() {
             info!("Existing /etc/iwd/main.conf found, checking for updates...");
-            return self.update_iwd_config(&iwd_conf_path);
+            return self.update_iwd_config(&iwd_conf_path, journal);
         }
 
         // Create directory (may fail on read-only filesystem)
@@ -139,6 +153,7 @@ BandModifier5GHz=2.0
 BandModifier6GHz=3.0
 "#, self.disable_periodic_scan);
 
+        journal.record("backend", Change::file(&iwd_conf_path));
         match File::create(&iwd_conf_path) {
             Ok(mut file) => {
                 file.write_all(config.as_bytes())?;
@@ -159,7 +174,7 @@ BandModifier6GHz=3.0
     }
 
     /// Update existing iwd config without full overwrite
-    fn update_iwd_config(&self, path: &Path) -> Result<()> {
+    fn update_iwd_config(&self, path: &Path, journal: &mut Journal) -> Result<()> {
         let content = match fs::read_to_string(path) {
             Ok(c) => c,
             Err(e) => {
@@ -176,6 +191,7 @@ BandModifier6GHz=3.0
 
         // Append Scan section if missing
         if !content.contains("[Scan]") && self.disable_periodic_scan {
+            journal.record("backend", Some(Change::File { path: path.to_path_buf(), previous: Some(content) }));
             match fs::OpenOptions::new().append(true).open(path) {
                 Ok(mut file) => {
                     let _ = writeln!(file, "\n[Scan]");
@@ -191,11 +207,20 @@ BandModifier6GHz=3.0
         Ok(())
     }
 
-    /// Apply wpa_supplicant optimizations (minimal, as NM handles most)
-    fn tune_wpa_supplicant(&self) -> Result<()> {
-        info!("wpa_supplicant backend detected - using NetworkManager defaults");
-        // wpa_supplicant is typically managed by NetworkManager
-        // Most optimizations are handled via nmcli connection settings
+    /// Apply wpa_supplicant optimizations to the current connections
+    /// bgscan only lasts for one connection; the Governor re-applies it on reconnect.
+    fn tune_wpa_supplicant(&self, interfaces: &[WifiInterface], journal: &mut Journal) -> Result<()> {
+        let Some(tuning) = &self.wpa_tuning else {
+            debug!("No wpa_supplicant tuning configured");
+            return Ok(());
+        };
+        info!("Applying wpa_supplicant optimizations...");
+
+        for ifc in interfaces.iter().filter(|i| i.interface_type == InterfaceType::Wifi) {
+            if let Err(e) = wpa::tune(&ifc.name, tuning, "backend", journal) {
+                warn!("Failed to tune wpa_supplicant on {}: {}", ifc.name, e);
+            }
+        }
         Ok(())
     }
 
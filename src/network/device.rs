@@ -0,0 +1,354 @@
+//! Network device identification
+//!
+//! Works out what an interface's adapter is and what it can do: bus and
+//! PCI/USB IDs from sysfs, a chipset name from an embedded ID table, the
+//! firmware version from `ethtool -i`, and supported bands and WiFi
+//! generation from the wiphy's capabilities (`iw phy`).
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::network::nm::WifiBand;
+
+/// Bus the adapter sits on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Bus {
+    Pci,
+    Usb,
+    Sdio,
+    Platform,
+    #[default]
+    Unknown,
+}
+
+impl fmt::Display for Bus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Bus::Pci => "PCI",
+            Bus::Usb => "USB",
+            Bus::Sdio => "SDIO",
+            Bus::Platform => "platform",
+            Bus::Unknown => "unknown bus",
+        };
+        f.write_str(name)
+    }
+}
+
+/// WiFi generation, from the highest PHY the adapter supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WifiGeneration {
+    /// 802.11n (HT)
+    Wifi4,
+    /// 802.11ac (VHT)
+    Wifi5,
+    /// 802.11ax (HE)
+    Wifi6,
+    /// 802.11ax with 6GHz
+    Wifi6E,
+    /// 802.11be (EHT)
+    Wifi7,
+}
+
+impl fmt::Display for WifiGeneration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            WifiGeneration::Wifi4 => "WiFi 4",
+            WifiGeneration::Wifi5 => "WiFi 5",
+            WifiGeneration::Wifi6 => "WiFi 6",
+            WifiGeneration::Wifi6E => "WiFi 6E",
+            WifiGeneration::Wifi7 => "WiFi 7",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Known chipsets by "vendor:device" ID
+const CHIPSETS: &[(&str, &str)] = &[
+    // Qualcomm / Atheros
+    ("17cb:1103", "Qualcomm WCN6855 / QCA2066"),
+    ("17cb:1101", "Qualcomm QCA6390"),
+    ("168c:003e", "Qualcomm Atheros QCA6174"),
+    ("168c:0032", "Atheros AR9485"),
+    // Realtek
+    ("10ec:c822", "Realtek RTL8822CE"),
+    ("10ec:8852", "Realtek RTL8852AE"),
+    ("10ec:b852", "Realtek RTL8852BE"),
+    ("10ec:c852", "Realtek RTL8852CE"),
+    ("10ec:818b", "Realtek RTL8192EE"),
+    ("10ec:8179", "Realtek RTL8188EE"),
+    ("0bda:8812", "Realtek RTL8812AU"),
+    // Intel
+    ("8086:2723", "Intel Wi-Fi 6 AX200"),
+    ("8086:a0f0", "Intel Wi-Fi 6 AX201"),
+    ("8086:2725", "Intel Wi-Fi 6E AX210"),
+    ("8086:51f0", "Intel Wi-Fi 6E AX211"),
+    ("8086:272b", "Intel Wi-Fi 7 BE200"),
+    // MediaTek
+    ("14c3:7961", "MediaTek MT7921"),
+    ("14c3:0608", "MediaTek MT7921K (RZ608)"),
+    ("14c3:0616", "MediaTek MT7922"),
+    ("0e8d:7961", "MediaTek MT7921AU"),
+    // Broadcom
+    ("14e4:43a0", "Broadcom BCM4360"),
+];
+
+/// Everything known about an interface's adapter
+#[derive(Debug, Clone, Default)]
+pub struct DeviceIdentity {
+    pub bus: Bus,
+    /// IDs as lowercase hex without "0x"
+    pub vendor: Option<String>,
+    pub device: Option<String>,
+    pub subsystem_vendor: Option<String>,
+    pub subsystem_device: Option<String>,
+    pub chipset: Option<&'static str>,
+    pub firmware_version: Option<String>,
+    /// Bands with at least one usable channel (WiFi only)
+    pub bands: Vec<WifiBand>,
+    pub generation: Option<WifiGeneration>,
+}
+
+impl DeviceIdentity {
+    /// Identify the adapter behind `ifc_name`; wiphy capabilities are only read for WiFi
+    pub fn identify(ifc_name: &str, wifi: bool) -> Self {
+        let device_dir = Path::new("/sys/class/net").join(ifc_name).join("device");
+        let mut identity = Self::from_sysfs(&device_dir);
+        identity.chipset = identity.id().and_then(|id| chipset_name(&id));
+        identity.firmware_version = firmware_version(ifc_name);
+        if wifi {
+            (identity.bands, identity.generation) = wiphy(ifc_name);
+        }
+        identity
+    }
+
+    /// IDs and bus of the device at `device_dir` (a netdev's `device` link or a PCI device)
+    pub fn from_sysfs(device_dir: &Path) -> Self {
+        let bus = match fs::read_link(device_dir.join("subsystem")) {
+            Ok(link) => match link.file_name().and_then(|n| n.to_str()) {
+                Some("pci") => Bus::Pci,
+                Some("usb") => Bus::Usb,
+                Some("sdio") => Bus::Sdio,
+                Some("platform") => Bus::Platform,
+                _ => Bus::Unknown,
+            },
+            Err(_) => Bus::Unknown,
+        };
+        let read = |name: &str| read_id(&device_dir.join(name));
+
+        match bus {
+            // The netdev's device is the USB interface; IDs live on its parent
+            Bus::Usb => Self {
+                bus,
+                vendor: read("../idVendor"),
+                device: read("../idProduct"),
+                ..Self::default()
+            },
+            _ => Self {
+                bus,
+                vendor: read("vendor"),
+                device: read("device"),
+                subsystem_vendor: read("subsystem_vendor"),
+                subsystem_device: read("subsystem_device"),
+                ..Self::default()
+            },
+        }
+    }
+
+    /// "vendor:device"
+    pub fn id(&self) -> Option<String> {
+        Some(format!("{}:{}", self.vendor.as_ref()?, self.device.as_ref()?))
+    }
+
+    /// Subsystem "vendor:device" (PCI only)
+    pub fn subsystem_id(&self) -> Option<String> {
+        Some(format!("{}:{}", self.subsystem_vendor.as_ref()?, self.subsystem_device.as_ref()?))
+    }
+
+    /// IDs to match against, most specific first:
+    /// "vendor:device:subsystem_vendor:subsystem_device", then "vendor:device"
+    pub fn ids(&self) -> Vec<String> {
+        let Some(id) = self.id() else {
+            return Vec::new();
+        };
+        match (&self.subsystem_vendor, &self.subsystem_device) {
+            (Some(sv), Some(sd)) => vec![format!("{}:{}:{}", id, sv, sd), id],
+            _ => vec![id],
+        }
+    }
+
+    /// One line for logs and `status`
+    pub fn summary(&self) -> String {
+        let mut parts = vec![match self.id() {
+            Some(id) => format!("{} {}", self.bus, id),
+            None => self.bus.to_string(),
+        }];
+        if let Some(chipset) = self.chipset {
+            parts.push(chipset.to_string());
+        }
+        if let Some(generation) = self.generation {
+            parts.push(generation.to_string());
+        }
+        if !self.bands.is_empty() {
+            parts.push(band_list(&self.bands));
+        }
+        if let Some(fw) = &self.firmware_version {
+            parts.push(format!("fw {}", fw));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Chipset name for a "vendor:device" ID
+pub fn chipset_name(id: &str) -> Option<&'static str> {
+    CHIPSETS.iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(id))
+        .map(|(_, name)| *name)
+}
+
+/// Bands as "2.4/5/6GHz"
+pub fn band_list(bands: &[WifiBand]) -> String {
+    let names: Vec<&str> = bands.iter()
+        .filter_map(|band| match band {
+            WifiBand::Band2_4GHz => Some("2.4"),
+            WifiBand::Band5GHz => Some("5"),
+            WifiBand::Band6GHz => Some("6"),
+            WifiBand::Unknown => None,
+        })
+        .collect();
+    format!("{}GHz", names.join("/"))
+}
+
+fn read_id(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok()
+        .map(|s| s.trim().trim_start_matches("0x").to_lowercase())
+        .filter(|s| !s.is_empty())
+}
+
+/// Firmware version from `ethtool -i`
+fn firmware_version(ifc_name: &str) -> Option<String> {
+    let output = Command::new("ethtool").args(["-i", ifc_name]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_firmware_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_firmware_version(ethtool_info: &str) -> Option<String> {
+    ethtool_info.lines()
+        .find_map(|line| line.strip_prefix("firmware-version:"))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty() && v != "N/A")
+}
+
+/// Supported bands and WiFi generation of the interface's wiphy
+pub fn wiphy(ifc_name: &str) -> (Vec<WifiBand>, Option<WifiGeneration>) {
+    let phy_name = Path::new("/sys/class/net").join(ifc_name).join("phy80211/name");
+    let Ok(phy) = fs::read_to_string(phy_name) else {
+        return (Vec::new(), None);
+    };
+    match Command::new("iw").args(["phy", phy.trim(), "info"]).output() {
+        Ok(output) if output.status.success() => parse_wiphy(&String::from_utf8_lossy(&output.stdout)),
+        _ => (Vec::new(), None),
+    }
+}
+
+/// Parse `iw phy <phy> info`: bands with an enabled channel, and the newest PHY listed
+fn parse_wiphy(info: &str) -> (Vec<WifiBand>, Option<WifiGeneration>) {
+    let mut bands = Vec::new();
+    for line in info.lines() {
+        // "* 5955.0 MHz [1] (22.0 dBm)" or "* 5180 MHz [36] (disabled)"
+        let Some(channel) = line.trim().strip_prefix("* ") else {
+            continue;
+        };
+        let Some((freq, rest)) = channel.split_once(" MHz") else {
+            continue;
+        };
+        let Ok(freq) = freq.trim().parse::<f64>() else {
+            continue;
+        };
+        let band = WifiBand::from_frequency(freq as u32);
+        if band != WifiBand::Unknown && !rest.contains("disabled") && !bands.contains(&band) {
+            bands.push(band);
+        }
+    }
+    bands.sort_by_key(|band| match band {
+        WifiBand::Band2_4GHz => 0,
+        WifiBand::Band5GHz => 1,
+        WifiBand::Band6GHz => 2,
+        WifiBand::Unknown => 3,
+    });
+
+    let generation = if info.contains("EHT Iftypes") || info.contains("EHT MAC Capabilities") {
+        Some(WifiGeneration::Wifi7)
+    } else if info.contains("HE Iftypes") || info.contains("HE PHY Capabilities") {
+        if bands.contains(&WifiBand::Band6GHz) {
+            Some(WifiGeneration::Wifi6E)
+        } else {
+            Some(WifiGeneration::Wifi6)
+        }
+    } else if info.contains("VHT Capabilities") {
+        Some(WifiGeneration::Wifi5)
+    } else if info.contains("HT TX/RX MCS rate indexes") || info.contains("HT Max RX data rate") {
+        Some(WifiGeneration::Wifi4)
+    } else {
+        None
+    };
+
+    (bands, generation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_wiphy() {
+        let info = "\
+Wiphy phy0
+\tBand 1:
+\t\tHT Max RX data rate: 300 Mbps
+\t\tHE Iftypes: managed
+\t\tFrequencies:
+\t\t\t* 2412.0 MHz [1] (22.0 dBm)
+\t\t\t* 2484.0 MHz [14] (disabled)
+\tBand 2:
+\t\tVHT Capabilities (0x0f8259b2):
+\t\tFrequencies:
+\t\t\t* 5180.0 MHz [36] (22.0 dBm)
+\tBand 4:
+\t\tFrequencies:
+\t\t\t* 5955 MHz [1] (disabled)
+\t\t\t* 5975 MHz [5] (disabled)
+";
+        let (bands, generation) = parse_wiphy(info);
+        assert_eq!(bands, vec![WifiBand::Band2_4GHz, WifiBand::Band5GHz]);
+        assert_eq!(generation, Some(WifiGeneration::Wifi6));
+
+        let enabled_6ghz = info.replace("[5] (disabled)", "[5] (12.0 dBm)");
+        let (bands, generation) = parse_wiphy(&enabled_6ghz);
+        assert_eq!(band_list(&bands), "2.4/5/6GHz");
+        assert_eq!(generation, Some(WifiGeneration::Wifi6E));
+    }
+
+    #[test]
+    fn test_ids_and_summary() {
+        let identity = DeviceIdentity {
+            bus: Bus::Pci,
+            vendor: Some("17cb".to_string()),
+            device: Some("1103".to_string()),
+            subsystem_vendor: Some("17cb".to_string()),
+            subsystem_device: Some("0108".to_string()),
+            chipset: chipset_name("17CB:1103"),
+            firmware_version: parse_firmware_version(
+                "driver: ath11k_pci\nversion: 6.5.0\nfirmware-version: WLAN.HSP.1.1-03125\nbus-info: 0000:03:00.0\n"),
+            bands: vec![WifiBand::Band2_4GHz, WifiBand::Band5GHz, WifiBand::Band6GHz],
+            generation: Some(WifiGeneration::Wifi6E),
+        };
+        assert_eq!(identity.ids(), vec!["17cb:1103:17cb:0108", "17cb:1103"]);
+        assert_eq!(identity.summary(),
+                   "PCI 17cb:1103, Qualcomm WCN6855 / QCA2066, WiFi 6E, 2.4/5/6GHz, fw WLAN.HSP.1.1-03125");
+        assert_eq!(parse_firmware_version("driver: r8169\nfirmware-version: N/A\n"), None);
+    }
+}
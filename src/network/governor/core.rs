@@ -0,0 +1,794 @@
+//! Governor decision logic
+//!
+//! Everything the Governor decides lives here, with no I/O: each tick takes a
+//! `TickInputs` snapshot (what NM, iw, sysfs, /proc/stat and the power supply
+//! reported) and returns the `Action`s to apply. Time is passed in as well, so
+//! the same inputs always produce the same actions.
+
+use log::{info, debug};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::Duration;
+
+use crate::config::structs::{GovernorConfig, PowerConfig, PowerTier, WifiConfig};
+use crate::network::nm::{AccessPoint, WifiBand};
+use crate::network::stats::{NetStats, PpsMonitor};
+use crate::network::tc::{self, TcManager};
+use crate::network::wan::{self, Constraint};
+use crate::network::wifi::InterfaceType;
+use crate::system::irq::Topology;
+
+use super::policy::{PowerPolicy, PowerReadings};
+use super::status::LinkStatus;
+
+/// One managed link as seen at the start of a tick
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkSnapshot {
+    pub interface: String,
+    /// Backend object path (NM device path), used for scan requests
+    pub device_path: String,
+    pub interface_type: InterfaceType,
+    /// Bitrate reported by NetworkManager (Kbit/s, 0 if unknown)
+    /// For Ethernet this is the negotiated link speed
+    pub nm_bitrate_kbit: u32,
+    /// Bitrate reported by `iw` (Kbit/s, 0 if unknown)
+    pub iw_bitrate_kbit: u32,
+    /// Packet/byte counters from sysfs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<NetStats>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_ap: Option<AccessPoint>,
+    /// Visible APs, only gathered when band steering will look at them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_points: Option<Vec<AccessPoint>>,
+    /// WAN uplink measured for this network (`hifi-wifi wan measure`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub measured_uplink_mbit: Option<u32>,
+    /// Bands the adapter can use (empty if unknown)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub supported_bands: Vec<WifiBand>,
+    /// CPUs the adapter's IRQs are handled on (empty if unknown or not read)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub irq_cpus: Vec<usize>,
+}
+
+/// Everything the Governor reads from the system in one tick
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TickInputs {
+    /// Monotonic time since the Governor started
+    #[serde(rename = "at_ms", with = "super::trace::millis")]
+    pub at: Duration,
+    /// Smoothed CPU load (0.0-1.0)
+    pub cpu_load: f64,
+    /// Load of each CPU since the previous sample (0.0-1.0), by CPU number
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub cpu_loads: BTreeMap<usize, f64>,
+    /// Power source, battery and temperature
+    #[serde(flatten)]
+    pub power: PowerReadings,
+    /// Background scans are being aborted, so the AP list is stale
+    #[serde(default)]
+    pub scan_suppress: bool,
+    pub links: Vec<LinkSnapshot>,
+}
+
+/// A change the Governor wants applied to the system
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    ApplyCake { interface: String, bandwidth_mbit: u32 },
+    SetCoalescing { interface: String, enabled: bool },
+    SetPowerSave { interface: String, enabled: bool },
+    SetEee { interface: String, enabled: bool },
+    /// Move the link's IRQs and RPS/XPS steering to these CPUs
+    SteerIrqs { interface: String, cpus: Vec<usize> },
+    /// Ask the backend to roam to `bssid` (on `frequency` MHz), or at least
+    /// scan so the driver can find it
+    RequestScan { interface: String, device_path: String, bssid: String, frequency: u32 },
+    /// Apply per-connection backend tuning (wpa_supplicant bgscan/autoscan)
+    TuneBackend { interface: String },
+}
+
+impl Action {
+    /// Interface the action targets
+    pub fn interface(&self) -> &str {
+        match self {
+            Action::ApplyCake { interface, .. }
+            | Action::SetCoalescing { interface, .. }
+            | Action::SetPowerSave { interface, .. }
+            | Action::SetEee { interface, .. }
+            | Action::SteerIrqs { interface, .. }
+            | Action::RequestScan { interface, .. }
+            | Action::TuneBackend { interface } => interface,
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let on_off = |enabled: &bool| if *enabled { "on" } else { "off" };
+        match self {
+            Action::ApplyCake { interface, bandwidth_mbit } => write!(f, "{}: CAKE {} Mbit", interface, bandwidth_mbit),
+            Action::SetCoalescing { interface, enabled } => write!(f, "{}: coalescing {}", interface, on_off(enabled)),
+            Action::SetPowerSave { interface, enabled } => write!(f, "{}: power save {}", interface, on_off(enabled)),
+            Action::SetEee { interface, enabled } => write!(f, "{}: EEE {}", interface, on_off(enabled)),
+            Action::SteerIrqs { interface, cpus } => write!(f, "{}: IRQs on CPU {:?}", interface, cpus),
+            Action::RequestScan { interface, .. } => write!(f, "{}: roam scan", interface),
+            Action::TuneBackend { interface } => write!(f, "{}: backend tuning", interface),
+        }
+    }
+}
+
+/// Whether WiFi power save and EEE should be on in `tier`
+fn saves_power(tier: PowerTier, in_game: bool, has_network_activity: bool) -> bool {
+    match tier {
+        PowerTier::Performance => false,
+        PowerTier::Balanced => !in_game && !has_network_activity,
+        PowerTier::Saver => true,
+    }
+}
+
+/// Why power save or EEE was switched, for the log
+fn power_save_reason(enabled: bool, tier: PowerTier, on_battery: bool, in_game: bool) -> &'static str {
+    match (enabled, tier) {
+        (true, PowerTier::Saver) => "saver tier",
+        (true, _) if on_battery => "battery, idle",
+        (true, _) => "running warm, idle",
+        (false, PowerTier::Performance) if on_battery => "performance tier",
+        (false, PowerTier::Performance) => "AC power",
+        (false, _) if in_game => "game mode",
+        (false, _) => "network activity",
+    }
+}
+
+/// Band steering candidate tracking for hysteresis
+#[derive(Debug, Default)]
+struct RoamCandidate {
+    bssid: String,
+    score: i32,
+    consecutive_ticks: u32,
+}
+
+/// Per-interface state
+struct InterfaceState {
+    pps_monitor: PpsMonitor,
+    tc_manager: TcManager,
+    roam_candidate: Option<RoamCandidate>,
+    game_mode_until: Option<Duration>,
+    coalescing_enabled: bool,
+    coalescing_stable_ticks: u32,
+    pending_coalescing: Option<bool>,
+    power_save_enabled: Option<bool>,
+    power_save_stable_ticks: u32,
+    pending_power_save: Option<bool>,
+    eee_enabled: Option<bool>,
+    eee_stable_ticks: u32,
+    pending_eee: Option<bool>,
+    /// CPUs the IRQs were last steered to (None until the first steer)
+    steering_cpus: Option<Vec<usize>>,
+    steering_stable_ticks: u32,
+    /// Backend tuning applied to the current connection
+    backend_tuned: bool,
+    /// Last known bytes for throughput calculation
+    last_rx_bytes: u64,
+    last_tx_bytes: u64,
+    last_stats_time: Option<Duration>,
+    /// Whether we have valid bandwidth data (false = CAKE disabled)
+    bandwidth_valid: bool,
+    /// Last known good bitrate (Kbit/s) - used when current reading is garbage (MCS0 probes)
+    last_good_bitrate: Option<u32>,
+    /// Which limit CAKE is currently sized from
+    cake_constraint: Option<Constraint>,
+    /// Smoothed PPS from the last tick
+    pps: u64,
+}
+
+impl InterfaceState {
+    fn new(config: &GovernorConfig) -> Self {
+        Self {
+            pps_monitor: PpsMonitor::new(),
+            tc_manager: TcManager::new(
+                config.cake_median_window,
+                config.cake_change_threshold_mbit,
+                config.cake_change_threshold_pct,
+                config.cake_hysteresis_up,
+                config.cake_hysteresis_down,
+            ),
+            roam_candidate: None,
+            game_mode_until: None,
+            coalescing_enabled: false,
+            coalescing_stable_ticks: 0,
+            pending_coalescing: None,
+            power_save_enabled: None,
+            power_save_stable_ticks: 0,
+            pending_power_save: None,
+            eee_enabled: None,
+            eee_stable_ticks: 0,
+            pending_eee: None,
+            steering_cpus: None,
+            steering_stable_ticks: 0,
+            backend_tuned: false,
+            last_rx_bytes: 0,
+            last_tx_bytes: 0,
+            last_stats_time: None,
+            bandwidth_valid: false,
+            last_good_bitrate: None,
+            cake_constraint: None,
+            pps: 0,
+        }
+    }
+
+    fn in_game(&self, now: Duration) -> bool {
+        self.game_mode_until.map(|until| now < until).unwrap_or(false)
+    }
+}
+
+/// The Governor's decision state machine
+pub struct GovernorCore {
+    config: GovernorConfig,
+    wifi_config: WifiConfig,
+    power_config: PowerConfig,
+    interface_states: HashMap<String, InterfaceState>,
+    /// When true, background scans are being suppressed (band steering is skipped)
+    scan_suppress_active: bool,
+    /// CPU layout for IRQ steering (empty = steering off)
+    topology: Topology,
+    /// Power tier, updated every tick
+    policy: PowerPolicy,
+}
+
+impl GovernorCore {
+    pub fn new(config: GovernorConfig, wifi_config: WifiConfig, power_config: PowerConfig) -> Self {
+        Self {
+            config,
+            wifi_config,
+            power_config,
+            interface_states: HashMap::new(),
+            scan_suppress_active: false,
+            topology: Topology::default(),
+            policy: PowerPolicy::default(),
+        }
+    }
+
+    /// Steer IRQs using this CPU layout
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// A new core with the same configuration and no interface state
+    pub fn fresh(&self) -> Self {
+        Self::new(self.config.clone(), self.wifi_config.clone(), self.power_config.clone())
+            .with_topology(self.topology.clone())
+    }
+
+    pub fn config(&self) -> &GovernorConfig {
+        &self.config
+    }
+
+    pub fn topology(&self) -> &Topology {
+        &self.topology
+    }
+
+    pub fn power_config(&self) -> &PowerConfig {
+        &self.power_config
+    }
+
+    /// Whether band steering will need the AP list this tick
+    /// Skipped when scan suppress is active - scan results are stale/empty
+    pub fn wants_access_points(&self, scan_suppress: bool) -> bool {
+        self.config.band_steering_enabled && !self.suppressing(scan_suppress)
+    }
+
+    /// The supervisor's scan suppression decision, as far as this config allows it
+    fn suppressing(&self, scan_suppress: bool) -> bool {
+        self.config.scan_suppress && scan_suppress
+    }
+
+    /// Whether the IRQ affinity is worth reading for the next tick
+    pub fn wants_irq_cpus(&self) -> bool {
+        self.config.irq_steering_enabled
+    }
+
+    /// Clear cached link state after a reconnect or roam
+    pub fn reset_link_state(&mut self) {
+        for (interface, state) in &mut self.interface_states {
+            if state.last_good_bitrate.is_some() {
+                info!("Clearing cached bitrate for {} (was {:?} Kbit/s)",
+                      interface, state.last_good_bitrate);
+            }
+            state.last_good_bitrate = None;
+            state.bandwidth_valid = false;
+            state.power_save_enabled = None; // Force re-apply on next tick
+            state.backend_tuned = false; // New connection, new wpa_supplicant network
+        }
+    }
+
+    /// Current state of `interface`, for `hifi-wifi watch`
+    pub fn status(&self, interface: &str, now: Duration) -> Option<LinkStatus> {
+        let state = self.interface_states.get(interface)?;
+        Some(LinkStatus {
+            interface: interface.to_string(),
+            pps: state.pps,
+            game_mode: state.in_game(now),
+            cake_mbit: state.tc_manager.last_applied(),
+            cake_constraint: state.cake_constraint,
+            power_save: state.power_save_enabled,
+            coalescing: state.coalescing_enabled,
+            eee: state.eee_enabled,
+        })
+    }
+
+    /// Feed back the outcome of an action returned by `tick`
+    pub fn action_completed(&mut self, action: &Action, ok: bool) {
+        let Some(state) = self.interface_states.get_mut(action.interface()) else {
+            return;
+        };
+        match action {
+            Action::ApplyCake { bandwidth_mbit, .. } if ok => {
+                state.tc_manager.set_last_applied(*bandwidth_mbit);
+            }
+            Action::SetPowerSave { .. } if !ok => {
+                // Unknown state - retried on the next tick
+                state.power_save_enabled = None;
+            }
+            Action::TuneBackend { .. } if !ok => {
+                state.backend_tuned = false;
+            }
+            Action::SteerIrqs { .. } if !ok => {
+                // Judged by the affinity read from the system again
+                state.steering_cpus = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Single tick of the governor loop
+    pub fn tick(&mut self, inputs: &TickInputs) -> Vec<Action> {
+        let mut actions = Vec::new();
+        let now = inputs.at;
+        let cpu_load = inputs.cpu_load;
+        debug!("Tick: CPU load {:.1}%", cpu_load * 100.0);
+
+        let tier = self.policy.update(&self.power_config, &inputs.power);
+
+        self.scan_suppress_active = self.suppressing(inputs.scan_suppress);
+
+        for link in &inputs.links {
+            let interface = &link.interface;
+            info!("Processing interface: {}, active_ap: {:?}, band_steering_enabled: {}",
+                  interface, link.active_ap.as_ref().map(|ap| &ap.bssid), self.config.band_steering_enabled);
+
+            let state = self.interface_states
+                .entry(interface.clone())
+                .or_insert_with(|| InterfaceState::new(&self.config));
+
+            // Backend settings that only last for one connection
+            if link.interface_type == InterfaceType::Wifi && !state.backend_tuned {
+                state.backend_tuned = true;
+                actions.push(Action::TuneBackend { interface: interface.clone() });
+            }
+
+            // PPS is sampled once per tick and shared by every block below
+            let pps = state.pps_monitor.sample(link.stats.as_ref(), now);
+            state.pps = pps;
+
+            // 3. Game Mode Detection (PPS) - with CAKE freezing
+            if self.config.game_mode_enabled {
+                let freeze_cake = self.config.game_mode_freeze_cake;
+                let was_in_game = state.in_game(now);
+
+                if pps > self.config.game_mode_pps_threshold {
+                    let cooldown = Duration::from_secs(self.config.game_mode_cooldown_secs);
+                    state.game_mode_until = Some(now + cooldown);
+
+                    // Freeze CAKE when entering game mode
+                    if freeze_cake && !was_in_game {
+                        state.tc_manager.enter_game_mode();
+                        info!("Game mode ACTIVATED: {} PPS on {} (CAKE frozen)", pps, interface);
+                    } else {
+                        debug!("Game mode extended: {} PPS on {}", pps, interface);
+                    }
+                } else if !was_in_game && state.game_mode_until.take().is_some() {
+                    // Cooldown expired since the last tick
+                    if freeze_cake {
+                        state.tc_manager.exit_game_mode();
+                        info!("Game mode ENDED on {} (CAKE unfrozen)", interface);
+                    }
+                }
+            }
+
+            // 4. Breathing CAKE (Dynamic QoS) with throughput monitoring
+            if self.config.breathing_cake_enabled {
+                // Get bitrate from BOTH sources and average for stability
+                let nm_bitrate = link.nm_bitrate_kbit;  // Already in Kbit/s from NetworkManager
+                let iw_bitrate = link.iw_bitrate_kbit;
+
+                // Average both sources if both valid, otherwise use whichever is valid
+                // Reject readings below 20Mbit (lowered for Steam Deck compatibility)
+                // WiFi 4 HT20 MCS7 = 65Mbit, but some devices report lower during idle
+                let min_valid_kbit = 20_000;  // 20 Mbit minimum
+
+                let nm_valid = nm_bitrate >= min_valid_kbit;
+                let iw_valid = iw_bitrate >= min_valid_kbit;
+
+                // Debug logging on first tick or when both invalid
+                if !nm_valid && !iw_valid {
+                    debug!("CAKE bitrate check on {}: NM={} Kbit (valid:{}), iw={} Kbit (valid:{})",
+                           interface, nm_bitrate, nm_valid, iw_bitrate, iw_valid);
+                }
+
+                let effective_bitrate = if link.interface_type == InterfaceType::Ethernet {
+                    // Negotiated speed is exact (10 Mbit links are real)
+                    nm_bitrate
+                } else {
+                    match (nm_valid, iw_valid) {
+                        (true, true) => (nm_bitrate + iw_bitrate) / 2,  // Average both
+                        (true, false) => nm_bitrate,
+                        (false, true) => iw_bitrate,
+                        (false, false) => 0,  // Both invalid - will use last known good
+                    }
+                };
+
+                // Update throughput estimate from actual traffic
+                Self::update_throughput_estimate(state, link.stats.as_ref(), now);
+
+                let scaled_mbit = if effective_bitrate > 0 {
+                    // Store as last known good bitrate
+                    state.last_good_bitrate = Some(effective_bitrate);
+
+                    // Convert Kbit to Mbit and scale using overhead factor (default 0.85)
+                    let bitrate_mbit = effective_bitrate / 1000;
+                    let scaled_mbit = (bitrate_mbit as f64 * self.config.cake_overhead_factor) as u32;
+
+                    debug!("CAKE: NM={}Kbit, iw={}Kbit, effective={}Kbit, scaled={}Mbit",
+                           nm_bitrate, iw_bitrate, effective_bitrate, scaled_mbit);
+                    scaled_mbit
+                } else if let Some(last_good) = state.last_good_bitrate {
+                    // Both sources invalid BUT we have a last known good value - use it
+                    // This handles MCS0 probe frames during idle periods
+                    let bitrate_mbit = last_good / 1000;
+                    let scaled_mbit = (bitrate_mbit as f64 * self.config.cake_overhead_factor) as u32;
+
+                    debug!("CAKE: Invalid readings (NM={}, iw={}), using last known good {}Kbit -> {}Mbit",
+                           nm_bitrate, iw_bitrate, last_good, scaled_mbit);
+                    scaled_mbit
+                } else {
+                    // No current OR historical valid bitrate
+                    // Use a conservative default of 100Mbit (safe for most WiFi 5/6 networks)
+                    // This ensures CAKE is enabled even when bitrate detection fails
+                    let default_mbit = 100;
+                    let scaled_mbit = (default_mbit as f64 * self.config.cake_overhead_factor) as u32;
+
+                    if !state.bandwidth_valid {
+                        info!("CAKE: No bitrate detected (NM={}, iw={}), using conservative default {}Mbit on {}",
+                              nm_bitrate, iw_bitrate, default_mbit, interface);
+                    }
+                    scaled_mbit
+                };
+
+                let scaled_mbit = scaled_mbit.max(tc::MIN_LINK_MBIT);
+
+                // WAN-aware: the ISP uplink is often far below the link rate
+                let ssid = link.active_ap.as_ref().map(|ap| ap.ssid.as_str());
+                let network = wan::network_key(interface, ssid);
+                let uplink = wan::resolve_uplink(&self.config.wan, &network, link.measured_uplink_mbit);
+                let (scaled_mbit, constraint) = wan::combine(&self.config.wan, scaled_mbit, uplink);
+                if state.cake_constraint != Some(constraint) {
+                    info!("CAKE on {} ({}): sized by {:?} at {}Mbit", interface, network, constraint, scaled_mbit);
+                    state.cake_constraint = Some(constraint);
+                }
+
+                if state.tc_manager.update_bandwidth(scaled_mbit) {
+                    actions.push(Action::ApplyCake {
+                        interface: interface.clone(),
+                        bandwidth_mbit: state.tc_manager.get_target_bandwidth(),
+                    });
+                }
+                state.bandwidth_valid = true;
+            }
+
+            let in_game = state.in_game(now);
+
+            // 5. CPU Governor (Smart Coalescing) - with hysteresis to prevent jitter
+            if self.config.cpu_coalescing_enabled {
+                let high_cpu = cpu_load > self.config.cpu_coalescing_threshold;
+                // Saver coalesces even in game mode
+                let should_coalesce = if tier == PowerTier::Saver || (in_game && high_cpu) {
+                    true
+                } else if in_game {
+                    false
+                } else {
+                    true // Idle or battery
+                };
+
+                // Hysteresis: require 2 stable ticks before changing coalescing state
+                if should_coalesce != state.coalescing_enabled {
+                    if state.pending_coalescing == Some(should_coalesce) {
+                        state.coalescing_stable_ticks += 1;
+                    } else {
+                        state.pending_coalescing = Some(should_coalesce);
+                        state.coalescing_stable_ticks = 1;
+                    }
+
+                    // Apply after 2 stable ticks (4 seconds)
+                    if state.coalescing_stable_ticks >= 2 {
+                        if should_coalesce {
+                            debug!("Coalescing ENABLED on {} (game:{}, cpu:{:.0}%, battery:{})",
+                                   interface, in_game, cpu_load * 100.0, inputs.power.on_battery);
+                        } else {
+                            debug!("Coalescing DISABLED on {} (game:{}, cpu:{:.0}%)",
+                                   interface, in_game, cpu_load * 100.0);
+                        }
+                        actions.push(Action::SetCoalescing {
+                            interface: interface.clone(),
+                            enabled: should_coalesce,
+                        });
+                        state.coalescing_enabled = should_coalesce;
+                        state.pending_coalescing = None;
+                        state.coalescing_stable_ticks = 0;
+                    }
+                } else {
+                    // State matches, reset pending
+                    state.pending_coalescing = None;
+                    state.coalescing_stable_ticks = 0;
+                }
+            }
+
+            // 5a. IRQ / RPS / XPS steering - move network work off saturated cores
+            if self.config.irq_steering_enabled && !inputs.cpu_loads.is_empty() {
+                let loads = &inputs.cpu_loads;
+                let threshold = self.config.irq_steering_threshold;
+                let mut targets = self.topology.select(loads, 2);
+                targets.dedup();
+
+                // Only move off a saturated core, and only to cores that are not
+                // saturated themselves. Until the first move the IRQs are where
+                // the kernel or irqbalance put them.
+                let saturated = |cpu: usize| self.topology.core_load(cpu, loads) > threshold;
+                let current = state.steering_cpus.as_deref().unwrap_or(&link.irq_cpus);
+                let should_move = !targets.is_empty()
+                    && current != targets.as_slice()
+                    && current.iter().any(|&cpu| saturated(cpu))
+                    && targets.iter().all(|&cpu| !saturated(cpu));
+
+                // Hysteresis: require 3 stable ticks (6 seconds) before moving
+                if should_move {
+                    state.steering_stable_ticks += 1;
+                    if state.steering_stable_ticks >= 3 {
+                        info!("Steering {} IRQs/RPS/XPS to CPU {:?} (was {:?})",
+                              interface, targets, state.steering_cpus);
+                        actions.push(Action::SteerIrqs { interface: interface.clone(), cpus: targets.clone() });
+                        state.steering_cpus = Some(targets);
+                        state.steering_stable_ticks = 0;
+                    }
+                } else {
+                    state.steering_stable_ticks = 0;
+                }
+            }
+
+            // 5b. Power Save Management - respects config mode
+            // "off"/"on" = user override (skip adaptive logic entirely)
+            // "adaptive" = hysteresis logic based on AC/battery/activity
+            if link.interface_type == InterfaceType::Wifi {
+                match self.power_config.wlan_power_save.as_str() {
+                    "off" => {
+                        // User wants power save permanently off — never enable it
+                        if state.power_save_enabled != Some(false) {
+                            info!("Power save forced OFF on {} (config override)", interface);
+                            actions.push(Action::SetPowerSave { interface: interface.clone(), enabled: false });
+                            state.power_save_enabled = Some(false);
+                        }
+                    }
+                    "on" => {
+                        // User wants power save permanently on — never disable it
+                        if state.power_save_enabled != Some(true) {
+                            info!("Power save forced ON on {} (config override)", interface);
+                            actions.push(Action::SetPowerSave { interface: interface.clone(), enabled: true });
+                            state.power_save_enabled = Some(true);
+                        }
+                    }
+                    _ => {
+                        let has_network_activity = pps > 50;
+
+                        // Balanced: disable power save if game mode is active or on
+                        // any significant network activity (>50 PPS)
+                        // Performance keeps it off, saver keeps it on
+                        let should_enable = saves_power(tier, in_game, has_network_activity);
+
+                        // Hysteresis: require 3 stable ticks before changing power save
+                        // This prevents AC/battery flapping from causing jitter
+                        if state.power_save_enabled != Some(should_enable) {
+                            if state.pending_power_save == Some(should_enable) {
+                                state.power_save_stable_ticks += 1;
+                            } else {
+                                state.pending_power_save = Some(should_enable);
+                                state.power_save_stable_ticks = 1;
+                            }
+
+                            // Apply after 3 stable ticks (6 seconds) to avoid brief AC disconnects
+                            if state.power_save_stable_ticks >= 3 {
+                                let reason = power_save_reason(should_enable, tier, inputs.power.on_battery, in_game);
+                                if should_enable {
+                                    info!("Power save ENABLED on {} ({})", interface, reason);
+                                } else {
+                                    info!("Power save DISABLED on {} ({})", interface, reason);
+                                }
+                                actions.push(Action::SetPowerSave {
+                                    interface: interface.clone(),
+                                    enabled: should_enable,
+                                });
+                                state.power_save_enabled = Some(should_enable);
+                                state.pending_power_save = None;
+                                state.power_save_stable_ticks = 0;
+                            }
+                        } else {
+                            // State matches, reset pending
+                            state.pending_power_save = None;
+                            state.power_save_stable_ticks = 0;
+                        }
+                    }
+                }
+            }
+
+            // 5c. Energy Efficient Ethernet (EEE) Management - Adaptive based on power source
+            // EEE causes 50-200us wakeup latency on ethernet, so disable for gaming/streaming
+            if link.interface_type == InterfaceType::Ethernet {
+                let has_network_activity = pps > 50;
+
+                // Same tiers as WiFi power save: balanced enables EEE only while
+                // idle (no game, no network activity)
+                let should_enable = saves_power(tier, in_game, has_network_activity);
+
+                // Hysteresis: require 3 stable ticks before changing EEE
+                if state.eee_enabled != Some(should_enable) {
+                    if state.pending_eee == Some(should_enable) {
+                        state.eee_stable_ticks += 1;
+                    } else {
+                        state.pending_eee = Some(should_enable);
+                        state.eee_stable_ticks = 1;
+                    }
+
+                    // Apply after 3 stable ticks (6 seconds)
+                    if state.eee_stable_ticks >= 3 {
+                        let reason = power_save_reason(should_enable, tier, inputs.power.on_battery, in_game);
+                        if should_enable {
+                            info!("EEE ENABLED on {} ({})", interface, reason);
+                        } else {
+                            info!("EEE DISABLED on {} ({})", interface, reason);
+                        }
+                        actions.push(Action::SetEee { interface: interface.clone(), enabled: should_enable });
+                        state.eee_enabled = Some(should_enable);
+                        state.pending_eee = None;
+                        state.eee_stable_ticks = 0;
+                    }
+                } else {
+                    // State matches, reset pending
+                    state.pending_eee = None;
+                    state.eee_stable_ticks = 0;
+                }
+            }
+
+            // 6. Smart Band Steering
+            // Skip when scan suppress is active — scan results are stale/empty
+            if self.config.band_steering_enabled && !self.scan_suppress_active {
+                if let Some(current_ap) = &link.active_ap {
+                    let bias_5 = self.wifi_config.band_bias_5ghz;
+                    let bias_6 = self.wifi_config.band_bias_6ghz;
+                    let current_score = current_ap.score(bias_5, bias_6);
+
+                    info!("Band steering: Checking for better AP (current: {} on {:?}, score: {})",
+                           current_ap.bssid, current_ap.band, current_score);
+
+                    let access_points = match &link.access_points {
+                        Some(aps) if !aps.is_empty() => aps,
+                        _ => {
+                            info!("Band steering: No APs returned from NetworkManager");
+                            continue;
+                        }
+                    };
+                    info!("Band steering: Found {} visible APs (current SSID: '{}')", access_points.len(), current_ap.ssid);
+
+                    let min_2g = self.wifi_config.min_signal_2g_dbm;
+                    let min_5g = self.wifi_config.min_signal_5g_dbm;
+                    let min_6g = self.wifi_config.min_signal_6g_dbm;
+
+                    // Find best AP with same SSID and usable signal for its band
+                    let best = access_points.iter()
+                        .filter(|ap| {
+                            let same_ssid = ap.ssid == current_ap.ssid;
+                            let different_bssid = ap.bssid != current_ap.bssid;
+                            let signal_ok = ap.signal_usable(min_2g, min_5g, min_6g);
+                            let band_ok = link.supported_bands.is_empty() || link.supported_bands.contains(&ap.band);
+
+                            info!("  AP {}: ssid={} (same={}), band={:?} (supported={}), signal={}dBm (ok={}), max_rate={}Mbps, score={}",
+                                   ap.bssid, ap.ssid, same_ssid, ap.band, band_ok, ap.signal_strength, signal_ok,
+                                   ap.max_bitrate / 1000, ap.score(bias_5, bias_6));
+
+                            same_ssid && different_bssid && signal_ok && band_ok
+                        })
+                        .max_by_key(|ap| ap.score(bias_5, bias_6));
+
+                    let Some(best_candidate) = best else {
+                        state.roam_candidate = None;
+                        continue;
+                    };
+
+                    let candidate_score = best_candidate.score(bias_5, bias_6);
+                    if candidate_score <= current_score {
+                        state.roam_candidate = None;
+                        continue;
+                    }
+
+                    // Update hysteresis
+                    let should_trigger = if let Some(ref mut roam) = state.roam_candidate {
+                        if roam.bssid == best_candidate.bssid {
+                            roam.consecutive_ticks += 1;
+                            roam.score = candidate_score;
+                        } else {
+                            *roam = RoamCandidate {
+                                bssid: best_candidate.bssid.clone(),
+                                score: candidate_score,
+                                consecutive_ticks: 1,
+                            };
+                        }
+                        roam.consecutive_ticks >= self.config.roam_hysteresis_ticks
+                    } else {
+                        state.roam_candidate = Some(RoamCandidate {
+                            bssid: best_candidate.bssid.clone(),
+                            score: candidate_score,
+                            consecutive_ticks: 1,
+                        });
+                        false
+                    };
+
+                    if should_trigger {
+                        info!("Band steering: {} -> {} (score: {} -> {}, band: {:?} -> {:?})",
+                              current_ap.bssid, best_candidate.bssid,
+                              current_score, candidate_score,
+                              current_ap.band, best_candidate.band);
+
+                        // Clear cached bitrate - after roaming it will be stale
+                        state.last_good_bitrate = None;
+                        state.bandwidth_valid = false;
+
+                        // Request scan to hint firmware/driver about better AP
+                        actions.push(Action::RequestScan {
+                            interface: interface.clone(),
+                            device_path: link.device_path.clone(),
+                            bssid: best_candidate.bssid.clone(),
+                            frequency: best_candidate.frequency,
+                        });
+                        state.roam_candidate = None;
+                    }
+                }
+            }
+        }
+
+        actions
+    }
+
+    /// Update throughput estimate from sysfs byte counters
+    fn update_throughput_estimate(state: &mut InterfaceState, stats: Option<&NetStats>, now: Duration) {
+        let (rx_bytes, tx_bytes) = stats.map(|s| (s.rx_bytes, s.tx_bytes)).unwrap_or((0, 0));
+
+        if let Some(last_time) = state.last_stats_time {
+            let elapsed = now.saturating_sub(last_time).as_secs_f64();
+            if elapsed > 0.5 {
+                let rx_delta = rx_bytes.saturating_sub(state.last_rx_bytes);
+                let tx_delta = tx_bytes.saturating_sub(state.last_tx_bytes);
+                let total_bytes = rx_delta + tx_delta;
+                let bytes_per_sec = (total_bytes as f64 / elapsed) as u64;
+
+                // Only update if there's meaningful traffic (>100KB/s)
+                if bytes_per_sec > 100_000 {
+                    state.tc_manager.update_throughput(bytes_per_sec);
+                }
+            }
+        }
+
+        state.last_rx_bytes = rx_bytes;
+        state.last_tx_bytes = tx_bytes;
+        state.last_stats_time = Some(now);
+    }
+}
@@ -0,0 +1,647 @@
+//! Governor inputs and outputs
+//!
+//! The Governor only touches the system through these traits, so the decision
+//! logic in `core.rs` can be driven by real hardware or by a scripted scenario.
+//!
+//! Inputs:  `LinkSource` (NM, iwd or sysfs/iw links and APs), `LinkStats` (iw + sysfs counters),
+//!          `CpuLoad` (/proc/stat), `PowerInput` (AC/battery, thermal zones)
+//! Outputs: `Actuator` (qdisc, ethtool, power save, IRQ steering, roam)
+//!
+//! Each link is driven by its own task on a single-threaded `LocalSet`, so
+//! nothing here may block: subprocesses run through `tokio::process` or
+//! `spawn_blocking`.
+
+use anyhow::{Context, Result};
+use log::{debug, info};
+use std::collections::BTreeMap;
+use std::fs;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::process::Command;
+use tokio::task;
+
+use crate::network::{device, kernel, wpa};
+use crate::network::iwd::{IwdClient, Station, StationState};
+use crate::network::nm::{AccessPoint, DeviceState, NmClient, WifiBand, WiredDevice, WirelessDevice};
+use crate::network::stats::NetStats;
+use crate::network::tc::{self, EthtoolManager};
+use crate::network::wifi::{InterfaceType, WifiInterface, WifiManager};
+use crate::system::cpu::CpuMonitor;
+use crate::system::irq;
+use crate::system::rollback::{self, Change};
+use crate::system::power::PowerManager;
+use crate::system::thermal;
+
+use super::policy::PowerReadings;
+
+/// An activated link reported by the link source
+#[derive(Debug, Clone)]
+pub struct ActiveLink {
+    pub interface: String,
+    pub device_path: String,
+    pub interface_type: InterfaceType,
+    /// Current bitrate in Kbit/s (0 if unknown)
+    /// WiFi: NM's reported bitrate; Ethernet: negotiated link speed
+    pub bitrate_kbit: u32,
+    pub active_ap: Option<AccessPoint>,
+}
+
+/// Source of managed links (WiFi and Ethernet)
+pub trait LinkSource {
+    /// Links that are connected and should be optimized
+    fn active_links(&mut self) -> impl Future<Output = Result<Vec<ActiveLink>>>;
+
+    /// Current state of one link, None once it is no longer connected
+    fn refresh(&mut self, link: &ActiveLink) -> impl Future<Output = Result<Option<ActiveLink>>>;
+
+    /// All access points visible to a link
+    fn access_points(&mut self, link: &ActiveLink) -> impl Future<Output = Result<Vec<AccessPoint>>>;
+}
+
+/// Per-interface link statistics
+pub trait LinkStats {
+    /// PHY bitrate from the driver (Kbit/s)
+    fn phy_bitrate_kbit(&mut self, interface: &str) -> impl Future<Output = Option<u32>>;
+
+    /// Packet and byte counters
+    fn counters(&mut self, interface: &str) -> Option<NetStats>;
+
+    /// Bands the adapter can use (empty if unknown)
+    fn supported_bands(&mut self, interface: &str) -> impl Future<Output = Vec<WifiBand>>;
+
+    /// CPUs the adapter's IRQs are handled on (empty if unknown)
+    fn irq_cpus(&mut self, interface: &str) -> Vec<usize>;
+}
+
+/// System CPU load
+pub trait CpuLoad {
+    /// Smoothed CPU load (0.0-1.0)
+    fn sample(&mut self) -> f64;
+
+    /// Load of each CPU (0.0-1.0) since the previous call, by CPU number
+    fn per_cpu(&mut self) -> BTreeMap<usize, f64>;
+}
+
+/// Power source, battery and temperature
+pub trait PowerInput {
+    fn readings(&mut self) -> PowerReadings;
+}
+
+/// Applies Governor decisions to the system
+pub trait Actuator {
+    fn apply_cake(&mut self, interface: &str, bandwidth_mbit: u32) -> impl Future<Output = Result<()>>;
+    fn remove_cake(&mut self, interface: &str) -> impl Future<Output = Result<()>>;
+    fn set_coalescing(&mut self, interface: &str, enabled: bool) -> impl Future<Output = Result<()>>;
+    fn set_eee(&mut self, interface: &str, enabled: bool) -> impl Future<Output = Result<()>>;
+    fn set_power_save(&mut self, interface: &str, enabled: bool) -> impl Future<Output = Result<()>>;
+    /// Move IRQs and RPS/XPS steering to `cpus`
+    fn steer_irqs(&mut self, interface: &str, cpus: &[usize]) -> impl Future<Output = Result<()>>;
+    /// Roam to `bssid`, or request a scan so the driver can find it
+    fn request_roam(&mut self, interface: &str, device_path: &str, bssid: &str, frequency: u32)
+        -> impl Future<Output = Result<()>>;
+    /// Apply per-connection backend tuning to the current connection
+    fn tune_backend(&mut self, interface: &str) -> impl Future<Output = Result<()>>;
+    /// Measure the gateway RTT CAKE is configured with again
+    fn remeasure_rtt(&mut self) -> impl Future<Output = Result<()>>;
+}
+
+impl LinkSource for NmClient {
+    async fn active_links(&mut self) -> Result<Vec<ActiveLink>> {
+        let mut links: Vec<ActiveLink> = self.get_wireless_devices().await?
+            .into_iter()
+            .filter_map(activated_link)
+            .collect();
+
+        for device in self.get_wired_devices().await? {
+            if let Some(link) = wired_link(device).await {
+                links.push(link);
+            }
+        }
+
+        Ok(links)
+    }
+
+    async fn refresh(&mut self, link: &ActiveLink) -> Result<Option<ActiveLink>> {
+        match link.interface_type {
+            InterfaceType::Wifi => {
+                Ok(self.get_wireless_device(&link.device_path).await?.and_then(activated_link))
+            }
+            InterfaceType::Ethernet => match self.get_wired_device(&link.device_path).await? {
+                Some(device) => Ok(wired_link(device).await),
+                None => Ok(None),
+            },
+        }
+    }
+
+    async fn access_points(&mut self, link: &ActiveLink) -> Result<Vec<AccessPoint>> {
+        self.get_access_points(&link.device_path).await
+    }
+}
+
+impl LinkSource for IwdClient {
+    async fn active_links(&mut self) -> Result<Vec<ActiveLink>> {
+        let mut links = Vec::new();
+        for station in self.stations().await? {
+            if let Some(link) = self.station_link(station).await {
+                links.push(link);
+            }
+        }
+        Ok(links)
+    }
+
+    async fn refresh(&mut self, link: &ActiveLink) -> Result<Option<ActiveLink>> {
+        match self.station(&link.device_path).await? {
+            Some(station) => Ok(self.station_link(station).await),
+            None => Ok(None),
+        }
+    }
+
+    async fn access_points(&mut self, link: &ActiveLink) -> Result<Vec<AccessPoint>> {
+        IwdClient::access_points(self, &link.device_path).await
+    }
+}
+
+impl IwdClient {
+    async fn station_link(&self, station: Station) -> Option<ActiveLink> {
+        if station.state != StationState::Connected {
+            return None;
+        }
+        let diagnostics = match self.diagnostics(&station.path).await {
+            Ok(diagnostics) => diagnostics,
+            Err(e) => {
+                debug!("No diagnostics for {}: {}", station.interface, e);
+                Default::default()
+            }
+        };
+        let active_ap = station.ssid.as_deref()
+            .filter(|_| !diagnostics.bssid.is_empty())
+            .map(|ssid| diagnostics.access_point(&station.path, ssid));
+
+        Some(ActiveLink {
+            interface: station.interface,
+            device_path: station.path,
+            interface_type: InterfaceType::Wifi,
+            bitrate_kbit: diagnostics.tx_bitrate_kbit,
+            active_ap,
+        })
+    }
+}
+
+/// Links read straight from sysfs and `iw`, for systems without NM or iwd
+#[derive(Clone)]
+pub struct KernelLinks;
+
+impl LinkSource for KernelLinks {
+    async fn active_links(&mut self) -> Result<Vec<ActiveLink>> {
+        let mut links = Vec::new();
+        for (interface, interface_type) in kernel::up_interfaces() {
+            if let Some(link) = kernel_link(interface, interface_type).await {
+                links.push(link);
+            }
+        }
+        Ok(links)
+    }
+
+    async fn refresh(&mut self, link: &ActiveLink) -> Result<Option<ActiveLink>> {
+        if !kernel::is_up(&link.interface) {
+            return Ok(None);
+        }
+        Ok(kernel_link(link.interface.clone(), link.interface_type.clone()).await)
+    }
+
+    async fn access_points(&mut self, link: &ActiveLink) -> Result<Vec<AccessPoint>> {
+        kernel::scan_results(&link.interface).await
+    }
+}
+
+async fn kernel_link(interface: String, interface_type: InterfaceType) -> Option<ActiveLink> {
+    let (bitrate_kbit, active_ap) = match interface_type {
+        InterfaceType::Wifi => {
+            let association = kernel::association(&interface).await?;
+            (association.tx_bitrate_kbit, Some(association.access_point(&interface)))
+        }
+        InterfaceType::Ethernet => {
+            if !has_carrier(&interface) {
+                return None;
+            }
+            (ethernet_speed_mbit(&interface).await.unwrap_or(0) * 1000, None)
+        }
+    };
+
+    Some(ActiveLink {
+        device_path: kernel::sysfs_path(&interface),
+        interface,
+        interface_type,
+        bitrate_kbit,
+        active_ap,
+    })
+}
+
+/// The connection manager on this system: NetworkManager, else standalone
+/// iwd, else whatever the kernel reports
+#[derive(Clone)]
+pub enum SystemLinks {
+    Nm(NmClient),
+    Iwd(IwdClient),
+    Kernel(KernelLinks),
+}
+
+impl SystemLinks {
+    pub async fn connect() -> Result<Self> {
+        match NmClient::new().await {
+            Ok(nm) => return Ok(SystemLinks::Nm(nm)),
+            Err(e) => debug!("NetworkManager unavailable: {}", e),
+        }
+        match IwdClient::new().await {
+            Ok(iwd) => {
+                info!("NetworkManager not running, managing iwd stations directly");
+                return Ok(SystemLinks::Iwd(iwd));
+            }
+            Err(e) => debug!("iwd unavailable: {:#}", e),
+        }
+        info!("Neither NetworkManager nor iwd is running, reading links from sysfs and iw");
+        Ok(SystemLinks::Kernel(KernelLinks))
+    }
+
+    async fn roam(&self, interface: &str, device_path: &str, bssid: &str, frequency: u32) -> Result<()> {
+        match self {
+            // NM can't target a BSS; a scan lets the driver find it
+            SystemLinks::Nm(nm) => nm.request_scan(device_path).await,
+            SystemLinks::Iwd(iwd) => iwd.roam(device_path, bssid, frequency).await,
+            SystemLinks::Kernel(_) => kernel::request_scan(interface, frequency).await,
+        }
+    }
+}
+
+impl LinkSource for SystemLinks {
+    async fn active_links(&mut self) -> Result<Vec<ActiveLink>> {
+        match self {
+            SystemLinks::Nm(nm) => nm.active_links().await,
+            SystemLinks::Iwd(iwd) => iwd.active_links().await,
+            SystemLinks::Kernel(kernel) => kernel.active_links().await,
+        }
+    }
+
+    async fn refresh(&mut self, link: &ActiveLink) -> Result<Option<ActiveLink>> {
+        match self {
+            SystemLinks::Nm(nm) => nm.refresh(link).await,
+            SystemLinks::Iwd(iwd) => iwd.refresh(link).await,
+            SystemLinks::Kernel(kernel) => kernel.refresh(link).await,
+        }
+    }
+
+    async fn access_points(&mut self, link: &ActiveLink) -> Result<Vec<AccessPoint>> {
+        match self {
+            SystemLinks::Nm(nm) => LinkSource::access_points(nm, link).await,
+            SystemLinks::Iwd(iwd) => {
+                // Without developer mode iwd has no per-BSS results; the kernel does
+                let aps = LinkSource::access_points(iwd, link).await?;
+                if aps.is_empty() {
+                    kernel::scan_results(&link.interface).await
+                } else {
+                    Ok(aps)
+                }
+            }
+            SystemLinks::Kernel(kernel) => kernel.access_points(link).await,
+        }
+    }
+}
+
+fn activated_link(device: WirelessDevice) -> Option<ActiveLink> {
+    if device.state != DeviceState::Activated {
+        return None;
+    }
+    Some(ActiveLink {
+        interface: device.interface,
+        device_path: device.path,
+        interface_type: InterfaceType::Wifi,
+        bitrate_kbit: device.bitrate,
+        active_ap: device.active_ap,
+    })
+}
+
+/// Ethernet link for an NM wired device
+/// Links NM leaves unmanaged are still optimized while they have carrier.
+async fn wired_link(device: WiredDevice) -> Option<ActiveLink> {
+    let up = match device.state {
+        DeviceState::Activated => true,
+        DeviceState::Unmanaged => has_carrier(&device.interface),
+        _ => false,
+    };
+    if !up {
+        return None;
+    }
+
+    let speed_mbit = if device.speed_mbit > 0 {
+        device.speed_mbit
+    } else {
+        ethernet_speed_mbit(&device.interface).await.unwrap_or(0)
+    };
+
+    Some(ActiveLink {
+        interface: device.interface,
+        device_path: device.path,
+        interface_type: InterfaceType::Ethernet,
+        bitrate_kbit: speed_mbit * 1000,
+        active_ap: None,
+    })
+}
+
+fn has_carrier(interface: &str) -> bool {
+    fs::read_to_string(format!("/sys/class/net/{}/carrier", interface))
+        .map(|s| s.trim() == "1")
+        .unwrap_or(false)
+}
+
+/// Negotiated Ethernet speed in Mb/s
+/// sysfs first (-1 when the driver doesn't know), then `ethtool`
+async fn ethernet_speed_mbit(interface: &str) -> Option<u32> {
+    let sysfs = fs::read_to_string(format!("/sys/class/net/{}/speed", interface))
+        .ok()
+        .and_then(|s| s.trim().parse::<i64>().ok())
+        .filter(|&speed| speed > 0);
+    if let Some(speed) = sysfs {
+        return u32::try_from(speed).ok();
+    }
+
+    let output = Command::new("ethtool").arg(interface).output().await.ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let speed = parse_ethtool_speed(&stdout);
+    debug!("ethtool fallback: {:?}Mb/s on {}", speed, interface);
+    speed
+}
+
+/// Parse "Speed: 1000Mb/s" from `ethtool <iface>` output
+fn parse_ethtool_speed(output: &str) -> Option<u32> {
+    output.lines()
+        .find_map(|line| line.trim().strip_prefix("Speed:"))
+        .and_then(|speed| speed.trim().strip_suffix("Mb/s"))
+        .and_then(|speed| speed.parse().ok())
+}
+
+/// Link statistics from `iw` and /sys/class/net
+#[derive(Clone)]
+pub struct SysfsLinkStats;
+
+impl LinkStats for SysfsLinkStats {
+    async fn phy_bitrate_kbit(&mut self, interface: &str) -> Option<u32> {
+        get_bitrate_from_iw(interface).await
+    }
+
+    fn counters(&mut self, interface: &str) -> Option<NetStats> {
+        NetStats::read(interface)
+    }
+
+    async fn supported_bands(&mut self, interface: &str) -> Vec<WifiBand> {
+        let interface = interface.to_string();
+        task::spawn_blocking(move || device::wiphy(&interface).0).await.unwrap_or_default()
+    }
+
+    fn irq_cpus(&mut self, interface: &str) -> Vec<usize> {
+        irq::current_cpus(interface)
+    }
+}
+
+impl CpuLoad for CpuMonitor {
+    fn sample(&mut self) -> f64 {
+        CpuMonitor::sample(self)
+    }
+
+    fn per_cpu(&mut self) -> BTreeMap<usize, f64> {
+        self.sample_per_cpu()
+    }
+}
+
+impl PowerInput for PowerManager {
+    fn readings(&mut self) -> PowerReadings {
+        PowerReadings {
+            on_battery: self.should_enable_power_save(),
+            battery_percent: self.battery_percentage(),
+            battery_minutes_left: self.battery_minutes_left(),
+            temperature_c: thermal::hottest_zone_c(),
+        }
+    }
+}
+
+/// Applies actions with tc, ethtool, iw and NetworkManager or iwd
+#[derive(Clone)]
+pub struct SystemActuator {
+    links: SystemLinks,
+    wifi_manager: Arc<WifiManager>,
+    /// wpa_supplicant settings re-applied on every connection (None = leave alone)
+    wpa_tuning: Option<Arc<wpa::Tuning>>,
+}
+
+impl SystemActuator {
+    pub fn new(links: SystemLinks, wifi_manager: WifiManager) -> Self {
+        Self { links, wifi_manager: Arc::new(wifi_manager), wpa_tuning: None }
+    }
+
+    pub fn with_wpa_tuning(mut self, tuning: Option<wpa::Tuning>) -> Self {
+        self.wpa_tuning = tuning.map(Arc::new);
+        self
+    }
+
+    /// Look up an interface, re-detecting from sysfs if it appeared after startup
+    async fn interface(&self, name: &str) -> Result<WifiInterface> {
+        if let Some(ifc) = self.wifi_manager.interfaces().iter().find(|i| i.name == name) {
+            return Ok(ifc.clone());
+        }
+        let name = name.to_string();
+        task::spawn_blocking(move || {
+            WifiManager::new_quiet()?.interfaces().iter()
+                .find(|i| i.name == name)
+                .cloned()
+                .with_context(|| format!("{} is not a managed interface", name))
+        }).await?
+    }
+}
+
+/// Run a blocking tool invocation without stalling the other link tasks
+async fn blocking<F>(f: F) -> Result<()>
+where
+    F: FnOnce() -> Result<()> + Send + 'static,
+{
+    task::spawn_blocking(f).await?
+}
+
+impl Actuator for SystemActuator {
+    async fn apply_cake(&mut self, interface: &str, bandwidth_mbit: u32) -> Result<()> {
+        let interface = interface.to_string();
+        blocking(move || {
+            rollback::with_runtime_journal(|journal| {
+                journal.record("governor", Change::qdisc(&interface));
+                tc::apply_cake(&interface, bandwidth_mbit)
+            })
+        }).await
+    }
+
+    async fn remove_cake(&mut self, interface: &str) -> Result<()> {
+        let interface = interface.to_string();
+        blocking(move || {
+            rollback::with_runtime_journal(|journal| {
+                journal.record("governor", Change::qdisc(&interface));
+                tc::remove_cake(&interface)
+            })
+        }).await
+    }
+
+    async fn set_coalescing(&mut self, interface: &str, enabled: bool) -> Result<()> {
+        let interface = interface.to_string();
+        blocking(move || {
+            rollback::with_runtime_journal(|journal| {
+                journal.record("governor", Change::coalescing(&interface));
+                if enabled {
+                    EthtoolManager::enable_coalescing(&interface)
+                } else {
+                    EthtoolManager::disable_coalescing(&interface)
+                }
+            })
+        }).await
+    }
+
+    async fn set_eee(&mut self, interface: &str, enabled: bool) -> Result<()> {
+        let interface = interface.to_string();
+        blocking(move || {
+            rollback::with_runtime_journal(|journal| {
+                journal.record("governor", Change::eee(&interface));
+                if enabled {
+                    EthtoolManager::enable_eee(&interface)
+                } else {
+                    EthtoolManager::disable_eee(&interface)
+                }
+            })
+        }).await
+    }
+
+    async fn set_power_save(&mut self, interface: &str, enabled: bool) -> Result<()> {
+        let ifc = self.interface(interface).await?;
+        let wifi_manager = self.wifi_manager.clone();
+        blocking(move || {
+            rollback::with_runtime_journal(|journal| {
+                journal.record("governor", Change::power_save(&ifc.name));
+                if enabled {
+                    wifi_manager.enable_power_save(&ifc)
+                } else {
+                    wifi_manager.disable_power_save(&ifc)
+                }
+            })
+        }).await
+    }
+
+    async fn steer_irqs(&mut self, interface: &str, cpus: &[usize]) -> Result<()> {
+        let ifc = self.interface(interface).await?;
+        let cpus = cpus.to_vec();
+        blocking(move || irq::steer(&ifc, &cpus)).await
+    }
+
+    async fn request_roam(&mut self, interface: &str, device_path: &str, bssid: &str, frequency: u32) -> Result<()> {
+        self.links.roam(interface, device_path, bssid, frequency).await
+    }
+
+    async fn tune_backend(&mut self, interface: &str) -> Result<()> {
+        let Some(tuning) = self.wpa_tuning.clone() else {
+            return Ok(());
+        };
+        let interface = interface.to_string();
+        blocking(move || {
+            rollback::with_runtime_journal(|journal| wpa::tune(&interface, &tuning, "governor", journal))
+        }).await
+    }
+
+    async fn remeasure_rtt(&mut self) -> Result<()> {
+        blocking(|| {
+            tc::remeasure_gateway_rtt();
+            Ok(())
+        }).await
+    }
+}
+
+/// Fallback: Get bitrate from `iw` when NetworkManager reports 0
+async fn get_bitrate_from_iw(interface: &str) -> Option<u32> {
+    let output = Command::new("iw")
+        .args(["dev", interface, "link"])
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // Parse bitrate from iw output - multiple formats supported:
+    // "tx bitrate: 866.7 MBit/s ..."
+    // "	tx bitrate: 866.7 MBit/s VHT-MCS 9 80MHz short GI VHT-NSS 2"
+    // Steam Deck ath11k may report: "tx bitrate: 1201.0 MBit/s 80MHz HE-MCS 11 HE-NSS 2 HE-GI 0 HE-DCM 0"
+    for line in stdout.lines() {
+        let line_lower = line.to_lowercase();
+        if line_lower.contains("tx bitrate:") || line_lower.contains("bitrate:") {
+            // Extract the number - look for pattern like "866.7 MBit/s" or "1201.0 Mbit/s"
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            for (i, part) in parts.iter().enumerate() {
+                // Look for "bitrate:" followed by a number
+                if part.to_lowercase().contains("bitrate:") {
+                    // Next part should be the number
+                    if i + 1 < parts.len() {
+                        if let Ok(mbit) = parts[i + 1].parse::<f64>() {
+                            // Convert to Kbit for consistency with NM
+                            debug!("iw fallback: {}Mbit on {}", mbit, interface);
+                            return Some((mbit * 1000.0) as u32);
+                        }
+                    }
+                }
+                // Also try matching "NNN.N" followed by "MBit" in case format varies
+                if i + 1 < parts.len() && parts[i + 1].to_lowercase().contains("mbit") {
+                    if let Ok(mbit) = part.parse::<f64>() {
+                        debug!("iw fallback (alt format): {}Mbit on {}", mbit, interface);
+                        return Some((mbit * 1000.0) as u32);
+                    }
+                }
+            }
+        }
+    }
+
+    // Final fallback: try to get signal from iw station dump
+    // Some drivers (ath11k) may report better data this way
+    let station_output = Command::new("iw")
+        .args(["dev", interface, "station", "dump"])
+        .output()
+        .await
+        .ok()?;
+
+    if station_output.status.success() {
+        let station_out = String::from_utf8_lossy(&station_output.stdout);
+        for line in station_out.lines() {
+            let line_lower = line.to_lowercase();
+            if line_lower.contains("tx bitrate:") {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                for (i, part) in parts.iter().enumerate() {
+                    if part.to_lowercase().contains("bitrate:") && i + 1 < parts.len() {
+                        if let Ok(mbit) = parts[i + 1].parse::<f64>() {
+                            debug!("iw station dump fallback: {}Mbit on {}", mbit, interface);
+                            return Some((mbit * 1000.0) as u32);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ethtool_speed() {
+        let output = "Settings for enp3s0:\n\tSupported ports: [ TP ]\n\tSpeed: 2500Mb/s\n\tDuplex: Full\n";
+        assert_eq!(parse_ethtool_speed(output), Some(2500));
+
+        // Link down: ethtool reports an unknown speed
+        assert_eq!(parse_ethtool_speed("\tSpeed: Unknown!\n"), None);
+    }
+}
@@ -0,0 +1,267 @@
+//! Per-link Governor task
+//!
+//! Every managed link is driven by its own `LinkWorker` with its own interval
+//! and its own `GovernorCore`. CPU load and power source are sampled once by
+//! the supervisor and shared over a watch channel, so a slow D-Bus reply or
+//! tool invocation on one adapter never delays another.
+
+use anyhow::Result;
+use log::{debug, info, warn};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use tokio::sync::{watch, Notify};
+use tokio::time::{self, Interval};
+
+use super::core::{Action, GovernorCore, LinkSnapshot, TickInputs};
+use super::io::{ActiveLink, Actuator, LinkSource, LinkStats};
+use super::policy::PowerReadings;
+use super::status;
+use super::trace::TraceRecorder;
+use crate::network::nm::WifiBand;
+use crate::network::wan;
+use crate::network::wifi::InterfaceType;
+
+/// System-wide inputs shared by every link task
+#[derive(Debug, Clone, Default)]
+pub struct GlobalInputs {
+    /// Smoothed CPU load (0.0-1.0)
+    pub cpu_load: f64,
+    /// Per-CPU load (0.0-1.0) by CPU number
+    pub cpu_loads: BTreeMap<usize, f64>,
+    /// Power source, battery and temperature
+    pub power: PowerReadings,
+    /// Background scans are being aborted (see `Governor::update_scan_suppress`)
+    pub scan_suppress: bool,
+}
+
+/// Pokes from the supervisor to one link task
+#[derive(Default)]
+pub struct LinkSignals {
+    /// The link reconnected or roamed
+    pub reset: Notify,
+    /// Something changed that is worth a tick before the next one is due
+    pub wake: Notify,
+}
+
+/// Drives the decision logic for a single link
+pub struct LinkWorker<L, S, A> {
+    link: ActiveLink,
+    core: GovernorCore,
+    links: L,
+    stats: S,
+    actuator: A,
+    recorder: Option<Rc<RefCell<TraceRecorder>>>,
+    status_dir: Option<PathBuf>,
+    /// Adapter bands, read on the first WiFi tick
+    supported_bands: Option<Vec<WifiBand>>,
+    /// `wan measure` results
+    wan_measurements: wan::MeasurementCache,
+}
+
+impl<L, S, A> LinkWorker<L, S, A>
+where
+    L: LinkSource,
+    S: LinkStats,
+    A: Actuator,
+{
+    pub fn new(
+        link: ActiveLink,
+        core: GovernorCore,
+        links: L,
+        stats: S,
+        actuator: A,
+        recorder: Option<Rc<RefCell<TraceRecorder>>>,
+    ) -> Self {
+        Self {
+            link,
+            core,
+            links,
+            stats,
+            actuator,
+            recorder,
+            status_dir: None,
+            supported_bands: None,
+            wan_measurements: wan::MeasurementCache::new(Path::new(wan::MEASUREMENTS_PATH)),
+        }
+    }
+
+    /// Publish the link's state to `dir` after every tick
+    pub fn with_status_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.status_dir = dir;
+        self
+    }
+
+    /// Tick every `period` until the task is aborted
+    /// A bump on `resets` (any link) or `signals.reset` (this link) means the
+    /// link reconnected: cached state is cleared, and the link gets 1 second to
+    /// stabilize before an immediate tick. `signals.wake` ticks early.
+    pub async fn run(
+        mut self,
+        period: Duration,
+        started: Instant,
+        globals: watch::Receiver<GlobalInputs>,
+        mut resets: watch::Receiver<u64>,
+        signals: Rc<LinkSignals>,
+    ) {
+        let mut interval = time::interval(period);
+        let mut last_tick = time::Instant::now();
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                Ok(()) = resets.changed() => self.reset(&mut interval).await,
+                _ = signals.reset.notified() => self.reset(&mut interval).await,
+                _ = signals.wake.notified() => {
+                    // A scan adds APs in bursts; extra ticks must not speed up
+                    // the tick-counted hysteresis
+                    if last_tick.elapsed() < period / 2 {
+                        continue;
+                    }
+                    interval.reset();
+                }
+            }
+            last_tick = time::Instant::now();
+
+            let inputs = globals.borrow().clone();
+            if let Err(e) = self.tick_at(started.elapsed(), inputs).await {
+                warn!("Governor tick error on {}: {}", self.link.interface, e);
+            }
+        }
+    }
+
+    async fn reset(&mut self, interval: &mut Interval) {
+        self.core.reset_link_state();
+        info!("Waiting 1s for {} to stabilize...", self.link.interface);
+        time::sleep(Duration::from_secs(1)).await;
+        interval.reset();
+    }
+
+    /// Refresh the link, run the decision logic and apply its actions
+    pub async fn tick_at(&mut self, at: Duration, globals: GlobalInputs) -> Result<()> {
+        let Some(link) = self.links.refresh(&self.link).await? else {
+            debug!("{} is no longer active, skipping tick", self.link.interface);
+            return Ok(());
+        };
+        self.link = link;
+
+        let inputs = TickInputs {
+            at,
+            cpu_load: globals.cpu_load,
+            cpu_loads: globals.cpu_loads,
+            power: globals.power,
+            scan_suppress: globals.scan_suppress,
+            links: vec![self.snapshot(globals.scan_suppress).await],
+        };
+
+        if let Some(recorder) = &self.recorder {
+            let result = recorder.borrow_mut().record(&inputs);
+            if let Err(e) = result {
+                warn!("Failed to write trace, recording stopped on {}: {}", self.link.interface, e);
+                self.recorder = None;
+            }
+        }
+
+        for action in self.core.tick(&inputs) {
+            let result = self.execute(&action).await;
+            if let Err(ref e) = result {
+                warn!("Failed to apply {:?}: {}", action, e);
+            }
+            self.core.action_completed(&action, result.is_ok());
+        }
+
+        if let Some(dir) = &self.status_dir {
+            if let Some(status) = self.core.status(&self.link.interface, at) {
+                if let Err(e) = status::write(dir, &status) {
+                    debug!("Failed to publish status for {}: {}", self.link.interface, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read everything the decision logic needs about this link
+    async fn snapshot(&mut self, scan_suppress: bool) -> LinkSnapshot {
+        let link = &self.link;
+
+        let wants_phy_rate = link.interface_type == InterfaceType::Wifi
+            && self.core.config().breathing_cake_enabled;
+        let iw_bitrate_kbit = if wants_phy_rate {
+            self.stats.phy_bitrate_kbit(&link.interface).await.unwrap_or(0)
+        } else {
+            0
+        };
+
+        // Only fetch the AP list when band steering will look at it, or for a
+        // trace that may be replayed with band steering on
+        let wants_aps = self.core.wants_access_points(scan_suppress) || self.recorder.is_some();
+        let access_points = if wants_aps && link.active_ap.is_some() {
+            match self.links.access_points(link).await {
+                Ok(aps) => Some(aps),
+                Err(e) => {
+                    debug!("Band steering: Failed to get APs: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        if link.interface_type == InterfaceType::Wifi && self.supported_bands.is_none() {
+            self.supported_bands = Some(self.stats.supported_bands(&link.interface).await);
+        }
+
+        let irq_cpus = if self.core.wants_irq_cpus() {
+            self.stats.irq_cpus(&link.interface)
+        } else {
+            Vec::new()
+        };
+
+        let ssid = link.active_ap.as_ref().map(|ap| ap.ssid.as_str());
+        let measured_uplink_mbit = self.wan_measurements.uplink(&wan::network_key(&link.interface, ssid));
+
+        LinkSnapshot {
+            interface: link.interface.clone(),
+            device_path: link.device_path.clone(),
+            interface_type: link.interface_type.clone(),
+            nm_bitrate_kbit: link.bitrate_kbit,
+            iw_bitrate_kbit,
+            stats: self.stats.counters(&link.interface),
+            active_ap: link.active_ap.clone(),
+            access_points,
+            measured_uplink_mbit,
+            supported_bands: self.supported_bands.clone().unwrap_or_default(),
+            irq_cpus,
+        }
+    }
+
+    /// Apply a single action through the actuator
+    async fn execute(&mut self, action: &Action) -> Result<()> {
+        match action {
+            Action::ApplyCake { interface, bandwidth_mbit } => {
+                self.actuator.apply_cake(interface, *bandwidth_mbit).await
+            }
+            Action::SetCoalescing { interface, enabled } => {
+                self.actuator.set_coalescing(interface, *enabled).await
+            }
+            Action::SetPowerSave { interface, enabled } => {
+                self.actuator.set_power_save(interface, *enabled).await
+            }
+            Action::SetEee { interface, enabled } => {
+                self.actuator.set_eee(interface, *enabled).await
+            }
+            Action::SteerIrqs { interface, cpus } => {
+                self.actuator.steer_irqs(interface, cpus).await
+            }
+            Action::RequestScan { interface, device_path, bssid, frequency } => {
+                self.actuator.request_roam(interface, device_path, bssid, *frequency).await
+            }
+            Action::TuneBackend { interface } => {
+                self.actuator.tune_backend(interface).await
+            }
+        }
+    }
+}
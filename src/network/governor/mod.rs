@@ -0,0 +1,577 @@
+//! The Governor - The "Brain" of hifi-wifi
+//!
+//! Per rewrite.md: Runs the async loop (Tick Rate: 2 seconds) and implements:
+//! - Breathing CAKE (Dynamic QoS with asymmetric response)
+//! - CPU Governor (Smart Coalescing, IRQ/RPS/XPS steering)
+//! - Smart Band Steering (with Hysteresis)
+//! - Game Mode Detection (PPS) with CAKE freezing
+//! - Connection Event Handling (NM/iwd D-Bus signals, inotify file trigger as fallback)
+//! - Suspend/Resume Handling (logind PrepareForSleep)
+//! - Power Source Changes (UPower OnBattery, sysfs polled per tick as fallback)
+//!
+//! The decision logic lives in `core.rs`; each link is driven by its own task
+//! (`link.rs`) that gathers inputs and applies actions through the traits in
+//! `io.rs`. The Governor itself only supervises those tasks.
+
+pub mod core;
+pub mod io;
+pub mod link;
+pub mod policy;
+pub mod status;
+pub mod trace;
+#[cfg(test)]
+mod sim;
+
+use anyhow::Result;
+use log::{info, warn};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::time::{Duration, Instant, SystemTime};
+use std::process::Stdio;
+use tokio::process::Command as TokioCommand;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+use tokio::task::{self, JoinHandle, LocalSet};
+use tokio::time;
+use notify::{Watcher, RecursiveMode, Config as NotifyConfig, RecommendedWatcher, Event, EventKind};
+
+use crate::config::structs::{BackendConfig, GovernorConfig, PowerConfig, PowerTier, WifiConfig};
+use crate::network::nm::LinkEvent;
+use crate::network::wifi::WifiManager;
+use crate::network::wpa;
+use crate::system::cpu::CpuMonitor;
+use crate::system::irq::Topology;
+use crate::system::power::{PowerManager, PowerSource};
+use crate::system::sleep::{self, SleepEvent};
+
+use self::core::GovernorCore;
+use self::io::{ActiveLink, Actuator, CpuLoad, LinkSource, LinkStats, PowerInput, SysfsLinkStats, SystemActuator, SystemLinks};
+use self::link::{GlobalInputs, LinkSignals, LinkWorker};
+use self::policy::PowerPolicy;
+use self::trace::TraceRecorder;
+
+/// Path for connection event signaling (touched by the optional NetworkManager dispatcher)
+const CONNECTION_EVENT_PATH: &str = "/run/hifi-wifi/connection-changed";
+
+/// The Network Governor - orchestrates all optimization logic
+pub struct Governor<L = SystemLinks, S = SysfsLinkStats, C = CpuMonitor, P = PowerManager, A = SystemActuator> {
+    /// Configuration template; every link task gets a fresh copy
+    core: GovernorCore,
+    links: L,
+    stats: S,
+    cpu: C,
+    power: P,
+    actuator: A,
+    /// Time base for `TickInputs::at`
+    started: Instant,
+    /// Shared flag: when true, the scan abort task actively suppresses background scans
+    scan_suppress_active: Arc<AtomicBool>,
+    /// Connects, roams, disconnects and link changes pushed by NM or iwd
+    link_events: Option<mpsc::UnboundedReceiver<LinkEvent>>,
+    /// AC/battery changes from UPower
+    power_events: Option<mpsc::UnboundedReceiver<PowerSource>>,
+    /// Suspend and resume from logind
+    sleep_events: Option<mpsc::UnboundedReceiver<SleepEvent>>,
+    /// When the system went to sleep; link tasks stay stopped until resume
+    suspended_at: Option<SystemTime>,
+    /// Power tier, tracked here for scan suppression (link tasks track their own)
+    policy: PowerPolicy,
+    /// Interfaces whose signal is below the roam threshold; scans stay allowed so they can roam
+    weak_signal: BTreeSet<String>,
+    /// Captures every tick's inputs for offline replay (`monitor --record`)
+    recorder: Option<Rc<RefCell<TraceRecorder>>>,
+    /// Where link tasks publish their state (`status::STATUS_DIR` for the daemon)
+    status_dir: Option<PathBuf>,
+    /// Running link tasks by interface
+    tasks: HashMap<String, LinkTask>,
+    /// Every interface a task was started for (CAKE is removed on stop)
+    managed: BTreeSet<String>,
+}
+
+/// A running link task
+struct LinkTask {
+    handle: JoinHandle<()>,
+    signals: Rc<LinkSignals>,
+}
+
+impl Governor {
+    /// Create a new Governor with the given configuration
+    pub async fn new(
+        config: GovernorConfig,
+        wifi_config: WifiConfig,
+        power_config: PowerConfig,
+        backend_config: &BackendConfig,
+    ) -> Result<Self> {
+        let links = SystemLinks::connect().await?;
+        let cpu_monitor = CpuMonitor::new(config.cpu_avg_window_size);
+        let mut power_manager = PowerManager::new();
+        let wifi_manager = WifiManager::new()?;
+        let actuator = SystemActuator::new(links.clone(), wifi_manager)
+            .with_wpa_tuning(Some(wpa::Tuning::from_config(backend_config)));
+
+        let link_events = match &links {
+            SystemLinks::Nm(nm) => Some(nm.watch().await),
+            SystemLinks::Iwd(iwd) => {
+                let threshold = backend_config.roam_threshold_dbm.clamp(i16::MIN.into(), 0) as i16;
+                Some(iwd.watch(threshold).await)
+            }
+            SystemLinks::Kernel(_) => None,
+        };
+        let link_events = match link_events {
+            Some(Ok(events)) => Some(events),
+            Some(Err(e)) => {
+                warn!("Link event subscription failed (falling back to the event file): {}", e);
+                None
+            }
+            None => None,
+        };
+
+        let power_events = match power_manager.watch().await {
+            Ok(events) => Some(events),
+            Err(e) => {
+                warn!("Power source notifications unavailable (reading sysfs every tick): {}", e);
+                None
+            }
+        };
+        let sleep_events = match sleep::watch().await {
+            Ok(events) => Some(events),
+            Err(e) => {
+                warn!("Suspend/resume notifications unavailable: {}", e);
+                None
+            }
+        };
+
+        let mut governor = Self::with_io(
+            GovernorCore::new(config, wifi_config, power_config).with_topology(Topology::read()),
+            links,
+            SysfsLinkStats,
+            cpu_monitor,
+            power_manager,
+            actuator,
+        );
+        governor.link_events = link_events;
+        governor.power_events = power_events;
+        governor.sleep_events = sleep_events;
+        governor.status_dir = Some(PathBuf::from(status::STATUS_DIR));
+        Ok(governor)
+    }
+}
+
+impl<L, S, C, P, A> Governor<L, S, C, P, A>
+where
+    L: LinkSource + Clone + 'static,
+    S: LinkStats + Clone + 'static,
+    C: CpuLoad,
+    P: PowerInput,
+    A: Actuator + Clone + 'static,
+{
+    /// Create a Governor over arbitrary inputs and outputs
+    pub fn with_io(core: GovernorCore, links: L, stats: S, cpu: C, power: P, actuator: A) -> Self {
+        Self {
+            core,
+            links,
+            stats,
+            cpu,
+            power,
+            actuator,
+            started: Instant::now(),
+            scan_suppress_active: Arc::new(AtomicBool::new(false)),
+            link_events: None,
+            power_events: None,
+            sleep_events: None,
+            suspended_at: None,
+            policy: PowerPolicy::default(),
+            weak_signal: BTreeSet::new(),
+            recorder: None,
+            status_dir: None,
+            tasks: HashMap::new(),
+            managed: BTreeSet::new(),
+        }
+    }
+
+    /// Record the inputs of every tick from now on
+    pub fn record_to(&mut self, mut recorder: TraceRecorder) -> Result<()> {
+        recorder.record_topology(self.core.topology())?;
+        self.recorder = Some(Rc::new(RefCell::new(recorder)));
+        Ok(())
+    }
+
+    /// Run the main governor loop
+    /// Per rewrite.md: Tick Rate 2 seconds, non-blocking
+    /// Connection events come from NM/iwd signals; the inotify file trigger
+    /// (per roadmap-beta2.md) is used when those aren't available or when enabled
+    pub async fn run(&mut self, tick_rate_secs: u64) -> Result<()> {
+        info!("Governor starting (tick rate: {}s)", tick_rate_secs);
+
+        // Spawn scan suppression task if enabled
+        if self.core.config().scan_suppress {
+            let flag = self.scan_suppress_active.clone();
+            tokio::spawn(async move {
+                scan_abort_task(flag).await;
+            });
+            info!("Scan suppression task started (500ms interval)");
+        } else {
+            info!("Scan suppression disabled by config");
+        }
+
+        // Setup inotify watcher for connection events
+        let (event_tx, event_rx) = channel();
+        let _watcher = if self.core.config().connection_event_file || self.link_events.is_none() {
+            match Self::setup_connection_watcher(event_tx) {
+                Ok(w) => {
+                    info!("Connection event watcher active (watching {})", CONNECTION_EVENT_PATH);
+                    Some(w)
+                }
+                Err(e) => {
+                    warn!("Connection event watcher failed (will use polling only): {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // Link tasks share this thread; none of them block
+        let local = LocalSet::new();
+        local.run_until(self.supervise(Duration::from_secs(tick_rate_secs), event_rx)).await
+    }
+
+    /// Sample global inputs, track active links and keep one task per link
+    /// Must run inside a `LocalSet`.
+    async fn supervise(&mut self, period: Duration, events: Receiver<notify::Result<Event>>) -> Result<()> {
+        let (globals_tx, _) = watch::channel(self.sample_globals());
+        let (resets_tx, _) = watch::channel(0u64);
+        let mut interval = time::interval(period);
+
+        loop {
+            // Check for connection events from the event file (non-blocking)
+            while let Ok(event) = events.try_recv() {
+                if let Ok(Event { kind: EventKind::Create(_) | EventKind::Modify(_), .. }) = event {
+                    info!("Connection event detected - clearing bitrate cache and re-optimizing");
+                    resets_tx.send_modify(|generation| *generation += 1);
+                }
+            }
+
+            // Power source changes tick every link early, once scan suppression is decided
+            let mut wake_tasks = false;
+            tokio::select! {
+                _ = interval.tick() => {
+                    if self.suspended_at.is_some() {
+                        continue;
+                    }
+                    globals_tx.send_replace(self.sample_globals());
+                }
+                Some(event) = next_event(&mut self.link_events) => {
+                    if !self.handle_link_event(event) || self.suspended_at.is_some() {
+                        continue;
+                    }
+                }
+                Some(source) = next_event(&mut self.power_events) => {
+                    if self.suspended_at.is_some() {
+                        continue;
+                    }
+                    info!("Power source changed to {:?}", source);
+                    globals_tx.send_replace(self.sample_globals());
+                    wake_tasks = true;
+                }
+                Some(event) = next_event(&mut self.sleep_events) => match event {
+                    SleepEvent::Suspending(inhibitor) => {
+                        self.suspend();
+                        // Lets the suspend proceed
+                        drop(inhibitor);
+                        continue;
+                    }
+                    SleepEvent::Resumed => {
+                        if !self.resume().await {
+                            continue;
+                        }
+                        interval.reset();
+                        globals_tx.send_replace(self.sample_globals());
+                    }
+                },
+            }
+
+            match self.links.active_links().await {
+                Ok(active) => self.sync_tasks(active, period, &globals_tx, &resets_tx),
+                Err(e) => warn!("Governor tick error: {}", e),
+            }
+            if wake_tasks {
+                for task in self.tasks.values() {
+                    task.signals.wake.notify_one();
+                }
+            }
+        }
+    }
+
+    /// React to a backend event; true if the set of links should be synced now
+    fn handle_link_event(&mut self, event: LinkEvent) -> bool {
+        match event {
+            LinkEvent::Connected { interface, roamed } => {
+                if roamed {
+                    info!("{} roamed - clearing bitrate cache and re-optimizing", interface);
+                } else {
+                    info!("{} connected - clearing bitrate cache and re-optimizing", interface);
+                }
+                if let Some(task) = self.tasks.get(&interface) {
+                    task.signals.reset.notify_one();
+                }
+                true
+            }
+            LinkEvent::Disconnected { interface } => {
+                info!("{} disconnected", interface);
+                true
+            }
+            LinkEvent::Changed { interface } => {
+                if let Some(task) = self.tasks.get(&interface) {
+                    task.signals.wake.notify_one();
+                }
+                false
+            }
+            LinkEvent::SignalLevel { interface, weak: true } => {
+                info!("{} signal below roam threshold, allowing background scans and band steering", interface);
+                self.weak_signal.insert(interface);
+                true
+            }
+            LinkEvent::SignalLevel { interface, weak: false } => {
+                if self.weak_signal.remove(&interface) {
+                    info!("{} signal recovered, suppressing background scans again", interface);
+                }
+                true
+            }
+        }
+    }
+
+    /// Stop every link task and scan suppression before the system sleeps
+    fn suspend(&mut self) {
+        info!("System suspending - pausing {} link task(s) and scan suppression", self.tasks.len());
+        for (_, task) in self.tasks.drain() {
+            task.handle.abort();
+        }
+        self.scan_suppress_active.store(false, Ordering::Relaxed);
+        self.suspended_at = Some(SystemTime::now());
+    }
+
+    /// Get ready to restart link tasks after a resume; false if no suspend was seen
+    /// Tasks start over with fresh state, so hysteresis, PPS deltas, cached
+    /// bitrates and game mode don't carry across the sleep, and CAKE and power
+    /// save are applied again on their first ticks.
+    async fn resume(&mut self) -> bool {
+        let Some(suspended_at) = self.suspended_at.take() else {
+            return false;
+        };
+        // Wall clock: Instant stops while suspended
+        let slept = suspended_at.elapsed().unwrap_or_default();
+        info!("System resumed after {}s asleep - re-measuring gateway RTT and restarting link tasks",
+              slept.as_secs());
+        if let Err(e) = self.actuator.remeasure_rtt().await {
+            warn!("Failed to re-measure gateway RTT: {}", e);
+        }
+        true
+    }
+
+    /// Start tasks for new links and stop tasks whose link went away
+    fn sync_tasks(
+        &mut self,
+        active: Vec<ActiveLink>,
+        period: Duration,
+        globals: &watch::Sender<GlobalInputs>,
+        resets: &watch::Sender<u64>,
+    ) {
+        let scan_suppress = self.update_scan_suppress(&active);
+        globals.send_if_modified(|g| std::mem::replace(&mut g.scan_suppress, scan_suppress) != scan_suppress);
+
+        self.tasks.retain(|interface, task| {
+            let keep = !task.handle.is_finished() && active.iter().any(|l| &l.interface == interface);
+            if !keep {
+                info!("{} is no longer active, stopping its governor task", interface);
+                task.handle.abort();
+            }
+            keep
+        });
+
+        for link in active {
+            if self.tasks.contains_key(&link.interface) {
+                continue;
+            }
+            info!("Starting governor task for {}", link.interface);
+            let interface = link.interface.clone();
+            let worker = self.worker(link);
+            let signals = Rc::new(LinkSignals::default());
+            let handle = task::spawn_local(worker.run(
+                period,
+                self.started,
+                globals.subscribe(),
+                resets.subscribe(),
+                signals.clone(),
+            ));
+            self.tasks.insert(interface.clone(), LinkTask { handle, signals });
+            self.managed.insert(interface);
+        }
+    }
+
+    /// Decide whether background scans are aborted, for the scan abort task
+    /// and every link task: suppress when connected, allow when disconnected,
+    /// when a link needs to roam away from a weak signal, or in the saver tier
+    /// (aborting scans wakes the CPU twice a second)
+    fn update_scan_suppress(&mut self, active: &[ActiveLink]) -> bool {
+        let weak = active.iter().any(|l| self.weak_signal.contains(&l.interface));
+        let saver = self.policy.tier() == PowerTier::Saver;
+        let suppress = self.core.config().scan_suppress && !active.is_empty() && !weak && !saver;
+        self.scan_suppress_active.store(suppress, Ordering::Relaxed);
+        suppress
+    }
+
+    /// A new task body for `link`
+    fn worker(&self, link: ActiveLink) -> LinkWorker<L, S, A> {
+        LinkWorker::new(
+            link,
+            self.core.fresh(),
+            self.links.clone(),
+            self.stats.clone(),
+            self.actuator.clone(),
+            self.recorder.clone(),
+        )
+        .with_status_dir(self.status_dir.clone())
+    }
+
+    /// CPU load and power readings, shared by every link task
+    fn sample_globals(&mut self) -> GlobalInputs {
+        let power = self.power.readings();
+        let previous = self.policy.tier();
+        let tier = self.policy.update(self.core.power_config(), &power);
+        policy::log_change(previous, tier, &power);
+
+        GlobalInputs {
+            cpu_load: self.cpu.sample(),
+            cpu_loads: self.cpu.per_cpu(),
+            power,
+            // Decided with the link list in `sync_tasks`
+            scan_suppress: self.scan_suppress_active.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Setup inotify watcher for connection events
+    /// The NetworkManager dispatcher touches /run/hifi-wifi/connection-changed on connect
+    fn setup_connection_watcher(tx: std::sync::mpsc::Sender<notify::Result<Event>>) -> Result<RecommendedWatcher> {
+        use std::fs;
+        
+        // Ensure /run/hifi-wifi directory exists
+        let run_dir = Path::new("/run/hifi-wifi");
+        if !run_dir.exists() {
+            fs::create_dir_all(run_dir)?;
+        }
+        
+        // Create the file if it doesn't exist (so we can watch it)
+        let event_file = Path::new(CONNECTION_EVENT_PATH);
+        if !event_file.exists() {
+            fs::write(event_file, "")?;
+        }
+        
+        // Create watcher with reasonable poll interval
+        let config = NotifyConfig::default()
+            .with_poll_interval(Duration::from_millis(200));
+        
+        let mut watcher = RecommendedWatcher::new(tx, config)?;
+        watcher.watch(event_file, RecursiveMode::NonRecursive)?;
+        
+        Ok(watcher)
+    }
+
+    /// Stop the governor and clean up
+    pub async fn stop(&mut self) {
+        info!("Governor stopping, cleaning up...");
+
+        for (_, task) in self.tasks.drain() {
+            task.handle.abort();
+        }
+        for interface in &self.managed {
+            let _ = self.actuator.remove_cake(interface).await;
+            if let Some(dir) = &self.status_dir {
+                status::clear(dir, interface);
+            }
+        }
+    }
+}
+
+/// The next event from an optional source; never resolves when there is none
+async fn next_event<T>(events: &mut Option<mpsc::UnboundedReceiver<T>>) -> Option<T> {
+    match events {
+        Some(events) => events.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Background task that aborts iwd's background scans every 500ms.
+///
+/// iwd initiates a full-channel scan cycle every ~15 seconds (5.8s of off-channel time)
+/// that causes 150-175ms latency spikes. By aborting these scans before the radio leaves
+/// the home channel for the 5GHz+6GHz sweep, latency drops from ~20ms avg / 170ms max
+/// to ~3.5ms avg / 4ms max.
+///
+/// The abort command is a no-op when no scan is in progress (returns ENOENT, harmless).
+/// Only aborts when the flag is set (interface is connected). When disconnected, scans
+/// are allowed so reconnection can proceed.
+///
+/// Uses tokio::process::Command for non-blocking subprocess execution to avoid
+/// blocking the async runtime and causing micro-stuttering during streaming.
+async fn scan_abort_task(active: Arc<AtomicBool>) {
+    // Cache the interface list to avoid reading /sys every tick
+    // Refresh every 10 ticks (5 seconds) to pick up hotplug changes
+    let mut cached_interfaces: Vec<String> = Vec::new();
+    let mut cache_refresh_counter = 0u32;
+
+    let mut interval = time::interval(Duration::from_millis(500));
+
+    loop {
+        interval.tick().await;
+
+        if !active.load(Ordering::Relaxed) {
+            continue;
+        }
+
+        // Refresh interface cache every 10 ticks (5 seconds)
+        cache_refresh_counter += 1;
+        if cache_refresh_counter >= 10 || cached_interfaces.is_empty() {
+            cached_interfaces = find_wifi_interfaces();
+            cache_refresh_counter = 0;
+        }
+
+        // Abort scans on all connected WiFi interfaces using async subprocess
+        for ifc in &cached_interfaces {
+            let _ = TokioCommand::new("iw")
+                .args(["dev", ifc, "scan", "abort"])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .output()
+                .await;
+        }
+    }
+}
+
+/// Find WiFi interfaces that are currently connected (operstate "up").
+/// Reads from /sys/class/net to avoid any D-Bus overhead.
+fn find_wifi_interfaces() -> Vec<String> {
+    let mut result = Vec::new();
+    if let Ok(entries) = std::fs::read_dir("/sys/class/net") {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Check if it's a wireless interface
+            let wireless_path = format!("/sys/class/net/{}/wireless", name);
+            if !Path::new(&wireless_path).exists() {
+                continue;
+            }
+            // Check if it's up (connected)
+            let operstate_path = format!("/sys/class/net/{}/operstate", name);
+            if let Ok(state) = std::fs::read_to_string(&operstate_path) {
+                if state.trim() == "up" {
+                    result.push(name);
+                }
+            }
+        }
+    }
+    result
+}
@@ -0,0 +1,149 @@
+//! Power policy tiers
+//!
+//! Adaptive power save used to be a single switch: battery and idle, or not.
+//! The tier looks at battery level, time left at the current discharge rate
+//! and temperature as well, and power save, coalescing, EEE and scan
+//! suppression all follow it:
+//!
+//! - `performance`: on AC and cool; power save and EEE off
+//! - `balanced`: on battery (or running warm); power save and EEE only while idle
+//! - `saver`: battery nearly empty, or running hot on battery; power save,
+//!   EEE and coalescing on even while gaming, and background scans are no
+//!   longer aborted. On AC heat only goes as far as `balanced`: the radio
+//!   is a small part of the heat, and a plugged-in game keeps its latency.
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::config::structs::{PowerConfig, PowerTier};
+
+/// Battery percentage and minutes left that must be regained to leave `saver`
+const BATTERY_MARGIN_PCT: u32 = 5;
+const BATTERY_MARGIN_MINUTES: u32 = 10;
+/// Degrees a zone must cool below a threshold to leave the tier it caused
+const TEMP_MARGIN_C: f64 = 5.0;
+
+/// Power supply and thermal readings for one tick
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PowerReadings {
+    /// Portable device running on battery (PowerManager::should_enable_power_save)
+    pub on_battery: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub battery_percent: Option<u32>,
+    /// Time to empty at the current discharge rate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub battery_minutes_left: Option<u32>,
+    /// Hottest thermal zone (°C)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature_c: Option<f64>,
+}
+
+/// Tracks the current tier
+/// Leaving a tier takes a margin past the threshold that entered it, so a
+/// reading hovering at the threshold doesn't flip settings back and forth.
+#[derive(Debug, Default)]
+pub struct PowerPolicy {
+    tier: PowerTier,
+}
+
+impl PowerPolicy {
+    pub fn tier(&self) -> PowerTier {
+        self.tier
+    }
+
+    /// Pick the tier for `readings`; returns the new tier
+    pub fn update(&mut self, config: &PowerConfig, readings: &PowerReadings) -> PowerTier {
+        self.tier = config.tier.unwrap_or_else(|| self.select(config, readings));
+        self.tier
+    }
+
+    fn select(&self, config: &PowerConfig, readings: &PowerReadings) -> PowerTier {
+        let in_saver = self.tier == PowerTier::Saver;
+        let (pct_margin, minutes_margin) = if in_saver {
+            (BATTERY_MARGIN_PCT, BATTERY_MARGIN_MINUTES)
+        } else {
+            (0, 0)
+        };
+        let low_battery = readings.on_battery
+            && (readings.battery_percent.is_some_and(|pct| pct < config.saver_below_battery_pct + pct_margin)
+                || readings.battery_minutes_left
+                    .is_some_and(|minutes| minutes < config.saver_below_minutes_left + minutes_margin));
+
+        let temp = readings.temperature_c.unwrap_or(f64::MIN);
+        let hot = readings.on_battery
+            && temp >= config.saver_above_temp_c - if in_saver { TEMP_MARGIN_C } else { 0.0 };
+        let warm = temp >= config.balanced_above_temp_c
+            - if self.tier >= PowerTier::Balanced { TEMP_MARGIN_C } else { 0.0 };
+
+        if low_battery || hot {
+            PowerTier::Saver
+        } else if readings.on_battery || warm {
+            PowerTier::Balanced
+        } else {
+            PowerTier::Performance
+        }
+    }
+}
+
+/// Log a tier change with the readings that caused it
+pub fn log_change(from: PowerTier, to: PowerTier, readings: &PowerReadings) {
+    if from == to {
+        return;
+    }
+    let mut why = vec![if readings.on_battery { "battery".to_string() } else { "AC".to_string() }];
+    if let Some(pct) = readings.battery_percent {
+        why.push(format!("{}%", pct));
+    }
+    if let Some(minutes) = readings.battery_minutes_left {
+        why.push(format!("{} min left", minutes));
+    }
+    if let Some(temp) = readings.temperature_c {
+        why.push(format!("{:.0}°C", temp));
+    }
+    info!("Power tier: {:?} -> {:?} ({})", from, to, why.join(", "));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn battery(pct: u32) -> PowerReadings {
+        PowerReadings { on_battery: true, battery_percent: Some(pct), ..Default::default() }
+    }
+
+    #[test]
+    fn test_tier_follows_battery_with_margin() {
+        let config = PowerConfig::default();
+        let mut policy = PowerPolicy::default();
+
+        assert_eq!(policy.update(&config, &PowerReadings::default()), PowerTier::Performance);
+        assert_eq!(policy.update(&config, &battery(60)), PowerTier::Balanced);
+        assert_eq!(policy.update(&config, &battery(14)), PowerTier::Saver);
+        // Charge hovering around the threshold doesn't flap
+        assert_eq!(policy.update(&config, &battery(16)), PowerTier::Saver);
+        assert_eq!(policy.update(&config, &battery(20)), PowerTier::Balanced);
+
+        // Fast drain counts even with charge left
+        let draining = PowerReadings { battery_minutes_left: Some(12), ..battery(40) };
+        assert_eq!(policy.update(&config, &draining), PowerTier::Saver);
+    }
+
+    #[test]
+    fn test_tier_follows_temperature_and_pin() {
+        let mut config = PowerConfig::default();
+        let mut policy = PowerPolicy::default();
+        let at = |temp: f64| PowerReadings { temperature_c: Some(temp), ..Default::default() };
+        let on_battery_at = |temp: f64| PowerReadings { temperature_c: Some(temp), ..battery(80) };
+
+        assert_eq!(policy.update(&config, &at(60.0)), PowerTier::Performance);
+        assert_eq!(policy.update(&config, &at(config.balanced_above_temp_c)), PowerTier::Balanced);
+        // On AC, heat alone stops at balanced
+        assert_eq!(policy.update(&config, &at(config.saver_above_temp_c + 1.0)), PowerTier::Balanced);
+        assert_eq!(policy.update(&config, &on_battery_at(config.saver_above_temp_c + 1.0)), PowerTier::Saver);
+        assert_eq!(policy.update(&config, &on_battery_at(config.saver_above_temp_c - 2.0)), PowerTier::Saver);
+        assert_eq!(policy.update(&config, &at(60.0)), PowerTier::Performance);
+
+        config.tier = Some(PowerTier::Performance);
+        assert_eq!(policy.update(&config, &battery(5)), PowerTier::Performance);
+    }
+}
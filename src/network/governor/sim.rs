@@ -0,0 +1,896 @@
+//! Scenario runner for the Governor
+//!
+//! Drives a real `Governor` against a scripted world: links, traffic, CPU load
+//! and power source change at fixed times, every link ticks every 2 seconds,
+//! and every action applied is recorded with the time it happened.
+//!
+//! Scenarios tick the link workers one after another so runs are repeatable;
+//! the task supervisor itself is covered by short real-time tests.
+
+use anyhow::{anyhow, Result};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::task::LocalSet;
+
+use super::core::{Action, GovernorCore};
+use super::io::{ActiveLink, Actuator, CpuLoad, LinkSource, LinkStats, PowerInput};
+use super::link::LinkWorker;
+use super::policy::PowerReadings;
+use super::trace::{self, TraceRecorder};
+use super::Governor;
+use crate::config::structs::{GovernorConfig, PowerConfig, WanProfile, WifiConfig};
+use crate::network::nm::{AccessPoint, LinkEvent, WifiBand};
+use crate::network::stats::NetStats;
+use crate::network::wifi::InterfaceType;
+use crate::system::irq::{Cpu, Topology};
+use crate::system::sleep::SleepEvent;
+
+const TICK_SECS: u64 = 2;
+
+/// A link in the simulated world
+struct SimLink {
+    interface: String,
+    interface_type: InterfaceType,
+    connected: bool,
+    nm_bitrate_kbit: u32,
+    iw_bitrate_kbit: u32,
+    /// Traffic rates, integrated into `counters` as time advances
+    pps: u64,
+    bytes_per_sec: u64,
+    counters: NetStats,
+    active_ap: Option<AccessPoint>,
+    access_points: Vec<AccessPoint>,
+    /// Bands the adapter reports (empty = unknown)
+    bands: Vec<WifiBand>,
+    /// CPUs the adapter's IRQs are on, moved by successful steering
+    irq_cpus: Vec<usize>,
+}
+
+impl SimLink {
+    fn wifi(interface: &str, bitrate_mbit: u32) -> Self {
+        Self {
+            interface: interface.to_string(),
+            interface_type: InterfaceType::Wifi,
+            connected: true,
+            nm_bitrate_kbit: bitrate_mbit * 1000,
+            iw_bitrate_kbit: 0,
+            pps: 0,
+            bytes_per_sec: 0,
+            counters: NetStats::default(),
+            active_ap: None,
+            access_points: Vec::new(),
+            bands: Vec::new(),
+            // The kernel's default
+            irq_cpus: vec![0],
+        }
+    }
+
+    fn ethernet(interface: &str, speed_mbit: u32) -> Self {
+        Self {
+            interface_type: InterfaceType::Ethernet,
+            ..Self::wifi(interface, speed_mbit)
+        }
+    }
+}
+
+/// Everything the fakes read from and write to
+#[derive(Default)]
+struct World {
+    now: Duration,
+    cpu_load: f64,
+    cpu_loads: BTreeMap<usize, f64>,
+    on_battery: bool,
+    battery_percent: Option<u32>,
+    temperature_c: Option<f64>,
+    links: Vec<SimLink>,
+    /// Actions the Governor applied: (time, action, succeeded)
+    applied: Vec<(Duration, Action, bool)>,
+    /// Actions that fail the next time they are applied
+    fail_once: Vec<Action>,
+    /// Links whose backend takes this long to answer a refresh
+    slow_refresh: Option<(String, Duration)>,
+    /// Times the gateway RTT was measured again
+    rtt_measurements: u32,
+}
+
+impl World {
+    fn link_mut(&mut self, interface: &str) -> &mut SimLink {
+        self.links.iter_mut()
+            .find(|l| l.interface == interface)
+            .expect("unknown simulated interface")
+    }
+
+    fn advance_to(&mut self, now: Duration) {
+        let secs = now.saturating_sub(self.now).as_secs_f64();
+        for link in &mut self.links {
+            let packets = (link.pps as f64 * secs) as u64;
+            let bytes = (link.bytes_per_sec as f64 * secs) as u64;
+            link.counters.rx_packets += packets / 2;
+            link.counters.tx_packets += packets - packets / 2;
+            link.counters.rx_bytes += bytes / 2;
+            link.counters.tx_bytes += bytes - bytes / 2;
+        }
+        self.now = now;
+    }
+
+    fn apply(&mut self, action: Action) -> Result<()> {
+        let failed = match self.fail_once.iter().position(|a| *a == action) {
+            Some(i) => {
+                self.fail_once.remove(i);
+                true
+            }
+            None => false,
+        };
+        self.applied.push((self.now, action.clone(), !failed));
+        if failed {
+            Err(anyhow!("simulated failure: {:?}", action))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Fake inputs and outputs backed by the shared world
+#[derive(Clone)]
+struct SimIo(Rc<RefCell<World>>);
+
+impl LinkSource for SimIo {
+    async fn active_links(&mut self) -> Result<Vec<ActiveLink>> {
+        let world = self.0.borrow();
+        Ok(world.links.iter()
+            .filter(|l| l.connected)
+            .map(|l| ActiveLink {
+                interface: l.interface.clone(),
+                device_path: format!("/sim/{}", l.interface),
+                interface_type: l.interface_type.clone(),
+                bitrate_kbit: l.nm_bitrate_kbit,
+                active_ap: l.active_ap.clone(),
+            })
+            .collect())
+    }
+
+    async fn refresh(&mut self, link: &ActiveLink) -> Result<Option<ActiveLink>> {
+        let delay = self.0.borrow().slow_refresh.clone()
+            .filter(|(interface, _)| *interface == link.interface)
+            .map(|(_, delay)| delay);
+        if let Some(delay) = delay {
+            tokio::time::sleep(delay).await;
+        }
+        let links = self.active_links().await?;
+        Ok(links.into_iter().find(|l| l.interface == link.interface))
+    }
+
+    async fn access_points(&mut self, link: &ActiveLink) -> Result<Vec<AccessPoint>> {
+        Ok(self.0.borrow_mut().link_mut(&link.interface).access_points.clone())
+    }
+}
+
+impl LinkStats for SimIo {
+    async fn phy_bitrate_kbit(&mut self, interface: &str) -> Option<u32> {
+        Some(self.0.borrow_mut().link_mut(interface).iw_bitrate_kbit).filter(|&b| b > 0)
+    }
+
+    fn counters(&mut self, interface: &str) -> Option<NetStats> {
+        Some(self.0.borrow_mut().link_mut(interface).counters.clone())
+    }
+
+    async fn supported_bands(&mut self, interface: &str) -> Vec<WifiBand> {
+        self.0.borrow_mut().link_mut(interface).bands.clone()
+    }
+
+    fn irq_cpus(&mut self, interface: &str) -> Vec<usize> {
+        self.0.borrow_mut().link_mut(interface).irq_cpus.clone()
+    }
+}
+
+impl CpuLoad for SimIo {
+    fn sample(&mut self) -> f64 {
+        self.0.borrow().cpu_load
+    }
+
+    fn per_cpu(&mut self) -> BTreeMap<usize, f64> {
+        self.0.borrow().cpu_loads.clone()
+    }
+}
+
+impl PowerInput for SimIo {
+    fn readings(&mut self) -> PowerReadings {
+        let world = self.0.borrow();
+        PowerReadings {
+            on_battery: world.on_battery,
+            battery_percent: world.battery_percent,
+            battery_minutes_left: None,
+            temperature_c: world.temperature_c,
+        }
+    }
+}
+
+impl Actuator for SimIo {
+    async fn apply_cake(&mut self, interface: &str, bandwidth_mbit: u32) -> Result<()> {
+        self.0.borrow_mut().apply(Action::ApplyCake { interface: interface.to_string(), bandwidth_mbit })
+    }
+
+    async fn remove_cake(&mut self, _interface: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn set_coalescing(&mut self, interface: &str, enabled: bool) -> Result<()> {
+        self.0.borrow_mut().apply(Action::SetCoalescing { interface: interface.to_string(), enabled })
+    }
+
+    async fn set_eee(&mut self, interface: &str, enabled: bool) -> Result<()> {
+        self.0.borrow_mut().apply(Action::SetEee { interface: interface.to_string(), enabled })
+    }
+
+    async fn set_power_save(&mut self, interface: &str, enabled: bool) -> Result<()> {
+        self.0.borrow_mut().apply(Action::SetPowerSave { interface: interface.to_string(), enabled })
+    }
+
+    async fn steer_irqs(&mut self, interface: &str, cpus: &[usize]) -> Result<()> {
+        let mut world = self.0.borrow_mut();
+        world.apply(Action::SteerIrqs { interface: interface.to_string(), cpus: cpus.to_vec() })?;
+        world.link_mut(interface).irq_cpus = cpus.to_vec();
+        Ok(())
+    }
+
+    async fn tune_backend(&mut self, interface: &str) -> Result<()> {
+        self.0.borrow_mut().apply(Action::TuneBackend { interface: interface.to_string() })
+    }
+
+    async fn request_roam(&mut self, interface: &str, device_path: &str, bssid: &str, frequency: u32) -> Result<()> {
+        self.0.borrow_mut().apply(Action::RequestScan {
+            interface: interface.to_string(),
+            device_path: device_path.to_string(),
+            bssid: bssid.to_string(),
+            frequency,
+        })
+    }
+
+    async fn remeasure_rtt(&mut self) -> Result<()> {
+        self.0.borrow_mut().rtt_measurements += 1;
+        Ok(())
+    }
+}
+
+type Event = Box<dyn FnOnce(&mut World)>;
+
+/// A scripted timeline of world changes
+struct Scenario {
+    config: GovernorConfig,
+    power_config: PowerConfig,
+    topology: Topology,
+    world: World,
+    events: Vec<(u64, Event)>,
+    /// Backend events delivered to the supervisor at a time
+    link_events: Vec<(u64, LinkEvent)>,
+    /// Trace file for `Governor::record_to`
+    record: Option<PathBuf>,
+}
+
+impl Scenario {
+    fn new() -> Self {
+        Self {
+            config: GovernorConfig::default(),
+            power_config: PowerConfig::default(),
+            topology: Topology::default(),
+            world: World::default(),
+            events: Vec::new(),
+            link_events: Vec::new(),
+            record: None,
+        }
+    }
+
+    fn config(mut self, f: impl FnOnce(&mut GovernorConfig)) -> Self {
+        f(&mut self.config);
+        self
+    }
+
+    fn power_save_mode(mut self, mode: &str) -> Self {
+        self.power_config.wlan_power_save = mode.to_string();
+        self
+    }
+
+    /// `cores` single-thread cores, idle
+    fn cpus(mut self, cores: usize) -> Self {
+        self.topology.cpus = (0..cores)
+            .map(|id| Cpu { id, core: (0, id as u32), max_khz: 0, isolated: false })
+            .collect();
+        self.world.cpu_loads = (0..cores).map(|id| (id, 0.05)).collect();
+        self
+    }
+
+    fn link(mut self, link: SimLink) -> Self {
+        self.world.links.push(link);
+        self
+    }
+
+    fn on_battery(mut self) -> Self {
+        self.world.on_battery = true;
+        self
+    }
+
+    /// Change the world at `secs`, before that tick runs
+    fn at(mut self, secs: u64, event: impl FnOnce(&mut World) + 'static) -> Self {
+        self.events.push((secs, Box::new(event)));
+        self
+    }
+
+    /// Deliver a backend event at `secs`, before that tick runs
+    fn link_event(mut self, secs: u64, event: LinkEvent) -> Self {
+        self.link_events.push((secs, event));
+        self
+    }
+
+    fn record_to(mut self, path: &Path) -> Self {
+        self.record = Some(path.to_path_buf());
+        self
+    }
+
+    /// Tick every 2 seconds from 0 to `until_secs` inclusive
+    async fn run(mut self, until_secs: u64) -> Trace {
+        self.events.sort_by_key(|(secs, _)| *secs);
+        let mut events = self.events.into_iter().peekable();
+        self.link_events.sort_by_key(|(secs, _)| *secs);
+        let mut link_events = self.link_events.into_iter().peekable();
+
+        let world = Rc::new(RefCell::new(self.world));
+        let io = SimIo(world.clone());
+        let core = GovernorCore::new(self.config, WifiConfig::default(), self.power_config)
+            .with_topology(self.topology);
+        let mut governor = Governor::with_io(core, io.clone(), io.clone(), io.clone(), io.clone(), io);
+        if let Some(path) = &self.record {
+            governor.record_to(TraceRecorder::create(path).unwrap()).unwrap();
+        }
+        let mut workers: BTreeMap<String, LinkWorker<SimIo, SimIo, SimIo>> = BTreeMap::new();
+
+        for secs in (0..=until_secs).step_by(TICK_SECS as usize) {
+            let at = Duration::from_secs(secs);
+            {
+                let mut world = world.borrow_mut();
+                world.advance_to(at);
+                while let Some((_, event)) = events.next_if(|(t, _)| *t <= secs) {
+                    event(&mut world);
+                }
+            }
+
+            // Same bookkeeping as Governor::supervise, without the tasks
+            while let Some((_, event)) = link_events.next_if(|(t, _)| *t <= secs) {
+                governor.handle_link_event(event);
+            }
+            let mut globals = governor.sample_globals();
+            let active = governor.links.active_links().await.expect("simulated links failed");
+            globals.scan_suppress = governor.update_scan_suppress(&active);
+            workers.retain(|interface, _| active.iter().any(|l| &l.interface == interface));
+            for link in active {
+                if !workers.contains_key(&link.interface) {
+                    workers.insert(link.interface.clone(), governor.worker(link));
+                }
+            }
+            for worker in workers.values_mut() {
+                worker.tick_at(at, globals.clone()).await.expect("simulated tick failed");
+            }
+        }
+
+        let applied = std::mem::take(&mut world.borrow_mut().applied);
+        Trace { applied }
+    }
+}
+
+/// Actions recorded during a scenario
+struct Trace {
+    applied: Vec<(Duration, Action, bool)>,
+}
+
+impl Trace {
+    fn select<T>(&self, f: impl Fn(&Action) -> Option<T>) -> Vec<(u64, T)> {
+        self.applied.iter()
+            .filter_map(|(at, action, _)| f(action).map(|v| (at.as_secs(), v)))
+            .collect()
+    }
+
+    fn cake(&self) -> Vec<(u64, u32)> {
+        self.select(|a| match a {
+            Action::ApplyCake { bandwidth_mbit, .. } => Some(*bandwidth_mbit),
+            _ => None,
+        })
+    }
+
+    fn coalescing(&self) -> Vec<(u64, bool)> {
+        self.select(|a| match a {
+            Action::SetCoalescing { enabled, .. } => Some(*enabled),
+            _ => None,
+        })
+    }
+
+    fn power_save(&self) -> Vec<(u64, bool)> {
+        self.select(|a| match a {
+            Action::SetPowerSave { enabled, .. } => Some(*enabled),
+            _ => None,
+        })
+    }
+
+    fn eee(&self) -> Vec<(u64, bool)> {
+        self.select(|a| match a {
+            Action::SetEee { enabled, .. } => Some(*enabled),
+            _ => None,
+        })
+    }
+
+    fn steering(&self) -> Vec<(u64, Vec<usize>)> {
+        self.select(|a| match a {
+            Action::SteerIrqs { cpus, .. } => Some(cpus.clone()),
+            _ => None,
+        })
+    }
+
+    fn scans(&self) -> Vec<u64> {
+        self.select(|a| matches!(a, Action::RequestScan { .. }).then_some(()))
+            .into_iter()
+            .map(|(at, _)| at)
+            .collect()
+    }
+
+    fn failures(&self) -> usize {
+        self.applied.iter().filter(|(_, _, ok)| !ok).count()
+    }
+}
+
+fn ap(bssid: &str, frequency: u32, signal_strength: i32) -> AccessPoint {
+    AccessPoint {
+        path: format!("/sim/ap/{}", bssid),
+        ssid: "home".to_string(),
+        bssid: bssid.to_string(),
+        frequency,
+        band: WifiBand::from_frequency(frequency),
+        signal_strength,
+        max_bitrate: 0,
+    }
+}
+
+#[tokio::test]
+async fn test_cake_drops_fast_and_rises_slowly() {
+    let trace = Scenario::new()
+        .link(SimLink::wifi("wlan0", 400))
+        .at(10, |w| w.link_mut("wlan0").nm_bitrate_kbit = 100_000)
+        .at(20, |w| w.link_mut("wlan0").nm_bitrate_kbit = 400_000)
+        .run(40)
+        .await;
+
+    // 85% of 400 Mbit after 3 stable ticks, the drop after 1, the recovery after 3
+    assert_eq!(trace.cake(), vec![(6, 340), (12, 85), (26, 340)]);
+}
+
+#[tokio::test]
+async fn test_game_mode_freezes_and_releases_cake() {
+    let trace = Scenario::new()
+        .link(SimLink::wifi("wlan0", 400))
+        .at(10, |w| w.link_mut("wlan0").pps = 1000)
+        .at(20, |w| w.link_mut("wlan0").nm_bitrate_kbit = 100_000)
+        .at(40, |w| w.link_mut("wlan0").pps = 0)
+        .run(100)
+        .await;
+
+    // Smoothed PPS stays above 200 until t=48, so the 30s cooldown ends at t=78
+    let cake = trace.cake();
+    assert_eq!(cake[0], (6, 340));
+    assert!(cake.iter().all(|(at, _)| *at < 10 || *at >= 78), "CAKE changed mid-game: {:?}", cake);
+    assert_eq!(cake.last(), Some(&(80, 85)));
+}
+
+#[tokio::test]
+async fn test_coalescing_ignores_single_tick_spikes() {
+    let trace = Scenario::new()
+        .link(SimLink::wifi("wlan0", 400))
+        .at(0, |w| w.link_mut("wlan0").pps = 1000)
+        .at(20, |w| w.cpu_load = 0.95)
+        .at(22, |w| w.cpu_load = 0.2)
+        .at(30, |w| w.cpu_load = 0.95)
+        .run(40)
+        .await;
+
+    // In game mode coalescing stays off until CPU is high for 2 ticks
+    assert_eq!(trace.coalescing(), vec![(32, true)]);
+}
+
+#[tokio::test]
+async fn test_irqs_move_off_saturated_core() {
+    let trace = Scenario::new()
+        .cpus(4)
+        .link(SimLink::wifi("wlan0", 400))
+        // The game saturates CPU 0, where the kernel placed the IRQs
+        .at(10, |w| { w.cpu_loads.insert(0, 0.97); })
+        // Then CPU 1, where they were moved
+        .at(20, |w| { w.cpu_loads.insert(1, 0.97); })
+        // A one-tick spike on the new core is ignored
+        .at(30, |w| { w.cpu_loads.insert(2, 0.95); })
+        .at(32, |w| { w.cpu_loads.insert(2, 0.1); })
+        .run(40)
+        .await;
+
+    // Nothing moves while the IRQs' core has room; each move after 3 saturated ticks
+    assert_eq!(trace.steering(), vec![(14, vec![1, 2]), (24, vec![2, 3])]);
+
+    let untouched = Scenario::new()
+        .link(SimLink::wifi("wlan0", 400))
+        .run(20)
+        .await;
+    assert!(untouched.steering().is_empty());
+}
+
+#[tokio::test]
+async fn test_adaptive_power_save_follows_power_source() {
+    let trace = Scenario::new()
+        .on_battery()
+        .link(SimLink::wifi("wlan0", 400))
+        .at(20, |w| w.on_battery = false)
+        .at(22, |w| w.on_battery = true)
+        .at(30, |w| w.on_battery = false)
+        .run(40)
+        .await;
+
+    // A brief AC connection at t=20 is ignored; 3 ticks on AC disable power save
+    assert_eq!(trace.power_save(), vec![(4, true), (34, false)]);
+}
+
+#[tokio::test]
+async fn test_saver_tier_trades_latency_for_battery() {
+    let trace = Scenario::new()
+        .on_battery()
+        .link(SimLink::wifi("wlan0", 400))
+        .at(0, |w| {
+            w.battery_percent = Some(60);
+            w.link_mut("wlan0").pps = 1000;
+        })
+        .at(20, |w| w.battery_percent = Some(12))
+        .at(40, |w| w.battery_percent = Some(16))
+        .run(50)
+        .await;
+
+    // Mid-game, power save stays off until the battery runs low; then power
+    // save and coalescing go on despite the game, and a charge hovering at the
+    // threshold doesn't switch them back
+    assert_eq!(trace.power_save(), vec![(6, false), (24, true)]);
+    assert_eq!(trace.coalescing(), vec![(22, true)]);
+}
+
+#[tokio::test]
+async fn test_power_save_override_retries_after_failure() {
+    let off = Action::SetPowerSave { interface: "wlan0".to_string(), enabled: false };
+    let trace = Scenario::new()
+        .power_save_mode("off")
+        .link(SimLink::wifi("wlan0", 400))
+        .at(0, move |w| w.fail_once.push(off))
+        .run(10)
+        .await;
+
+    assert_eq!(trace.power_save(), vec![(0, false), (2, false)]);
+    assert_eq!(trace.failures(), 1);
+}
+
+#[tokio::test]
+async fn test_irq_steering_retries_after_failure() {
+    let steer = Action::SteerIrqs { interface: "wlan0".to_string(), cpus: vec![1, 2] };
+    let trace = Scenario::new()
+        .cpus(4)
+        .link(SimLink::wifi("wlan0", 400))
+        .at(0, move |w| {
+            w.cpu_loads.insert(0, 0.97);
+            w.fail_once.push(steer);
+        })
+        .run(12)
+        .await;
+
+    // The failed placement is forgotten, so it is tried again 3 ticks later
+    assert_eq!(trace.steering(), vec![(4, vec![1, 2]), (10, vec![1, 2])]);
+    assert_eq!(trace.failures(), 1);
+}
+
+#[tokio::test]
+async fn test_ethernet_uses_eee_not_power_save() {
+    let trace = Scenario::new()
+        .on_battery()
+        .link(SimLink::ethernet("eth0", 1000))
+        .at(20, |w| w.link_mut("eth0").pps = 500)
+        .run(40)
+        .await;
+
+    // Traffic first shows up in the t=22 sample, EEE goes off 3 ticks later
+    assert!(trace.power_save().is_empty());
+    assert_eq!(trace.eee(), vec![(4, true), (26, false)]);
+}
+
+#[tokio::test]
+async fn test_ethernet_cake_follows_link_speed_and_uplink() {
+    let negotiated = Scenario::new()
+        .link(SimLink::ethernet("eth0", 1000))
+        .at(10, |w| w.link_mut("eth0").nm_bitrate_kbit = 100_000)
+        .run(20)
+        .await;
+    assert_eq!(negotiated.cake(), vec![(6, 850), (12, 85)]);
+
+    // A 10 Mbit link is real on Ethernet, not a bogus idle reading
+    let slow = Scenario::new()
+        .link(SimLink::ethernet("eth0", 10))
+        .run(10)
+        .await;
+    assert_eq!(slow.cake(), vec![(6, 10)]);
+
+}
+
+#[tokio::test]
+async fn test_cake_capped_by_wan_uplink() {
+    let mut link = SimLink::wifi("wlan0", 866);
+    link.active_ap = Some(ap("aa:aa", 5180, -50));
+    let trace = Scenario::new()
+        .config(|c| c.wan.profiles.push(WanProfile {
+            network: "home".to_string(),
+            upload_mbit: Some(40),
+            download_mbit: None,
+        }))
+        .link(link)
+        .link(SimLink::ethernet("eth0", 1000))
+        // Signal collapses below the line rate: the link binds again
+        .at(20, |w| w.link_mut("wlan0").nm_bitrate_kbit = 30_000)
+        .run(40)
+        .await;
+
+    let wlan0: Vec<_> = trace.applied.iter()
+        .filter_map(|(at, a, _)| match a {
+            Action::ApplyCake { interface, bandwidth_mbit } if interface == "wlan0" => Some((at.as_secs(), *bandwidth_mbit)),
+            _ => None,
+        })
+        .collect();
+    // 95% of the 40 Mbit uplink instead of 85% of 866 Mbit, then 85% of 30 Mbit
+    assert_eq!(wlan0, vec![(6, 38), (22, 25)]);
+
+    // eth0 is a different network with no profile
+    assert!(trace.applied.iter().any(|(_, a, _)| *a == Action::ApplyCake { interface: "eth0".to_string(), bandwidth_mbit: 850 }));
+}
+
+#[tokio::test]
+async fn test_cake_follows_uplink_below_link_floor() {
+    let mut link = SimLink::wifi("wlan0", 866);
+    link.active_ap = Some(ap("aa:aa", 5180, -50));
+    let trace = Scenario::new()
+        .config(|c| c.wan.profiles.push(WanProfile {
+            network: "home".to_string(),
+            upload_mbit: Some(4),
+            download_mbit: None,
+        }))
+        .link(link)
+        // A probe-frame reading is floored, but the uplink still binds
+        .at(10, |w| w.link_mut("wlan0").nm_bitrate_kbit = 6_000)
+        .run(20)
+        .await;
+
+    // 95% of 4 Mbit, not the 10 Mbit link floor
+    assert_eq!(trace.cake(), vec![(6, 3)]);
+}
+
+#[tokio::test]
+async fn test_band_steering_requires_consecutive_ticks() {
+    let current = ap("aa:aa", 2437, -50);
+    let better = ap("bb:bb", 5180, -60);
+    let mut link = SimLink::wifi("wlan0", 400);
+    link.active_ap = Some(current.clone());
+    link.access_points = vec![current.clone(), better.clone()];
+
+    let trace = Scenario::new()
+        .config(|c| c.scan_suppress = false)
+        .link(link)
+        .at(6, move |w| w.link_mut("wlan0").active_ap = Some(better))
+        .run(20)
+        .await;
+
+    // 5GHz at -60 scores -45 against -50; roam on the 3rd consecutive tick only
+    assert_eq!(trace.scans(), vec![4]);
+}
+
+#[tokio::test]
+async fn test_band_steering_ignores_flapping_candidate() {
+    let current = ap("aa:aa", 2437, -50);
+    let mut link = SimLink::wifi("wlan0", 400);
+    link.active_ap = Some(current.clone());
+    link.access_points = vec![current, ap("bb:bb", 5180, -60)];
+
+    let mut scenario = Scenario::new()
+        .config(|c| c.scan_suppress = false)
+        .link(link);
+    // Candidate drops below the 5GHz minimum every third tick
+    for secs in (4..=40).step_by(6) {
+        scenario = scenario
+            .at(secs, |w| w.link_mut("wlan0").access_points[1].signal_strength = -80)
+            .at(secs + 2, |w| w.link_mut("wlan0").access_points[1].signal_strength = -60);
+    }
+    let trace = scenario.run(40).await;
+
+    assert_eq!(trace.scans(), Vec::<u64>::new());
+}
+
+#[tokio::test]
+async fn test_band_steering_skips_bands_the_adapter_lacks() {
+    let current = ap("aa:aa", 5180, -55);
+    let mut link = SimLink::wifi("wlan0", 400);
+    link.active_ap = Some(current.clone());
+    link.access_points = vec![current, ap("bb:bb", 5955, -50)];
+    link.bands = vec![WifiBand::Band2_4GHz, WifiBand::Band5GHz];
+
+    let trace = Scenario::new()
+        .config(|c| c.scan_suppress = false)
+        .link(link)
+        .run(20)
+        .await;
+
+    // The 6GHz AP outscores the current one but this adapter can't use it
+    assert_eq!(trace.scans(), Vec::<u64>::new());
+}
+
+#[tokio::test]
+async fn test_band_steering_resumes_when_saver_allows_scans() {
+    let current = ap("aa:aa", 2437, -50);
+    let mut link = SimLink::wifi("wlan0", 400);
+    link.active_ap = Some(current.clone());
+    let better = ap("bb:bb", 5180, -60);
+    link.access_points = vec![current, better.clone()];
+
+    // scan_suppress stays on; the saver tier lets the background scans through
+    let trace = Scenario::new()
+        .on_battery()
+        .link(link)
+        .at(0, |w| w.battery_percent = Some(60))
+        .at(10, |w| w.battery_percent = Some(10))
+        .at(16, move |w| w.link_mut("wlan0").active_ap = Some(better))
+        .run(24)
+        .await;
+
+    assert_eq!(trace.scans(), vec![14]);
+}
+
+#[tokio::test]
+async fn test_band_steering_runs_while_signal_is_weak() {
+    let current = ap("aa:aa", 2437, -50);
+    let mut link = SimLink::wifi("wlan0", 400);
+    link.active_ap = Some(current.clone());
+    link.access_points = vec![current, ap("bb:bb", 5180, -60)];
+
+    let weak = |weak| LinkEvent::SignalLevel { interface: "wlan0".to_string(), weak };
+    let trace = Scenario::new()
+        .link(link)
+        .link_event(10, weak(true))
+        .link_event(16, weak(false))
+        .run(30)
+        .await;
+
+    // Scans are allowed for the roam, and the Governor's own steering takes part
+    assert_eq!(trace.scans(), vec![14]);
+}
+
+#[tokio::test]
+async fn test_trace_keeps_access_points_for_replay() {
+    let current = ap("aa:aa", 2437, -50);
+    let mut link = SimLink::wifi("wlan0", 400);
+    link.active_ap = Some(current.clone());
+    link.access_points = vec![current, ap("bb:bb", 5180, -60)];
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("trace.jsonl");
+    let live = Scenario::new().link(link).record_to(&path).run(8).await;
+    assert_eq!(live.scans(), Vec::<u64>::new());
+
+    // Recorded with scan suppression on, replayed with it off
+    let recorded = trace::read_trace(&path).unwrap();
+    assert!(recorded.ticks.iter().all(|t| t.links[0].access_points.as_ref().is_some_and(|aps| aps.len() == 2)));
+    let config = GovernorConfig { scan_suppress: false, ..Default::default() };
+    let core = GovernorCore::new(config, WifiConfig::default(), PowerConfig::default());
+    let scans: Vec<_> = trace::replay(&core, &recorded.ticks).into_iter()
+        .filter(|(_, a)| matches!(a, Action::RequestScan { .. }))
+        .map(|(at, _)| at.as_secs())
+        .collect();
+    assert_eq!(scans, vec![4]);
+}
+
+#[tokio::test]
+async fn test_slow_link_does_not_delay_other_links() {
+    let mut world = World::default();
+    world.links.push(SimLink::wifi("wlan0", 400));
+    world.links.push(SimLink::ethernet("eth0", 1000));
+    world.slow_refresh = Some(("wlan0".to_string(), Duration::from_secs(30)));
+
+    let world = Rc::new(RefCell::new(world));
+    let io = SimIo(world.clone());
+    let core = GovernorCore::new(GovernorConfig::default(), WifiConfig::default(), PowerConfig::default());
+    let mut governor = Governor::with_io(core, io.clone(), io.clone(), io.clone(), io.clone(), io);
+
+    let (_events_tx, events_rx) = std::sync::mpsc::channel();
+    let local = LocalSet::new();
+    let supervise = governor.supervise(Duration::from_millis(20), events_rx);
+    let _ = local.run_until(tokio::time::timeout(Duration::from_millis(500), supervise)).await;
+
+    // eth0 gets CAKE after 4 ticks while wlan0 is still waiting on its backend
+    let applied = std::mem::take(&mut world.borrow_mut().applied);
+    assert!(applied.iter().any(|(_, a, _)| matches!(a, Action::ApplyCake { interface, .. } if interface == "eth0")));
+    assert!(applied.iter().all(|(_, a, _)| a.interface() == "eth0"));
+}
+
+#[tokio::test]
+async fn test_disconnect_event_stops_task_before_next_tick() {
+    let mut world = World::default();
+    world.links.push(SimLink::wifi("wlan0", 400));
+    let world = Rc::new(RefCell::new(world));
+    let io = SimIo(world.clone());
+    let core = GovernorCore::new(GovernorConfig::default(), WifiConfig::default(), PowerConfig::default());
+    let mut governor = Governor::with_io(core, io.clone(), io.clone(), io.clone(), io.clone(), io);
+    let (link_tx, link_rx) = tokio::sync::mpsc::unbounded_channel();
+    governor.link_events = Some(link_rx);
+
+    let (_events_tx, events_rx) = std::sync::mpsc::channel();
+    let local = LocalSet::new();
+    let disconnect = world.clone();
+    local.spawn_local(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        disconnect.borrow_mut().link_mut("wlan0").connected = false;
+        let _ = link_tx.send(LinkEvent::Disconnected { interface: "wlan0".to_string() });
+    });
+    // The next regular tick would be 10s away
+    let supervise = governor.supervise(Duration::from_secs(10), events_rx);
+    let _ = local.run_until(tokio::time::timeout(Duration::from_millis(300), supervise)).await;
+
+    assert!(governor.managed.contains("wlan0"));
+    assert!(governor.tasks.is_empty());
+}
+
+#[tokio::test]
+async fn test_backend_tuned_once_per_connection() {
+    let tune = Action::TuneBackend { interface: "wlan0".to_string() };
+    let trace = Scenario::new()
+        .link(SimLink::wifi("wlan0", 400))
+        .link(SimLink::ethernet("eth0", 1000))
+        .at(0, move |w| w.fail_once.push(tune))
+        .run(20)
+        .await;
+
+    // A failed attempt is retried on the next tick; Ethernet has nothing to tune
+    let tuned = trace.select(|a| match a {
+        Action::TuneBackend { interface } => Some(interface.clone()),
+        _ => None,
+    });
+    assert_eq!(tuned, vec![(0, "wlan0".to_string()), (2, "wlan0".to_string())]);
+    assert_eq!(trace.failures(), 1);
+}
+
+#[tokio::test]
+async fn test_resume_restarts_link_tasks_with_fresh_state() {
+    let mut world = World::default();
+    world.links.push(SimLink::wifi("wlan0", 400));
+    let world = Rc::new(RefCell::new(world));
+    let io = SimIo(world.clone());
+    let core = GovernorCore::new(GovernorConfig::default(), WifiConfig::default(), PowerConfig::default());
+    let mut governor = Governor::with_io(core, io.clone(), io.clone(), io.clone(), io.clone(), io);
+    let (sleep_tx, sleep_rx) = tokio::sync::mpsc::unbounded_channel();
+    governor.sleep_events = Some(sleep_rx);
+
+    let (_events_tx, events_rx) = std::sync::mpsc::channel();
+    let local = LocalSet::new();
+    let cycle = world.clone();
+    local.spawn_local(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let _ = sleep_tx.send(SleepEvent::Suspending(None));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // Nothing is applied while asleep
+        cycle.borrow_mut().applied.clear();
+        let _ = sleep_tx.send(SleepEvent::Resumed);
+    });
+    let supervise = governor.supervise(Duration::from_secs(10), events_rx);
+    let _ = local.run_until(tokio::time::timeout(Duration::from_millis(300), supervise)).await;
+
+    assert!(governor.tasks.contains_key("wlan0"));
+    assert!(governor.suspended_at.is_none());
+    let world = world.borrow();
+    assert_eq!(world.rtt_measurements, 1);
+    // The new task starts from fresh state instead of trusting what was applied before sleep
+    assert_eq!(world.applied.len(), 1);
+    assert!(matches!(world.applied[0].1, Action::TuneBackend { .. }));
+}
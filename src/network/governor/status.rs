@@ -0,0 +1,90 @@
+//! Live Governor state for other processes
+//!
+//! After every tick each link task writes its state to
+//! `/run/hifi-wifi/governor/<interface>.json`, so `hifi-wifi watch` can show
+//! what the daemon is doing without talking to it.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::network::wan::Constraint;
+
+pub const STATUS_DIR: &str = "/run/hifi-wifi/governor";
+
+/// A status file older than this belongs to a stopped daemon or a dropped link
+const STALE_AFTER: Duration = Duration::from_secs(10);
+
+/// Governor state for one link
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkStatus {
+    pub interface: String,
+    /// Smoothed packets per second, as used for game mode detection
+    pub pps: u64,
+    pub game_mode: bool,
+    /// Last CAKE bandwidth successfully applied
+    pub cake_mbit: Option<u32>,
+    pub cake_constraint: Option<Constraint>,
+    pub power_save: Option<bool>,
+    pub coalescing: bool,
+    pub eee: Option<bool>,
+}
+
+/// Replace the status file for `status.interface`
+pub fn write(dir: &Path, status: &LinkStatus) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}.json", status.interface));
+    // Write-then-rename so readers never see a partial file
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec(status)?)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+/// Current status of `interface`, if the Governor published one recently
+pub fn read(dir: &Path, interface: &str) -> Option<LinkStatus> {
+    let path = dir.join(format!("{}.json", interface));
+    let age = fs::metadata(&path).ok()?
+        .modified().ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .unwrap_or_default();
+    if age > STALE_AFTER {
+        return None;
+    }
+    serde_json::from_slice(&fs::read(&path).ok()?).ok()
+}
+
+/// Remove the status file for `interface`
+pub fn clear(dir: &Path, interface: &str) {
+    let _ = fs::remove_file(dir.join(format!("{}.json", interface)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::wan::UplinkSource;
+
+    #[test]
+    fn test_status_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let status = LinkStatus {
+            interface: "wlan0".to_string(),
+            pps: 450,
+            game_mode: true,
+            cake_mbit: Some(38),
+            cake_constraint: Some(Constraint::Wan(UplinkSource::Profile)),
+            power_save: Some(false),
+            coalescing: false,
+            eee: None,
+        };
+
+        write(dir.path(), &status).unwrap();
+        assert_eq!(read(dir.path(), "wlan0"), Some(status));
+        assert_eq!(read(dir.path(), "eth0"), None);
+
+        clear(dir.path(), "wlan0");
+        assert_eq!(read(dir.path(), "wlan0"), None);
+    }
+}
@@ -0,0 +1,212 @@
+//! Governor input traces (record and replay)
+//!
+//! `monitor --record <file>` writes the CPU topology on the first line, then
+//! appends every link tick's `TickInputs` as one JSON object per line.
+//! `replay <file>` feeds them back through a fresh `GovernorCore` per
+//! interface, as the daemon runs one per link, so a user's trace reproduces
+//! the exact decisions offline and can be re-run against different
+//! `GovernorConfig` values.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use super::core::{Action, GovernorCore, TickInputs};
+use crate::system::irq::Topology;
+
+/// First line of a trace: what the decisions depend on besides the ticks
+#[derive(Debug, Serialize, Deserialize)]
+struct TraceHeader {
+    topology: Topology,
+}
+
+/// A recorded trace
+#[derive(Debug, Default)]
+pub struct Trace {
+    /// CPU layout of the recording machine (empty in traces without a header)
+    pub topology: Topology,
+    pub ticks: Vec<TickInputs>,
+}
+
+/// Appends tick inputs to a trace file
+pub struct TraceRecorder {
+    writer: BufWriter<File>,
+}
+
+impl TraceRecorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create trace file {}", path.display()))?;
+        Ok(Self { writer: BufWriter::new(file) })
+    }
+
+    /// Write the header; call before the first tick
+    pub fn record_topology(&mut self, topology: &Topology) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, &TraceHeader { topology: topology.clone() })?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Write one tick; flushed immediately so an interrupted daemon keeps its trace
+    pub fn record(&mut self, inputs: &TickInputs) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, inputs)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Read a trace written by `TraceRecorder`
+pub fn read_trace(path: &Path) -> Result<Trace> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open trace file {}", path.display()))?;
+
+    let mut trace = Trace::default();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if i == 0 {
+            if let Ok(header) = serde_json::from_str::<TraceHeader>(&line) {
+                trace.topology = header.topology;
+                continue;
+            }
+        }
+        let inputs: TickInputs = serde_json::from_str(&line)
+            .with_context(|| format!("{}:{}: invalid trace entry", path.display(), i + 1))?;
+        trace.ticks.push(inputs);
+    }
+    Ok(trace)
+}
+
+/// Run recorded ticks through the decision logic, one fresh copy of
+/// `template` per interface like the daemon's link tasks
+/// Every action is assumed to succeed, as it would on a healthy system.
+pub fn replay(template: &GovernorCore, ticks: &[TickInputs]) -> Vec<(Duration, Action)> {
+    let mut cores: HashMap<String, GovernorCore> = HashMap::new();
+    let mut decisions = Vec::new();
+    for inputs in ticks {
+        let interface = inputs.links.first().map(|l| l.interface.clone()).unwrap_or_default();
+        let core = cores.entry(interface).or_insert_with(|| template.fresh());
+        for action in core.tick(inputs) {
+            core.action_completed(&action, true);
+            decisions.push((inputs.at, action));
+        }
+    }
+    decisions
+}
+
+/// Serialize `Duration` as whole milliseconds to keep traces compact
+pub mod millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u64(d.as_millis() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+        Ok(Duration::from_millis(u64::deserialize(d)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::structs::{GovernorConfig, PowerConfig, WifiConfig};
+    use crate::network::governor::core::LinkSnapshot;
+    use crate::network::stats::NetStats;
+    use crate::network::wifi::InterfaceType;
+    use crate::system::irq::Cpu;
+
+    fn tick(secs: u64, nm_mbit: u32) -> TickInputs {
+        link_tick("wlan0", secs, nm_mbit)
+    }
+
+    fn link_tick(interface: &str, secs: u64, nm_mbit: u32) -> TickInputs {
+        TickInputs {
+            at: Duration::from_secs(secs),
+            cpu_load: 0.1,
+            cpu_loads: Default::default(),
+            power: Default::default(),
+            scan_suppress: false,
+            links: vec![LinkSnapshot {
+                interface: interface.to_string(),
+                device_path: "/dev/0".to_string(),
+                interface_type: InterfaceType::Wifi,
+                nm_bitrate_kbit: nm_mbit * 1000,
+                iw_bitrate_kbit: 0,
+                stats: Some(NetStats { rx_packets: secs * 10, ..Default::default() }),
+                active_ap: None,
+                access_points: None,
+                measured_uplink_mbit: None,
+                supported_bands: Vec::new(),
+                irq_cpus: Vec::new(),
+            }],
+        }
+    }
+
+    fn core() -> GovernorCore {
+        GovernorCore::new(GovernorConfig::default(), WifiConfig::default(), PowerConfig::default())
+    }
+
+    #[test]
+    fn test_trace_round_trip_replays_identically() {
+        let ticks: Vec<TickInputs> = (0..20).map(|i| tick(i * 2, if i < 10 { 400 } else { 100 })).collect();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.jsonl");
+        let topology = Topology {
+            cpus: (0..4).map(|id| Cpu { id, core: (0, id as u32), max_khz: 0, isolated: false }).collect(),
+        };
+        let mut recorder = TraceRecorder::create(&path).unwrap();
+        recorder.record_topology(&topology).unwrap();
+        for t in &ticks {
+            recorder.record(t).unwrap();
+        }
+
+        let loaded = read_trace(&path).unwrap();
+        assert_eq!(loaded.ticks.len(), ticks.len());
+        assert_eq!(loaded.topology.cpus, topology.cpus);
+        assert_eq!(replay(&core(), &loaded.ticks), replay(&core(), &ticks));
+    }
+
+    #[test]
+    fn test_replay_with_different_config() {
+        let ticks: Vec<TickInputs> = (0..10).map(|i| tick(i * 2, 400)).collect();
+        let cakes = |decisions: Vec<(Duration, Action)>| -> Vec<u32> {
+            decisions.into_iter().filter_map(|(_, a)| match a {
+                Action::ApplyCake { bandwidth_mbit, .. } => Some(bandwidth_mbit),
+                _ => None,
+            }).collect()
+        };
+
+        let tuned = GovernorConfig { cake_overhead_factor: 0.5, ..Default::default() };
+        let tuned = GovernorCore::new(tuned, WifiConfig::default(), PowerConfig::default());
+
+        assert_eq!(cakes(replay(&core(), &ticks)), vec![340]);
+        assert_eq!(cakes(replay(&tuned, &ticks)), vec![200]);
+    }
+
+    #[test]
+    fn test_replay_keeps_one_core_per_link() {
+        // Interleaved ticks of two links, as the daemon's link tasks record them
+        let ticks: Vec<TickInputs> = (0..10)
+            .flat_map(|i| [link_tick("wlan0", i * 2, 400), link_tick("wlan1", i * 2, 100)])
+            .collect();
+        let cakes: Vec<(String, u32)> = replay(&core(), &ticks).into_iter()
+            .filter_map(|(_, a)| match a {
+                Action::ApplyCake { interface, bandwidth_mbit } => Some((interface, bandwidth_mbit)),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(cakes, [("wlan0".to_string(), 340), ("wlan1".to_string(), 85)]);
+    }
+}
@@ -0,0 +1,460 @@
+//! iwd D-Bus Client
+//!
+//! Talks to iwd (`net.connman.iwd`) directly, so the Governor also runs where
+//! iwd manages WiFi on its own, without NetworkManager. Connection state comes
+//! from the Station interface, link details from StationDiagnostic, and a
+//! signal level agent reports when the signal crosses the roam threshold.
+//!
+//! Targeted scans and roams use StationDebug, which iwd only exports when
+//! started in developer mode (`iwd -E`); otherwise a full scan is requested.
+
+use anyhow::{Context, Result};
+use futures_lite::StreamExt;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue};
+use zbus::{interface, proxy, Connection, MatchRule, MessageStream};
+
+use crate::network::nm::{AccessPoint, LinkEvent, WifiBand};
+
+const SERVICE: &str = "net.connman.iwd";
+const STATION: &str = "net.connman.iwd.Station";
+const DEVICE: &str = "net.connman.iwd.Device";
+const NETWORK: &str = "net.connman.iwd.Network";
+const KNOWN_NETWORK: &str = "net.connman.iwd.KnownNetwork";
+
+/// Where our signal level agent lives on the bus
+const AGENT_PATH: &str = "/org/hifi_wifi/SignalLevelAgent";
+
+/// Object path -> interface name -> properties
+type ManagedObjects = HashMap<OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>>;
+
+#[proxy(
+    interface = "org.freedesktop.DBus.ObjectManager",
+    default_service = "net.connman.iwd",
+    default_path = "/"
+)]
+trait ObjectManager {
+    fn get_managed_objects(&self) -> zbus::Result<ManagedObjects>;
+}
+
+#[proxy(interface = "net.connman.iwd.Device", default_service = "net.connman.iwd")]
+trait IwdDevice {
+    #[zbus(property)]
+    fn name(&self) -> zbus::Result<String>;
+}
+
+#[proxy(interface = "net.connman.iwd.Station", default_service = "net.connman.iwd")]
+trait IwdStation {
+    fn scan(&self) -> zbus::Result<()>;
+
+    /// Notify `path` when the signal moves between the bands `levels` (dBm, descending) divide
+    fn register_signal_level_agent(&self, path: &ObjectPath<'_>, levels: &[i16]) -> zbus::Result<()>;
+}
+
+#[proxy(interface = "net.connman.iwd.StationDiagnostic", default_service = "net.connman.iwd")]
+trait IwdStationDiagnostic {
+    fn get_diagnostics(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+}
+
+/// Only exported in developer mode
+#[proxy(interface = "net.connman.iwd.StationDebug", default_service = "net.connman.iwd")]
+trait IwdStationDebug {
+    fn roam(&self, address: &[u8]) -> zbus::Result<()>;
+
+    fn scan(&self, frequencies: &[u16]) -> zbus::Result<()>;
+
+    /// Every BSS of every visible network
+    fn get_networks(&self) -> zbus::Result<HashMap<OwnedObjectPath, Vec<HashMap<String, OwnedValue>>>>;
+}
+
+/// iwd station state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StationState {
+    Connected,
+    Connecting,
+    Roaming,
+    Disconnecting,
+    Disconnected,
+    Unknown,
+}
+
+impl From<&str> for StationState {
+    fn from(state: &str) -> Self {
+        match state {
+            "connected" => StationState::Connected,
+            "connecting" => StationState::Connecting,
+            "roaming" => StationState::Roaming,
+            "disconnecting" => StationState::Disconnecting,
+            "disconnected" => StationState::Disconnected,
+            _ => StationState::Unknown,
+        }
+    }
+}
+
+/// A WiFi device in station mode
+#[derive(Debug, Clone)]
+pub struct Station {
+    pub path: String,
+    pub interface: String,
+    pub state: StationState,
+    /// SSID of the connected network
+    pub ssid: Option<String>,
+}
+
+/// Link details of a connected station (StationDiagnostic)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diagnostics {
+    pub bssid: String,
+    pub frequency: u32,
+    pub rssi_dbm: i32,
+    /// Kbit/s, 0 if unknown
+    pub tx_bitrate_kbit: u32,
+    pub rx_bitrate_kbit: u32,
+}
+
+impl Diagnostics {
+    fn parse(values: &HashMap<String, OwnedValue>) -> Self {
+        // Bitrates are reported in 100 Kbit/s units
+        Self {
+            bssid: prop::<String>(values, "ConnectedBss").unwrap_or_default(),
+            frequency: prop::<u32>(values, "Frequency").unwrap_or(0),
+            rssi_dbm: prop::<i16>(values, "RSSI").map(i32::from).unwrap_or(-100),
+            tx_bitrate_kbit: prop::<u32>(values, "TxBitrate").unwrap_or(0) * 100,
+            rx_bitrate_kbit: prop::<u32>(values, "RxBitrate").unwrap_or(0) * 100,
+        }
+    }
+
+    /// The connected BSS as an access point
+    pub fn access_point(&self, path: &str, ssid: &str) -> AccessPoint {
+        AccessPoint {
+            path: path.to_string(),
+            ssid: ssid.to_string(),
+            bssid: self.bssid.clone(),
+            frequency: self.frequency,
+            band: WifiBand::from_frequency(self.frequency),
+            signal_strength: self.rssi_dbm,
+            max_bitrate: 0,
+        }
+    }
+}
+
+/// A network iwd has credentials for
+#[derive(Debug, Clone)]
+pub struct KnownNetwork {
+    pub name: String,
+    pub auto_connect: bool,
+}
+
+/// iwd D-Bus Client
+#[derive(Clone)]
+pub struct IwdClient {
+    connection: Connection,
+}
+
+impl IwdClient {
+    /// Create a new iwd client
+    pub async fn new() -> Result<Self> {
+        let connection = Connection::system()
+            .await
+            .context("Failed to connect to system D-Bus")?;
+
+        // Verify iwd is available
+        let stations = ObjectManagerProxy::new(&connection).await?
+            .get_managed_objects().await
+            .context("iwd is not running")?
+            .values()
+            .filter(|interfaces| interfaces.contains_key(STATION))
+            .count();
+        info!("Connected to iwd ({} station(s))", stations);
+
+        Ok(Self { connection })
+    }
+
+    async fn objects(&self) -> Result<ManagedObjects> {
+        Ok(ObjectManagerProxy::new(&self.connection).await?.get_managed_objects().await?)
+    }
+
+    /// All WiFi devices in station mode
+    pub async fn stations(&self) -> Result<Vec<Station>> {
+        let objects = self.objects().await?;
+        Ok(objects.iter()
+            .filter_map(|(path, interfaces)| station_from(&objects, path, interfaces))
+            .collect())
+    }
+
+    /// A single station by object path, None if it is gone
+    pub async fn station(&self, path: &str) -> Result<Option<Station>> {
+        let objects = self.objects().await?;
+        Ok(objects.iter()
+            .find(|(p, _)| p.as_str() == path)
+            .and_then(|(path, interfaces)| station_from(&objects, path, interfaces)))
+    }
+
+    /// Link details of a connected station
+    pub async fn diagnostics(&self, path: &str) -> Result<Diagnostics> {
+        let diagnostic = IwdStationDiagnosticProxy::builder(&self.connection)
+            .path(path)?
+            .build()
+            .await?;
+        Ok(Diagnostics::parse(&diagnostic.get_diagnostics().await?))
+    }
+
+    /// Every visible BSS of every network (needs developer mode, else empty)
+    pub async fn access_points(&self, path: &str) -> Result<Vec<AccessPoint>> {
+        let debug = IwdStationDebugProxy::builder(&self.connection)
+            .path(path)?
+            .build()
+            .await?;
+        let networks = match debug.get_networks().await {
+            Ok(networks) => networks,
+            Err(e) => {
+                debug!("Per-BSS scan results unavailable (iwd not in developer mode?): {}", e);
+                return Ok(Vec::new());
+            }
+        };
+
+        let objects = self.objects().await?;
+        let mut access_points = Vec::new();
+        for (network, bsses) in networks {
+            let ssid = objects.get(&network)
+                .and_then(|interfaces| interfaces.get(NETWORK))
+                .and_then(|props| prop::<String>(props, "Name"))
+                .unwrap_or_default();
+            for bss in bsses {
+                let diagnostics = Diagnostics {
+                    bssid: prop::<String>(&bss, "Address").unwrap_or_default(),
+                    frequency: prop::<u32>(&bss, "Frequency").unwrap_or(0),
+                    rssi_dbm: prop::<i16>(&bss, "RSSI").map(i32::from).unwrap_or(-100),
+                    ..Diagnostics::default()
+                };
+                access_points.push(diagnostics.access_point(network.as_str(), &ssid));
+            }
+        }
+        Ok(access_points)
+    }
+
+    /// Request a full scan
+    pub async fn request_scan(&self, path: &str) -> Result<()> {
+        let station = IwdStationProxy::builder(&self.connection)
+            .path(path)?
+            .build()
+            .await?;
+        station.scan().await?;
+        debug!("Scan requested for station: {}", path);
+        Ok(())
+    }
+
+    /// Roam to `bssid`, or scan its channel so iwd can pick it itself
+    /// Both need developer mode; a full scan is the last resort.
+    pub async fn roam(&self, path: &str, bssid: &str, frequency: u32) -> Result<()> {
+        let debug = IwdStationDebugProxy::builder(&self.connection)
+            .path(path)?
+            .build()
+            .await?;
+
+        if let Some(address) = parse_mac(bssid) {
+            match debug.roam(&address).await {
+                Ok(()) => {
+                    info!("Asked iwd to roam {} to {}", path, bssid);
+                    return Ok(());
+                }
+                Err(e) => debug!("iwd roam to {} failed: {}", bssid, e),
+            }
+        }
+        if let Ok(frequency) = u16::try_from(frequency) {
+            if frequency > 0 && debug.scan(&[frequency]).await.is_ok() {
+                debug!("Scan of {} MHz requested for station: {}", frequency, path);
+                return Ok(());
+            }
+        }
+        self.request_scan(path).await
+    }
+
+    /// Networks iwd has credentials for
+    pub async fn known_networks(&self) -> Result<Vec<KnownNetwork>> {
+        let mut known: Vec<KnownNetwork> = self.objects().await?
+            .values()
+            .filter_map(|interfaces| interfaces.get(KNOWN_NETWORK))
+            .map(|props| KnownNetwork {
+                name: prop::<String>(props, "Name").unwrap_or_default(),
+                auto_connect: prop::<bool>(props, "AutoConnect").unwrap_or(true),
+            })
+            .collect();
+        known.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(known)
+    }
+
+    /// Report connects, roams, disconnects and signal level changes on the returned channel
+    /// The signal is weak below `roam_threshold_dbm`.
+    pub async fn watch(&self, roam_threshold_dbm: i16) -> Result<mpsc::UnboundedReceiver<LinkEvent>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let agent = SignalLevelAgent { connection: self.connection.clone(), events: tx.clone() };
+        self.connection.object_server().at(AGENT_PATH, agent).await?;
+        let levels = [roam_threshold_dbm];
+        for station in self.stations().await? {
+            self.register_agent(&station.path, &levels).await;
+        }
+
+        let rule = MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .sender(SERVICE)?
+            .interface("org.freedesktop.DBus.Properties")?
+            .member("PropertiesChanged")?
+            .add_arg(STATION)?
+            .build();
+        let mut stream = MessageStream::for_match_rule(rule, &self.connection, None).await?;
+        let client = self.clone();
+
+        tokio::spawn(async move {
+            // Last state of each station, to tell a roam from a fresh connection
+            let mut states: HashMap<String, StationState> = HashMap::new();
+            while let Some(Ok(message)) = stream.next().await {
+                let Some(path) = message.header().path().map(|p| p.to_string()) else {
+                    continue;
+                };
+                let Ok((_, changed, _)) = message.body()
+                    .deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>() else {
+                    continue;
+                };
+                let Some(state) = prop::<String>(&changed, "State").map(|s| StationState::from(s.as_str())) else {
+                    continue;
+                };
+
+                let previous = states.insert(path.clone(), state);
+                let event = match state {
+                    StationState::Connected if previous != Some(StationState::Connected) => {
+                        // New connections may come from a device that appeared after we started
+                        client.register_agent(&path, &levels).await;
+                        let interface = client.device_name(&path).await;
+                        LinkEvent::Connected { interface, roamed: previous == Some(StationState::Roaming) }
+                    }
+                    StationState::Disconnecting | StationState::Disconnected
+                        if matches!(previous, Some(StationState::Connected | StationState::Roaming)) =>
+                    {
+                        LinkEvent::Disconnected { interface: client.device_name(&path).await }
+                    }
+                    _ => continue,
+                };
+                if tx.send(event).is_err() {
+                    break;
+                }
+            }
+            warn!("iwd event stream ended");
+        });
+
+        Ok(rx)
+    }
+
+    async fn register_agent(&self, path: &str, levels: &[i16]) {
+        let register = async {
+            let station = IwdStationProxy::builder(&self.connection).path(path)?.build().await?;
+            station.register_signal_level_agent(&ObjectPath::from_static_str_unchecked(AGENT_PATH), levels).await
+        };
+        // iwd refuses a second registration for the same station
+        if let Err(e) = register.await {
+            debug!("Signal level agent not registered on {}: {}", path, e);
+        }
+    }
+
+    async fn device_name(&self, path: &str) -> String {
+        device_name(&self.connection, path).await
+    }
+}
+
+/// Receives iwd's signal level callbacks
+struct SignalLevelAgent {
+    connection: Connection,
+    events: mpsc::UnboundedSender<LinkEvent>,
+}
+
+#[interface(name = "net.connman.iwd.SignalLevelAgent")]
+impl SignalLevelAgent {
+    fn release(&self, device: OwnedObjectPath) {
+        debug!("iwd released the signal level agent for {}", device.as_str());
+    }
+
+    /// `level` is the index of the band the signal is in, 0 being the strongest
+    async fn changed(&self, device: OwnedObjectPath, level: u8) {
+        let interface = device_name(&self.connection, device.as_str()).await;
+        let _ = self.events.send(LinkEvent::SignalLevel { interface, weak: level > 0 });
+    }
+}
+
+/// Interface name of an iwd device, falling back to its object path
+async fn device_name(connection: &Connection, path: &str) -> String {
+    let name = async {
+        IwdDeviceProxy::builder(connection).path(path)?.build().await?.name().await
+    };
+    name.await.unwrap_or_else(|_: zbus::Error| path.to_string())
+}
+
+fn station_from(
+    objects: &ManagedObjects,
+    path: &OwnedObjectPath,
+    interfaces: &HashMap<String, HashMap<String, OwnedValue>>,
+) -> Option<Station> {
+    let station = interfaces.get(STATION)?;
+    let interface = interfaces.get(DEVICE).and_then(|device| prop::<String>(device, "Name"))?;
+    let state = prop::<String>(station, "State")
+        .map(|s| StationState::from(s.as_str()))
+        .unwrap_or(StationState::Unknown);
+    let ssid = prop::<OwnedObjectPath>(station, "ConnectedNetwork")
+        .and_then(|network| objects.get(&network))
+        .and_then(|interfaces| interfaces.get(NETWORK))
+        .and_then(|network| prop::<String>(network, "Name"));
+
+    Some(Station { path: path.to_string(), interface, state, ssid })
+}
+
+fn prop<T: TryFrom<OwnedValue>>(props: &HashMap<String, OwnedValue>, key: &str) -> Option<T> {
+    T::try_from(props.get(key)?.try_clone().ok()?).ok()
+}
+
+/// "aa:bb:cc:dd:ee:ff" -> bytes
+fn parse_mac(mac: &str) -> Option<Vec<u8>> {
+    let bytes: Vec<u8> = mac.split(':')
+        .map(|b| u8::from_str_radix(b, 16))
+        .collect::<Result<_, _>>()
+        .ok()?;
+    (bytes.len() == 6).then_some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zbus::zvariant::Value;
+
+    fn value(v: Value<'_>) -> OwnedValue {
+        v.try_to_owned().unwrap()
+    }
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let values = HashMap::from([
+            ("ConnectedBss".to_string(), value(Value::from("aa:bb:cc:dd:ee:ff"))),
+            ("Frequency".to_string(), value(Value::from(5180u32))),
+            ("RSSI".to_string(), value(Value::from(-58i16))),
+            ("TxBitrate".to_string(), value(Value::from(8660u32))),
+        ]);
+        let diagnostics = Diagnostics::parse(&values);
+        assert_eq!(diagnostics.tx_bitrate_kbit, 866_000);
+        assert_eq!(diagnostics.rx_bitrate_kbit, 0);
+
+        let ap = diagnostics.access_point("/net/connman/iwd/0/4/6c6f", "home");
+        assert_eq!(ap.band, WifiBand::Band5GHz);
+        assert_eq!(ap.signal_strength, -58);
+        assert_eq!(ap.bssid, "aa:bb:cc:dd:ee:ff");
+    }
+
+    #[test]
+    fn test_parse_mac_and_state() {
+        assert_eq!(parse_mac("aa:bb:cc:dd:ee:0f"), Some(vec![0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x0f]));
+        assert_eq!(parse_mac("aa:bb:cc"), None);
+        assert_eq!(parse_mac("zz:bb:cc:dd:ee:ff"), None);
+        assert_eq!(StationState::from("roaming"), StationState::Roaming);
+        assert_eq!(StationState::from("bogus"), StationState::Unknown);
+    }
+}
@@ -0,0 +1,212 @@
+//! Link state straight from the kernel
+//!
+//! Used when neither NetworkManager nor iwd is on the bus (systemd-networkd
+//! with wpa_supplicant, ConnMan, embedded images). Interface and carrier state
+//! come from /sys/class/net, the current BSS, signal and bitrate from `iw`
+//! (nl80211), and visible APs from the kernel's cached scan results.
+
+use anyhow::{bail, Result};
+use log::debug;
+use std::fs;
+use tokio::process::Command;
+
+use crate::network::nm::{AccessPoint, WifiBand};
+use crate::network::wifi::InterfaceType;
+
+/// The BSS a WiFi interface is associated with, from `iw dev <ifc> link`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Association {
+    pub bssid: String,
+    pub ssid: String,
+    pub frequency: u32,
+    pub signal_dbm: i32,
+    /// Kbit/s, 0 if unknown
+    pub tx_bitrate_kbit: u32,
+}
+
+impl Association {
+    pub fn access_point(&self, interface: &str) -> AccessPoint {
+        AccessPoint {
+            path: sysfs_path(interface),
+            ssid: self.ssid.clone(),
+            bssid: self.bssid.clone(),
+            frequency: self.frequency,
+            band: WifiBand::from_frequency(self.frequency),
+            signal_strength: self.signal_dbm,
+            max_bitrate: 0,
+        }
+    }
+}
+
+/// Interfaces hifi-wifi manages whose operstate is up
+pub fn up_interfaces() -> Vec<(String, InterfaceType)> {
+    let Ok(entries) = fs::read_dir("/sys/class/net") else {
+        return Vec::new();
+    };
+    let mut interfaces: Vec<(String, InterfaceType)> = entries.flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let interface_type = InterfaceType::from_name(&name)?;
+            is_up(&name).then_some((name, interface_type))
+        })
+        .collect();
+    interfaces.sort_by(|a, b| a.0.cmp(&b.0));
+    interfaces
+}
+
+/// operstate is "up" (carrier, and for WiFi an authorized association)
+pub fn is_up(interface: &str) -> bool {
+    fs::read_to_string(format!("/sys/class/net/{}/operstate", interface))
+        .map(|s| s.trim() == "up")
+        .unwrap_or(false)
+}
+
+/// Stands in for a backend object path
+pub fn sysfs_path(interface: &str) -> String {
+    format!("/sys/class/net/{}", interface)
+}
+
+/// The current association, None when not connected
+pub async fn association(interface: &str) -> Option<Association> {
+    let output = Command::new("iw").args(["dev", interface, "link"]).output().await.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_link(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// APs from the kernel's last scan (no new scan is started)
+pub async fn scan_results(interface: &str) -> Result<Vec<AccessPoint>> {
+    let output = Command::new("iw").args(["dev", interface, "scan", "dump"]).output().await?;
+    if !output.status.success() {
+        bail!("iw scan dump failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(parse_scan(interface, &String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Start a scan, limited to `frequency` if known, so the supplicant can roam
+pub async fn request_scan(interface: &str, frequency: u32) -> Result<()> {
+    let frequency = frequency.to_string();
+    let mut args = vec!["dev", interface, "scan", "trigger"];
+    if frequency != "0" {
+        args.extend(["freq", frequency.as_str()]);
+    }
+    let output = Command::new("iw").args(&args).output().await?;
+    if !output.status.success() {
+        bail!("iw scan trigger failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    debug!("Scan requested on {} ({} MHz)", interface, frequency);
+    Ok(())
+}
+
+/// Parse `iw dev <ifc> link`
+fn parse_link(output: &str) -> Option<Association> {
+    let first = output.lines().next()?;
+    let bssid = first.strip_prefix("Connected to ")?.split_whitespace().next()?.to_string();
+
+    let mut association = Association {
+        bssid,
+        ssid: String::new(),
+        frequency: 0,
+        signal_dbm: -100,
+        tx_bitrate_kbit: 0,
+    };
+    for line in output.lines().skip(1) {
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key {
+            "SSID" => association.ssid = value.to_string(),
+            "freq" => association.frequency = parse_number(value).unwrap_or(0.0) as u32,
+            "signal" => association.signal_dbm = parse_number(value).unwrap_or(-100.0) as i32,
+            "tx bitrate" => association.tx_bitrate_kbit = (parse_number(value).unwrap_or(0.0) * 1000.0) as u32,
+            _ => {}
+        }
+    }
+    Some(association)
+}
+
+/// Parse `iw dev <ifc> scan dump`
+fn parse_scan(interface: &str, output: &str) -> Vec<AccessPoint> {
+    let mut access_points: Vec<AccessPoint> = Vec::new();
+    for line in output.lines() {
+        if let Some(rest) = line.strip_prefix("BSS ") {
+            // "BSS aa:bb:cc:dd:ee:ff(on wlan0) -- associated"
+            let bssid: String = rest.chars().take_while(|c| c.is_ascii_hexdigit() || *c == ':').collect();
+            access_points.push(AccessPoint {
+                path: sysfs_path(interface),
+                ssid: String::new(),
+                bssid,
+                frequency: 0,
+                band: WifiBand::Unknown,
+                signal_strength: -100,
+                max_bitrate: 0,
+            });
+            continue;
+        }
+        let (Some(ap), Some((key, value))) = (access_points.last_mut(), line.trim().split_once(':')) else {
+            continue;
+        };
+        let value = value.trim();
+        match key {
+            "SSID" => ap.ssid = value.to_string(),
+            "freq" => {
+                ap.frequency = parse_number(value).unwrap_or(0.0) as u32;
+                ap.band = WifiBand::from_frequency(ap.frequency);
+            }
+            "signal" => ap.signal_strength = parse_number(value).unwrap_or(-100.0) as i32,
+            _ => {}
+        }
+    }
+    access_points
+}
+
+/// Leading number of "866.7 MBit/s VHT-MCS 9" or "-58.00 dBm"
+fn parse_number(value: &str) -> Option<f64> {
+    value.split_whitespace().next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_link() {
+        let output = "Connected to aa:bb:cc:dd:ee:ff (on wlan0)\n\
+            \tSSID: home\n\
+            \tfreq: 5180.0\n\
+            \tRX: 1234 bytes (10 packets)\n\
+            \tsignal: -58 dBm\n\
+            \trx bitrate: 780.0 MBit/s VHT-MCS 8 80MHz VHT-NSS 2\n\
+            \ttx bitrate: 866.7 MBit/s VHT-MCS 9 80MHz short GI VHT-NSS 2\n";
+        let association = parse_link(output).unwrap();
+        assert_eq!(association.bssid, "aa:bb:cc:dd:ee:ff");
+        assert_eq!(association.ssid, "home");
+        assert_eq!(association.frequency, 5180);
+        assert_eq!(association.signal_dbm, -58);
+        assert_eq!(association.tx_bitrate_kbit, 866_700);
+
+        assert_eq!(parse_link("Not connected.\n"), None);
+    }
+
+    #[test]
+    fn test_parse_scan() {
+        let output = "BSS aa:bb:cc:dd:ee:ff(on wlan0) -- associated\n\
+            \tlast seen: 120 ms ago\n\
+            \tfreq: 5180\n\
+            \tsignal: -58.00 dBm\n\
+            \tSSID: home\n\
+            BSS 11:22:33:44:55:66(on wlan0)\n\
+            \tfreq: 2437\n\
+            \tsignal: -71.00 dBm\n\
+            \tSSID: home\n";
+        let aps = parse_scan("wlan0", output);
+        assert_eq!(aps.len(), 2);
+        assert_eq!(aps[0].bssid, "aa:bb:cc:dd:ee:ff");
+        assert_eq!(aps[0].band, WifiBand::Band5GHz);
+        assert_eq!(aps[1].signal_strength, -71);
+        assert_eq!(aps[1].band, WifiBand::Band2_4GHz);
+        assert_eq!(aps[1].ssid, "home");
+    }
+}
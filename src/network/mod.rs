@@ -1,6 +1,11 @@
 pub mod wifi;
+pub mod device;
+pub mod iwd;
+pub mod kernel;
+pub mod wpa;
 pub mod backend_tuner;
 pub mod nm;
 pub mod tc;
 pub mod stats;
 pub mod governor;
+pub mod wan;
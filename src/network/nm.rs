@@ -4,12 +4,16 @@
 //! Per rewrite.md: No text parsing - use structured DBus APIs.
 
 use anyhow::{Context, Result};
-use log::{info, debug};
+use futures_lite::StreamExt;
+use log::{info, debug, warn};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use zbus::{Connection, proxy};
+use tokio::sync::mpsc;
+use zbus::zvariant::OwnedValue;
+use zbus::{Connection, MatchRule, MessageStream, proxy};
 
 /// WiFi frequency band
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum WifiBand {
     Band2_4GHz,
     Band5GHz,
@@ -30,7 +34,7 @@ impl WifiBand {
 }
 
 /// Access Point information from NetworkManager
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AccessPoint {
     #[allow(dead_code)]
     pub path: String,
@@ -114,6 +118,19 @@ impl From<u32> for DeviceState {
     }
 }
 
+/// Something a link backend (NetworkManager or iwd) reported between ticks
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkEvent {
+    /// Connected, either to a new network or to another AP after roaming
+    Connected { interface: String, roamed: bool },
+    /// The link went down
+    Disconnected { interface: String },
+    /// Bitrate or visible APs changed; worth a tick before the next one is due
+    Changed { interface: String },
+    /// The signal crossed the roam threshold
+    SignalLevel { interface: String, weak: bool },
+}
+
 /// Wireless device info from NetworkManager
 #[derive(Debug, Clone)]
 pub struct WirelessDevice {
@@ -124,6 +141,15 @@ pub struct WirelessDevice {
     pub active_ap: Option<AccessPoint>,
 }
 
+/// Wired (Ethernet) device info from NetworkManager
+#[derive(Debug, Clone)]
+pub struct WiredDevice {
+    pub path: String,
+    pub interface: String,
+    pub state: DeviceState,
+    pub speed_mbit: u32,      // Negotiated speed in Mb/s (0 if unknown)
+}
+
 // NetworkManager D-Bus proxy for the main interface
 #[proxy(
     interface = "org.freedesktop.NetworkManager",
@@ -154,6 +180,17 @@ trait NmDevice {
     fn state(&self) -> zbus::Result<u32>;
 }
 
+// Wired device proxy
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.Device.Wired",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+trait NmWired {
+    /// Negotiated link speed in Mb/s (0 if unknown)
+    #[zbus(property)]
+    fn speed(&self) -> zbus::Result<u32>;
+}
+
 // Wireless device proxy
 #[proxy(
     interface = "org.freedesktop.NetworkManager.Device.Wireless",
@@ -195,6 +232,7 @@ trait NmAccessPoint {
 }
 
 /// NetworkManager D-Bus Client
+#[derive(Clone)]
 pub struct NmClient {
     connection: Connection,
 }
@@ -222,54 +260,114 @@ impl NmClient {
         let mut wireless_devices = Vec::new();
         
         for path in device_paths {
-            let device = NmDeviceProxy::builder(&self.connection)
-                .path(path.as_ref())?
-                .build()
-                .await?;
-            
-            // Check if it's a WiFi device (type 2)
-            let device_type = device.device_type().await.unwrap_or(0);
-            if device_type != 2 {
-                continue;
-            }
-            
-            let interface = device.interface().await.unwrap_or_default();
-            let state = DeviceState::from(device.state().await.unwrap_or(0));
-            
-            // Skip virtual interfaces per rewrite.md
-            if Self::is_virtual_interface(&interface) {
-                debug!("Skipping virtual interface: {}", interface);
-                continue;
+            if let Some(device) = self.get_wireless_device(path.as_str()).await? {
+                wireless_devices.push(device);
             }
-            
-            // Get wireless-specific properties
-            let wireless = NmWirelessProxy::builder(&self.connection)
-                .path(path.as_ref())?
-                .build()
-                .await?;
-            
-            let bitrate = wireless.bitrate().await.unwrap_or(0);
-            
-            // Get active AP info
-            let active_ap = match wireless.active_access_point().await {
-                Ok(ap_path) if !ap_path.as_str().is_empty() && ap_path.as_str() != "/" => {
-                    self.get_access_point_info(ap_path.as_str()).await.ok()
-                }
-                _ => None,
-            };
-            
-            wireless_devices.push(WirelessDevice {
-                path: path.to_string(),
-                interface,
-                state,
-                bitrate,
-                active_ap,
-            });
         }
         
         Ok(wireless_devices)
     }
 
+    /// Get a single wireless device by object path
+    /// Returns None if the device is not WiFi or is a virtual interface
+    pub async fn get_wireless_device(&self, path: &str) -> Result<Option<WirelessDevice>> {
+        let device = NmDeviceProxy::builder(&self.connection)
+            .path(path)?
+            .build()
+            .await?;
+        
+        // Check if it's a WiFi device (type 2)
+        let device_type = device.device_type().await.unwrap_or(0);
+        if device_type != 2 {
+            return Ok(None);
+        }
+        
+        let interface = device.interface().await.unwrap_or_default();
+        let state = DeviceState::from(device.state().await.unwrap_or(0));
+        
+        // Skip virtual interfaces per rewrite.md
+        if Self::is_virtual_interface(&interface) {
+            debug!("Skipping virtual interface: {}", interface);
+            return Ok(None);
+        }
+        
+        // Get wireless-specific properties
+        let wireless = NmWirelessProxy::builder(&self.connection)
+            .path(path)?
+            .build()
+            .await?;
+        
+        let bitrate = wireless.bitrate().await.unwrap_or(0);
+        
+        // Get active AP info
+        let active_ap = match wireless.active_access_point().await {
+            Ok(ap_path) if !ap_path.as_str().is_empty() && ap_path.as_str() != "/" => {
+                self.get_access_point_info(ap_path.as_str()).await.ok()
+            }
+            _ => None,
+        };
+        
+        Ok(Some(WirelessDevice {
+            path: path.to_string(),
+            interface,
+            state,
+            bitrate,
+            active_ap,
+        }))
+    }
+
+    /// Get all wired (Ethernet) devices, including ones NM leaves unmanaged
+    pub async fn get_wired_devices(&self) -> Result<Vec<WiredDevice>> {
+        let nm = NetworkManagerProxy::new(&self.connection).await?;
+        let device_paths = nm.devices().await?;
+
+        let mut wired_devices = Vec::new();
+
+        for path in device_paths {
+            if let Some(device) = self.get_wired_device(path.as_str()).await? {
+                wired_devices.push(device);
+            }
+        }
+
+        Ok(wired_devices)
+    }
+
+    /// Get a single wired device by object path
+    /// Returns None if the device is not Ethernet or is a virtual interface
+    pub async fn get_wired_device(&self, path: &str) -> Result<Option<WiredDevice>> {
+        let device = NmDeviceProxy::builder(&self.connection)
+            .path(path)?
+            .build()
+            .await?;
+
+        // Check if it's an Ethernet device (type 1)
+        let device_type = device.device_type().await.unwrap_or(0);
+        if device_type != 1 {
+            return Ok(None);
+        }
+
+        let interface = device.interface().await.unwrap_or_default();
+        if Self::is_virtual_interface(&interface) {
+            debug!("Skipping virtual interface: {}", interface);
+            return Ok(None);
+        }
+
+        let state = DeviceState::from(device.state().await.unwrap_or(0));
+
+        let wired = NmWiredProxy::builder(&self.connection)
+            .path(path)?
+            .build()
+            .await?;
+        let speed_mbit = wired.speed().await.unwrap_or(0);
+
+        Ok(Some(WiredDevice {
+            path: path.to_string(),
+            interface,
+            state,
+            speed_mbit,
+        }))
+    }
+
     /// Get access point information
     async fn get_access_point_info(&self, path: &str) -> Result<AccessPoint> {
         let ap = NmAccessPointProxy::builder(&self.connection)
@@ -331,6 +429,106 @@ impl NmClient {
         Ok(())
     }
 
+    /// Report device state, roam, bitrate and AP list changes on the returned channel
+    pub async fn watch(&self) -> Result<mpsc::UnboundedReceiver<LinkEvent>> {
+        let rule = MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .sender("org.freedesktop.NetworkManager")?
+            .path_namespace("/org/freedesktop/NetworkManager/Devices")?
+            .build();
+        let mut stream = MessageStream::for_match_rule(rule, &self.connection, None).await?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        let client = self.clone();
+
+        tokio::spawn(async move {
+            // Interface of each managed WiFi/Ethernet device (None = not ours)
+            let mut interfaces: HashMap<String, Option<String>> = HashMap::new();
+            while let Some(Ok(message)) = stream.next().await {
+                let header = message.header();
+                let (Some(path), Some(member)) = (header.path(), header.member()) else {
+                    continue;
+                };
+                let path = path.to_string();
+                let interface = match interfaces.get(&path) {
+                    Some(interface) => interface.clone(),
+                    None => match client.managed_interface(&path).await {
+                        Ok(interface) => {
+                            interfaces.insert(path.clone(), interface.clone());
+                            interface
+                        }
+                        Err(e) => {
+                            // Not cached: asked again on the device's next signal
+                            debug!("Failed to identify NetworkManager device {}: {}", path, e);
+                            continue;
+                        }
+                    },
+                };
+                let Some(interface) = interface else {
+                    continue;
+                };
+
+                let event = match (header.interface().map(|i| i.as_str()), member.as_str()) {
+                    (Some("org.freedesktop.NetworkManager.Device"), "StateChanged") => {
+                        let Ok((new, old, _)) = message.body().deserialize::<(u32, u32, u32)>() else {
+                            continue;
+                        };
+                        match (DeviceState::from(new), DeviceState::from(old)) {
+                            (DeviceState::Activated, _) => LinkEvent::Connected { interface, roamed: false },
+                            (_, DeviceState::Activated) => LinkEvent::Disconnected { interface },
+                            _ => continue,
+                        }
+                    }
+                    (Some("org.freedesktop.NetworkManager.Device.Wireless"), "AccessPointAdded" | "AccessPointRemoved") => {
+                        LinkEvent::Changed { interface }
+                    }
+                    (Some("org.freedesktop.DBus.Properties"), "PropertiesChanged") => {
+                        let Ok((_, changed, _)) = message.body()
+                            .deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>() else {
+                            continue;
+                        };
+                        if changed.contains_key("ActiveAccessPoint") {
+                            // Also changes while a connection is set up; only a roam
+                            // happens on an activated device
+                            if !client.is_activated(&path).await {
+                                continue;
+                            }
+                            LinkEvent::Connected { interface, roamed: true }
+                        } else if changed.contains_key("Bitrate") || changed.contains_key("Speed") {
+                            LinkEvent::Changed { interface }
+                        } else {
+                            continue;
+                        }
+                    }
+                    _ => continue,
+                };
+                if tx.send(event).is_err() {
+                    break;
+                }
+            }
+            warn!("NetworkManager event stream ended");
+        });
+
+        Ok(rx)
+    }
+
+    /// Interface name of a device the Governor manages (WiFi or Ethernet, not virtual)
+    /// `Ok(None)` means the device is not one of ours; an error says nothing either way.
+    async fn managed_interface(&self, path: &str) -> zbus::Result<Option<String>> {
+        let device = NmDeviceProxy::builder(&self.connection).path(path)?.build().await?;
+        if !matches!(device.device_type().await?, 1 | 2) {
+            return Ok(None);
+        }
+        let interface = device.interface().await?;
+        Ok((!Self::is_virtual_interface(&interface)).then_some(interface))
+    }
+
+    async fn is_activated(&self, path: &str) -> bool {
+        let state = async {
+            NmDeviceProxy::builder(&self.connection).path(path)?.build().await?.state().await
+        };
+        state.await.map(DeviceState::from) == Ok(DeviceState::Activated)
+    }
+
     /// Check if interface is virtual (per rewrite.md: ignore docker, veth, virbr, tun, tap)
     fn is_virtual_interface(name: &str) -> bool {
         name.starts_with("docker") ||
@@ -5,14 +5,17 @@
 //! Uses EMA smoothing to prevent game mode flapping from brief PPS spikes.
 
 use log::debug;
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::time::Instant;
+use std::time::Duration;
 
 /// Network statistics from sysfs
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct NetStats {
     pub rx_packets: u64,
     pub tx_packets: u64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
 }
 
 impl NetStats {
@@ -23,6 +26,8 @@ impl NetStats {
         Some(NetStats {
             rx_packets: Self::read_stat(&base, "rx_packets")?,
             tx_packets: Self::read_stat(&base, "tx_packets")?,
+            rx_bytes: Self::read_stat(&base, "rx_bytes")?,
+            tx_bytes: Self::read_stat(&base, "tx_bytes")?,
         })
     }
 
@@ -42,9 +47,12 @@ impl NetStats {
 
 /// Packets Per Second (PPS) monitor for game mode detection
 /// Uses EMA smoothing to prevent brief spikes from triggering game mode
+///
+/// Timestamps are supplied by the caller (monotonic time since the governor
+/// started) so the same counters always produce the same PPS.
 pub struct PpsMonitor {
     last_stats: Option<NetStats>,
-    last_sample_time: Option<Instant>,
+    last_sample_time: Option<Duration>,
     current_pps: u64,
     /// EMA-smoothed PPS for more stable game mode detection
     smoothed_pps: f64,
@@ -63,18 +71,17 @@ impl PpsMonitor {
         }
     }
 
-    /// Sample current PPS for an interface
+    /// Feed the latest counters for an interface, read at time `now`
     /// Returns EMA-smoothed PPS for stable game mode detection
     /// Per rewrite.md: (Current - Last) / TimeDelta
-    pub fn sample(&mut self, interface: &str) -> u64 {
-        let now = Instant::now();
-        let stats = match NetStats::read(interface) {
-            Some(s) => s,
+    pub fn sample(&mut self, stats: Option<&NetStats>, now: Duration) -> u64 {
+        let stats = match stats {
+            Some(s) => s.clone(),
             None => return self.smoothed_pps.round() as u64,
         };
 
         if let (Some(last_stats), Some(last_time)) = (&self.last_stats, self.last_sample_time) {
-            let time_delta = now.duration_since(last_time).as_secs_f64();
+            let time_delta = now.saturating_sub(last_time).as_secs_f64();
             
             if time_delta > 0.0 {
                 let packet_delta = stats.total_packets().saturating_sub(last_stats.total_packets());
@@ -94,7 +101,7 @@ impl PpsMonitor {
         self.last_sample_time = Some(now);
 
         let smoothed = self.smoothed_pps.round() as u64;
-        debug!("PPS for {}: {} (raw: {})", interface, smoothed, self.current_pps);
+        debug!("PPS: {} (raw: {})", smoothed, self.current_pps);
         smoothed
     }
 }
@@ -7,23 +7,33 @@ use anyhow::{Context, Result};
 use log::{info, debug, warn};
 use std::process::Command;
 use std::collections::VecDeque;
-use std::sync::OnceLock;
+use std::sync::Mutex;
 
-static GATEWAY_RTT: OnceLock<String> = OnceLock::new();
+static GATEWAY_RTT: Mutex<Option<&'static str>> = Mutex::new(None);
 
 /// Detect appropriate CAKE RTT by pinging the default gateway.
 /// Result is cached after first call.
 pub fn detect_gateway_rtt() -> &'static str {
-    GATEWAY_RTT.get_or_init(|| {
+    let mut cached = GATEWAY_RTT.lock().unwrap_or_else(|e| e.into_inner());
+    cached.get_or_insert_with(|| {
         let rtt = measure_gateway_rtt();
         info!("CAKE: Auto-detected gateway RTT -> using {}", rtt);
         rtt
-    }).as_str()
+    })
 }
 
-fn measure_gateway_rtt() -> String {
-    // Get default gateway IP from routing table
-    let gateway_ip = Command::new("ip")
+/// Ping the gateway again and replace the cached RTT (the path may have
+/// changed, e.g. after resuming on another network)
+pub fn remeasure_gateway_rtt() -> &'static str {
+    let rtt = measure_gateway_rtt();
+    info!("CAKE: Re-measured gateway RTT -> using {}", rtt);
+    *GATEWAY_RTT.lock().unwrap_or_else(|e| e.into_inner()) = Some(rtt);
+    rtt
+}
+
+/// Default gateway IP from the routing table
+pub fn default_gateway() -> Option<String> {
+    Command::new("ip")
         .args(["route", "show", "default"])
         .output()
         .ok()
@@ -33,13 +43,15 @@ fn measure_gateway_rtt() -> String {
                 .skip_while(|w| *w != "via")
                 .nth(1)
                 .map(|s| s.to_string())
-        });
+        })
+}
 
-    let gateway_ip = match gateway_ip {
+fn measure_gateway_rtt() -> &'static str {
+    let gateway_ip = match default_gateway() {
         Some(ip) => ip,
         None => {
             debug!("Could not detect default gateway, using 50ms RTT");
-            return "50ms".to_string();
+            return "50ms";
         }
     };
 
@@ -61,23 +73,31 @@ fn measure_gateway_rtt() -> String {
     match avg_ms {
         Some(rtt) if rtt < 5.0 => {
             info!("Gateway RTT {:.1}ms (local WiFi)", rtt);
-            "20ms".to_string()
+            "20ms"
         }
         Some(rtt) if rtt < 20.0 => {
             info!("Gateway RTT {:.1}ms (mesh/multi-hop)", rtt);
-            "50ms".to_string()
+            "50ms"
         }
         Some(rtt) => {
             info!("Gateway RTT {:.1}ms (high latency path)", rtt);
-            "100ms".to_string()
+            "100ms"
         }
         None => {
             debug!("Could not measure gateway RTT, using 50ms");
-            "50ms".to_string()
+            "50ms"
         }
     }
 }
 
+/// Lowest bandwidth CAKE is ever configured with (Mbit)
+const MIN_CAKE_MBIT: u32 = 1;
+
+/// Floor for link-rate estimates (Mbit): lower PHY rates are probe frames or a
+/// failing link, not a bottleneck worth shaping to. A known WAN uplink below
+/// it still applies.
+pub const MIN_LINK_MBIT: u32 = 10;
+
 /// Traffic Control manager with asymmetric response
 /// 
 /// Design philosophy: Bandwidth DROPS are dangerous (bufferbloat), INCREASES are safe.
@@ -221,8 +241,10 @@ impl TcManager {
         }
 
         // Stage 2: Get median (removes outliers) - NO EMA, direct response
+        // Compared against what would actually be applied, or slow links
+        // below the floor re-apply CAKE on every tick
         let target_mbit = match self.median() {
-            Some(m) => m,
+            Some(m) => m.max(MIN_CAKE_MBIT),
             None => return false,
         };
         
@@ -292,84 +314,147 @@ impl TcManager {
 
     /// Get the target bandwidth to apply
     pub fn get_target_bandwidth(&self) -> u32 {
-        self.median().unwrap_or(200).max(10)
+        self.median().unwrap_or(200).max(MIN_CAKE_MBIT)
     }
 
-    /// Apply CAKE qdisc to interface
-    pub fn apply_cake(&mut self, interface: &str) -> Result<()> {
-        let bandwidth_mbit = self.get_target_bandwidth();
-        
-        info!("Applying CAKE on {} with {}mbit bandwidth", interface, bandwidth_mbit);
-        
+    /// Bandwidth CAKE was last successfully applied at
+    pub fn last_applied(&self) -> Option<u32> {
+        self.last_bandwidth
+    }
+
+    /// Record that CAKE was successfully applied at `mbit`
+    /// Later changes are measured against this value
+    pub fn set_last_applied(&mut self, mbit: u32) {
+        self.last_bandwidth = Some(mbit);
+    }
+
+    #[cfg(test)]
+    pub fn is_game_mode(&self) -> bool {
+        self.game_mode_frozen
+    }
+
+    #[cfg(test)]
+    pub fn get_target_mbit(&self) -> u32 {
+        self.get_target_bandwidth()
+    }
+}
+
+/// Apply CAKE qdisc to interface
+pub fn apply_cake(interface: &str, bandwidth_mbit: u32) -> Result<()> {
+    info!("Applying CAKE on {} with {}mbit bandwidth", interface, bandwidth_mbit);
+
+    let output = Command::new("tc")
+        .args([
+            "qdisc", "replace", "dev", interface, "root", "cake",
+            "bandwidth", &format!("{}mbit", bandwidth_mbit),
+            "rtt", detect_gateway_rtt(),
+            "diffserv4",      // Differentiated services
+            "dual-dsthost",   // Fair queuing per destination
+            "nat",            // NAT awareness
+            "wash",           // Clear DSCP on ingress
+            "ack-filter",     // ACK filtering
+        ])
+        .output()
+        .context("Failed to execute tc command")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        warn!("tc failed: {}", stderr);
+
+        // Fallback to simpler CAKE config
         let output = Command::new("tc")
             .args([
                 "qdisc", "replace", "dev", interface, "root", "cake",
                 "bandwidth", &format!("{}mbit", bandwidth_mbit),
                 "rtt", detect_gateway_rtt(),
-                "diffserv4",      // Differentiated services
-                "dual-dsthost",   // Fair queuing per destination
-                "nat",            // NAT awareness
-                "wash",           // Clear DSCP on ingress
-                "ack-filter",     // ACK filtering
+                "besteffort", "nat",
             ])
-            .output()
-            .context("Failed to execute tc command")?;
+            .output()?;
 
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            warn!("tc failed: {}", stderr);
-            
-            // Fallback to simpler CAKE config
-            let output = Command::new("tc")
-                .args([
-                    "qdisc", "replace", "dev", interface, "root", "cake",
-                    "bandwidth", &format!("{}mbit", bandwidth_mbit),
-                    "rtt", detect_gateway_rtt(),
-                    "besteffort", "nat",
-                ])
-                .output()?;
-            
-            if !output.status.success() {
-                anyhow::bail!("Failed to apply CAKE qdisc");
-            }
+            anyhow::bail!("Failed to apply CAKE qdisc");
         }
-
-        self.last_bandwidth = Some(bandwidth_mbit);
-        info!("CAKE applied successfully: {}mbit on {}", bandwidth_mbit, interface);
-        
-        Ok(())
     }
 
-    /// Remove CAKE qdisc from interface
-    pub fn remove_cake(&self, interface: &str) -> Result<()> {
-        let output = Command::new("tc")
-            .args(["qdisc", "del", "dev", interface, "root"])
-            .output();
-        
-        // Ignore errors (may not have qdisc)
-        if let Ok(o) = output {
-            if o.status.success() {
-                info!("Removed CAKE from {}", interface);
-            }
+    info!("CAKE applied successfully: {}mbit on {}", bandwidth_mbit, interface);
+
+    Ok(())
+}
+
+/// Remove CAKE qdisc from interface
+pub fn remove_cake(interface: &str) -> Result<()> {
+    let output = Command::new("tc")
+        .args(["qdisc", "del", "dev", interface, "root"])
+        .output();
+
+    // Ignore errors (may not have qdisc)
+    if let Ok(o) = output {
+        if o.status.success() {
+            info!("Removed CAKE from {}", interface);
         }
-        
-        Ok(())
     }
 
-    #[cfg(test)]
-    pub fn is_game_mode(&self) -> bool {
-        self.game_mode_frozen
-    }
+    Ok(())
+}
 
-    #[cfg(test)]
-    pub fn get_target_mbit(&self) -> u32 {
-        self.get_target_bandwidth()
-    }
+/// Counters of an installed CAKE qdisc
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CakeStats {
+    pub bandwidth_mbit: Option<u32>,
+    pub dropped: u64,
+    pub backlog_packets: u64,
+}
 
-    #[cfg(test)]
-    pub fn set_last_applied(&mut self, mbit: u32) {
-        self.last_bandwidth = Some(mbit);
+/// Read CAKE counters with `tc -s` (None if CAKE is not installed)
+pub fn cake_stats(interface: &str) -> Option<CakeStats> {
+    let output = Command::new("tc")
+        .args(["-s", "qdisc", "show", "dev", interface, "root"])
+        .output()
+        .ok()?;
+    parse_cake_stats(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse `tc -s qdisc show` output:
+/// "qdisc cake 8001: root refcnt 2 bandwidth 85Mbit diffserv4 ..."
+/// " Sent 123 bytes 4 pkt (dropped 12, overlimits 34 requeues 0)"
+/// " backlog 0b 0p requeues 0"
+fn parse_cake_stats(output: &str) -> Option<CakeStats> {
+    let mut lines = output.lines().skip_while(|l| !l.starts_with("qdisc cake"));
+    let header = lines.next()?;
+
+    let bandwidth_mbit = header.split_whitespace()
+        .skip_while(|w| *w != "bandwidth")
+        .nth(1)
+        .and_then(parse_rate_mbit);
+
+    let mut stats = CakeStats { bandwidth_mbit, ..Default::default() };
+    for line in lines.take_while(|l| !l.starts_with("qdisc ")) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if let Some(i) = words.iter().position(|w| *w == "(dropped") {
+            stats.dropped = words.get(i + 1)
+                .and_then(|n| n.trim_end_matches(',').parse().ok())
+                .unwrap_or(0);
+        } else if words.first() == Some(&"backlog") {
+            stats.backlog_packets = words.get(2)
+                .and_then(|n| n.trim_end_matches('p').parse().ok())
+                .unwrap_or(0);
+        }
     }
+    Some(stats)
+}
+
+/// "85Mbit", "1Gbit", "500Kbit" -> Mbit
+fn parse_rate_mbit(rate: &str) -> Option<u32> {
+    let digits = rate.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+    let value: f64 = rate[..digits].parse().ok()?;
+    let mbit = match &rate[digits..] {
+        "Gbit" => value * 1000.0,
+        "Mbit" => value,
+        "Kbit" => value / 1000.0,
+        "bit" => value / 1_000_000.0,
+        _ => return None,
+    };
+    Some(mbit.round() as u32)
 }
 
 /// Ethtool wrapper for hardware offload settings
@@ -542,4 +627,22 @@ mod tests {
         let target = tc.get_target_bandwidth();
         assert!(target < 600, "Should limit based on throughput, got {}", target);
     }
+
+    #[test]
+    fn test_parse_cake_stats() {
+        let output = "qdisc cake 8001: root refcnt 2 bandwidth 85Mbit diffserv4 dual-dsthost nat wash ack-filter split-gso rtt 20ms raw overhead 0 \n \
+ Sent 48230193 bytes 61204 pkt (dropped 57, overlimits 12055 requeues 0) \n \
+ backlog 4542b 3p requeues 0\n \
+ memory used: 338Kb of 4Mb\n";
+        assert_eq!(parse_cake_stats(output), Some(CakeStats {
+            bandwidth_mbit: Some(85),
+            dropped: 57,
+            backlog_packets: 3,
+        }));
+
+        let fq = "qdisc fq_codel 0: root refcnt 2 limit 10240p flows 1024\n Sent 100 bytes 1 pkt (dropped 0, overlimits 0 requeues 0)\n";
+        assert_eq!(parse_cake_stats(fq), None);
+        assert_eq!(parse_rate_mbit("1Gbit"), Some(1000));
+        assert_eq!(parse_rate_mbit("1500Kbit"), Some(2));
+    }
 }
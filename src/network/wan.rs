@@ -0,0 +1,283 @@
+//! WAN uplink capacity for CAKE sizing
+//!
+//! On a fast link the bottleneck is usually the ISP, not the PHY rate. Each
+//! network (SSID for WiFi, interface name for Ethernet) can have a configured
+//! uplink in `[governor.wan]`, or one measured with `hifi-wifi wan measure`.
+//! CAKE is then sized from the lower of the WAN and link-derived limits.
+//!
+//! CAKE shapes egress only, so the upload rate is what bounds it; download is
+//! recorded for reference.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+
+use crate::config::structs::WanConfig;
+use crate::network::tc;
+
+/// Measured capacities, keyed by network
+pub const MEASUREMENTS_PATH: &str = "/var/lib/hifi-wifi/wan.json";
+
+/// Cloudflare's speed test endpoints (no account, plain HTTP transfer)
+const DOWNLOAD_URL: &str = "https://speed.cloudflare.com/__down?bytes=25000000";
+const UPLOAD_URL: &str = "https://speed.cloudflare.com/__up";
+const UPLOAD_BYTES: usize = 10_000_000;
+
+/// A measured WAN capacity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WanMeasurement {
+    pub upload_mbit: u32,
+    pub download_mbit: u32,
+    pub measured_at: DateTime<Utc>,
+}
+
+/// Where a WAN uplink figure came from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum UplinkSource {
+    /// `[[governor.wan.profiles]]` entry for this network
+    Profile,
+    /// `hifi-wifi wan measure`
+    Measured,
+    /// `[governor.wan] upload_mbit`
+    Default,
+}
+
+/// Which limit CAKE ended up sized from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Constraint {
+    /// Link rate (WiFi PHY / Ethernet speed) times the overhead factor
+    Link,
+    /// WAN uplink times the shaping factor
+    Wan(UplinkSource),
+}
+
+/// Identify the network a link is on: SSID for WiFi, interface name otherwise
+pub fn network_key(interface: &str, ssid: Option<&str>) -> String {
+    match ssid {
+        Some(ssid) if !ssid.is_empty() => ssid.to_string(),
+        _ => interface.to_string(),
+    }
+}
+
+/// Uplink for `network`: configured profile, then measurement, then default
+pub fn resolve_uplink(config: &WanConfig, network: &str, measured_mbit: Option<u32>) -> Option<(u32, UplinkSource)> {
+    if !config.enabled {
+        return None;
+    }
+    let profile = config.profiles.iter()
+        .find(|p| p.network == network)
+        .and_then(|p| p.upload_mbit);
+
+    profile.map(|mbit| (mbit, UplinkSource::Profile))
+        .or(measured_mbit.map(|mbit| (mbit, UplinkSource::Measured)))
+        .or(config.upload_mbit.map(|mbit| (mbit, UplinkSource::Default)))
+        .filter(|(mbit, _)| *mbit > 0)
+}
+
+/// CAKE bandwidth from the link-derived limit and the WAN uplink, and which binds
+pub fn combine(config: &WanConfig, link_mbit: u32, uplink: Option<(u32, UplinkSource)>) -> (u32, Constraint) {
+    match uplink {
+        Some((mbit, source)) => {
+            let wan_mbit = (mbit as f64 * config.shaping_factor) as u32;
+            if wan_mbit < link_mbit {
+                (wan_mbit, Constraint::Wan(source))
+            } else {
+                (link_mbit, Constraint::Link)
+            }
+        }
+        None => (link_mbit, Constraint::Link),
+    }
+}
+
+/// Load all measurements (empty if none were taken)
+pub fn load_measurements(path: &Path) -> HashMap<String, WanMeasurement> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Measurements file, re-read only when it changes
+///
+/// The Governor looks up the uplink on every tick of every link; a `stat`
+/// is all that costs until `wan measure` writes a new figure.
+pub struct MeasurementCache {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    measurements: HashMap<String, WanMeasurement>,
+}
+
+impl MeasurementCache {
+    pub fn new(path: &Path) -> Self {
+        Self { path: path.to_path_buf(), modified: None, measurements: HashMap::new() }
+    }
+
+    /// Measured uplink for one network
+    pub fn uplink(&mut self, network: &str) -> Option<u32> {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if modified != self.modified {
+            self.measurements = load_measurements(&self.path);
+            self.modified = modified;
+        }
+        self.measurements.get(network).map(|m| m.upload_mbit)
+    }
+}
+
+/// Measured uplink for one network
+pub fn measured_uplink(network: &str) -> Option<u32> {
+    load_measurements(Path::new(MEASUREMENTS_PATH))
+        .get(network)
+        .map(|m| m.upload_mbit)
+}
+
+/// Store a measurement for `network`, replacing any earlier one
+pub fn save_measurement(path: &Path, network: &str, measurement: WanMeasurement) -> Result<()> {
+    let mut all = load_measurements(path);
+    all.insert(network.to_string(), measurement);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(&all)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Measure WAN capacity with a download and an upload transfer
+pub fn measure() -> Result<WanMeasurement> {
+    info!("Measuring download capacity...");
+    let download_mbit = curl_rate(&[DOWNLOAD_URL, "-o", "/dev/null", "-w", "%{speed_download}"], None)?;
+
+    info!("Measuring upload capacity...");
+    let upload_mbit = curl_rate(
+        &[UPLOAD_URL, "-o", "/dev/null", "-X", "POST", "--data-binary", "@-", "-w", "%{speed_upload}"],
+        Some(vec![0u8; UPLOAD_BYTES]),
+    )?;
+
+    Ok(WanMeasurement {
+        upload_mbit,
+        download_mbit,
+        measured_at: Utc::now(),
+    })
+}
+
+/// Measure with CAKE lifted from `interfaces` and put back afterwards
+///
+/// CAKE sized from an earlier measurement would cap the transfers, so every
+/// re-measure would come out lower than the last.
+pub fn measure_unshaped(interfaces: &[String]) -> Result<WanMeasurement> {
+    let shaped: Vec<(String, u32)> = interfaces.iter()
+        .filter_map(|i| Some((i.clone(), tc::cake_stats(i)?.bandwidth_mbit?)))
+        .collect();
+    for (interface, mbit) in &shaped {
+        info!("Lifting CAKE ({}Mbit) on {} for the measurement", mbit, interface);
+        tc::remove_cake(interface)?;
+    }
+
+    let measurement = measure();
+
+    for (interface, mbit) in &shaped {
+        if let Err(e) = tc::apply_cake(interface, *mbit) {
+            warn!("Failed to restore CAKE on {}: {}", interface, e);
+        }
+    }
+    measurement
+}
+
+/// Run curl and convert its reported bytes/sec to Mbit/s
+fn curl_rate(args: &[&str], body: Option<Vec<u8>>) -> Result<u32> {
+    let mut child = Command::new("curl")
+        .args(["-sf", "--max-time", "30"])
+        .args(args)
+        .stdin(if body.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("curl command not found - please install curl")?;
+
+    if let (Some(body), Some(mut stdin)) = (body, child.stdin.take()) {
+        stdin.write_all(&body)?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("Speed test transfer failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    let bytes_per_sec: f64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .context("Unexpected curl output")?;
+    debug!("curl transfer rate: {} bytes/s", bytes_per_sec);
+
+    Ok((bytes_per_sec * 8.0 / 1_000_000.0) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::structs::WanProfile;
+
+    fn config() -> WanConfig {
+        WanConfig {
+            profiles: vec![WanProfile {
+                network: "home".to_string(),
+                upload_mbit: Some(40),
+                download_mbit: Some(300),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_uplink_precedence() {
+        let mut config = config();
+        assert_eq!(resolve_uplink(&config, "home", Some(20)), Some((40, UplinkSource::Profile)));
+        assert_eq!(resolve_uplink(&config, "cafe", Some(20)), Some((20, UplinkSource::Measured)));
+        assert_eq!(resolve_uplink(&config, "cafe", None), None);
+
+        config.upload_mbit = Some(100);
+        assert_eq!(resolve_uplink(&config, "cafe", None), Some((100, UplinkSource::Default)));
+
+        config.enabled = false;
+        assert_eq!(resolve_uplink(&config, "home", Some(20)), None);
+    }
+
+    #[test]
+    fn test_binding_constraint() {
+        let config = config();
+        let uplink = Some((40, UplinkSource::Profile));
+
+        // 866 Mbit WiFi on a 40 Mbit line: the WAN binds (95% of 40)
+        assert_eq!(combine(&config, 736, uplink), (38, Constraint::Wan(UplinkSource::Profile)));
+        // Weak WiFi slower than the line: the link binds
+        assert_eq!(combine(&config, 30, uplink), (30, Constraint::Link));
+        assert_eq!(combine(&config, 736, None), (736, Constraint::Link));
+    }
+
+    #[test]
+    fn test_measurements_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wan.json");
+        assert!(load_measurements(&path).is_empty());
+
+        let m = WanMeasurement { upload_mbit: 20, download_mbit: 200, measured_at: Utc::now() };
+        save_measurement(&path, "home", m.clone()).unwrap();
+        save_measurement(&path, "eth0", WanMeasurement { upload_mbit: 900, ..m }).unwrap();
+
+        let all = load_measurements(&path);
+        assert_eq!(all["home"].upload_mbit, 20);
+        assert_eq!(all["eth0"].upload_mbit, 900);
+
+        let mut cache = MeasurementCache::new(&path);
+        assert_eq!(cache.uplink("home"), Some(20));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(cache.uplink("home"), None);
+    }
+}
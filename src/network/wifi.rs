@@ -4,21 +4,39 @@
 
 use anyhow::{Context, Result};
 use log::{info, warn, debug};
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
 use std::process::Command;
 
+use crate::network::device::DeviceIdentity;
 use crate::network::tc::detect_gateway_rtt;
+use crate::system::devices;
+use crate::system::quirks::{self, Quirk};
 
 /// Interface type (WiFi or Ethernet)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum InterfaceType {
     Wifi,
     Ethernet,
 }
 
-/// Detected driver category for applying specific optimizations
-#[derive(Debug, Clone, PartialEq)]
+impl InterfaceType {
+    /// Classify an interface by name; None for interfaces hifi-wifi leaves alone
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.starts_with("wl") {
+            Some(InterfaceType::Wifi)
+        } else if name.starts_with("en") || name.starts_with("eth") {
+            Some(InterfaceType::Ethernet)
+        } else {
+            None
+        }
+    }
+}
+
+/// Detected driver category, as labelled in the quirk database
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DriverCategory {
     Rtw89,      // Realtek RTW89 (modern)
     Rtw88,      // Realtek RTW88
@@ -29,6 +47,7 @@ pub enum DriverCategory {
     Broadcom,   // Broadcom
     Ralink,     // Ralink/MediaTek Legacy
     Marvell,    // Marvell
+    #[default]
     Generic,    // Unknown - apply universal optimizations
 }
 
@@ -41,6 +60,10 @@ pub struct WifiInterface {
     pub interface_type: InterfaceType,
     #[allow(dead_code)]
     pub is_active: bool,
+    /// Bus, IDs, chipset, firmware and wiphy capabilities
+    pub device: DeviceIdentity,
+    /// Quirk database entry for the device, if any matched
+    pub quirk: Option<Quirk>,
 }
 
 /// Manages Wi-Fi interfaces and applies optimizations
@@ -75,16 +98,17 @@ impl WifiManager {
             let ifc_name = entry.file_name().to_string_lossy().to_string();
             
             // Check if it's a wireless or ethernet interface
-            let interface_type = if ifc_name.starts_with("wl") {
-                InterfaceType::Wifi
-            } else if ifc_name.starts_with("en") || ifc_name.starts_with("eth") {
-                InterfaceType::Ethernet
-            } else {
+            let Some(interface_type) = InterfaceType::from_name(&ifc_name) else {
                 continue;
             };
 
             let driver = Self::detect_driver(&ifc_name);
-            let category = Self::categorize_driver(&driver);
+            let device = DeviceIdentity::identify(&ifc_name, interface_type == InterfaceType::Wifi);
+            let mut quirk = quirks::database().lookup(&driver, &device.ids()).cloned();
+            if let Some(model) = devices::current() {
+                quirk = model.adjust_quirk(quirk, &device.ids());
+            }
+            let category = quirk.as_ref().map(|q| q.category.clone()).unwrap_or_default();
             let is_active = Self::is_interface_active(&ifc_name);
 
             if log_output {
@@ -94,6 +118,7 @@ impl WifiManager {
                 };
                 info!("Detected interface: {} (type: {}, driver: {}, category: {:?})", 
                       ifc_name, type_str, driver, category);
+                info!("  Device: {}", device.summary());
             }
 
             interfaces.push(WifiInterface {
@@ -102,6 +127,8 @@ impl WifiManager {
                 category,
                 interface_type,
                 is_active,
+                device,
+                quirk,
             });
         }
 
@@ -121,22 +148,6 @@ impl WifiManager {
         "unknown".to_string()
     }
 
-    /// Categorize driver for optimization selection
-    fn categorize_driver(driver: &str) -> DriverCategory {
-        match driver {
-            d if d.contains("rtw89") => DriverCategory::Rtw89,
-            d if d.contains("rtw88") => DriverCategory::Rtw88,
-            d if d.starts_with("rtl") => DriverCategory::RtlLegacy,
-            d if d.starts_with("mt7") || d.contains("mt76") => DriverCategory::MediaTek,
-            d if d.starts_with("iwl") => DriverCategory::Intel,
-            d if d.starts_with("ath") => DriverCategory::Atheros,
-            d if d.starts_with("brcm") || d == "wl" => DriverCategory::Broadcom,
-            d if d.starts_with("rt2") || d.starts_with("rt5") => DriverCategory::Ralink,
-            d if d.starts_with("mwifiex") || d.starts_with("mwl") => DriverCategory::Marvell,
-            _ => DriverCategory::Generic,
-        }
-    }
-
     /// Check if interface is currently active (has carrier)
     fn is_interface_active(ifc_name: &str) -> bool {
         let carrier_path = format!("/sys/class/net/{}/carrier", ifc_name);
@@ -181,6 +192,11 @@ impl WifiManager {
             return Ok(());
         }
 
+        if ifc.quirk.as_ref().is_some_and(|q| q.forbids_power_save()) {
+            info!("Keeping power save off on {}: not reliable with {}", ifc.name, ifc.driver);
+            return self.disable_power_save(ifc);
+        }
+
         info!("Enabling power save on {}", ifc.name);
         
         let output = Command::new("iw")
@@ -211,7 +227,12 @@ impl WifiManager {
                 
                 for line in stdout.lines() {
                     let line = line.trim();
-                    if line.starts_with("signal:") {
+                    if let Some(rest) = line.strip_prefix("Connected to ") {
+                        // "Connected to aa:bb:cc:dd:ee:ff (on wlan0)"
+                        stats.bssid = rest.split_whitespace().next().map(|s| s.to_string());
+                    } else if let Some(ssid) = line.strip_prefix("SSID:") {
+                        stats.ssid = Some(ssid.trim().to_string());
+                    } else if line.starts_with("signal:") {
                         if let Some(val) = line.split_whitespace().nth(1) {
                             stats.signal_dbm = val.parse().unwrap_or(-100);
                         }
@@ -326,6 +347,10 @@ pub struct LinkStats {
     pub signal_dbm: i32,
     pub tx_bitrate_mbps: f64,
     pub rx_bitrate_mbps: f64,
+    /// Connected network (WiFi only)
+    pub ssid: Option<String>,
+    /// Connected AP (WiFi only)
+    pub bssid: Option<String>,
 }
 
 impl Default for WifiManager {
@@ -0,0 +1,172 @@
+//! wpa_supplicant tuning over D-Bus
+//!
+//! NetworkManager hands each connection to wpa_supplicant as a network block
+//! with its own `bgscan` (background scan and roaming trigger, e.g.
+//! "simple:30:-70:86400"). We replace it on the current network and set the
+//! interface's `autoscan`. NM builds a fresh network block on every
+//! activation, so the Governor re-applies after each reconnect.
+//!
+//! What was there before goes into the rollback journal. wpa_supplicant's
+//! D-Bus API can't read `autoscan` back; NM never sets it, so it's restored
+//! by clearing it.
+
+use anyhow::{Context, Result};
+use log::{debug, info};
+use std::collections::HashMap;
+use zbus::blocking::Connection;
+use zbus::proxy;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+use crate::config::structs::BackendConfig;
+use crate::system::rollback::{Change, Journal};
+
+#[proxy(
+    interface = "fi.w1.wpa_supplicant1",
+    default_service = "fi.w1.wpa_supplicant1",
+    default_path = "/fi/w1/wpa_supplicant1"
+)]
+trait Supplicant {
+    fn get_interface(&self, ifname: &str) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[proxy(interface = "fi.w1.wpa_supplicant1.Interface", default_service = "fi.w1.wpa_supplicant1")]
+trait SupplicantInterface {
+    #[zbus(property)]
+    fn current_network(&self) -> zbus::Result<OwnedObjectPath>;
+
+    fn auto_scan(&self, arg: &str) -> zbus::Result<()>;
+}
+
+#[proxy(interface = "fi.w1.wpa_supplicant1.Network", default_service = "fi.w1.wpa_supplicant1")]
+trait SupplicantNetwork {
+    /// Network block fields, as in wpa_supplicant.conf
+    #[zbus(property, name = "Properties")]
+    fn fields(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+
+    #[zbus(property, name = "Properties")]
+    fn set_fields(&self, fields: HashMap<&str, Value<'_>>) -> zbus::Result<()>;
+}
+
+/// wpa_supplicant settings applied per connection
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuning {
+    /// `bgscan` for the current network; empty disables background scans
+    pub bgscan: String,
+    /// `autoscan` for the interface (scanning while disconnected)
+    pub autoscan: Option<String>,
+}
+
+impl Tuning {
+    pub fn from_config(config: &BackendConfig) -> Self {
+        let bgscan = if config.wpa_bgscan_disable {
+            String::new()
+        } else {
+            format!("simple:{}:{}:{}", config.bgscan_short_interval_secs,
+                    config.roam_threshold_dbm, config.bgscan_long_interval_secs)
+        };
+        let autoscan = Some(config.wpa_autoscan.clone()).filter(|a| !a.is_empty());
+        Self { bgscan, autoscan }
+    }
+}
+
+/// Apply `tuning` to `interface`'s current connection
+/// Does nothing if wpa_supplicant isn't running or doesn't manage the interface.
+pub fn tune(interface: &str, tuning: &Tuning, stage: &str, journal: &mut Journal) -> Result<()> {
+    let connection = Connection::system().context("Failed to connect to system D-Bus")?;
+    let supplicant = SupplicantProxyBlocking::new(&connection)?;
+    let Ok(path) = supplicant.get_interface(interface) else {
+        debug!("{} is not managed by wpa_supplicant", interface);
+        return Ok(());
+    };
+    let ifc = SupplicantInterfaceProxyBlocking::builder(&connection).path(path)?.build()?;
+
+    if let Some(autoscan) = &tuning.autoscan {
+        journal.record(stage, Some(Change::WpaAutoscan { interface: interface.to_string() }));
+        ifc.auto_scan(autoscan).context("Failed to set autoscan")?;
+    }
+
+    let network = ifc.current_network()?;
+    if network.as_str() == "/" {
+        debug!("{} has no current network, bgscan left for the next connection", interface);
+        return Ok(());
+    }
+
+    // Network objects from earlier connections are gone
+    journal.forget_vanished(|c| matches!(c, Change::WpaBgscan { interface: i, .. } if i == interface));
+
+    let current = network_proxy(&connection, network.as_str())?.fields()?;
+    let previous = current.get("bgscan").and_then(|v| String::try_from(v.clone()).ok()).map(|v| unquote(&v));
+    if previous.as_deref() == Some(tuning.bgscan.as_str()) {
+        return Ok(());
+    }
+
+    journal.record(stage, Some(Change::WpaBgscan {
+        interface: interface.to_string(),
+        network: network.to_string(),
+        previous,
+    }));
+    set_bgscan(network.as_str(), &tuning.bgscan)?;
+
+    if tuning.bgscan.is_empty() {
+        info!("wpa_supplicant background scans disabled on {}", interface);
+    } else {
+        info!("wpa_supplicant bgscan on {} set to {}", interface, tuning.bgscan);
+    }
+    Ok(())
+}
+
+/// Set `bgscan` on a network object; empty disables background scans
+pub fn set_bgscan(network: &str, bgscan: &str) -> Result<()> {
+    let connection = Connection::system()?;
+    // wpa_supplicant quotes string fields itself
+    let fields = HashMap::from([("bgscan", Value::from(bgscan))]);
+    network_proxy(&connection, network)?.set_fields(fields)
+        .with_context(|| format!("Failed to set bgscan on {}", network))
+}
+
+/// Set `autoscan` on an interface; empty clears it
+pub fn set_autoscan(interface: &str, autoscan: &str) -> Result<()> {
+    let connection = Connection::system()?;
+    let path = SupplicantProxyBlocking::new(&connection)?.get_interface(interface)?;
+    let ifc = SupplicantInterfaceProxyBlocking::builder(&connection).path(path)?.build()?;
+    ifc.auto_scan(autoscan).context("Failed to set autoscan")
+}
+
+/// Whether a network object still exists (NM replaces it on reconnect)
+pub fn network_exists(network: &str) -> bool {
+    Connection::system().ok()
+        .and_then(|connection| network_proxy(&connection, network).ok()?.fields().ok())
+        .is_some()
+}
+
+fn network_proxy<'a>(connection: &Connection, path: &'a str) -> Result<SupplicantNetworkProxyBlocking<'a>> {
+    Ok(SupplicantNetworkProxyBlocking::builder(connection).path(path)?.build()?)
+}
+
+/// String fields come back quoted ("\"simple:30:-70:86400\"")
+fn unquote(value: &str) -> String {
+    value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tuning_from_config() {
+        let mut config = BackendConfig::default();
+        let tuning = Tuning::from_config(&config);
+        // Roaming keeps working by default, only with fewer scans than NM's
+        assert_eq!(tuning.bgscan, "simple:120:-75:86400");
+        assert_eq!(tuning.autoscan.as_deref(), Some("exponential:3:300"));
+
+        config.wpa_bgscan_disable = true;
+        config.wpa_autoscan = String::new();
+        let tuning = Tuning::from_config(&config);
+        assert_eq!(tuning.bgscan, "");
+        assert_eq!(tuning.autoscan, None);
+
+        assert_eq!(unquote("\"simple:30:-70:86400\""), "simple:30:-70:86400");
+        assert_eq!(unquote("learn"), "learn");
+    }
+}
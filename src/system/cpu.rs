@@ -5,7 +5,9 @@
 
 use log::debug;
 use std::fs;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+use std::thread;
+use std::time::Duration;
 
 /// CPU statistics from /proc/stat
 #[derive(Debug, Clone, Default)]
@@ -30,7 +32,23 @@ impl CpuTimes {
             return None;
         }
 
-        let parts: Vec<u64> = first_line
+        Self::parse(first_line)
+    }
+
+    /// Parse the per-CPU lines ("cpu0 ...", "cpu1 ...") of /proc/stat
+    fn per_cpu_from_proc_stat() -> BTreeMap<usize, Self> {
+        let content = fs::read_to_string("/proc/stat").unwrap_or_default();
+        content.lines()
+            .filter_map(|line| {
+                let id = line.split_whitespace().next()?.strip_prefix("cpu")?.parse().ok()?;
+                Some((id, Self::parse(line)?))
+            })
+            .collect()
+    }
+
+    /// Parse one "cpu..." line of /proc/stat
+    fn parse(line: &str) -> Option<Self> {
+        let parts: Vec<u64> = line
             .split_whitespace()
             .skip(1) // Skip "cpu" label
             .filter_map(|s| s.parse().ok())
@@ -52,6 +70,18 @@ impl CpuTimes {
         })
     }
 
+    /// Busy fraction (0.0-1.0) between `last` and `self`
+    fn load_since(&self, last: &CpuTimes) -> f64 {
+        let total_delta = self.total().saturating_sub(last.total());
+        let idle_delta = self.idle_time().saturating_sub(last.idle_time());
+
+        if total_delta > 0 {
+            1.0 - (idle_delta as f64 / total_delta as f64)
+        } else {
+            0.0
+        }
+    }
+
     /// Total CPU time (all states)
     fn total(&self) -> u64 {
         self.user + self.nice + self.system + self.idle + 
@@ -72,6 +102,8 @@ pub struct CpuMonitor {
     samples: VecDeque<f64>,
     /// Window size for rolling average
     window_size: usize,
+    /// Previous per-CPU times for `sample_per_cpu`
+    last_per_cpu: BTreeMap<usize, CpuTimes>,
 }
 
 impl CpuMonitor {
@@ -80,6 +112,7 @@ impl CpuMonitor {
             last_times: None,
             samples: VecDeque::with_capacity(window_size),
             window_size,
+            last_per_cpu: BTreeMap::new(),
         }
     }
 
@@ -92,14 +125,7 @@ impl CpuMonitor {
         };
 
         let load = if let Some(ref last) = self.last_times {
-            let total_delta = current.total().saturating_sub(last.total());
-            let idle_delta = current.idle_time().saturating_sub(last.idle_time());
-            
-            if total_delta > 0 {
-                1.0 - (idle_delta as f64 / total_delta as f64)
-            } else {
-                0.0
-            }
+            current.load_since(last)
         } else {
             0.0 // First sample, no delta yet
         };
@@ -118,6 +144,17 @@ impl CpuMonitor {
         smoothed
     }
 
+    /// Load of each CPU (0.0-1.0) since the previous call, keyed by CPU number
+    /// Empty on the first call. Not smoothed: callers apply their own hysteresis.
+    pub fn sample_per_cpu(&mut self) -> BTreeMap<usize, f64> {
+        let current = CpuTimes::per_cpu_from_proc_stat();
+        let loads = current.iter()
+            .filter_map(|(id, now)| Some((*id, now.load_since(self.last_per_cpu.get(id)?))))
+            .collect();
+        self.last_per_cpu = current;
+        loads
+    }
+
     /// Get the smoothed (rolling average) CPU load
     pub fn smoothed_load(&self) -> f64 {
         if self.samples.is_empty() {
@@ -128,6 +165,17 @@ impl CpuMonitor {
 
 }
 
+/// Load of each CPU (0.0-1.0) over `interval`, keyed by CPU number
+/// Blocks for `interval`; used for one-off placement decisions, not per tick.
+pub fn per_cpu_load(interval: Duration) -> BTreeMap<usize, f64> {
+    let before = CpuTimes::per_cpu_from_proc_stat();
+    thread::sleep(interval);
+    CpuTimes::per_cpu_from_proc_stat()
+        .into_iter()
+        .filter_map(|(id, now)| Some((id, now.load_since(before.get(&id)?))))
+        .collect()
+}
+
 impl Default for CpuMonitor {
     fn default() -> Self {
         Self::new(3) // Per rewrite.md: window size ~3 samples
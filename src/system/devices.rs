@@ -0,0 +1,247 @@
+//! Device database
+//!
+//! Which machine hifi-wifi runs on, from DMI strings: the device type (the
+//! chassis type is wrong on most handhelds; the ROG Ally reports a desktop),
+//! the WiFi chips it ships with, chip quirks specific to the model and
+//! defaults for the power policy. Like the quirk database it is an embedded
+//! TOML file, extended or overridden by files in `/etc/hifi-wifi/devices.d`.
+
+use anyhow::Result;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::config::loader::{load_toml_dir, merge_by_name};
+use crate::system::power::DeviceType;
+use crate::system::quirks::{glob_match, PowerSaveAdvice, Quirk};
+
+const BUILTIN: &str = include_str!("devices.toml");
+const DEVICES_DIR: &str = "/etc/hifi-wifi/devices.d";
+
+/// [power] defaults for every handheld, below the device's own
+const HANDHELD_POWER: &str = r#"
+saver_below_battery_pct = 20
+saver_below_minutes_left = 30
+balanced_above_temp_c = 90.0
+"#;
+
+/// DMI identification strings from /sys/class/dmi/id
+#[derive(Debug, Clone, Default)]
+pub struct Dmi {
+    pub vendor: String,
+    pub product: String,
+    pub version: String,
+    pub board: String,
+}
+
+impl Dmi {
+    pub fn read() -> Self {
+        let read = |attr: &str| {
+            fs::read_to_string(format!("/sys/class/dmi/id/{}", attr))
+                .map(|s| s.trim().to_string())
+                .unwrap_or_default()
+        };
+        Self {
+            vendor: read("sys_vendor"),
+            product: read("product_name"),
+            version: read("product_version"),
+            board: read("board_name"),
+        }
+    }
+}
+
+/// What hifi-wifi knows about one model
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Device {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub device_type: DeviceType,
+    pub vendors: Vec<String>,
+    pub products: Vec<String>,
+    pub versions: Vec<String>,
+    pub boards: Vec<String>,
+    /// PCI/USB "vendor:device" IDs of the stock WiFi chips
+    pub wifi: Vec<String>,
+    /// Features to avoid on the stock chip in this model
+    pub avoid: Vec<String>,
+    pub power_save: Option<PowerSaveAdvice>,
+    /// Defaults for the [power] config section
+    pub power: toml::Table,
+}
+
+impl Default for Device {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            device_type: DeviceType::Laptop,
+            vendors: Vec::new(),
+            products: Vec::new(),
+            versions: Vec::new(),
+            boards: Vec::new(),
+            wifi: Vec::new(),
+            avoid: Vec::new(),
+            power_save: None,
+            power: toml::Table::new(),
+        }
+    }
+}
+
+impl Device {
+    fn matches(&self, dmi: &Dmi) -> bool {
+        let any = |patterns: &[String], value: &str| patterns.iter().any(|p| glob_match(p, value));
+        let vendor_ok = self.vendors.is_empty() || any(&self.vendors, &dmi.vendor);
+        let model_ok = (self.products.is_empty() && self.versions.is_empty() && self.boards.is_empty())
+            || any(&self.products, &dmi.product)
+            || any(&self.versions, &dmi.version)
+            || any(&self.boards, &dmi.board);
+        vendor_ok && model_ok
+    }
+
+    /// Whether a chip with these IDs is one the model ships with
+    pub fn is_stock_wifi(&self, ids: &[String]) -> bool {
+        ids.iter().any(|id| self.wifi.iter().any(|known| known.eq_ignore_ascii_case(id)))
+    }
+
+    /// Add this model's quirks to the stock chip's own
+    pub fn adjust_quirk(&self, quirk: Option<Quirk>, ids: &[String]) -> Option<Quirk> {
+        if (self.avoid.is_empty() && self.power_save.is_none()) || !self.is_stock_wifi(ids) {
+            return quirk;
+        }
+        let mut quirk = quirk.unwrap_or_else(|| Quirk { name: self.name.clone(), ..Default::default() });
+        for feature in &self.avoid {
+            if !quirk.avoids(feature) {
+                quirk.avoid.push(feature.clone());
+            }
+        }
+        if self.power_save.is_some() {
+            quirk.power_save = self.power_save;
+        }
+        Some(quirk)
+    }
+
+    /// [power] defaults: the type's, then the device's own
+    pub fn power_defaults(&self) -> toml::Table {
+        let mut defaults = match self.device_type {
+            DeviceType::SteamDeck | DeviceType::Handheld => {
+                toml::from_str(HANDHELD_POWER).expect("handheld power defaults are valid")
+            }
+            _ => toml::Table::new(),
+        };
+        defaults.extend(self.power.clone());
+        defaults
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct DeviceFile {
+    #[serde(default)]
+    device: Vec<Device>,
+}
+
+/// Built-in devices plus the ones from devices.d, in match order
+#[derive(Debug, Default)]
+pub struct Database {
+    devices: Vec<Device>,
+}
+
+impl Database {
+    /// Parse one device file
+    pub fn parse(text: &str) -> Result<Vec<Device>> {
+        let file: DeviceFile = toml::from_str(text)?;
+        Ok(file.device)
+    }
+
+    /// The embedded database merged with `overrides` (see `merge_by_name`)
+    fn with_overrides(overrides: Vec<Device>) -> Self {
+        let builtin = Self::parse(BUILTIN).expect("embedded devices.toml is valid");
+        Self { devices: merge_by_name(overrides, builtin, |d| &d.name) }
+    }
+
+    /// Load the embedded database and the files in `dir`
+    pub fn load(dir: &Path) -> Self {
+        let files: Vec<DeviceFile> = load_toml_dir(dir);
+        Self::with_overrides(files.into_iter().flat_map(|f| f.device).collect())
+    }
+
+    pub fn lookup(&self, dmi: &Dmi) -> Option<&Device> {
+        self.devices.iter().find(|d| d.matches(dmi))
+    }
+}
+
+/// This machine's entry, looked up on first use
+pub fn current() -> Option<&'static Device> {
+    static DEVICE: OnceLock<Option<Device>> = OnceLock::new();
+    DEVICE.get_or_init(|| Database::load(Path::new(DEVICES_DIR)).lookup(&Dmi::read()).cloned())
+        .as_ref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dmi(vendor: &str, product: &str, version: &str, board: &str) -> Dmi {
+        Dmi {
+            vendor: vendor.to_string(),
+            product: product.to_string(),
+            version: version.to_string(),
+            board: board.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_builtin_database_matches_handhelds() {
+        let db = Database::with_overrides(Vec::new());
+        let name = |dmi: Dmi| db.lookup(&dmi).map(|d| d.name.as_str());
+
+        assert_eq!(name(dmi("Valve", "Jupiter", "1", "Jupiter")), Some("Steam Deck LCD"));
+        assert_eq!(name(dmi("Valve", "Galileo", "1", "Galileo")), Some("Steam Deck OLED"));
+        assert_eq!(name(dmi("ASUSTeK COMPUTER INC.", "ROG Ally RC71L_RC71L", "1.0", "RC71L")), Some("ASUS ROG Ally"));
+        assert_eq!(name(dmi("ASUSTeK COMPUTER INC.", "ROG Ally X RC72LA_RC72LA", "1.0", "RC72LA")), Some("ASUS ROG Ally X"));
+        assert_eq!(name(dmi("LENOVO", "83E1", "Legion Go 8APU1", "LNVNB161216")), Some("Lenovo Legion Go"));
+        assert_eq!(name(dmi("LENOVO", "83L3", "Legion Go S 8APU1", "LNVNB161216")), Some("Lenovo Legion Go S"));
+        assert_eq!(name(dmi("Micro-Star International Co., Ltd.", "Claw A1M", "REV:1.0", "MS-1T41")), Some("MSI Claw"));
+        assert_eq!(name(dmi("GPD", "G1618-04", "Default string", "G1618-04")), Some("GPD handheld"));
+        assert_eq!(name(dmi("AYANEO", "AIR Plus", "Default string", "AB05-Mendocino")), Some("AYANEO handheld"));
+        assert_eq!(name(dmi("LENOVO", "21CB", "ThinkPad X1 Carbon Gen 10", "21CBCTO1WW")), None);
+
+        let ally = db.lookup(&dmi("ASUSTeK COMPUTER INC.", "ROG Ally RC71L_RC71L", "1.0", "RC71L")).unwrap();
+        assert_eq!(ally.device_type, DeviceType::Handheld);
+        assert!(ally.is_stock_wifi(&["14C3:0616".to_string()]));
+        assert_eq!(ally.power_defaults().get("saver_below_battery_pct"), Some(&toml::Value::Integer(20)));
+
+        let deck = db.lookup(&dmi("Valve", "Galileo", "1", "Galileo")).unwrap();
+        assert_eq!(deck.device_type, DeviceType::SteamDeck);
+        assert_eq!(deck.power_defaults().get("saver_below_minutes_left"), Some(&toml::Value::Integer(30)));
+    }
+
+    #[test]
+    fn test_override_adds_quirks_and_power_defaults() {
+        let overrides = Database::parse(r#"
+            [[device]]
+            name = "ASUS ROG Ally"
+            type = "handheld"
+            vendors = ["ASUSTeK*"]
+            products = ["ROG Ally RC71L*"]
+            wifi = ["14c3:0616"]
+            avoid = ["gro"]
+            power_save = "off"
+
+            [device.power]
+            saver_below_battery_pct = 25
+        "#).unwrap();
+        let db = Database::with_overrides(overrides);
+        let ally = db.lookup(&dmi("ASUSTeK COMPUTER INC.", "ROG Ally RC71L_RC71L", "1.0", "RC71L")).unwrap();
+
+        let defaults = ally.power_defaults();
+        assert_eq!(defaults.get("saver_below_battery_pct"), Some(&toml::Value::Integer(25)));
+        assert_eq!(defaults.get("saver_below_minutes_left"), Some(&toml::Value::Integer(30)));
+
+        let stock = ally.adjust_quirk(None, &["14c3:0616".to_string()]).unwrap();
+        assert!(stock.avoids("gro"));
+        assert!(stock.forbids_power_save());
+        // A replacement card keeps its own quirks
+        assert!(ally.adjust_quirk(None, &["8086:2725".to_string()]).is_none());
+    }
+}
@@ -0,0 +1,371 @@
+//! IRQ placement from CPU topology and load
+//!
+//! Picks the CPUs Wi-Fi/Ethernet interrupts are pinned to: one thread per
+//! physical core (never an SMT sibling of a busy thread), away from the cores
+//! the game is loading, avoiding CPU 0 and isolated CPUs. On ties the lower
+//! capacity core wins, which on hybrid laptops keeps interrupts on E-cores and
+//! the P-cores free for the game.
+//!
+//! irqbalance would move pinned IRQs again, so they are added to its
+//! `--banirq` list instead of only warning about it.
+//!
+//! The Governor re-runs the selection as load changes and moves IRQs and
+//! RPS/XPS steering (`steer`) with it; every value it replaces goes into the
+//! rollback journal first.
+
+use anyhow::{bail, Context, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::network::wifi::WifiInterface;
+use crate::system::rollback::{self, Change, Journal};
+
+const CPU_SYSFS: &str = "/sys/devices/system/cpu";
+
+/// irqbalance environment files, Debian/Arch style first
+pub const IRQBALANCE_CONFIGS: &[&str] = &["/etc/default/irqbalance", "/etc/sysconfig/irqbalance"];
+
+/// Spread multi-vector devices (ath11k MSI-X) over two cores once there are this many
+const SPREAD_MIN_CORES: usize = 4;
+
+/// One logical CPU
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cpu {
+    pub id: usize,
+    /// (package, core) identifying the physical core
+    pub core: (u32, u32),
+    /// Maximum frequency in kHz; 0 if unknown (VMs)
+    pub max_khz: u64,
+    pub isolated: bool,
+}
+
+/// Online CPUs of the machine
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Topology {
+    pub cpus: Vec<Cpu>,
+}
+
+impl Topology {
+    /// Read from sysfs; unknown fields fall back to one core per CPU
+    pub fn read() -> Self {
+        let online = fs::read_to_string(format!("{}/online", CPU_SYSFS))
+            .map(|list| parse_cpu_list(&list))
+            .unwrap_or_default();
+        let isolated = fs::read_to_string(format!("{}/isolated", CPU_SYSFS))
+            .map(|list| parse_cpu_list(&list))
+            .unwrap_or_default();
+
+        let cpus = online.into_iter()
+            .map(|id| {
+                let read = |file: &str| fs::read_to_string(format!("{}/cpu{}/{}", CPU_SYSFS, id, file))
+                    .ok()
+                    .and_then(|v| v.trim().parse::<u64>().ok());
+                Cpu {
+                    id,
+                    core: (
+                        read("topology/physical_package_id").unwrap_or(0) as u32,
+                        read("topology/core_id").unwrap_or(id as u64) as u32,
+                    ),
+                    max_khz: read("cpufreq/cpuinfo_max_freq").unwrap_or(0),
+                    isolated: isolated.contains(&id),
+                }
+            })
+            .collect();
+        Self { cpus }
+    }
+
+    /// Load of the busiest thread on `cpu`'s physical core
+    /// A core is only as free as its busiest SMT sibling.
+    pub fn core_load(&self, cpu: usize, load: &BTreeMap<usize, f64>) -> f64 {
+        let Some(core) = self.cpus.iter().find(|c| c.id == cpu).map(|c| c.core) else {
+            return load.get(&cpu).copied().unwrap_or(0.0);
+        };
+        self.cpus.iter()
+            .filter(|c| c.core == core)
+            .map(|c| load.get(&c.id).copied().unwrap_or(0.0))
+            .fold(0.0, f64::max)
+    }
+
+    /// CPU for each of `vectors` IRQs, given per-CPU load (0.0-1.0)
+    /// Empty when pinning cannot help (a single usable CPU).
+    pub fn select(&self, load: &BTreeMap<usize, f64>, vectors: usize) -> Vec<usize> {
+        // One usable thread per core
+        let mut cores: Vec<&Cpu> = Vec::new();
+        for cpu in self.cpus.iter().filter(|c| !c.isolated) {
+            if !cores.iter().any(|c| c.core == cpu.core) {
+                cores.push(cpu);
+            }
+        }
+        if self.cpus.iter().filter(|c| !c.isolated).count() < 2 || vectors == 0 {
+            return Vec::new();
+        }
+
+        let has_cpu0 = |core: (u32, u32)| self.cpus.iter().any(|c| c.id == 0 && c.core == core);
+        cores.sort_by_key(|cpu| {
+            // 10% load buckets so noise doesn't outweigh the tie-breaks;
+            // CPU 0 carries housekeeping work, so its core counts one bucket busier
+            let bucket = (self.core_load(cpu.id, load) * 10.0).round() as u32 + has_cpu0(cpu.core) as u32;
+            (bucket, cpu.max_khz, cpu.id)
+        });
+
+        let spread = if vectors > 1 && cores.len() >= SPREAD_MIN_CORES { 2 } else { 1 };
+        (0..vectors).map(|i| cores[i % spread].id).collect()
+    }
+}
+
+/// IRQ numbers belonging to `ifc`, from /proc/interrupts
+pub fn find_irqs(ifc: &WifiInterface) -> Result<Vec<String>> {
+    // Read /proc/interrupts to find the Wi-Fi IRQ(s)
+    let interrupts = fs::read_to_string("/proc/interrupts")
+        .context("Failed to read /proc/interrupts")?;
+
+    // Some drivers report different names in /proc/interrupts (rtl8192ee as
+    // "rtl_pci", ath11k's MSI-X vectors as wcn/mhi/...); the quirk database
+    // lists them
+    let mut search_terms: Vec<&str> = vec![ifc.driver.as_str(), &ifc.name];
+    if let Some(quirk) = &ifc.quirk {
+        search_terms.extend(quirk.irq_names.iter().map(String::as_str));
+    }
+
+    // Find ALL matching IRQs (important for MSI-X drivers like ath11k)
+    let irqs = interrupts.lines()
+        .filter(|line| {
+            let lower = line.to_lowercase();
+            search_terms.iter().any(|term| lower.contains(&term.to_lowercase()))
+        })
+        .filter_map(|line| line.trim().split(':').next())
+        .map(|s| s.trim().to_string())
+        .collect();
+
+    Ok(irqs)
+}
+
+/// CPUs the IRQs of `interface`'s device are currently handled on
+/// MSI vectors from sysfs, else the legacy IRQ; empty for virtual devices
+pub fn current_cpus(interface: &str) -> Vec<usize> {
+    let device = format!("/sys/class/net/{}/device", interface);
+    let mut irqs: Vec<String> = fs::read_dir(format!("{}/msi_irqs", device))
+        .map(|entries| entries.flatten().map(|e| e.file_name().to_string_lossy().to_string()).collect())
+        .unwrap_or_default();
+    if irqs.is_empty() {
+        irqs.extend(fs::read_to_string(format!("{}/irq", device)).ok().map(|irq| irq.trim().to_string()));
+    }
+
+    let mut cpus: Vec<usize> = irqs.iter()
+        .filter_map(|irq| {
+            fs::read_to_string(format!("/proc/irq/{}/effective_affinity_list", irq))
+                .or_else(|_| fs::read_to_string(format!("/proc/irq/{}/smp_affinity_list", irq)))
+                .ok()
+        })
+        .flat_map(|list| parse_cpu_list(&list))
+        .collect();
+    cpus.sort_unstable();
+    cpus.dedup();
+    cpus
+}
+
+/// Parse a sysfs CPU list such as "0-3,6,8-9"
+pub fn parse_cpu_list(list: &str) -> Vec<usize> {
+    list.trim()
+        .split(',')
+        .filter(|part| !part.is_empty())
+        .flat_map(|part| {
+            let (start, end) = part.split_once('-').unwrap_or((part, part));
+            match (start.trim().parse::<usize>(), end.trim().parse::<usize>()) {
+                (Ok(start), Ok(end)) => start..end + 1,
+                _ => 0..0,
+            }
+        })
+        .collect()
+}
+
+/// `smp_affinity` mask for `cpus`: hex in comma separated 32-bit groups
+pub fn affinity_mask(cpus: &[usize]) -> String {
+    let groups = cpus.iter().max().map_or(1, |max| max / 32 + 1);
+    let mut words = vec![0u32; groups];
+    for cpu in cpus {
+        words[cpu / 32] |= 1 << (cpu % 32);
+    }
+    words.iter().rev()
+        .enumerate()
+        .map(|(i, w)| if i == 0 { format!("{:x}", w) } else { format!("{:08x}", w) })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Move `ifc`'s IRQs and RPS/XPS steering to `cpus` (Governor, at runtime)
+/// IRQ vectors are spread round-robin; every queue gets the whole set.
+pub fn steer(ifc: &WifiInterface, cpus: &[usize]) -> Result<()> {
+    if cpus.is_empty() {
+        return Ok(());
+    }
+    rollback::with_runtime_journal(|journal| steer_recorded(ifc, cpus, journal))
+}
+
+fn steer_recorded(ifc: &WifiInterface, cpus: &[usize], journal: &mut Journal) -> Result<()> {
+    let mut failed = 0;
+    for (irq, cpu) in find_irqs(ifc)?.iter().zip(cpus.iter().cycle()) {
+        journal.record("governor", Change::irq_affinity(irq));
+        if let Err(e) = fs::write(format!("/proc/irq/{}/smp_affinity", irq), affinity_mask(&[*cpu])) {
+            debug!("Failed to move IRQ {} to CPU {}: {}", irq, cpu, e);
+            failed += 1;
+        }
+    }
+
+    let mask = affinity_mask(cpus);
+    for file in queue_mask_files(&ifc.name) {
+        journal.record("governor", Change::queue_mask(&ifc.name, &file));
+        if let Err(e) = fs::write(format!("/sys/class/net/{}/queues/{}", ifc.name, file), &mask) {
+            // XPS is refused on some single-queue drivers
+            debug!("Failed to set {} on {}: {}", file, ifc.name, e);
+        }
+    }
+
+    if failed > 0 {
+        bail!("{} IRQ(s) of {} could not be moved", failed, ifc.name);
+    }
+    Ok(())
+}
+
+/// RPS and XPS mask files of `interface`, relative to its `queues` directory
+fn queue_mask_files(interface: &str) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(format!("/sys/class/net/{}/queues", interface)) else {
+        return Vec::new();
+    };
+    let mut files: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|queue| {
+            let file = if queue.starts_with("rx-") { "rps_cpus" } else if queue.starts_with("tx-") { "xps_cpus" } else { return None };
+            Some(format!("{}/{}", queue, file))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Whether the irqbalance daemon is running
+pub fn irqbalance_running() -> bool {
+    Command::new("pgrep").arg("irqbalance").output().map(|o| o.status.success()).unwrap_or(false)
+}
+
+/// Keep irqbalance from moving `irqs`, restarting it if its config changed
+pub fn ban_in_irqbalance(irqs: &[String], journal: &mut Journal) -> Result<()> {
+    let path = IRQBALANCE_CONFIGS.iter()
+        .map(Path::new)
+        .find(|p| p.exists())
+        .or_else(|| IRQBALANCE_CONFIGS.iter().map(Path::new).find(|p| p.parent().is_some_and(Path::exists)));
+    let Some(path) = path else {
+        warn!("irqbalance is running but no config location was found; it may move pinned IRQs");
+        return Ok(());
+    };
+
+    let content = fs::read_to_string(path).unwrap_or_default();
+    let Some(updated) = ban_irqs(&content, irqs) else {
+        debug!("irqbalance already skips IRQs {:?}", irqs);
+        return Ok(());
+    };
+
+    journal.record("irq", Change::file(path));
+    fs::write(path, updated)?;
+    let _ = Command::new("systemctl").args(["try-restart", "irqbalance.service"]).output();
+    info!("Told irqbalance to leave IRQs {} alone ({})", irqs.join(","), path.display());
+    Ok(())
+}
+
+/// Add `--banirq=N` for each IRQ to `IRQBALANCE_ARGS`; None if all are present
+fn ban_irqs(content: &str, irqs: &[String]) -> Option<String> {
+    let is_args = |line: &str| line.trim_start().starts_with("IRQBALANCE_ARGS=");
+    let current = content.lines()
+        .find(|l| is_args(l))
+        .map(|l| l.trim_start()["IRQBALANCE_ARGS=".len()..].trim().trim_matches(['"', '\'']).to_string())
+        .unwrap_or_default();
+
+    let mut args: Vec<String> = current.split_whitespace().map(str::to_string).collect();
+    let missing: Vec<String> = irqs.iter()
+        .map(|irq| format!("--banirq={}", irq))
+        .filter(|ban| !args.contains(ban))
+        .collect();
+    if missing.is_empty() {
+        return None;
+    }
+    args.extend(missing);
+
+    let line = format!("IRQBALANCE_ARGS=\"{}\"", args.join(" "));
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    match lines.iter().position(|l| is_args(l)) {
+        Some(i) => lines[i] = line,
+        None => {
+            lines.push("# Wi-Fi IRQs pinned by hifi-wifi".to_string());
+            lines.push(line);
+        }
+    }
+    Some(lines.join("\n") + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpu(id: usize, core: u32, max_khz: u64) -> Cpu {
+        Cpu { id, core: (0, core), max_khz, isolated: false }
+    }
+
+    #[test]
+    fn test_select_avoids_busy_siblings_and_cpu0() {
+        // 4 cores with SMT: cpu N and N+4 are siblings
+        let topo = Topology { cpus: (0..8).map(|id| cpu(id, id as u32 % 4, 3_500_000)).collect() };
+
+        let idle = BTreeMap::new();
+        assert_eq!(topo.select(&idle, 1), vec![1]);
+
+        // Game on cpu 1 and 2 → their siblings 5 and 6 are off limits too
+        let load = BTreeMap::from([(1, 0.9), (2, 0.8)]);
+        assert_eq!(topo.select(&load, 1), vec![3]);
+
+        // ath11k: many vectors over two free cores
+        assert_eq!(topo.select(&load, 4), vec![3, 0, 3, 0]);
+    }
+
+    #[test]
+    fn test_select_hybrid_and_small_machines() {
+        // 2 P-cores with SMT (0-3), 4 E-cores (4-7)
+        let mut cpus: Vec<Cpu> = (0..4).map(|id| cpu(id, id as u32 / 2, 4_700_000)).collect();
+        cpus.extend((4..8).map(|id| cpu(id, id as u32, 3_400_000)));
+        let topo = Topology { cpus };
+        assert_eq!(topo.select(&BTreeMap::new(), 1), vec![4]);
+
+        let single = Topology { cpus: vec![cpu(0, 0, 0)] };
+        assert!(single.select(&BTreeMap::new(), 1).is_empty());
+
+        let mut isolated = Topology { cpus: (0..4).map(|id| cpu(id, id as u32, 0)).collect() };
+        isolated.cpus[1].isolated = true;
+        assert_eq!(isolated.select(&BTreeMap::new(), 1), vec![2]);
+    }
+
+    #[test]
+    fn test_cpu_list_and_mask() {
+        assert_eq!(parse_cpu_list("0-3,6,8-9\n"), vec![0, 1, 2, 3, 6, 8, 9]);
+        assert_eq!(parse_cpu_list("\n"), Vec::<usize>::new());
+        assert_eq!(affinity_mask(&[1]), "2");
+        assert_eq!(affinity_mask(&[3, 4]), "18");
+        assert_eq!(affinity_mask(&[33]), "2,00000000");
+    }
+
+    #[test]
+    fn test_ban_irqs() {
+        let irqs = vec!["130".to_string(), "131".to_string()];
+        let debian = "# Options\n#IRQBALANCE_ONESHOT=\nIRQBALANCE_ARGS=\"--policyscript=/x\"\n";
+        assert_eq!(
+            ban_irqs(debian, &irqs).unwrap(),
+            "# Options\n#IRQBALANCE_ONESHOT=\nIRQBALANCE_ARGS=\"--policyscript=/x --banirq=130 --banirq=131\"\n"
+        );
+
+        let updated = ban_irqs("", &irqs).unwrap();
+        assert!(updated.ends_with("IRQBALANCE_ARGS=\"--banirq=130 --banirq=131\"\n"));
+        assert_eq!(ban_irqs(&updated, &irqs), None);
+    }
+}
@@ -1,3 +1,10 @@
 pub mod power;
 pub mod optimizer;
 pub mod cpu;
+pub mod irq;
+pub mod rollback;
+pub mod sysctl;
+pub mod quirks;
+pub mod devices;
+pub mod sleep;
+pub mod thermal;
@@ -2,20 +2,48 @@
 //!
 //! Handles sysctl tuning, driver module parameters, IRQ affinity, and ethtool settings.
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 use log::{info, warn, debug};
+use std::collections::BTreeMap;
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::Path;
 use std::process::Command;
+use std::time::Duration;
+
+use crate::config::structs::SysctlProfile;
+use crate::network::wifi::{WifiInterface, InterfaceType};
+use crate::system::cpu;
+use crate::system::irq::{self, Topology};
+use crate::system::quirks;
+use crate::system::rollback::{Change, Journal};
+use crate::system::sysctl;
+
+/// How long per-CPU load is sampled before choosing IRQ CPUs
+const IRQ_LOAD_SAMPLE: Duration = Duration::from_millis(500);
+
+/// Topology and current per-CPU load, read once per apply
+struct IrqPlacement {
+    topology: Topology,
+    load: BTreeMap<usize, f64>,
+}
+
+impl IrqPlacement {
+    fn sample() -> Self {
+        Self { topology: Topology::read(), load: cpu::per_cpu_load(IRQ_LOAD_SAMPLE) }
+    }
+}
 
-use crate::network::wifi::{DriverCategory, WifiInterface, InterfaceType};
+const SYSCTL_CONF: &str = "/etc/sysctl.d/99-hifi-wifi.conf";
 
 /// System optimizer for kernel and driver tuning
 pub struct SystemOptimizer {
     sysctl_enabled: bool,
     irq_affinity_enabled: bool,
     driver_tweaks_enabled: bool,
+    sysctl_profile: SysctlProfile,
+    /// User keys added to / overriding the profile
+    sysctl_extra: BTreeMap<String, String>,
 }
 
 impl SystemOptimizer {
@@ -24,63 +52,75 @@ impl SystemOptimizer {
             sysctl_enabled: sysctl,
             irq_affinity_enabled: irq,
             driver_tweaks_enabled: driver,
+            sysctl_profile: SysctlProfile::default(),
+            sysctl_extra: BTreeMap::new(),
         }
     }
 
-    /// Apply all system optimizations
-    pub fn apply(&self, interfaces: &[WifiInterface]) -> Result<()> {
+    /// Use a sysctl profile other than `latency`, plus user keys
+    pub fn with_sysctl(mut self, profile: SysctlProfile, extra: BTreeMap<String, String>) -> Self {
+        self.sysctl_profile = profile;
+        self.sysctl_extra = extra;
+        self
+    }
+
+    /// Apply all system optimizations, recording prior state in `journal`
+    pub fn apply(&self, interfaces: &[WifiInterface], journal: &mut Journal) -> Result<()> {
         if self.sysctl_enabled {
-            self.apply_sysctl_tuning()?;
+            self.apply_sysctl_tuning(interfaces, journal)?;
+        } else {
+            self.restore_sysctl(journal);
         }
 
         if self.driver_tweaks_enabled {
             for ifc in interfaces {
-                self.apply_driver_config(&ifc.category)?;
+                self.apply_driver_config(ifc, journal)?;
             }
         }
 
         if self.irq_affinity_enabled {
+            let placement = IrqPlacement::sample();
             for ifc in interfaces {
-                self.optimize_irq_affinity(ifc)?;
+                self.optimize_irq_affinity(ifc, &placement, journal)?;
             }
         }
 
         // Apply ethtool optimizations
         for ifc in interfaces {
-            self.apply_ethtool_settings(ifc)?;
+            self.apply_ethtool_settings(ifc, journal)?;
         }
 
         Ok(())
     }
 
     /// Apply sysctl tuning for network performance
-    fn apply_sysctl_tuning(&self) -> Result<()> {
-        info!("Applying sysctl network optimizations...");
-
-        let settings = [
-            ("net.ipv4.tcp_congestion_control", "bbr"),
-            ("net.core.rmem_default", "262144"),
-            ("net.core.wmem_default", "262144"),
-            ("net.core.rmem_max", "4194304"),
-            ("net.core.wmem_max", "4194304"),
-            ("net.ipv4.tcp_rmem", "4096 131072 4194304"),
-            ("net.ipv4.tcp_wmem", "4096 65536 4194304"),
-            ("net.ipv4.tcp_fastopen", "3"),
-            ("net.core.netdev_max_backlog", "2000"),
-            ("net.ipv4.tcp_ecn", "1"),
-            ("net.ipv4.tcp_keepalive_time", "60"),
-            ("net.ipv4.tcp_keepalive_intvl", "10"),
-            ("net.ipv4.tcp_keepalive_probes", "6"),
-            ("net.ipv4.tcp_tw_reuse", "1"),
-            // Note: NAPI busy polling (busy_poll/busy_read) was removed because it causes
-            // IRQ handling issues with the ath11k driver (Steam Deck OLED WCN6855).
-        ];
-
-        let sysctl_path = Path::new("/etc/sysctl.d/99-hifi-wifi.conf");
-        let mut config_content = String::from("# hifi-wifi Network Optimizations\n");
-        for (key, val) in settings.iter() {
-            config_content.push_str(&format!("{} = {}\n", key, val));
+    fn apply_sysctl_tuning(&self, interfaces: &[WifiInterface], journal: &mut Journal) -> Result<()> {
+        info!("Applying sysctl network optimizations (profile: {})...", self.sysctl_profile);
+
+        let mut settings = sysctl::supported(sysctl::resolve(self.sysctl_profile, &self.sysctl_extra));
+        settings.retain(|(key, _)| {
+            let avoided_by = interfaces.iter()
+                .find(|ifc| ifc.quirk.as_ref().is_some_and(|q| q.avoids_sysctl(key)));
+            if let Some(ifc) = avoided_by {
+                warn!("Skipping sysctl {}: known to misbehave with {} ({})", key, ifc.driver, ifc.name);
+            }
+            avoided_by.is_none()
+        });
+
+        // Keys an earlier profile set but this one doesn't go back to their original values
+        let dropped = |change: &Change| matches!(change,
+            Change::Sysctl { key, .. } if !settings.iter().any(|(k, _)| k == key));
+        if let Err(e) = journal.revert_matching(dropped) {
+            warn!("Failed to restore sysctls dropped from the profile: {}", e);
+        }
+
+        let sysctl_path = Path::new(SYSCTL_CONF);
+        journal.record("sysctl", Change::file(sysctl_path));
+        for (key, _) in &settings {
+            journal.record("sysctl", Change::sysctl(key));
         }
+
+        let config_content = sysctl::config_file(self.sysctl_profile, &settings);
         
         // Try to persist to file (best effort)
         let persistence_success = if let Some(parent) = sysctl_path.parent() {
@@ -106,7 +146,7 @@ impl SystemOptimizer {
         // If persistence worked, use 'sysctl -p'. Otherwise, apply manually.
         if persistence_success {
              let output = Command::new("sysctl")
-                .args(["-p", sysctl_path.to_str().unwrap()])
+                .args(["-p", SYSCTL_CONF])
                 .output();
              if let Ok(o) = output {
                  if !o.status.success() {
@@ -119,87 +159,35 @@ impl SystemOptimizer {
         }
 
         // Fallback: Apply manually
-        info!("Applying sysctl settings transiently (runtime only)...");
-        for (key, val) in settings.iter() {
-             let _ = Command::new("sysctl")
-                .arg("-w")
-                .arg(format!("{}={}", key, val))
-                .status();
-        }
+        sysctl::write_runtime(&settings);
 
         Ok(())
     }
 
-    /// Apply driver-specific module parameters
-    /// 
-    /// References:
-    /// - RTW89: https://github.com/lwfinger/rtw89 (disable_aspm_l1, disable_aspm_l1ss for HP/Lenovo)
-    /// - MT7921: https://wiki.archlinux.org/title/Network_configuration/Wireless#mt7921_/_mt7922
-    /// - iwlwifi: https://wiki.archlinux.org/title/Power_management#Intel_wireless_cards_(iwlwifi)
-    /// - ath11k: Steam Deck OLED WCN6855 - limited params, kernel handles most
-    fn apply_driver_config(&self, category: &DriverCategory) -> Result<()> {
-        let (filename, config) = match category {
-            DriverCategory::Rtw89 => ("rtw89.conf", r#"# Realtek RTW89 optimizations (RTL8851BE/RTL8852AE/RTL8852BE/RTL8852CE)
-# Disables PCIe Active State Power Management for stability
-# Required for HP/Lenovo laptops with buggy BIOS PCIe implementations
-options rtw89_pci disable_aspm_l1=y disable_aspm_l1ss=y
-# Disable firmware-level power save for consistent low latency
-options rtw89_core disable_ps_mode=y
-"#),
-            DriverCategory::Rtw88 => ("rtw88.conf", r#"# Realtek RTW88 optimizations (RTL8822CE - Steam Deck LCD)
-# Disables PCIe ASPM for stability and lower latency
-options rtw88_pci disable_aspm=1
-# Disables deep low-power states that cause reconnection issues
-options rtw88_core disable_lps_deep=Y
-"#),
-            DriverCategory::RtlLegacy => ("rtl_legacy.conf", r#"# Legacy Realtek optimizations (RTL8192EE/RTL8188EE)
-# swenc=1: Software encryption (more stable on some chips)
-# ips=0: Disable inactive power save
-# fwlps=0: Disable firmware low-power state
-options rtl8192ee swenc=1 ips=0 fwlps=0
-options rtl8188ee swenc=1 ips=0 fwlps=0
-options rtl_pci disable_aspm=1
-"#),
-            DriverCategory::MediaTek => ("mediatek.conf", r#"# MediaTek optimizations (MT7921/MT7922/MT76)
-# Fixes high latency issues documented in Arch Wiki
-options mt7921e disable_aspm=1
-# Disable USB scatter-gather for better stability on USB adapters
-options mt76_usb disable_usb_sg=1
-"#),
-            DriverCategory::Intel => ("iwlwifi.conf", r#"# Intel Wi-Fi optimizations (AX200/AX201/AX210/AX211/BE200)
-# power_save=0: Disable driver-level power saving
-# uapsd_disable=1: Disable U-APSD (unscheduled automatic power save delivery)
-#   - U-APSD can cause latency spikes during gaming
-options iwlwifi power_save=0 uapsd_disable=1
-# power_scheme=1: "Always Active" mode (vs 2=Balanced, 3=Low-power)
-# Prevents WiFi card disappearing on battery or after suspend
-options iwlmvm power_scheme=1
-"#),
-            DriverCategory::Atheros => ("ath_wifi.conf", r#"# Qualcomm Atheros optimizations
-# ath11k: Steam Deck OLED (WCN6855) and other WiFi 6E chips
-# disable_aspm=1: Prevents latency spikes from PCIe power transitions
-options ath11k_pci disable_aspm=1
-# ath9k: Legacy 802.11n chips (AR9285/AR9287/etc)
-# ps_enable=0: Disable hardware power save
-options ath9k ps_enable=0
-"#),
-            DriverCategory::Broadcom => ("broadcom.conf", r#"# Broadcom optimizations
-options brcmfmac roamoff=1
-options wl interference=0
-"#),
-            DriverCategory::Ralink => ("ralink.conf", r#"# Ralink/MediaTek Legacy optimizations
-options rt2800usb nohwcrypt=0
-options rt2800pci nohwcrypt=0
-"#),
-            DriverCategory::Marvell => ("marvell.conf", r#"# Marvell optimizations
-options mwifiex disable_auto_ds=1
-"#),
-            DriverCategory::Generic => ("wifi_generic.conf", r#"# Universal Wi-Fi optimizations
-# Applied for unknown drivers
-"#),
+    /// Put back every sysctl hifi-wifi changed (sysctl tuning turned off)
+    fn restore_sysctl(&self, journal: &mut Journal) {
+        let ours = |change: &Change| match change {
+            Change::Sysctl { .. } => true,
+            Change::File { path, .. } => path == Path::new(SYSCTL_CONF),
+            _ => false,
         };
+        if let Err(e) = journal.revert_matching(ours) {
+            warn!("Failed to restore sysctls: {}", e);
+        }
+    }
+
+    /// Write the modprobe options the quirk database has for the interface's driver
+    fn apply_driver_config(&self, ifc: &WifiInterface, journal: &mut Journal) -> Result<()> {
+        let Some(quirk) = &ifc.quirk else {
+            debug!("No driver quirks for {} (driver: {})", ifc.name, ifc.driver);
+            return Ok(());
+        };
+        let Some(filename) = &quirk.modprobe_file else {
+            return Ok(());
+        };
+        let config = &quirk.modprobe;
 
-        info!("Applying {:?} driver configuration...", category);
+        info!("Applying {} driver configuration...", quirk.name);
 
         let modprobe_path = Path::new("/etc/modprobe.d").join(filename);
         
@@ -207,6 +195,7 @@ options mwifiex disable_auto_ds=1
             fs::create_dir_all(parent).ok();
         }
 
+        journal.record("driver", Change::file(&modprobe_path));
         match File::create(&modprobe_path) {
             Ok(mut file) => {
                 if let Err(e) = file.write_all(config.as_bytes()) {
@@ -225,61 +214,48 @@ options mwifiex disable_auto_ds=1
     }
 
     /// Optimize IRQ affinity for Wi-Fi adapter
-    fn optimize_irq_affinity(&self, ifc: &WifiInterface) -> Result<()> {
+    fn optimize_irq_affinity(&self, ifc: &WifiInterface, placement: &IrqPlacement, journal: &mut Journal) -> Result<()> {
         info!("Optimizing IRQ affinity for {}", ifc.name);
 
-        // Check for irqbalance
-        if Command::new("pgrep").arg("irqbalance").output().map(|o| o.status.success()).unwrap_or(false) {
-            warn!("'irqbalance' daemon detected! It may undo Wi-Fi IRQ pinning.");
-            // We proceed anyway, but the warning is crucial for debugging
+        let irqs = irq::find_irqs(ifc)?;
+
+        if irqs.is_empty() {
+            debug!("Could not find IRQ for {} (driver: {})", ifc.name, ifc.driver);
+            return Ok(());
         }
 
-        // Read /proc/interrupts to find the Wi-Fi IRQ(s)
-        let interrupts = fs::read_to_string("/proc/interrupts")
-            .context("Failed to read /proc/interrupts")?;
-
-        // Special mappings for drivers that report different names in /proc/interrupts
-        // - rtl8192ee reports as "rtl_pci" 
-        // - rtw88_8822ce (Steam Deck LCD) may show as rtw88, rtw_pci, or interface name
-        // - ath11k uses MSI-X with multiple IRQ vectors (ath11k_pci:base, DP, CE0-CE11, MHI)
-        // - Steam Deck OLED (WCN6855) may show as wcn, ath11k, or other variants
-        let search_terms: Vec<&str> = match ifc.driver.as_str() {
-            "rtl8192ee" => vec!["rtl_pci"],
-            "rtw88_8822ce" | "rtw88_pci" | "rtw_pci" => vec!["rtw88", "rtw_pci", &ifc.name],
-            "ath11k_pci" | "ath11k" => vec!["ath11k", "wcn", "mhi", "bhi", &ifc.name],  // WCN6855 variants
-            _ => vec![ifc.driver.as_str(), &ifc.name],
-        };
+        let cpus = placement.topology.select(&placement.load, irqs.len());
+        if cpus.is_empty() {
+            info!("Only one usable CPU - leaving {} IRQs unpinned", ifc.name);
+            return Ok(());
+        }
 
-        // Find ALL matching IRQs (important for MSI-X drivers like ath11k)
-        let irqs: Vec<String> = interrupts.lines()
-            .filter(|line| {
-                let lower = line.to_lowercase();
-                search_terms.iter().any(|term| lower.contains(&term.to_lowercase())) || lower.contains(&ifc.name.to_lowercase())
-            })
-            .filter_map(|line| line.trim().split(':').next())
-            .map(|s| s.trim().to_string())
-            .collect();
+        // Pin ALL matching IRQs (MSI-X vectors are spread if there are enough cores)
+        let mut pinned = Vec::new();
+        for (irq_num, cpu) in irqs.iter().zip(&cpus) {
+            let affinity_path = format!("/proc/irq/{}/smp_affinity", irq_num);
+            journal.record("irq", Change::irq_affinity(irq_num));
 
-        if irqs.is_empty() {
-            debug!("Could not find IRQ for {} (driver: {})", ifc.name, ifc.driver);
-        } else {
-            // Pin ALL matching IRQs to CPU 1
-            let mut pinned = 0;
-            for irq_num in &irqs {
-                let affinity_path = format!("/proc/irq/{}/smp_affinity", irq_num);
-                
-                // Bind to CPU 1 (affinity mask 0x2)
-                if let Err(e) = fs::write(&affinity_path, "2") {
-                    warn!("Failed to set IRQ affinity for {}: {}", irq_num, e);
-                } else {
-                    pinned += 1;
-                }
-            }
-            
-            if irqs.len() > 1 {
-                info!("Wi-Fi {} IRQs bound to CPU 1 ({} vectors)", pinned, irqs.len());
+            if let Err(e) = fs::write(&affinity_path, irq::affinity_mask(&[*cpu])) {
+                warn!("Failed to set IRQ affinity for {}: {}", irq_num, e);
             } else {
-                info!("Wi-Fi IRQ {} bound to CPU 1", irqs[0]);
+                pinned.push(irq_num.clone());
+            }
+        }
+
+        let mut targets = cpus.clone();
+        targets.sort_unstable();
+        targets.dedup();
+        if irqs.len() > 1 {
+            info!("Wi-Fi {} IRQs bound to CPU {:?} ({} vectors)", pinned.len(), targets, irqs.len());
+        } else {
+            info!("Wi-Fi IRQ {} bound to CPU {}", irqs[0], cpus[0]);
+        }
+
+        // irqbalance would move them again
+        if !pinned.is_empty() && irq::irqbalance_running() {
+            if let Err(e) = irq::ban_in_irqbalance(&pinned, journal) {
+                warn!("Failed to update irqbalance config: {} - it may undo Wi-Fi IRQ pinning", e);
             }
         }
 
@@ -287,13 +263,21 @@ options mwifiex disable_auto_ds=1
     }
 
     /// Apply ethtool optimizations
-    fn apply_ethtool_settings(&self, ifc: &WifiInterface) -> Result<()> {
+    fn apply_ethtool_settings(&self, ifc: &WifiInterface, journal: &mut Journal) -> Result<()> {
         debug!("Applying ethtool settings for {}", ifc.name);
+        // Disable TSO/GSO for all interfaces (reduces latency, CAKE handles segmentation),
+        // leaving alone any offload the driver's quirks say to avoid
+        let offloads: Vec<(&str, &str)> = [("tso", "off"), ("gso", "off"), ("gro", "on")].into_iter()
+            .filter(|(feature, _)| !ifc.quirk.as_ref().is_some_and(|q| q.avoids(feature)))
+            .collect();
+        let features: Vec<&str> = offloads.iter().map(|(feature, _)| *feature).collect();
+        journal.record("ethtool", Change::offloads(&ifc.name, &features));
 
-        // Disable TSO/GSO for all interfaces (reduces latency, CAKE handles segmentation)
-        let _ = Command::new("ethtool")
-            .args(["-K", &ifc.name, "tso", "off", "gso", "off", "gro", "on"])
-            .output();
+        if !offloads.is_empty() {
+            let mut args = vec!["-K", ifc.name.as_str()];
+            args.extend(offloads.iter().flat_map(|(feature, state)| [*feature, *state]));
+            let _ = Command::new("ethtool").args(&args).output();
+        }
 
         // Ethernet-specific optimizations for streaming/gaming
         if ifc.interface_type == InterfaceType::Ethernet {
@@ -301,6 +285,7 @@ options mwifiex disable_auto_ds=1
             
             // Disable Energy Efficient Ethernet (EEE) - causes micro-stutters in streaming
             // EEE puts the link into low-power state between packets, causing 50-200us wakeup latency
+            journal.record("ethtool", Change::eee(&ifc.name));
             let eee_result = Command::new("ethtool")
                 .args(["--set-eee", &ifc.name, "eee", "off"])
                 .output();
@@ -321,6 +306,7 @@ options mwifiex disable_auto_ds=1
             // Set initial low-latency coalescing defaults for ethernet
             // The governor will dynamically adjust this based on CPU load
             // rx-usecs=0, rx-frames=1 means "interrupt immediately on every packet"
+            journal.record("ethtool", Change::coalescing(&ifc.name));
             let coal_result = Command::new("ethtool")
                 .args(["-C", &ifc.name, "rx-usecs", "0", "rx-frames", "1", "tx-usecs", "0", "tx-frames", "1"])
                 .output();
@@ -342,21 +328,33 @@ options mwifiex disable_auto_ds=1
         Ok(())
     }
 
-    /// Revert all system optimizations
-    pub fn revert(&self) -> Result<()> {
+    /// Remove every file hifi-wifi may have created and reset IRQ pinning and
+    /// offloads to kernel defaults, for installs without a rollback journal
+    pub fn revert(&self, interfaces: &[WifiInterface]) -> Result<()> {
         info!("Reverting system optimizations...");
 
-        // Remove sysctl config
-        let _ = fs::remove_file("/etc/sysctl.d/99-hifi-wifi.conf");
+        let default_mask = fs::read_to_string("/proc/irq/default_smp_affinity")
+            .map(|mask| mask.trim().to_string())
+            .ok();
+        for ifc in interfaces {
+            if let Some(mask) = &default_mask {
+                for irq in irq::find_irqs(ifc).unwrap_or_default() {
+                    if let Err(e) = fs::write(format!("/proc/irq/{}/smp_affinity", irq), mask) {
+                        debug!("Failed to reset affinity of IRQ {}: {}", irq, e);
+                    }
+                }
+            }
+
+            let _ = Command::new("ethtool")
+                .args(["-K", &ifc.name, "tso", "on", "gso", "on"])
+                .output();
+        }
 
-        // Remove modprobe configs (list all possible files)
-        let modprobe_files = [
-            "rtw89.conf", "rtw88.conf", "rtl_legacy.conf", "mediatek.conf",
-            "iwlwifi.conf", "ath_wifi.conf", "broadcom.conf", "ralink.conf",
-            "marvell.conf", "wifi_generic.conf",
-        ];
+        // Remove sysctl config
+        let _ = fs::remove_file(SYSCTL_CONF);
 
-        for file in modprobe_files {
+        // Remove modprobe configs (every file the quirk database may write)
+        for file in quirks::database().modprobe_files() {
             let path = Path::new("/etc/modprobe.d").join(file);
             let _ = fs::remove_file(path);
         }
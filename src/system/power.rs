@@ -2,9 +2,46 @@
 //!
 //! Adaptive power management based on AC/battery status.
 
-use log::info;
+use anyhow::{Context, Result};
+use futures_lite::StreamExt;
+use log::{debug, info, warn};
+use serde::Deserialize;
 use std::fs;
 use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use zbus::proxy::CacheProperties;
+use zbus::{proxy, Connection};
+
+use crate::system::devices;
+
+#[proxy(
+    interface = "org.freedesktop.UPower",
+    default_service = "org.freedesktop.UPower",
+    default_path = "/org/freedesktop/UPower"
+)]
+trait UPower {
+    #[zbus(property)]
+    fn on_battery(&self) -> zbus::Result<bool>;
+}
+
+/// UPower's composite of all system batteries
+#[proxy(
+    interface = "org.freedesktop.UPower.Device",
+    default_service = "org.freedesktop.UPower",
+    default_path = "/org/freedesktop/UPower/devices/DisplayDevice"
+)]
+trait DisplayDevice {
+    #[zbus(property)]
+    fn is_present(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn percentage(&self) -> zbus::Result<f64>;
+
+    /// Seconds until empty; 0 unless discharging
+    #[zbus(property)]
+    fn time_to_empty(&self) -> zbus::Result<i64>;
+}
 
 /// Power source state
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -14,17 +51,30 @@ pub enum PowerSource {
     Unknown,
 }
 
+impl PowerSource {
+    fn from_on_battery(on_battery: bool) -> Self {
+        if on_battery { PowerSource::Battery } else { PowerSource::AC }
+    }
+}
+
 /// Device type classification
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DeviceType {
     Desktop,
     Laptop,
     SteamDeck,
+    /// Other handheld gaming PCs (ROG Ally, Legion Go, MSI Claw, ...)
+    Handheld,
 }
 
 /// Manages power-aware Wi-Fi settings
 pub struct PowerManager {
     device_type: DeviceType,
+    /// Kept current by UPower once `watch` succeeds; None = read sysfs on every call
+    cached: Option<Arc<Mutex<PowerSource>>>,
+    /// Battery level and time left, kept current by UPower like `cached`
+    display_device: Option<DisplayDeviceProxy<'static>>,
 }
 
 impl PowerManager {
@@ -36,16 +86,17 @@ impl PowerManager {
         
         Self {
             device_type,
+            cached: None,
+            display_device: None,
         }
     }
 
     /// Detect if this is a portable/battery-powered device
     fn detect_device_type() -> DeviceType {
-        // Check for Steam Deck
-        if let Ok(board) = fs::read_to_string("/sys/class/dmi/id/board_name") {
-            if board.trim().contains("Jupiter") || board.trim().contains("Galileo") {
-                return DeviceType::SteamDeck;
-            }
+        // Known models (Steam Deck, handhelds) from the device database
+        if let Some(device) = devices::current() {
+            info!("Device: {}", device.name);
+            return device.device_type.clone();
         }
 
         // Check chassis type
@@ -110,75 +161,52 @@ impl PowerManager {
     /// Detect current power source
     /// FIXED: Collect ALL power supply info first, then decide (prevents race condition)
     pub fn detect_power_source() -> PowerSource {
-        let power_supply = Path::new("/sys/class/power_supply");
-        
-        let mut ac_online = false;
-        let mut battery_discharging = false;
-        let mut battery_found = false;
-        
-        if let Ok(entries) = fs::read_dir(power_supply) {
-            for entry in entries.flatten() {
-                let name = entry.file_name().to_string_lossy().to_string();
-                
-                // Skip peripheral batteries
-                if name.contains("hidpp") || name.contains("hid") || 
-                   name.contains("mouse") || name.contains("keyboard") {
-                    continue;
-                }
-                
-                // Check AC adapters
-                if name.starts_with("AC") || name.starts_with("ADP") || name.contains("ACAD") {
-                    let online_path = entry.path().join("online");
-                    if let Ok(status) = fs::read_to_string(&online_path) {
-                        if status.trim() == "1" {
-                            ac_online = true;
-                        }
-                    }
-                }
+        classify(&read_supplies())
+    }
 
-                // Check battery status
-                if name.starts_with("BAT") || name == "battery" {
-                    battery_found = true;
-                    let status_path = entry.path().join("status");
-                    if let Ok(status) = fs::read_to_string(&status_path) {
-                        let status = status.trim();
-                        match status {
-                            "Charging" | "Full" | "Not charging" => {
-                                // Battery connected to power = AC
-                                ac_online = true;
-                            }
-                            "Discharging" => {
-                                battery_discharging = true;
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-            }
-        }
+    /// Follow UPower's OnBattery property and battery
+    /// From then on `power_source`, `battery_percentage` and
+    /// `battery_minutes_left` answer from cached values instead of walking
+    /// sysfs, and every power source change is sent on the returned channel.
+    pub async fn watch(&mut self) -> Result<mpsc::UnboundedReceiver<PowerSource>> {
+        let connection = Connection::system().await.context("Failed to connect to system D-Bus")?;
+        let upower = UPowerProxy::new(&connection).await?;
+        let mut changes = upower.receive_on_battery_changed().await;
+        let on_battery = upower.on_battery().await.context("UPower is not running")?;
 
-        // AC takes priority - if adapter is online, we're on AC regardless of battery state
-        if ac_online {
-            return PowerSource::AC;
-        }
-        
-        // Only report battery if we found one and it's discharging
-        if battery_found && battery_discharging {
-            return PowerSource::Battery;
-        }
-        
-        // No battery = desktop = treat as AC
-        if !battery_found {
-            return PowerSource::AC;
+        let cached = Arc::new(Mutex::new(PowerSource::from_on_battery(on_battery)));
+        self.cached = Some(cached.clone());
+
+        // Cached up front, then updated from UPower's PropertiesChanged signals
+        match DisplayDeviceProxy::builder(&connection).cache_properties(CacheProperties::Yes).build().await {
+            Ok(device) => self.display_device = Some(device),
+            Err(e) => debug!("UPower DisplayDevice unavailable, reading the battery from sysfs: {}", e),
         }
+        let (tx, rx) = mpsc::unbounded_channel();
 
-        PowerSource::Unknown
+        tokio::spawn(async move {
+            while let Some(change) = changes.next().await {
+                let Ok(on_battery) = change.get().await else {
+                    continue;
+                };
+                let source = PowerSource::from_on_battery(on_battery);
+                let previous = std::mem::replace(&mut *cached.lock().unwrap_or_else(|e| e.into_inner()), source);
+                if previous != source && tx.send(source).is_err() {
+                    break;
+                }
+            }
+            warn!("UPower OnBattery stream ended");
+        });
+
+        Ok(rx)
     }
 
     /// Get current power source (refreshed dynamically)
     pub fn power_source(&self) -> PowerSource {
-        // Always get fresh reading
-        Self::detect_power_source()
+        match &self.cached {
+            Some(cached) => *cached.lock().unwrap_or_else(|e| e.into_inner()),
+            None => Self::detect_power_source(),
+        }
     }
 
     /// Get device type
@@ -189,11 +217,11 @@ impl PowerManager {
     /// Should power saving be enabled based on current state?
     /// FIXED: Now refreshes power source dynamically instead of using cached value
     pub fn should_enable_power_save(&self) -> bool {
-        let current_source = Self::detect_power_source();
+        let current_source = self.power_source();
         
         match self.device_type {
             DeviceType::Desktop => false, // Always performance mode
-            DeviceType::SteamDeck | DeviceType::Laptop => {
+            DeviceType::SteamDeck | DeviceType::Handheld | DeviceType::Laptop => {
                 // Enable power save only when on battery
                 current_source == PowerSource::Battery
             }
@@ -202,22 +230,40 @@ impl PowerManager {
 
     /// Get battery percentage (if available)
     pub fn battery_percentage(&self) -> Option<u32> {
+        if let Some(device) = &self.display_device {
+            device.cached_is_present().ok().flatten().filter(|present| *present)?;
+            return device.cached_percentage().ok().flatten().map(|pct| pct.round() as u32);
+        }
+
         let power_supply = Path::new("/sys/class/power_supply");
         
-        if let Ok(entries) = fs::read_dir(power_supply) {
-            for entry in entries.flatten() {
-                let name = entry.file_name().to_string_lossy().to_string();
-                
-                if name.starts_with("BAT") || name == "battery" {
-                    let capacity_path = entry.path().join("capacity");
-                    if let Ok(capacity) = fs::read_to_string(&capacity_path) {
-                        return capacity.trim().parse().ok();
-                    }
-                }
-            }
+        read_supplies().into_iter()
+            .find(|supply| supply.is_system_battery())
+            .and_then(|supply| {
+                fs::read_to_string(power_supply.join(&supply.name).join("capacity")).ok()
+            })
+            .and_then(|capacity| capacity.trim().parse().ok())
+    }
+
+    /// Minutes until empty at the current discharge rate (None unless discharging)
+    pub fn battery_minutes_left(&self) -> Option<u32> {
+        if let Some(device) = &self.display_device {
+            let seconds = device.cached_time_to_empty().ok().flatten()?;
+            return (seconds > 0).then_some((seconds / 60) as u32);
         }
 
-        None
+        let supply = read_supplies().into_iter()
+            .find(|supply| supply.is_system_battery() && supply.status == "Discharging")?;
+        let path = Path::new("/sys/class/power_supply").join(&supply.name);
+        let read = |attr: &str| -> Option<f64> {
+            fs::read_to_string(path.join(attr)).ok()?.trim().parse().ok()
+        };
+        // Energy in µWh over power in µW, or charge in µAh over current in µA
+        let (left, rate) = match (read("energy_now"), read("power_now")) {
+            (Some(energy), Some(power)) => (energy, power),
+            _ => (read("charge_now")?, read("current_now")?),
+        };
+        (rate > 0.0).then(|| (left / rate.abs() * 60.0) as u32)
     }
 }
 
@@ -226,3 +272,141 @@ impl Default for PowerManager {
         Self::new()
     }
 }
+
+/// One entry of /sys/class/power_supply
+#[derive(Debug, Clone, Default)]
+struct Supply {
+    name: String,
+    /// "Mains", "USB", "Battery", ... (empty on drivers that don't report it)
+    kind: String,
+    /// "Device" for peripherals (mice, controllers), otherwise "System" or empty
+    scope: String,
+    online: bool,
+    /// Battery status ("Charging", "Discharging", ...)
+    status: String,
+}
+
+impl Supply {
+    /// Peripheral batteries and chargers that don't power the system
+    fn is_peripheral(&self) -> bool {
+        self.scope == "Device"
+            || ["hidpp", "hid", "mouse", "keyboard", "wacom"].iter().any(|p| self.name.contains(p))
+    }
+
+    fn is_system_battery(&self) -> bool {
+        !self.is_peripheral()
+            && (self.kind == "Battery"
+                || (self.kind.is_empty() && (self.name.starts_with("BAT") || self.name == "battery")))
+    }
+
+    /// Mains adapters and USB/USB-C PD chargers (ucsi-source-psy-*, tcpm-source-psy-*)
+    fn is_charger(&self) -> bool {
+        if self.is_peripheral() {
+            return false;
+        }
+        if self.kind.is_empty() {
+            return self.name.starts_with("AC") || self.name.starts_with("ADP") || self.name.contains("ACAD");
+        }
+        self.kind == "Mains" || self.kind.starts_with("USB")
+    }
+}
+
+fn read_supplies() -> Vec<Supply> {
+    let Ok(entries) = fs::read_dir("/sys/class/power_supply") else {
+        return Vec::new();
+    };
+    let read = |path: &Path, attr: &str| {
+        fs::read_to_string(path.join(attr)).map(|s| s.trim().to_string()).unwrap_or_default()
+    };
+    entries.flatten()
+        .map(|entry| {
+            let path = entry.path();
+            Supply {
+                name: entry.file_name().to_string_lossy().to_string(),
+                kind: read(&path, "type"),
+                scope: read(&path, "scope"),
+                online: read(&path, "online") == "1",
+                status: read(&path, "status"),
+            }
+        })
+        .collect()
+}
+
+/// Decide the power source from every supply at once
+fn classify(supplies: &[Supply]) -> PowerSource {
+    let mut ac_online = false;
+    let mut battery_discharging = false;
+    let mut battery_found = false;
+
+    for supply in supplies {
+        if supply.is_charger() && supply.online {
+            ac_online = true;
+        }
+        if supply.is_system_battery() {
+            battery_found = true;
+            match supply.status.as_str() {
+                // Battery connected to power = AC
+                "Charging" | "Full" | "Not charging" => ac_online = true,
+                "Discharging" => battery_discharging = true,
+                _ => {}
+            }
+        }
+    }
+
+    // AC takes priority - if adapter is online, we're on AC regardless of battery state
+    if ac_online {
+        return PowerSource::AC;
+    }
+
+    // Only report battery if we found one and it's discharging
+    if battery_found && battery_discharging {
+        return PowerSource::Battery;
+    }
+
+    // No battery = desktop = treat as AC
+    if !battery_found {
+        return PowerSource::AC;
+    }
+
+    PowerSource::Unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supply(name: &str, kind: &str, online: bool, status: &str) -> Supply {
+        Supply {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            online,
+            status: status.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_usb_c_pd_charger_counts_as_ac() {
+        // ROG Ally / Legion Go: no AC*/ADP* supply, only a UCSI USB-C port
+        let mut supplies = vec![
+            supply("BAT0", "Battery", false, "Unknown"),
+            supply("ucsi-source-psy-USBC000:001", "USB", true, ""),
+        ];
+        assert_eq!(classify(&supplies), PowerSource::AC);
+
+        supplies[1].online = false;
+        supplies[0].status = "Discharging".to_string();
+        assert_eq!(classify(&supplies), PowerSource::Battery);
+    }
+
+    #[test]
+    fn test_peripheral_battery_ignored() {
+        let mut controller = supply("ps-controller-battery-aa:bb", "Battery", false, "Discharging");
+        controller.scope = "Device".to_string();
+        assert_eq!(classify(&[controller.clone()]), PowerSource::AC);
+
+        // Legacy naming without a type attribute
+        let supplies = [supply("AC0", "", true, ""), supply("BAT1", "", false, "Discharging"), controller];
+        assert_eq!(classify(&supplies), PowerSource::AC);
+    }
+}
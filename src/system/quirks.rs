@@ -0,0 +1,227 @@
+//! Driver quirk database
+//!
+//! Per-chip knowledge (modprobe options, IRQ names, features that misbehave,
+//! power save advice) lives in an embedded TOML file rather than in code.
+//! Files in `/etc/hifi-wifi/quirks.d` add chips or replace built-in entries,
+//! so supporting a new adapter doesn't need a new release.
+
+use anyhow::Result;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::config::loader::{load_toml_dir, merge_by_name};
+use crate::network::wifi::DriverCategory;
+
+const BUILTIN: &str = include_str!("quirks.toml");
+const QUIRKS_DIR: &str = "/etc/hifi-wifi/quirks.d";
+
+/// What hifi-wifi knows about one chip family
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Quirk {
+    pub name: String,
+    pub category: DriverCategory,
+    /// Driver name patterns, `*` matching any run of characters
+    pub drivers: Vec<String>,
+    /// PCI or USB "vendor:device" IDs, or PCI
+    /// "vendor:device:subsystem_vendor:subsystem_device" for one board variant
+    pub ids: Vec<String>,
+    /// File name under /etc/modprobe.d
+    pub modprobe_file: Option<String>,
+    pub modprobe: String,
+    /// Extra names the driver's IRQs use in /proc/interrupts
+    pub irq_names: Vec<String>,
+    /// Features known to misbehave with this driver
+    pub avoid: Vec<String>,
+    pub power_save: Option<PowerSaveAdvice>,
+}
+
+/// Power save recommendation for a chip
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerSaveAdvice {
+    /// Safe to enable when the policy asks for it
+    On,
+    /// Never enable: the driver drops packets or disconnects in power save
+    Off,
+}
+
+/// Sysctls behind each feature name usable in `avoid`
+const FEATURE_SYSCTLS: &[(&str, &[&str])] = &[
+    ("busy_poll", &["net.core.busy_poll", "net.core.busy_read"]),
+];
+
+impl Quirk {
+    pub fn avoids(&self, feature: &str) -> bool {
+        self.avoid.iter().any(|f| f == feature)
+    }
+
+    /// Whether an avoided feature is controlled by sysctl `key`
+    pub fn avoids_sysctl(&self, key: &str) -> bool {
+        FEATURE_SYSCTLS.iter()
+            .any(|(feature, keys)| self.avoids(feature) && keys.contains(&key))
+    }
+
+    pub fn forbids_power_save(&self) -> bool {
+        self.power_save == Some(PowerSaveAdvice::Off)
+    }
+
+    fn matches_driver(&self, driver: &str) -> bool {
+        self.drivers.iter().any(|pattern| glob_match(pattern, driver))
+    }
+
+    fn matches_id(&self, id: &str) -> bool {
+        self.ids.iter().any(|known| known.eq_ignore_ascii_case(id))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct QuirkFile {
+    #[serde(default)]
+    quirk: Vec<Quirk>,
+}
+
+/// Built-in quirks plus the ones from quirks.d, in match order
+#[derive(Debug, Default)]
+pub struct Database {
+    quirks: Vec<Quirk>,
+}
+
+impl Database {
+    /// Parse one quirk file
+    pub fn parse(text: &str) -> Result<Vec<Quirk>> {
+        let file: QuirkFile = toml::from_str(text)?;
+        Ok(file.quirk)
+    }
+
+    /// The embedded database merged with `overrides` (see `merge_by_name`)
+    fn with_overrides(overrides: Vec<Quirk>) -> Self {
+        let builtin = Self::parse(BUILTIN).expect("embedded quirks.toml is valid");
+        Self { quirks: merge_by_name(overrides, builtin, |q| &q.name) }
+    }
+
+    /// Load the embedded database and the files in `dir`
+    pub fn load(dir: &Path) -> Self {
+        let files: Vec<QuirkFile> = load_toml_dir(dir);
+        Self::with_overrides(files.into_iter().flat_map(|f| f.quirk).collect())
+    }
+
+    /// The quirk for a device: an ID match first (`ids` most specific first),
+    /// then the first driver match
+    pub fn lookup(&self, driver: &str, ids: &[String]) -> Option<&Quirk> {
+        ids.iter().find_map(|id| self.quirks.iter().find(|q| q.matches_id(id)))
+            .or_else(|| self.quirks.iter().find(|q| q.matches_driver(driver)))
+    }
+
+    /// Every modprobe file any quirk may write
+    pub fn modprobe_files(&self) -> Vec<&str> {
+        let mut files: Vec<&str> = self.quirks.iter()
+            .filter_map(|q| q.modprobe_file.as_deref())
+            .collect();
+        files.sort_unstable();
+        files.dedup();
+        files
+    }
+}
+
+/// The database for this process, loaded on first use
+pub fn database() -> &'static Database {
+    static DATABASE: OnceLock<Database> = OnceLock::new();
+    DATABASE.get_or_init(|| Database::load(Path::new(QUIRKS_DIR)))
+}
+
+/// Match `text` against `pattern`, where `*` matches any run of characters
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_database_matches_drivers() {
+        let db = Database::with_overrides(Vec::new());
+        let category = |driver: &str| db.lookup(driver, &[]).map(|q| q.category.clone());
+
+        assert_eq!(category("rtw89_8852be"), Some(DriverCategory::Rtw89));
+        assert_eq!(category("rtw88_8822ce"), Some(DriverCategory::Rtw88));
+        assert_eq!(category("rtl8192ee"), Some(DriverCategory::RtlLegacy));
+        assert_eq!(category("mt7921e"), Some(DriverCategory::MediaTek));
+        assert_eq!(category("iwlwifi"), Some(DriverCategory::Intel));
+        assert_eq!(category("wl"), Some(DriverCategory::Broadcom));
+        assert_eq!(category("wlx"), Some(DriverCategory::Generic));
+        assert_eq!(category("e1000e"), Some(DriverCategory::Generic));
+
+        let ath11k = db.lookup("ath11k_pci", &[]).unwrap();
+        assert!(ath11k.avoids_sysctl("net.core.busy_poll"));
+        assert!(!ath11k.avoids_sysctl("net.ipv4.tcp_ecn"));
+        assert_eq!(db.lookup("ath12k_pci", &[]).unwrap().name, "Qualcomm ath12k");
+
+        // ath9k/ath10k only get the generic Atheros options
+        let ath9k = db.lookup("ath9k", &[]).unwrap();
+        assert_eq!(ath9k.category, DriverCategory::Atheros);
+        assert!(ath9k.irq_names.is_empty());
+        assert!(!ath9k.avoids("busy_poll"));
+        assert!(db.modprobe_files().contains(&"rtw89.conf"));
+    }
+
+    #[test]
+    fn test_overrides_and_id_matches_win() {
+        let overrides = Database::parse(r#"
+            [[quirk]]
+            name = "Intel iwlwifi"
+            category = "intel"
+            drivers = ["iwl*"]
+            modprobe_file = "iwlwifi.conf"
+            modprobe = "options iwlwifi power_save=0\n"
+            power_save = "off"
+
+            [[quirk]]
+            name = "Vendor dongle"
+            ids = ["0BDA:8812"]
+            avoid = ["gro"]
+        "#).unwrap();
+        let db = Database::with_overrides(overrides);
+
+        let intel = db.lookup("iwlwifi", &[]).unwrap();
+        assert_eq!(intel.modprobe, "options iwlwifi power_save=0\n");
+        assert!(intel.forbids_power_save());
+        assert_eq!(db.quirks.iter().filter(|q| q.name == "Intel iwlwifi").count(), 1);
+
+        // The ID match beats the driver's own entry
+        let dongle = db.lookup("rtl8812au", &["0bda:8812".to_string()]).unwrap();
+        assert_eq!(dongle.name, "Vendor dongle");
+        assert!(dongle.avoids("gro"));
+        assert_eq!(db.lookup("rtl8812au", &[]).unwrap().name, "Realtek legacy");
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*rtw89*", "rtw89_pci"));
+        assert!(glob_match("*rtw89*", "rtw89"));
+        assert!(glob_match("iwl*", "iwlwifi"));
+        assert!(glob_match("wl", "wl"));
+        assert!(!glob_match("wl", "wlan"));
+        assert!(!glob_match("ath*", "brcmfmac"));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("ab*b", "ab"));
+    }
+}
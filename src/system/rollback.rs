@@ -0,0 +1,649 @@
+//! Rollback journal for `apply` / `revert`
+//!
+//! Every stage of `apply` records what it is about to change, with the value
+//! it had before, and the journal is saved before the change is made. `revert`
+//! undoes the entries newest-first, so it restores exactly what was there
+//! (including settings the user had chosen) instead of assumed defaults.
+//!
+//! Only the first record for a target is kept: re-applying on every boot must
+//! not replace the user's original value with one hifi-wifi set earlier.
+
+use anyhow::{bail, Context, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+use crate::network::wpa;
+
+pub const JOURNAL_PATH: &str = "/var/lib/hifi-wifi/rollback.json";
+
+/// Serializes journal updates from Governor link tasks acting at the same time
+static RUNTIME_JOURNAL: Mutex<()> = Mutex::new(());
+
+/// Root qdiscs the kernel installs by itself; restored by deleting ours
+const DEFAULT_QDISCS: &[&str] = &["noqueue", "mq", "fq_codel", "pfifo_fast", "fq"];
+
+/// Coalescing parameters captured and restored
+const COALESCE_PARAMS: &[&str] = &["rx-usecs", "rx-frames", "tx-usecs", "tx-frames"];
+
+/// A single change, with the state before it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Change {
+    /// Runtime sysctl value
+    Sysctl { key: String, previous: String },
+    /// Config file; `previous: None` means it did not exist
+    File { path: PathBuf, previous: Option<String> },
+    /// `/proc/irq/<irq>/smp_affinity` mask
+    /// IRQ numbers change across reboots, so the entry only holds for `boot_id`.
+    IrqAffinity {
+        irq: String,
+        previous: String,
+        #[serde(default)]
+        boot_id: String,
+    },
+    /// ethtool -K features (short name, enabled)
+    Offloads { interface: String, previous: Vec<(String, bool)> },
+    /// ethtool -C parameters (name, value), including adaptive-rx/tx
+    Coalescing { interface: String, previous: Vec<(String, String)> },
+    Eee { interface: String, previous: bool },
+    PowerSave { interface: String, previous: bool },
+    /// Root qdisc kind; `None` if there was none
+    Qdisc {
+        interface: String,
+        previous: Option<String>,
+        /// Its parameters as `tc qdisc replace` takes them ("bandwidth", "85Mbit", ...)
+        #[serde(default)]
+        options: Vec<String>,
+    },
+    /// RPS/XPS mask, `file` relative to `/sys/class/net/<if>/queues` (e.g. `rx-0/rps_cpus`)
+    QueueMask { interface: String, file: String, previous: String },
+    /// wpa_supplicant `bgscan` of one network object; `None` if unset
+    WpaBgscan { interface: String, network: String, previous: Option<String> },
+    /// wpa_supplicant `autoscan` (write-only over D-Bus; restored by clearing it)
+    WpaAutoscan { interface: String },
+}
+
+impl Change {
+    /// What this change touches; one journal entry per target
+    fn target(&self) -> String {
+        match self {
+            Change::Sysctl { key, .. } => format!("sysctl:{}", key),
+            Change::File { path, .. } => format!("file:{}", path.display()),
+            Change::IrqAffinity { irq, .. } => format!("irq:{}", irq),
+            Change::Offloads { interface, .. } => format!("offloads:{}", interface),
+            Change::Coalescing { interface, .. } => format!("coalescing:{}", interface),
+            Change::Eee { interface, .. } => format!("eee:{}", interface),
+            Change::PowerSave { interface, .. } => format!("power_save:{}", interface),
+            Change::Qdisc { interface, .. } => format!("qdisc:{}", interface),
+            Change::QueueMask { interface, file, .. } => format!("queue:{}/{}", interface, file),
+            Change::WpaBgscan { network, .. } => format!("bgscan:{}", network),
+            Change::WpaAutoscan { interface } => format!("autoscan:{}", interface),
+        }
+    }
+
+    pub fn sysctl(key: &str) -> Option<Self> {
+        let path = Path::new("/proc/sys").join(key.replace('.', "/"));
+        let previous = fs::read_to_string(path).ok()?;
+        // Multi-value keys (tcp_rmem) are tab separated in /proc
+        let previous = previous.split_whitespace().collect::<Vec<_>>().join(" ");
+        Some(Change::Sysctl { key: key.to_string(), previous })
+    }
+
+    pub fn file(path: &Path) -> Option<Self> {
+        let previous = match fs::read_to_string(path) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                debug!("Cannot snapshot {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        Some(Change::File { path: path.to_path_buf(), previous })
+    }
+
+    pub fn irq_affinity(irq: &str) -> Option<Self> {
+        let previous = fs::read_to_string(format!("/proc/irq/{}/smp_affinity", irq)).ok()?;
+        Some(Change::IrqAffinity { irq: irq.to_string(), previous: previous.trim().to_string(), boot_id: boot_id() })
+    }
+
+    pub fn offloads(interface: &str, features: &[&str]) -> Option<Self> {
+        let output = run_stdout("ethtool", &["-k", interface])?;
+        let previous: Vec<(String, bool)> = features.iter()
+            .filter_map(|f| parse_offload(&output, f).map(|on| (f.to_string(), on)))
+            .collect();
+        (!previous.is_empty()).then(|| Change::Offloads { interface: interface.to_string(), previous })
+    }
+
+    pub fn coalescing(interface: &str) -> Option<Self> {
+        let output = run_stdout("ethtool", &["-c", interface])?;
+        let previous = parse_coalescing(&output);
+        (!previous.is_empty()).then(|| Change::Coalescing { interface: interface.to_string(), previous })
+    }
+
+    pub fn eee(interface: &str) -> Option<Self> {
+        let output = run_stdout("ethtool", &["--show-eee", interface])?;
+        let previous = parse_eee(&output)?;
+        Some(Change::Eee { interface: interface.to_string(), previous })
+    }
+
+    pub fn power_save(interface: &str) -> Option<Self> {
+        let output = run_stdout("iw", &["dev", interface, "get", "power_save"])?;
+        let previous = parse_power_save(&output)?;
+        Some(Change::PowerSave { interface: interface.to_string(), previous })
+    }
+
+    pub fn qdisc(interface: &str) -> Option<Self> {
+        let output = run_stdout("tc", &["qdisc", "show", "dev", interface, "root"])?;
+        let (previous, options) = parse_qdisc(&output).unzip();
+        Some(Change::Qdisc { interface: interface.to_string(), previous, options: options.unwrap_or_default() })
+    }
+
+    /// The IRQ or interface no longer exists (driver reloaded, adapter unplugged),
+    /// so there is nothing left to restore
+    fn vanished(&self) -> bool {
+        let path = match self {
+            Change::IrqAffinity { irq, .. } => format!("/proc/irq/{}", irq),
+            Change::Offloads { interface, .. }
+            | Change::Coalescing { interface, .. }
+            | Change::Eee { interface, .. }
+            | Change::PowerSave { interface, .. }
+            | Change::Qdisc { interface, .. }
+            | Change::QueueMask { interface, .. }
+            | Change::WpaAutoscan { interface } => format!("/sys/class/net/{}", interface),
+            // Gone once NM reconnects, which restores NM's own bgscan
+            Change::WpaBgscan { network, .. } => return !wpa::network_exists(network),
+            Change::Sysctl { .. } | Change::File { .. } => return false,
+        };
+        !Path::new(&path).exists()
+    }
+
+    pub fn queue_mask(interface: &str, file: &str) -> Option<Self> {
+        let previous = fs::read_to_string(format!("/sys/class/net/{}/queues/{}", interface, file)).ok()?;
+        Some(Change::QueueMask { interface: interface.to_string(), file: file.to_string(), previous: previous.trim().to_string() })
+    }
+
+    /// Put back the previous state
+    fn undo(&self) -> Result<()> {
+        match self {
+            Change::Sysctl { key, previous } => {
+                run("sysctl", &["-w", &format!("{}={}", key, previous)])
+            }
+            Change::File { path, previous: Some(content) } => {
+                fs::write(path, content).with_context(|| format!("Failed to restore {}", path.display()))
+            }
+            Change::File { path, previous: None } => match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+                }
+                _ => Ok(()),
+            },
+            Change::IrqAffinity { irq, previous, .. } => {
+                fs::write(format!("/proc/irq/{}/smp_affinity", irq), previous)
+                    .with_context(|| format!("Failed to restore affinity of IRQ {}", irq))
+            }
+            Change::Offloads { interface, previous } => {
+                let mut args = vec!["-K".to_string(), interface.clone()];
+                for (feature, on) in previous {
+                    args.push(feature.clone());
+                    args.push(if *on { "on" } else { "off" }.to_string());
+                }
+                run("ethtool", &args.iter().map(String::as_str).collect::<Vec<_>>())
+            }
+            Change::Coalescing { interface, previous } => {
+                let mut args = vec!["-C".to_string(), interface.clone()];
+                for (param, value) in previous {
+                    args.push(param.clone());
+                    args.push(value.clone());
+                }
+                run("ethtool", &args.iter().map(String::as_str).collect::<Vec<_>>())
+            }
+            Change::Eee { interface, previous } => {
+                run("ethtool", &["--set-eee", interface, "eee", if *previous { "on" } else { "off" }])
+            }
+            Change::PowerSave { interface, previous } => {
+                run("iw", &["dev", interface, "set", "power_save", if *previous { "on" } else { "off" }])
+            }
+            Change::Qdisc { interface, previous, options } => match previous.as_deref() {
+                Some(kind) if !DEFAULT_QDISCS.contains(&kind) => {
+                    let mut args = vec!["qdisc", "replace", "dev", interface, "root", kind];
+                    args.extend(options.iter().map(String::as_str));
+                    run("tc", &args).or_else(|e| {
+                        // Some shown parameters are statistics, not settings
+                        warn!("Restoring {} with its parameters failed ({}), using its defaults", kind, e);
+                        run("tc", &["qdisc", "replace", "dev", interface, "root", kind])
+                    })
+                }
+                // Deleting the root qdisc brings back the kernel default
+                _ => {
+                    let _ = run("tc", &["qdisc", "del", "dev", interface, "root"]);
+                    Ok(())
+                }
+            },
+            Change::QueueMask { interface, file, previous } => {
+                fs::write(format!("/sys/class/net/{}/queues/{}", interface, file), previous)
+                    .with_context(|| format!("Failed to restore {} on {}", file, interface))
+            }
+            // Unset and empty both mean no background scans
+            Change::WpaBgscan { network, previous, .. } => {
+                wpa::set_bgscan(network, previous.as_deref().unwrap_or(""))
+            }
+            Change::WpaAutoscan { interface } => wpa::set_autoscan(interface, ""),
+        }
+    }
+}
+
+/// A journal entry: which apply stage made the change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub stage: String,
+    pub change: Change,
+}
+
+/// Persistent record of everything `apply` changed
+pub struct Journal {
+    path: PathBuf,
+    entries: Vec<Entry>,
+    /// Set after the first failed save, so the warning is logged once
+    save_failed: bool,
+}
+
+impl Journal {
+    /// Load the journal at `path`, or start an empty one
+    pub fn open(path: &Path) -> Self {
+        let entries = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| match serde_json::from_str(&content) {
+                Ok(entries) => Some(entries),
+                Err(e) => {
+                    warn!("Ignoring unreadable rollback journal {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        let mut journal = Self { path: path.to_path_buf(), entries, save_failed: false };
+        journal.forget_earlier_boots(&boot_id());
+        journal
+    }
+
+    /// Drop IRQ affinity entries recorded before the last reboot: the IRQ
+    /// number may now belong to another device
+    fn forget_earlier_boots(&mut self, current: &str) {
+        let before = self.entries.len();
+        self.entries.retain(|e| !matches!(&e.change, Change::IrqAffinity { boot_id, .. } if boot_id != current));
+        if self.entries.len() != before {
+            debug!("Dropped {} IRQ affinity entries from an earlier boot", before - self.entries.len());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Whether a file change for `path` is recorded
+    pub fn touches_file(&self, path: &Path) -> bool {
+        self.entries.iter().any(|e| matches!(&e.change, Change::File { path: p, .. } if p == path))
+    }
+
+    /// Record `change` before making it; kept only if its target is new
+    /// `None` (prior state unreadable) is ignored, so revert leaves that target alone.
+    pub fn record(&mut self, stage: &str, change: Option<Change>) {
+        let Some(change) = change else {
+            return;
+        };
+        let target = change.target();
+        if self.entries.iter().any(|e| e.change.target() == target) {
+            return;
+        }
+        debug!("Rollback journal: {} ({})", target, stage);
+        self.entries.push(Entry { stage: stage.to_string(), change });
+
+        if let Err(e) = self.save() {
+            if !self.save_failed {
+                warn!("Failed to save rollback journal {}: {}", self.path.display(), e);
+                self.save_failed = true;
+            }
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&self.entries)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Drop entries matching `pred` whose target is gone, without undoing anything
+    pub fn forget_vanished(&mut self, pred: impl Fn(&Change) -> bool) {
+        let before = self.entries.len();
+        self.entries.retain(|e| !(pred(&e.change) && e.change.vanished()));
+        if self.entries.len() != before {
+            if let Err(e) = self.save() {
+                debug!("Failed to save rollback journal: {}", e);
+            }
+        }
+    }
+
+    /// Undo every change, newest first, then delete the journal
+    pub fn revert(mut self) -> Result<()> {
+        self.revert_matching(|_| true)
+    }
+
+    /// Undo only the changes matching `pred` (e.g. sysctls a new profile no
+    /// longer sets), newest first, and drop them from the journal
+    pub fn revert_matching(&mut self, pred: impl Fn(&Change) -> bool) -> Result<()> {
+        self.revert_matching_with(pred, Change::undo)
+    }
+
+    fn revert_matching_with(
+        &mut self,
+        pred: impl Fn(&Change) -> bool,
+        mut undo: impl FnMut(&Change) -> Result<()>,
+    ) -> Result<()> {
+        let (mut matching, kept): (Vec<Entry>, Vec<Entry>) =
+            std::mem::take(&mut self.entries).into_iter().partition(|e| pred(&e.change));
+        self.entries = kept;
+        if matching.is_empty() {
+            return Ok(());
+        }
+
+        let mut failed = Vec::new();
+        while let Some(entry) = matching.pop() {
+            if entry.change.vanished() {
+                debug!("Skipping {}: no longer present", entry.change.target());
+                continue;
+            }
+            match undo(&entry.change) {
+                Ok(()) => info!("Restored {} ({})", entry.change.target(), entry.stage),
+                Err(e) => {
+                    warn!("Failed to restore {}: {}", entry.change.target(), e);
+                    failed.push(entry);
+                }
+            }
+        }
+
+        // Keep what could not be restored so a later revert can retry it
+        failed.reverse();
+        let count = failed.len();
+        self.entries.extend(failed);
+        if self.entries.is_empty() {
+            let _ = fs::remove_file(&self.path);
+        } else {
+            self.save()?;
+        }
+
+        if count > 0 {
+            bail!("{} change(s) could not be restored", count);
+        }
+        Ok(())
+    }
+}
+
+/// Run `f` on the journal at `JOURNAL_PATH`, for changes the Governor makes at runtime
+pub fn with_runtime_journal<T>(f: impl FnOnce(&mut Journal) -> T) -> T {
+    let _guard = RUNTIME_JOURNAL.lock().unwrap_or_else(|e| e.into_inner());
+    let mut journal = Journal::open(Path::new(JOURNAL_PATH));
+    f(&mut journal)
+}
+
+/// Random ID of the running boot (empty if unreadable)
+fn boot_id() -> String {
+    fs::read_to_string("/proc/sys/kernel/random/boot_id")
+        .map(|id| id.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn run_stdout(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(program).args(args).output()
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !output.status.success() {
+        bail!("{} {}: {}", program, args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// `ethtool -k` uses long names; map the `-K` short ones
+fn parse_offload(output: &str, feature: &str) -> Option<bool> {
+    let long = match feature {
+        "tso" => "tcp-segmentation-offload",
+        "gso" => "generic-segmentation-offload",
+        "gro" => "generic-receive-offload",
+        "lro" => "large-receive-offload",
+        other => other,
+    };
+    output.lines()
+        .find_map(|l| l.trim().strip_prefix(long)?.strip_prefix(':'))
+        .and_then(|v| match v.split_whitespace().next()? {
+            "on" => Some(true),
+            "off" => Some(false),
+            _ => None,
+        })
+}
+
+/// `ethtool -c`: "Adaptive RX: on  TX: off" and "rx-usecs: 3" lines
+fn parse_coalescing(output: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    for line in output.lines() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("Adaptive RX:") {
+            let mut words = rest.split_whitespace();
+            if let Some(rx) = words.next().filter(|v| *v == "on" || *v == "off") {
+                params.push(("adaptive-rx".to_string(), rx.to_string()));
+            }
+            if let Some(tx) = words.skip_while(|w| *w != "TX:").nth(1).filter(|v| *v == "on" || *v == "off") {
+                params.push(("adaptive-tx".to_string(), tx.to_string()));
+            }
+            continue;
+        }
+        let Some((name, value)) = line.split_once(':') else { continue };
+        let value = value.trim();
+        if COALESCE_PARAMS.contains(&name) && value.parse::<u32>().is_ok() {
+            params.push((name.to_string(), value.to_string()));
+        }
+    }
+    params
+}
+
+/// `ethtool --show-eee`: "EEE status: enabled - active" (None if unsupported)
+fn parse_eee(output: &str) -> Option<bool> {
+    let status = output.lines().find_map(|l| l.trim().strip_prefix("EEE status:"))?.trim();
+    if status.starts_with("enabled") {
+        Some(true)
+    } else if status.starts_with("disabled") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// `iw dev <if> get power_save`: "Power save: on"
+fn parse_power_save(output: &str) -> Option<bool> {
+    match output.trim().strip_prefix("Power save:")?.trim() {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// `tc qdisc show dev <if> root`: "qdisc cake 8001: root refcnt 2 bandwidth 85Mbit ..."
+/// -> kind and parameters; packet counts ("10240p") lose their unit, which tc won't take back
+fn parse_qdisc(output: &str) -> Option<(String, Vec<String>)> {
+    let mut words = output.lines().find_map(|l| l.strip_prefix("qdisc "))?.split_whitespace();
+    let kind = words.next()?.to_string();
+    let mut options = Vec::new();
+    while let Some(word) = words.next() {
+        match word {
+            "root" => {}
+            "refcnt" | "parent" => {
+                words.next();
+            }
+            handle if handle.ends_with(':') && options.is_empty() => {}
+            packets if packets.strip_suffix('p').is_some_and(|n| n.parse::<u64>().is_ok()) => {
+                options.push(packets.trim_end_matches('p').to_string());
+            }
+            _ => options.push(word.to_string()),
+        }
+    }
+    Some((kind, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_keeps_first_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rollback.json");
+
+        let mut journal = Journal::open(&path);
+        journal.record("power", Some(Change::PowerSave { interface: "wlan0".into(), previous: true }));
+        journal.record("cake", Some(Change::Qdisc { interface: "wlan0".into(), previous: Some("mq".into()), options: vec![] }));
+        journal.record("power", None);
+
+        // Next boot: hifi-wifi already turned power save off
+        let mut journal = Journal::open(&path);
+        journal.record("power", Some(Change::PowerSave { interface: "wlan0".into(), previous: false }));
+
+        assert_eq!(journal.entries().len(), 2);
+        assert_eq!(journal.entries()[0].change, Change::PowerSave { interface: "wlan0".into(), previous: true });
+    }
+
+    #[test]
+    fn test_revert_newest_first_and_keeps_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rollback.json");
+        let existing = dir.path().join("iwd.conf");
+        fs::write(&existing, "user config").unwrap();
+        let created = dir.path().join("99-hifi-wifi.conf");
+
+        let mut journal = Journal::open(&path);
+        journal.record("backend", Change::file(&existing));
+        journal.record("sysctl", Change::file(&created));
+        journal.record("sysctl", Some(Change::Sysctl { key: "net.core.rmem_max".into(), previous: "212992".into() }));
+        fs::write(&existing, "hifi-wifi config").unwrap();
+        fs::write(&created, "hifi-wifi sysctl").unwrap();
+
+        let mut order = Vec::new();
+        let result = Journal::open(&path).revert_matching_with(|_| true, |change| {
+            order.push(change.target());
+            match change {
+                Change::Sysctl { .. } => bail!("read-only"),
+                other => other.undo(),
+            }
+        });
+
+        assert!(result.is_err());
+        assert_eq!(order[0], "sysctl:net.core.rmem_max");
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "user config");
+        assert!(!created.exists());
+
+        let remaining = Journal::open(&path);
+        assert_eq!(remaining.entries().len(), 1);
+        assert!(matches!(remaining.entries()[0].change, Change::Sysctl { .. }));
+    }
+
+    #[test]
+    fn test_revert_matching_keeps_the_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rollback.json");
+
+        let mut journal = Journal::open(&path);
+        for key in ["net.ipv4.tcp_ecn", "net.core.rmem_max"] {
+            journal.record("sysctl", Some(Change::Sysctl { key: key.into(), previous: "0".into() }));
+        }
+        journal.record("power", Some(Change::PowerSave { interface: "wlan0".into(), previous: true }));
+
+        let mut restored = Vec::new();
+        journal.revert_matching_with(
+            |c| matches!(c, Change::Sysctl { key, .. } if key != "net.ipv4.tcp_ecn"),
+            |c| { restored.push(c.target()); Ok(()) },
+        ).unwrap();
+
+        assert_eq!(restored, vec!["sysctl:net.core.rmem_max"]);
+        assert_eq!(Journal::open(&path).entries().len(), 2);
+    }
+
+    #[test]
+    fn test_revert_skips_vanished_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rollback.json");
+
+        let mut journal = Journal::open(&path);
+        journal.record("ethtool", Some(Change::Offloads {
+            interface: "hifi-gone0".into(),
+            previous: vec![("tso".into(), true)],
+        }));
+        journal.record("irq", Some(Change::IrqAffinity { irq: "999999".into(), previous: "ff".into(), boot_id: boot_id() }));
+
+        journal.revert_matching_with(|_| true, |change| panic!("tried to restore {:?}", change)).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_irq_entries_only_hold_for_their_boot() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rollback.json");
+
+        let mut journal = Journal::open(&path);
+        journal.record("irq", Some(Change::IrqAffinity { irq: "24".into(), previous: "f".into(), boot_id: "earlier".into() }));
+        journal.record("irq", Some(Change::IrqAffinity { irq: "25".into(), previous: "f".into(), boot_id: "now".into() }));
+        journal.record("power", Some(Change::PowerSave { interface: "wlan0".into(), previous: true }));
+
+        journal.forget_earlier_boots("now");
+        let targets: Vec<_> = journal.entries().iter().map(|e| e.change.target()).collect();
+        assert_eq!(targets, ["irq:25", "power_save:wlan0"]);
+
+        // A journal from before this field existed
+        fs::write(&path, r#"[{"stage":"irq","change":{"kind":"irq_affinity","irq":"24","previous":"f"}}]"#).unwrap();
+        assert!(Journal::open(&path).is_empty());
+    }
+
+    #[test]
+    fn test_parse_tool_output() {
+        let features = "Features for eth0:\nrx-checksumming: on\ntcp-segmentation-offload: on\n\
+                        \ttx-tcp-segmentation: on\ngeneric-segmentation-offload: off [fixed]\n\
+                        generic-receive-offload: on\n";
+        assert_eq!(parse_offload(features, "tso"), Some(true));
+        assert_eq!(parse_offload(features, "gso"), Some(false));
+        assert_eq!(parse_offload(features, "lro"), None);
+
+        let coalesce = "Coalesce parameters for eth0:\nAdaptive RX: on  TX: off\nstats-block-usecs: n/a\n\
+                        rx-usecs: 3\nrx-frames: n/a\ntx-usecs: 0\ntx-frames: 1\n";
+        assert_eq!(parse_coalescing(coalesce), vec![
+            ("adaptive-rx".to_string(), "on".to_string()),
+            ("adaptive-tx".to_string(), "off".to_string()),
+            ("rx-usecs".to_string(), "3".to_string()),
+            ("tx-usecs".to_string(), "0".to_string()),
+            ("tx-frames".to_string(), "1".to_string()),
+        ]);
+
+        assert_eq!(parse_eee("EEE settings for eth0:\n\tEEE status: enabled - active\n"), Some(true));
+        assert_eq!(parse_eee("EEE settings for eth0:\n\tEEE status: disabled\n"), Some(false));
+        assert_eq!(parse_eee("EEE settings for eth0:\n\tEEE status: not supported\n"), None);
+        assert_eq!(parse_power_save("Power save: off\n"), Some(false));
+        assert_eq!(parse_qdisc("qdisc fq_codel 0: root refcnt 2 limit 10240p\n"),
+                   Some(("fq_codel".to_string(), vec!["limit".to_string(), "10240".to_string()])));
+        let (kind, options) = parse_qdisc(
+            "qdisc cake 8001: root refcnt 2 bandwidth 85Mbit diffserv4 dual-dsthost nat wash ack-filter split-gso rtt 50ms raw overhead 0 \n",
+        ).unwrap();
+        assert_eq!(kind, "cake");
+        assert_eq!(options.join(" "), "bandwidth 85Mbit diffserv4 dual-dsthost nat wash ack-filter split-gso rtt 50ms raw overhead 0");
+        assert_eq!(parse_qdisc(""), None);
+    }
+}
@@ -0,0 +1,76 @@
+//! Suspend and resume notifications from systemd-logind
+//!
+//! logind emits `PrepareForSleep(true)` before the system sleeps and
+//! `PrepareForSleep(false)` once it is back. A "delay" inhibitor lock holds
+//! the suspend back (up to logind's `InhibitDelayMaxSec`) until it is closed,
+//! which gives the Governor time to pause before the radio goes down.
+
+use anyhow::{Context, Result};
+use futures_lite::StreamExt;
+use log::{debug, warn};
+use tokio::sync::mpsc;
+use zbus::zvariant::OwnedFd;
+use zbus::{proxy, Connection};
+
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Login1Manager {
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<OwnedFd>;
+
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+/// A logind delay lock; suspend proceeds once it is dropped
+pub struct SleepInhibitor(#[allow(dead_code)] OwnedFd);
+
+/// A suspend/resume transition
+pub enum SleepEvent {
+    /// The system is about to sleep. Drop the inhibitor once paused.
+    Suspending(Option<SleepInhibitor>),
+    /// The system woke up
+    Resumed,
+}
+
+/// Report suspend and resume on the returned channel
+pub async fn watch() -> Result<mpsc::UnboundedReceiver<SleepEvent>> {
+    let connection = Connection::system().await.context("Failed to connect to system D-Bus")?;
+    let manager = Login1ManagerProxy::new(&connection).await?;
+    let mut signals = manager.receive_prepare_for_sleep().await?;
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let mut inhibitor = inhibit(&manager).await;
+        while let Some(signal) = signals.next().await {
+            let Ok(args) = signal.args() else {
+                continue;
+            };
+            let event = if args.start {
+                SleepEvent::Suspending(inhibitor.take())
+            } else {
+                // Taken again for the next suspend
+                inhibitor = inhibit(&manager).await;
+                SleepEvent::Resumed
+            };
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+        warn!("logind sleep signal stream ended");
+    });
+
+    Ok(rx)
+}
+
+async fn inhibit(manager: &Login1ManagerProxy<'_>) -> Option<SleepInhibitor> {
+    match manager.inhibit("sleep", "hifi-wifi", "Pause link tuning before suspend", "delay").await {
+        Ok(fd) => Some(SleepInhibitor(fd)),
+        Err(e) => {
+            debug!("Could not take a sleep delay lock: {}", e);
+            None
+        }
+    }
+}
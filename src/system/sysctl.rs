@@ -0,0 +1,173 @@
+//! Sysctl tuning profiles
+//!
+//! `[system] sysctl_profile` picks a named set of network sysctls and
+//! `[system.sysctl_extra]` adds or overrides keys. Keys the running kernel
+//! doesn't have are skipped, and BBR falls back to the kernel's default
+//! congestion control when the module can't be loaded.
+
+use log::{debug, info, warn};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::structs::SysctlProfile;
+
+/// The sysctls a profile sets, before `[system.sysctl_extra]`
+fn profile_settings(profile: SysctlProfile) -> &'static [(&'static str, &'static str)] {
+    match profile {
+        SysctlProfile::Latency => &[
+            ("net.ipv4.tcp_congestion_control", "bbr"),
+            ("net.core.rmem_default", "262144"),
+            ("net.core.wmem_default", "262144"),
+            ("net.core.rmem_max", "4194304"),
+            ("net.core.wmem_max", "4194304"),
+            ("net.ipv4.tcp_rmem", "4096 131072 4194304"),
+            ("net.ipv4.tcp_wmem", "4096 65536 4194304"),
+            ("net.ipv4.tcp_fastopen", "3"),
+            ("net.core.netdev_max_backlog", "2000"),
+            ("net.ipv4.tcp_ecn", "1"),
+            ("net.ipv4.tcp_keepalive_time", "60"),
+            ("net.ipv4.tcp_keepalive_intvl", "10"),
+            ("net.ipv4.tcp_keepalive_probes", "6"),
+            ("net.ipv4.tcp_tw_reuse", "1"),
+            // Note: NAPI busy polling (busy_poll/busy_read) was removed because it causes
+            // IRQ handling issues with the ath11k driver (Steam Deck OLED WCN6855).
+        ],
+        SysctlProfile::Throughput => &[
+            ("net.ipv4.tcp_congestion_control", "bbr"),
+            ("net.core.rmem_default", "262144"),
+            ("net.core.wmem_default", "262144"),
+            ("net.core.rmem_max", "16777216"),
+            ("net.core.wmem_max", "16777216"),
+            ("net.ipv4.tcp_rmem", "4096 131072 16777216"),
+            ("net.ipv4.tcp_wmem", "4096 65536 16777216"),
+            ("net.ipv4.tcp_fastopen", "3"),
+            ("net.core.netdev_max_backlog", "5000"),
+            ("net.ipv4.tcp_ecn", "1"),
+            ("net.ipv4.tcp_mtu_probing", "1"),
+            ("net.ipv4.tcp_tw_reuse", "1"),
+        ],
+        SysctlProfile::Battery => &[
+            ("net.ipv4.tcp_congestion_control", "bbr"),
+            ("net.ipv4.tcp_fastopen", "3"),
+            ("net.ipv4.tcp_ecn", "1"),
+            // Fewer keepalive wakeups than the latency profile's 60s
+            ("net.ipv4.tcp_keepalive_time", "600"),
+            ("net.ipv4.tcp_keepalive_intvl", "60"),
+            ("net.ipv4.tcp_keepalive_probes", "5"),
+            ("net.ipv4.tcp_tw_reuse", "1"),
+        ],
+        SysctlProfile::Minimal => &[
+            ("net.ipv4.tcp_congestion_control", "bbr"),
+            ("net.ipv4.tcp_fastopen", "3"),
+        ],
+    }
+}
+
+/// Profile settings with `extra` added or overriding, in profile order
+pub fn resolve(profile: SysctlProfile, extra: &BTreeMap<String, String>) -> Vec<(String, String)> {
+    let mut settings: Vec<(String, String)> = profile_settings(profile).iter()
+        .map(|(key, value)| (key.to_string(), extra.get(*key).cloned().unwrap_or_else(|| value.to_string())))
+        .collect();
+    for (key, value) in extra {
+        if !settings.iter().any(|(k, _)| k == key) {
+            settings.push((key.clone(), value.clone()));
+        }
+    }
+    settings
+}
+
+/// Drop the settings the running kernel can't take
+pub fn supported(settings: Vec<(String, String)>) -> Vec<(String, String)> {
+    let wants_bbr = settings.iter().any(|(k, v)| k == CONGESTION_CONTROL && v == "bbr");
+    if wants_bbr && !available_congestion_control().split_whitespace().any(|cc| cc == "bbr") {
+        // Usually built as a module that isn't loaded yet
+        let _ = Command::new("modprobe").arg("tcp_bbr").output();
+    }
+    filter_supported(settings, |key| key_path(key).exists(), &available_congestion_control())
+}
+
+const CONGESTION_CONTROL: &str = "net.ipv4.tcp_congestion_control";
+
+fn available_congestion_control() -> String {
+    fs::read_to_string(key_path("net.ipv4.tcp_available_congestion_control")).unwrap_or_default()
+}
+
+fn key_path(key: &str) -> std::path::PathBuf {
+    Path::new("/proc/sys").join(key.replace('.', "/"))
+}
+
+fn filter_supported(
+    settings: Vec<(String, String)>,
+    exists: impl Fn(&str) -> bool,
+    available_cc: &str,
+) -> Vec<(String, String)> {
+    settings.into_iter()
+        .filter(|(key, value)| {
+            if !exists(key) {
+                warn!("Skipping sysctl {}: not supported by this kernel", key);
+                return false;
+            }
+            if key == CONGESTION_CONTROL && !available_cc.split_whitespace().any(|cc| cc == value) {
+                warn!("Congestion control '{}' unavailable (have: {}), keeping the kernel default",
+                      value, available_cc.trim());
+                return false;
+            }
+            true
+        })
+        .collect()
+}
+
+/// Contents of the persistent sysctl.d file
+pub fn config_file(profile: SysctlProfile, settings: &[(String, String)]) -> String {
+    let mut content = format!("# hifi-wifi Network Optimizations (profile: {})\n", profile);
+    for (key, value) in settings {
+        content.push_str(&format!("{} = {}\n", key, value));
+    }
+    content
+}
+
+/// Set every key at runtime, for when the config file can't be written
+pub fn write_runtime(settings: &[(String, String)]) {
+    info!("Applying sysctl settings transiently (runtime only)...");
+    for (key, value) in settings {
+        match Command::new("sysctl").arg("-w").arg(format!("{}={}", key, value)).output() {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => warn!("sysctl {}={} failed: {}", key, value, String::from_utf8_lossy(&output.stderr).trim()),
+            Err(e) => debug!("sysctl failed: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extra_keys_override_and_extend() {
+        let extra = BTreeMap::from([
+            ("net.ipv4.tcp_fastopen".to_string(), "1".to_string()),
+            ("net.core.somaxconn".to_string(), "4096".to_string()),
+        ]);
+        let settings = resolve(SysctlProfile::Minimal, &extra);
+        assert_eq!(settings, vec![
+            ("net.ipv4.tcp_congestion_control".to_string(), "bbr".to_string()),
+            ("net.ipv4.tcp_fastopen".to_string(), "1".to_string()),
+            ("net.core.somaxconn".to_string(), "4096".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_unsupported_keys_are_skipped() {
+        let settings = resolve(SysctlProfile::Latency, &BTreeMap::new());
+        let kept = filter_supported(settings, |key| key != "net.ipv4.tcp_tw_reuse", "reno cubic\n");
+
+        assert!(!kept.iter().any(|(k, _)| k == "net.ipv4.tcp_tw_reuse"));
+        assert!(!kept.iter().any(|(k, _)| k == CONGESTION_CONTROL));
+        assert_eq!(kept.len(), profile_settings(SysctlProfile::Latency).len() - 2);
+
+        let bbr = filter_supported(resolve(SysctlProfile::Minimal, &BTreeMap::new()), |_| true, "reno cubic bbr");
+        assert_eq!(bbr.len(), 2);
+    }
+}
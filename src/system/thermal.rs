@@ -0,0 +1,20 @@
+//! Thermal zone readings
+//!
+//! Temperatures come from /sys/class/thermal; the power policy uses the
+//! hottest zone to back off when the device is running hot.
+
+use std::fs;
+
+/// Temperature of the hottest thermal zone (°C)
+pub fn hottest_zone_c() -> Option<f64> {
+    let entries = fs::read_dir("/sys/class/thermal").ok()?;
+    entries.flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("thermal_zone"))
+        .filter_map(|entry| {
+            let millidegrees: i64 = fs::read_to_string(entry.path().join("temp")).ok()?.trim().parse().ok()?;
+            Some(millidegrees as f64 / 1000.0)
+        })
+        // Some firmware reports 0 or nonsense for absent sensors
+        .filter(|temp| *temp > 0.0 && *temp < 150.0)
+        .max_by(|a, b| a.total_cmp(b))
+}
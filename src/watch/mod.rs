@@ -0,0 +1,291 @@
+//! Live streaming-session monitor (`hifi-wifi watch`)
+//!
+//! Refreshes once per second with PPS, throughput, gateway RTT and jitter,
+//! signal, bitrate, CAKE drops and the Governor's state, and flags latency
+//! spikes together with any scan, roam or CAKE change just before them.
+//! Prints a summary on Ctrl+C.
+
+pub mod session;
+
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use tokio::time;
+
+use crate::bench::probe::Pinger;
+use crate::network::governor::status::{self, LinkStatus};
+use crate::network::stats::NetStats;
+use crate::network::tc;
+use crate::network::wan::Constraint;
+use crate::network::wifi::{InterfaceType, WifiInterface, WifiManager};
+
+use self::session::{Row, Second, Session, Summary};
+
+/// Rows kept on screen
+const HISTORY_ROWS: usize = 15;
+const EVENT_ROWS: usize = 6;
+
+const RED: &str = "\x1b[0;31m";
+const GREEN: &str = "\x1b[0;32m";
+const YELLOW: &str = "\x1b[0;33m";
+const CYAN: &str = "\x1b[0;36m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const NC: &str = "\x1b[0m";
+
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// Interface to watch (default: first connected)
+    pub interface: Option<String>,
+    /// Host to ping (default: gateway)
+    pub target: Option<String>,
+    /// RTT above baseline that counts as a spike (ms)
+    pub spike_ms: f64,
+}
+
+/// Watch until Ctrl+C, then print the summary
+pub async fn run(options: WatchOptions) -> Result<()> {
+    let wifi = Arc::new(WifiManager::new_quiet()?);
+    let ifc = pick_interface(&wifi, options.interface.as_deref())?;
+    let target = match options.target {
+        Some(target) => target,
+        None => tc::default_gateway().context("No default gateway found - pass --target")?,
+    };
+
+    let (reply_tx, mut replies) = mpsc::unbounded_channel();
+    let mut pinger = Pinger::spawn(&target)?;
+    let ping_task = tokio::spawn(async move {
+        while let Ok(Some(reply)) = pinger.next_reply().await {
+            if reply_tx.send(reply).is_err() {
+                break;
+            }
+        }
+    });
+
+    let (radio_tx, mut radio) = mpsc::unbounded_channel();
+    let iw_task = (ifc.interface_type == InterfaceType::Wifi)
+        .then(|| tokio::spawn(iw_events(ifc.name.clone(), radio_tx)));
+
+    let mut session = Session::new(options.spike_ms);
+    let mut rows: VecDeque<Row> = VecDeque::with_capacity(HISTORY_ROWS);
+    let started = Instant::now();
+    let mut last_counters = NetStats::read(&ifc.name);
+    let mut last_read = Instant::now();
+    let mut last_seq: Option<u32> = None;
+
+    let mut interval = time::interval(Duration::from_secs(1));
+    interval.tick().await;
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    loop {
+        tokio::select! {
+            _ = &mut ctrl_c => break,
+            _ = interval.tick() => {}
+        }
+
+        let mut second = Second { at: started.elapsed(), ..Default::default() };
+        while let Ok((seq, rtt)) = replies.try_recv() {
+            if let Some(last) = last_seq {
+                second.lost += seq.saturating_sub(last + 1);
+            }
+            last_seq = Some(seq);
+            second.rtts.push(rtt);
+        }
+        while let Ok(event) = radio.try_recv() {
+            second.radio.push(event);
+        }
+
+        // iw and tc are blocking tools
+        let (wifi_mgr, sampled_ifc) = (wifi.clone(), ifc.clone());
+        let (counters, link, cake, governor) = tokio::task::spawn_blocking(move || {
+            let name = &sampled_ifc.name;
+            (
+                NetStats::read(name),
+                wifi_mgr.get_link_stats(&sampled_ifc).ok(),
+                tc::cake_stats(name),
+                status::read(Path::new(status::STATUS_DIR), name),
+            )
+        }).await?;
+
+        let elapsed = last_read.elapsed().as_secs_f64().max(0.001);
+        last_read = Instant::now();
+        if let (Some(prev), Some(cur)) = (&last_counters, &counters) {
+            second.rx_pps = (cur.rx_packets.saturating_sub(prev.rx_packets) as f64 / elapsed) as u64;
+            second.tx_pps = (cur.tx_packets.saturating_sub(prev.tx_packets) as f64 / elapsed) as u64;
+            second.rx_mbit = cur.rx_bytes.saturating_sub(prev.rx_bytes) as f64 * 8.0 / elapsed / 1_000_000.0;
+            second.tx_mbit = cur.tx_bytes.saturating_sub(prev.tx_bytes) as f64 * 8.0 / elapsed / 1_000_000.0;
+        }
+        last_counters = counters;
+
+        if let Some(link) = link {
+            if ifc.interface_type == InterfaceType::Wifi {
+                second.signal_dbm = Some(link.signal_dbm);
+                second.bssid = link.bssid;
+            }
+            second.bitrate_mbit = Some(link.tx_bitrate_mbps);
+        }
+        second.cake = cake;
+        second.governor = governor;
+
+        if rows.len() == HISTORY_ROWS {
+            rows.pop_front();
+        }
+        rows.push_back(session.observe(second));
+        render(&ifc.name, &target, &rows, &session);
+    }
+
+    ping_task.abort();
+    if let Some(task) = iw_task {
+        task.abort();
+    }
+
+    print_summary(&session.summary(), &session);
+    Ok(())
+}
+
+fn pick_interface(wifi: &WifiManager, name: Option<&str>) -> Result<WifiInterface> {
+    let interfaces = wifi.interfaces();
+    let found = match name {
+        Some(name) => interfaces.iter().find(|i| i.name == name),
+        None => interfaces.iter().find(|i| wifi.is_interface_connected(i)),
+    };
+    found.cloned().context("No connected interface found - pass --interface")
+}
+
+/// Forward scan and disconnect events for `interface` from `iw event`
+async fn iw_events(interface: String, tx: mpsc::UnboundedSender<session::RadioEvent>) {
+    let Ok(mut child) = Command::new("iw")
+        .arg("event")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn() else {
+        return;
+    };
+    let Some(stdout) = child.stdout.take() else {
+        return;
+    };
+
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if let Some(event) = session::parse_iw_event(&line, &interface) {
+            if tx.send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn mmss(at: Duration) -> String {
+    format!("{:02}:{:02}", at.as_secs() / 60, at.as_secs() % 60)
+}
+
+fn describe_governor(status: &LinkStatus) -> String {
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    let game = if status.game_mode {
+        format!("{}game mode ON{}", YELLOW, NC)
+    } else {
+        "game mode off".to_string()
+    };
+    let cake = match (status.cake_mbit, status.cake_constraint) {
+        (Some(mbit), Some(Constraint::Wan(_))) => format!("CAKE {} Mbit (WAN uplink)", mbit),
+        (Some(mbit), _) => format!("CAKE {} Mbit (link rate)", mbit),
+        (None, _) => "CAKE pending".to_string(),
+    };
+    let mut parts = vec![game, cake, format!("coalescing {}", on_off(status.coalescing))];
+    if let Some(ps) = status.power_save {
+        parts.push(format!("power save {}", on_off(ps)));
+    }
+    if let Some(eee) = status.eee {
+        parts.push(format!("EEE {}", on_off(eee)));
+    }
+    parts.push(format!("{} pps", status.pps));
+    parts.join(" | ")
+}
+
+fn render(interface: &str, target: &str, rows: &VecDeque<Row>, session: &Session) {
+    let mut out = String::from("\x1b[2J\x1b[H");
+    let elapsed = rows.back().map(|r| r.second.at).unwrap_or_default();
+
+    let _ = writeln!(out, "{}{}hifi-wifi watch{}  {} -> {}   {}   {}(Ctrl+C for summary){}",
+                     BOLD, CYAN, NC, interface, target, mmss(elapsed), DIM, NC);
+    let governor = rows.back().and_then(|r| r.second.governor.as_ref());
+    match governor {
+        Some(status) => { let _ = writeln!(out, "Governor: {}", describe_governor(status)); }
+        None => { let _ = writeln!(out, "{}Governor: not running{}", DIM, NC); }
+    }
+    let _ = writeln!(out);
+
+    let _ = writeln!(out, "{}{:>5}  {:>7} {:>7}  {:>7} {:>7}  {:>7} {:>7}  {:>4}  {:>6}  {:>7}  {:>6} {:>5}{}",
+                     BOLD, "Time", "RX pps", "TX pps", "RX Mb/s", "TX Mb/s", "RTT", "Jitter", "Loss",
+                     "Signal", "Bitrate", "CAKE", "Drops", NC);
+    for row in rows {
+        let s = &row.second;
+        let ms = |v: Option<f64>| v.map(|v| format!("{:.1}ms", v)).unwrap_or_else(|| "-".to_string());
+        let line = format!("{:>5}  {:>7} {:>7}  {:>7.1} {:>7.1}  {:>7} {:>7}  {:>4}  {:>6}  {:>7}  {:>6} {:>5}",
+                           mmss(s.at), s.rx_pps, s.tx_pps, s.rx_mbit, s.tx_mbit,
+                           ms(row.rtt_ms), ms(row.jitter_ms), s.lost,
+                           s.signal_dbm.map(|d| format!("{}dBm", d)).unwrap_or_else(|| "-".to_string()),
+                           s.bitrate_mbit.map(|b| format!("{:.0}", b)).unwrap_or_else(|| "-".to_string()),
+                           s.cake.as_ref().and_then(|c| c.bandwidth_mbit)
+                               .map(|b| b.to_string()).unwrap_or_else(|| "-".to_string()),
+                           row.cake_drops);
+        if row.spike {
+            let _ = writeln!(out, "{}{}  <- spike{}", RED, line, NC);
+        } else {
+            let _ = writeln!(out, "{}", line);
+        }
+    }
+
+    let _ = writeln!(out);
+    let _ = writeln!(out, "{}Events{}", BOLD, NC);
+    if session.events.is_empty() {
+        let _ = writeln!(out, "  {}none yet{}", DIM, NC);
+    }
+    for (at, event) in session.events.iter().rev().take(EVENT_ROWS) {
+        let _ = writeln!(out, "  {}  {}", mmss(*at), event);
+    }
+
+    let _ = writeln!(out);
+    let _ = writeln!(out, "{}Spikes: {}{}", BOLD, session.spikes.len(), NC);
+    for spike in session.spikes.iter().rev().take(3) {
+        let causes = if spike.causes.is_empty() { "no scan/roam/CAKE change".to_string() } else { spike.causes.join(", ") };
+        let _ = writeln!(out, "  {}  {:.0}ms (baseline {:.0}ms, {} lost, {} CAKE drops) - {}",
+                         mmss(spike.at), spike.rtt_ms, spike.baseline_ms, spike.lost, spike.cake_drops, causes);
+    }
+
+    print!("{}", out);
+    let _ = std::io::stdout().flush();
+}
+
+fn print_summary(summary: &Summary, session: &Session) {
+    println!();
+    println!("{}{}══ Session summary ({}) ══{}", BOLD, CYAN, mmss(Duration::from_secs(summary.seconds)), NC);
+    println!("Latency:  avg {:.1} ms, p95 {:.1} ms, max {:.1} ms",
+             summary.rtt_avg_ms, summary.rtt_p95_ms, summary.rtt_max_ms);
+    println!("Jitter:   avg {:.1} ms", summary.jitter_avg_ms);
+    println!("Loss:     {:.1}%", summary.loss_pct);
+    println!("CAKE:     {} drops, {} bandwidth changes", summary.cake_drops, summary.cake_changes);
+    println!("Radio:    {} scans, {} roams", summary.scans, summary.roams);
+
+    let color = if summary.spikes == 0 { GREEN } else { RED };
+    let causes: Vec<String> = summary.spike_causes.iter().map(|(c, n)| format!("{}: {}", c, n)).collect();
+    if causes.is_empty() {
+        println!("Spikes:   {}{}{}", color, summary.spikes, NC);
+    } else {
+        println!("Spikes:   {}{}{} ({})", color, summary.spikes, NC, causes.join(", "));
+    }
+    for spike in &session.spikes {
+        let causes = if spike.causes.is_empty() { "-".to_string() } else { spike.causes.join(", ") };
+        println!("  {}  {:>6.0} ms  {}", mmss(spike.at), spike.rtt_ms, causes);
+    }
+}
@@ -0,0 +1,421 @@
+//! Per-second samples, event detection and spike attribution
+//!
+//! No I/O here: `watch` feeds one `Second` at a time and renders what comes
+//! back, so the detection logic can be tested with synthetic samples.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::time::Duration;
+
+use crate::network::governor::status::LinkStatus;
+use crate::network::tc::CakeStats;
+
+/// Seconds of history used for the RTT baseline
+const BASELINE_WINDOW: usize = 30;
+/// Samples needed before spikes are detected
+const BASELINE_MIN: usize = 3;
+/// How far back an event can be and still explain a spike
+const CAUSE_WINDOW: Duration = Duration::from_secs(3);
+
+/// Radio events reported by `iw event`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RadioEvent {
+    ScanStarted,
+    Disconnected,
+}
+
+/// Everything measured during one second
+#[derive(Debug, Clone, Default)]
+pub struct Second {
+    /// Time since the watch started
+    pub at: Duration,
+    pub rx_pps: u64,
+    pub tx_pps: u64,
+    pub rx_mbit: f64,
+    pub tx_mbit: f64,
+    /// RTTs of the replies received this second (ms)
+    pub rtts: Vec<f64>,
+    /// Echo requests that got no reply
+    pub lost: u32,
+    pub signal_dbm: Option<i32>,
+    pub bitrate_mbit: Option<f64>,
+    pub bssid: Option<String>,
+    pub cake: Option<CakeStats>,
+    pub governor: Option<LinkStatus>,
+    pub radio: Vec<RadioEvent>,
+}
+
+/// Something that changed and may explain a stutter
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Scan,
+    Disconnect,
+    Roam { from: String, to: String },
+    CakeChange { from: Option<u32>, to: Option<u32> },
+    GameMode(bool),
+    PowerSave(bool),
+}
+
+impl Event {
+    /// Short label used when attributing spikes
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::Scan => "scan",
+            Event::Disconnect => "disconnect",
+            Event::Roam { .. } => "roam",
+            Event::CakeChange { .. } => "CAKE change",
+            Event::GameMode(_) => "game mode",
+            Event::PowerSave(_) => "power save",
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mbit = |m: &Option<u32>| m.map(|m| format!("{} Mbit", m)).unwrap_or_else(|| "off".to_string());
+        match self {
+            Event::Scan => write!(f, "Background scan"),
+            Event::Disconnect => write!(f, "Disconnected"),
+            Event::Roam { from, to } => write!(f, "Roamed {} -> {}", from, to),
+            Event::CakeChange { from, to } => write!(f, "CAKE {} -> {}", mbit(from), mbit(to)),
+            Event::GameMode(on) => write!(f, "Game mode {}", if *on { "ON" } else { "OFF" }),
+            Event::PowerSave(on) => write!(f, "Power save {}", if *on { "ON" } else { "OFF" }),
+        }
+    }
+}
+
+/// A second whose latency jumped well above the baseline, or lost replies
+#[derive(Debug, Clone)]
+pub struct Spike {
+    pub at: Duration,
+    pub rtt_ms: f64,
+    pub baseline_ms: f64,
+    pub lost: u32,
+    pub cake_drops: u64,
+    /// Kinds of events seen shortly before
+    pub causes: Vec<&'static str>,
+}
+
+/// One rendered row
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub second: Second,
+    pub rtt_ms: Option<f64>,
+    pub jitter_ms: Option<f64>,
+    pub cake_drops: u64,
+    pub spike: bool,
+}
+
+/// Accumulated state of a watch session
+pub struct Session {
+    spike_threshold_ms: f64,
+    prev: Option<Second>,
+    last_rtt: Option<f64>,
+    baseline: VecDeque<f64>,
+    pub events: Vec<(Duration, Event)>,
+    pub spikes: Vec<Spike>,
+    rtts: Vec<f64>,
+    jitter: Vec<f64>,
+    lost: u64,
+    cake_drops: u64,
+    seconds: u64,
+}
+
+/// Totals shown when the watch ends
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub seconds: u64,
+    pub rtt_avg_ms: f64,
+    pub rtt_p95_ms: f64,
+    pub rtt_max_ms: f64,
+    pub jitter_avg_ms: f64,
+    pub loss_pct: f64,
+    pub cake_drops: u64,
+    pub scans: usize,
+    pub roams: usize,
+    pub cake_changes: usize,
+    pub spikes: usize,
+    /// Spike count per cause; "unexplained" when nothing preceded it
+    pub spike_causes: Vec<(&'static str, usize)>,
+}
+
+impl Session {
+    pub fn new(spike_threshold_ms: f64) -> Self {
+        Self {
+            spike_threshold_ms,
+            prev: None,
+            last_rtt: None,
+            baseline: VecDeque::with_capacity(BASELINE_WINDOW),
+            events: Vec::new(),
+            spikes: Vec::new(),
+            rtts: Vec::new(),
+            jitter: Vec::new(),
+            lost: 0,
+            cake_drops: 0,
+            seconds: 0,
+        }
+    }
+
+    /// Take in one second of samples
+    pub fn observe(&mut self, second: Second) -> Row {
+        let at = second.at;
+        self.seconds += 1;
+
+        for event in self.detect_events(&second) {
+            self.events.push((at, event));
+        }
+
+        let cake_drops = match (&self.prev.as_ref().and_then(|p| p.cake.clone()), &second.cake) {
+            (Some(prev), Some(cur)) => cur.dropped.saturating_sub(prev.dropped),
+            _ => 0,
+        };
+        self.cake_drops += cake_drops;
+        self.lost += second.lost as u64;
+
+        // Jitter: mean difference between consecutive replies, across second boundaries
+        let mut diffs = Vec::new();
+        for &rtt in &second.rtts {
+            if let Some(last) = self.last_rtt {
+                diffs.push((rtt - last).abs());
+            }
+            self.last_rtt = Some(rtt);
+        }
+        let jitter_ms = (!diffs.is_empty()).then(|| diffs.iter().sum::<f64>() / diffs.len() as f64);
+        self.jitter.extend(jitter_ms);
+
+        let rtt_ms = (!second.rtts.is_empty())
+            .then(|| second.rtts.iter().sum::<f64>() / second.rtts.len() as f64);
+        self.rtts.extend(&second.rtts);
+
+        let max_rtt = second.rtts.iter().copied().fold(None, |m: Option<f64>, r| Some(m.map_or(r, |m| m.max(r))));
+        let spike = self.detect_spike(at, max_rtt, second.lost, cake_drops);
+
+        // Baseline tracks the quietest reply of each second, so spikes don't raise it
+        if let Some(min) = second.rtts.iter().copied().reduce(f64::min) {
+            if self.baseline.len() == BASELINE_WINDOW {
+                self.baseline.pop_front();
+            }
+            self.baseline.push_back(min);
+        }
+
+        self.prev = Some(second.clone());
+        Row { second, rtt_ms, jitter_ms, cake_drops, spike }
+    }
+
+    fn detect_events(&self, second: &Second) -> Vec<Event> {
+        let mut events = Vec::new();
+        for radio in &second.radio {
+            events.push(match radio {
+                RadioEvent::ScanStarted => Event::Scan,
+                RadioEvent::Disconnected => Event::Disconnect,
+            });
+        }
+
+        let Some(prev) = &self.prev else {
+            return events;
+        };
+
+        if let (Some(from), Some(to)) = (&prev.bssid, &second.bssid) {
+            if from != to {
+                events.push(Event::Roam { from: from.clone(), to: to.clone() });
+            }
+        }
+
+        let bandwidth = |s: &Second| s.cake.as_ref().and_then(|c| c.bandwidth_mbit);
+        if bandwidth(prev) != bandwidth(second) {
+            events.push(Event::CakeChange { from: bandwidth(prev), to: bandwidth(second) });
+        }
+
+        if let (Some(prev), Some(cur)) = (&prev.governor, &second.governor) {
+            if prev.game_mode != cur.game_mode {
+                events.push(Event::GameMode(cur.game_mode));
+            }
+            if let (Some(was), Some(now)) = (prev.power_save, cur.power_save) {
+                if was != now {
+                    events.push(Event::PowerSave(now));
+                }
+            }
+        }
+
+        events
+    }
+
+    fn baseline_ms(&self) -> Option<f64> {
+        if self.baseline.len() < BASELINE_MIN {
+            return None;
+        }
+        let mut sorted: Vec<f64> = self.baseline.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        Some(sorted[sorted.len() / 2])
+    }
+
+    fn detect_spike(&mut self, at: Duration, max_rtt: Option<f64>, lost: u32, cake_drops: u64) -> bool {
+        let Some(baseline_ms) = self.baseline_ms() else {
+            return false;
+        };
+        let rtt_ms = max_rtt.unwrap_or(0.0);
+        if rtt_ms <= baseline_ms + self.spike_threshold_ms && lost == 0 {
+            return false;
+        }
+
+        let mut causes: Vec<&'static str> = self.events.iter()
+            .filter(|(t, _)| at.saturating_sub(*t) <= CAUSE_WINDOW)
+            .map(|(_, e)| e.kind())
+            .collect();
+        if cake_drops > 0 {
+            causes.push("CAKE drops");
+        }
+        // Events can interleave (scan, CAKE change, scan): keep each kind once
+        let mut seen = HashSet::new();
+        causes.retain(|cause| seen.insert(*cause));
+
+        self.spikes.push(Spike { at, rtt_ms, baseline_ms, lost, cake_drops, causes });
+        true
+    }
+
+    pub fn summary(&self) -> Summary {
+        let mut sorted = self.rtts.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let avg = |v: &[f64]| if v.is_empty() { 0.0 } else { v.iter().sum::<f64>() / v.len() as f64 };
+        let sent = sorted.len() as u64 + self.lost;
+
+        let count = |kind: &str| self.events.iter().filter(|(_, e)| e.kind() == kind).count();
+
+        let mut spike_causes: Vec<(&'static str, usize)> = Vec::new();
+        for spike in &self.spikes {
+            let causes = if spike.causes.is_empty() { vec!["unexplained"] } else { spike.causes.clone() };
+            for cause in causes {
+                match spike_causes.iter_mut().find(|(c, _)| *c == cause) {
+                    Some((_, n)) => *n += 1,
+                    None => spike_causes.push((cause, 1)),
+                }
+            }
+        }
+        spike_causes.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+
+        Summary {
+            seconds: self.seconds,
+            rtt_avg_ms: avg(&sorted),
+            rtt_p95_ms: sorted.get(((sorted.len() as f64 - 1.0) * 0.95).round().max(0.0) as usize).copied().unwrap_or(0.0),
+            rtt_max_ms: sorted.last().copied().unwrap_or(0.0),
+            jitter_avg_ms: avg(&self.jitter),
+            loss_pct: if sent > 0 { 100.0 * self.lost as f64 / sent as f64 } else { 0.0 },
+            cake_drops: self.cake_drops,
+            scans: count("scan"),
+            roams: count("roam"),
+            cake_changes: count("CAKE change"),
+            spikes: self.spikes.len(),
+            spike_causes,
+        }
+    }
+}
+
+/// Parse an `iw event` line for `interface`
+/// e.g. "wlan0 (phy #0): scan started", "wlan0 (phy #0): disconnected (by AP) reason: ..."
+pub fn parse_iw_event(line: &str, interface: &str) -> Option<RadioEvent> {
+    let rest = line.strip_prefix(interface)?.strip_prefix(' ')?;
+    let (_, message) = rest.split_once("): ")?;
+    if message.starts_with("scan started") {
+        Some(RadioEvent::ScanStarted)
+    } else if message.starts_with("disconnected") {
+        Some(RadioEvent::Disconnected)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn second(at: u64, rtts: &[f64]) -> Second {
+        Second {
+            at: Duration::from_secs(at),
+            rtts: rtts.to_vec(),
+            bssid: Some("aa".to_string()),
+            cake: Some(CakeStats { bandwidth_mbit: Some(100), ..Default::default() }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_spike_attributed_to_scan() {
+        let mut session = Session::new(30.0);
+        for t in 0..5 {
+            assert!(!session.observe(second(t, &[3.0, 4.0, 3.0])).spike);
+        }
+
+        let mut scan = second(5, &[3.0, 4.0, 3.0]);
+        scan.radio.push(RadioEvent::ScanStarted);
+        session.observe(scan);
+        assert!(session.observe(second(6, &[3.0, 170.0, 90.0])).spike);
+
+        // Well after the scan: nothing to blame
+        assert!(session.observe(second(12, &[3.0, 120.0])).spike);
+
+        let summary = session.summary();
+        assert_eq!(summary.spikes, 2);
+        assert_eq!(summary.scans, 1);
+        assert_eq!(summary.spike_causes, vec![("scan", 1), ("unexplained", 1)]);
+    }
+
+    #[test]
+    fn test_interleaved_causes_counted_once() {
+        let mut session = Session::new(30.0);
+        for t in 0..5 {
+            session.observe(second(t, &[3.0]));
+        }
+
+        let mut scan = second(5, &[3.0]);
+        scan.radio.push(RadioEvent::ScanStarted);
+        session.observe(scan);
+        let mut cake = second(6, &[3.0]);
+        cake.cake = Some(CakeStats { bandwidth_mbit: Some(60), ..Default::default() });
+        session.observe(cake);
+        let mut rescan = second(7, &[3.0, 150.0]);
+        rescan.cake = Some(CakeStats { bandwidth_mbit: Some(60), ..Default::default() });
+        rescan.radio.push(RadioEvent::ScanStarted);
+        assert!(session.observe(rescan).spike);
+
+        assert_eq!(session.spikes[0].causes, vec!["scan", "CAKE change"]);
+    }
+
+    #[test]
+    fn test_roam_and_cake_events() {
+        let mut session = Session::new(30.0);
+        session.observe(second(0, &[3.0]));
+
+        let mut roamed = second(1, &[3.0]);
+        roamed.bssid = Some("bb".to_string());
+        roamed.cake = Some(CakeStats { bandwidth_mbit: Some(60), dropped: 5, ..Default::default() });
+        let row = session.observe(roamed);
+
+        assert_eq!(row.cake_drops, 5);
+        assert_eq!(session.events.iter().map(|(_, e)| e.clone()).collect::<Vec<_>>(), vec![
+            Event::Roam { from: "aa".to_string(), to: "bb".to_string() },
+            Event::CakeChange { from: Some(100), to: Some(60) },
+        ]);
+    }
+
+    #[test]
+    fn test_lost_replies_count_as_spike() {
+        let mut session = Session::new(30.0);
+        for t in 0..4 {
+            session.observe(second(t, &[3.0, 3.0]));
+        }
+        let mut lossy = second(4, &[3.0]);
+        lossy.lost = 2;
+        assert!(session.observe(lossy).spike);
+        assert!((session.summary().loss_pct - 2.0 / 11.0 * 100.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_parse_iw_event() {
+        assert_eq!(parse_iw_event("wlan0 (phy #0): scan started", "wlan0"), Some(RadioEvent::ScanStarted));
+        assert_eq!(parse_iw_event("wlan0 (phy #0): disconnected (by AP) reason: 3", "wlan0"),
+                   Some(RadioEvent::Disconnected));
+        assert_eq!(parse_iw_event("wlan0 (phy #0): new scan results", "wlan0"), None);
+        assert_eq!(parse_iw_event("wlan1 (phy #1): scan started", "wlan0"), None);
+        assert_eq!(parse_iw_event("wlan01 (phy #1): scan started", "wlan0"), None);
+    }
+}